@@ -0,0 +1,94 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::models::circle::InviteStatus;
+use crate::services::invites;
+use crate::services::repositories::use_circle_repo;
+use crate::utils::format::short_date;
+use crate::utils::now_millis;
+
+// Landing page for invite links: shows the circle before joining it
+#[component]
+pub fn AcceptInvite(token: String) -> Element {
+    let mut circles = use_circle_repo();
+    let mut error = use_signal(|| None::<String>);
+    let navigator = use_navigator();
+
+    let preview = match circles.read().preview_invite(&token) {
+        Ok(preview) => preview,
+        Err(e) => {
+            return rsx! {
+                div { class: "container mt-5 text-center",
+                    i { class: "bi bi-envelope-x display-1 text-muted" }
+                    p { class: "mt-3 fw-bold", {e} }
+                    Link { to: Route::Circles {}, class: "btn btn-primary", "Go to Circles" }
+                }
+            };
+        }
+    };
+    let circle = preview.circle;
+    let status = preview.invite.status(now_millis());
+    let inviter = if preview.inviter.is_empty() { "Someone".to_string() } else { preview.inviter };
+    let verified = preview.verified;
+
+    rsx! {
+        div { class: "container mt-4",
+            div { class: "card text-center",
+                div { class: "card-body",
+                    div {
+                        class: "rounded-circle bg-body-tertiary d-inline-flex align-items-center justify-content-center mb-3",
+                        style: "width: 80px; height: 80px;",
+                        i { class: "bi {circle.details.icon} fs-1" }
+                    }
+                    p { class: "text-muted mb-1", "{inviter} invited you to join" }
+                    h4 { {circle.details.name.clone()} }
+                    p { {circle.details.description.clone()} }
+                    p { class: "small text-muted",
+                        if verified {
+                            "{circle.details.visibility.label()} circle · {circle.member_count()} members"
+                        } else {
+                            "{circle.details.visibility.label()} circle"
+                        }
+                    }
+                    div { class: "d-flex justify-content-center mb-3",
+                        for member in circle.active_members().into_iter().take(5) {
+                            img {
+                                key: "{member.id}",
+                                class: "rounded-circle border border-2 border-white",
+                                src: member.avatar.clone(),
+                                style: "width: 36px; height: 36px; object-fit: cover; margin-left: -8px;"
+                            }
+                        }
+                    }
+                    if let Some(message) = error.read().clone() {
+                        div { class: "alert alert-danger py-2 small", {message} }
+                    }
+                    if preview.already_member {
+                        p { class: "text-muted", "You're already a member" }
+                        Link { to: Route::CircleMembers { id: circle.id.clone() }, class: "btn btn-primary", "Open circle" }
+                    } else if status == InviteStatus::Active {
+                        p { class: "small text-muted", "Invite expires {short_date(preview.invite.expires_at)}" }
+                        button {
+                            class: "btn btn-primary",
+                            onclick: move |_| {
+                                let token = token.clone();
+                                spawn(async move {
+                                    // Circles we don't hold are checked by the server
+                                    let redeemed = if verified { Ok(()) } else { invites::redeem(&token).await };
+                                    match redeemed.and_then(|()| circles.write().accept_invite(&token, !verified)) {
+                                        Ok(id) => {
+                                            navigator.push(Route::CircleMembers { id });
+                                        }
+                                        Err(e) => error.set(Some(e)),
+                                    }
+                                });
+                            },
+                            "Join circle"
+                        }
+                    } else {
+                        p { class: "fw-bold text-danger", "This invite is {status.label().to_lowercase()}" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -25,8 +25,8 @@ pub fn BottomNav() -> Element {
     // Determine which bottom nav item is active based on the current route
     let active_item = match route {
         Route::Profile {} => BottomNavItem::Profile,
-        Route::Comms {} => BottomNavItem::Comms,
-        Route::Circles {} => BottomNavItem::Circles,
+        Route::Comms {} | Route::Conversation { .. } => BottomNavItem::Comms,
+        Route::Circles {} | Route::CircleFeed { .. } | Route::CircleMembers { .. } | Route::CircleEvents { .. } | Route::Calendar {} | Route::ImportContacts { .. } | Route::AcceptInvite { .. } => BottomNavItem::Circles,
         Route::Tree {} => BottomNavItem::Trees,
         Route::Settings {} => BottomNavItem::Settings,
         _ => BottomNavItem::Trees, // Default to Trees for any other route
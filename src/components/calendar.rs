@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use dioxus::prelude::*;
+use crate::Route;
+use crate::components::circle_events::{day_label, EventCard};
+use crate::components::event_editor::EventEditor;
+use crate::models::circle::{Circle, Visibility};
+use crate::models::event::{local_date_time, Occurrence, Weekday};
+use crate::services::ical;
+use crate::services::repositories::{use_circle_repo, use_event_repo};
+use crate::utils::format::{civil_from_days, days_from_civil, local_offset_millis};
+use crate::utils::now_millis;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December",
+];
+
+// Local days an occurrence touches
+fn days_of(occurrence: &Occurrence, offset: i64) -> std::ops::RangeInclusive<i64> {
+    let first = (occurrence.starts_at as i64 + offset).div_euclid(DAY_MILLIS);
+    let last = (occurrence.ends_at.max(occurrence.starts_at + 1) as i64 - 1 + offset).div_euclid(DAY_MILLIS);
+    first..=last.max(first)
+}
+
+// A month of events from every circle we belong to, with the chosen day's
+// agenda underneath
+#[component]
+pub fn Calendar() -> Element {
+    let circles = use_circle_repo();
+    let events = use_event_repo();
+    let offset = local_offset_millis();
+    let today = (now_millis() as i64 + offset).div_euclid(DAY_MILLIS);
+    let (this_year, this_month, _) = civil_from_days(today);
+    // Months since year 0, so stepping across years is plain arithmetic
+    let mut month = use_signal(|| this_year * 12 + i64::from(this_month) - 1);
+    let mut selected = use_signal(|| today);
+    // None while closed; Some(None) adds an event, Some(Some(id)) edits one
+    let mut editing = use_signal(|| None::<Option<String>>);
+
+    let ours: Vec<Circle> = Visibility::ALL.into_iter().flat_map(|v| circles.read().with_visibility(v)).collect();
+    let names: BTreeMap<String, String> = ours.iter().map(|c| (c.id.clone(), c.details.name.clone())).collect();
+    let circle_ids: Vec<String> = names.keys().cloned().collect();
+
+    let (year, month_number) = (month().div_euclid(12), month().rem_euclid(12) as u32 + 1);
+    let first = days_from_civil(year, month_number, 1);
+    let grid_start = first - Weekday::of_day(first).index();
+    let grid_days: Vec<i64> = (grid_start..grid_start + 42).collect();
+    let range_start = (grid_start * DAY_MILLIS - offset).max(0) as u64;
+    let range_end = ((grid_start + 42) * DAY_MILLIS - offset).max(0) as u64;
+    let occurrences = events.read().agenda(&circle_ids, range_start, range_end);
+
+    let busy: BTreeSet<i64> = occurrences.iter().flat_map(|o| days_of(o, offset)).collect();
+    let chosen: Vec<Occurrence> = occurrences.iter().filter(|o| days_of(o, offset).contains(&selected())).cloned().collect();
+    let selected_millis = (selected() * DAY_MILLIS - offset).max(0) as u64;
+    let export_url = ical::data_url(
+        &circle_ids.iter().flat_map(|id| events.read().for_circle(id)).collect::<Vec<_>>(),
+        offset,
+    );
+    let default_circle = editing
+        .read()
+        .clone()
+        .flatten()
+        .and_then(|id| events.read().event(&id).map(|e| e.circle_id.clone()))
+        .or_else(|| circle_ids.first().cloned())
+        .unwrap_or_default();
+
+    rsx! {
+        div { class: "p-3",
+            div { class: "d-flex align-items-center mb-3",
+                Link { to: Route::Circles {}, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                div { class: "ms-1 fw-bold flex-grow-1", "Calendar" }
+                a {
+                    class: "btn btn-outline-secondary btn-sm me-2",
+                    href: export_url,
+                    download: "jeebon.ics",
+                    title: "Export every circle's events",
+                    i { class: "bi bi-download" }
+                }
+                button {
+                    class: "btn btn-primary rounded-circle",
+                    style: "width: 40px; height: 40px; padding: 0;",
+                    title: "New event",
+                    disabled: circle_ids.is_empty(),
+                    onclick: move |_| editing.set(Some(None)),
+                    i { class: "bi bi-plus", style: "font-size: 1.5rem;" }
+                }
+            }
+
+            div { class: "d-flex align-items-center mb-2",
+                button { class: "btn btn-link text-secondary", onclick: move |_| month -= 1,
+                    i { class: "bi bi-chevron-left" }
+                }
+                div { class: "flex-grow-1 text-center fw-bold", "{MONTH_NAMES[month_number as usize - 1]} {year}" }
+                button { class: "btn btn-link text-secondary", onclick: move |_| month += 1,
+                    i { class: "bi bi-chevron-right" }
+                }
+                button {
+                    class: "btn btn-outline-secondary btn-sm",
+                    onclick: move |_| {
+                        month.set(this_year * 12 + i64::from(this_month) - 1);
+                        selected.set(today);
+                    },
+                    "Today"
+                }
+            }
+
+            div { class: "calendar-grid mb-3",
+                for day in Weekday::ALL {
+                    div { key: "{day.label()}", class: "small text-muted text-center", {day.label()} }
+                }
+                for day in grid_days {
+                    button {
+                        key: "{day}",
+                        class: match day {
+                            d if d == selected() => "btn btn-primary btn-sm calendar-day",
+                            d if d == today => "btn btn-outline-primary btn-sm calendar-day",
+                            _ => "btn btn-light btn-sm calendar-day",
+                        },
+                        opacity: if civil_from_days(day).1 == month_number { "1" } else { "0.45" },
+                        onclick: move |_| selected.set(day),
+                        div { "{civil_from_days(day).2}" }
+                        div { class: "calendar-dot",
+                            if busy.contains(&day) { "•" }
+                        }
+                    }
+                }
+            }
+
+            div { class: "small fw-bold text-muted mb-2", {day_label(selected_millis)} }
+            if chosen.is_empty() {
+                p { class: "text-muted small", "Nothing planned" }
+            }
+            for occurrence in chosen {
+                EventCard {
+                    key: "{occurrence.event.id}-{occurrence.starts_at}",
+                    circle_name: names.get(&occurrence.event.circle_id).cloned(),
+                    occurrence: occurrence.clone(),
+                    onedit: move |event_id| editing.set(Some(Some(event_id))),
+                }
+            }
+
+            if let Some(event_id) = editing.read().clone() {
+                EventEditor {
+                    circle_id: default_circle,
+                    event_id,
+                    date: Some(local_date_time(selected_millis, offset).0),
+                    onclose: move |_| editing.set(None),
+                }
+            }
+
+            style {
+                ".calendar-grid {{ display: grid; grid-template-columns: repeat(7, 1fr); gap: 4px; }}
+                .calendar-day {{ padding: 4px 0; line-height: 1.1; }}
+                .calendar-dot {{ height: 0.8em; font-size: 0.8em; }}"
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use dioxus::prelude::*;
+use crate::models::call::CallKind;
+use crate::models::LOCAL_USER_ID;
+use crate::services::calls::group::GroupCall;
+use crate::services::calls::room::RoomRole;
+use crate::services::calls::signaling::CallPhase;
+use crate::services::calls::use_calls;
+use crate::utils::format::duration;
+use crate::utils::{now_millis, sleep};
+
+// Full-screen overlay for the call in progress, shown over every page
+#[component]
+pub fn CallScreen() -> Element {
+    let mut calls = use_calls();
+    let mut now = use_signal(now_millis);
+
+    // Keep the call timer moving
+    use_future(move || async move {
+        loop {
+            sleep(1000).await;
+            now.set(now_millis());
+        }
+    });
+
+    if let Some(group) = calls.group_call() {
+        return rsx! {
+            GroupCallScreen { call: group, now: now() }
+        };
+    }
+    let Some(call) = calls.active() else {
+        return rsx! {};
+    };
+
+    let kind = match call.kind {
+        CallKind::Audio => "audio",
+        CallKind::Video => "video",
+    };
+    let status = match call.phase {
+        CallPhase::Dialing => "Calling…".to_string(),
+        CallPhase::RingingOut => "Ringing…".to_string(),
+        CallPhase::RingingIn => format!("Incoming {} call", kind),
+        CallPhase::Connecting => "Connecting…".to_string(),
+        CallPhase::Connected => call
+            .connected_at
+            .map_or(String::new(), |at| duration(now().saturating_sub(at) / 1000)),
+        CallPhase::Ended(reason) => reason.label().to_string(),
+    };
+
+    rsx! {
+        div {
+            class: "position-fixed top-0 start-0 w-100 h-100 d-flex flex-column align-items-center justify-content-between bg-dark text-white py-5",
+            style: "z-index: 1080;",
+            div { class: "text-center mt-5",
+                img {
+                    class: "rounded-circle mb-3",
+                    src: call.peer.avatar.clone(),
+                    style: "width: 120px; height: 120px; object-fit: cover;"
+                }
+                h3 { {call.peer.name.clone()} }
+                p { class: "text-white-50", {status} }
+            }
+            div { class: "d-flex gap-5 mb-5",
+                match call.phase {
+                    CallPhase::RingingIn => rsx! {
+                        button {
+                            class: "btn btn-danger rounded-circle",
+                            style: "width: 64px; height: 64px;",
+                            title: "Decline",
+                            onclick: move |_| calls.decline(),
+                            i { class: "bi bi-telephone-x fs-4" }
+                        }
+                        button {
+                            class: "btn btn-success rounded-circle",
+                            style: "width: 64px; height: 64px;",
+                            title: "Accept",
+                            onclick: move |_| calls.accept(),
+                            i { class: if call.kind == CallKind::Video { "bi bi-camera-video fs-4" } else { "bi bi-telephone fs-4" } }
+                        }
+                    },
+                    CallPhase::Ended(_) => rsx! {
+                        button {
+                            class: "btn btn-outline-light",
+                            onclick: move |_| calls.dismiss(),
+                            "Close"
+                        }
+                    },
+                    _ => rsx! {
+                        button {
+                            class: "btn btn-danger rounded-circle",
+                            style: "width: 64px; height: 64px;",
+                            title: "Hang up",
+                            onclick: move |_| calls.hang_up(),
+                            i { class: "bi bi-telephone-x fs-4" }
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+// Group call with the roster as tiles and host controls on each one
+#[component]
+fn GroupCallScreen(call: GroupCall, now: u64) -> Element {
+    let mut calls = use_calls();
+    let roster = call.roster.clone();
+    let me = roster.as_ref().and_then(|r| r.entry(LOCAL_USER_ID)).cloned();
+    let am_host = roster.as_ref().is_some_and(|r| r.is_host(LOCAL_USER_ID));
+    let muted = me.as_ref().is_some_and(|e| e.muted);
+    let video = me.as_ref().is_some_and(|e| e.video);
+    let hand_raised = me.as_ref().is_some_and(|e| e.hand_raised_at.is_some());
+    let status = match &roster {
+        _ if call.ended => "Call ended".to_string(),
+        None => "Joining…".to_string(),
+        Some(roster) => format!("{} · {} in call", duration(now.saturating_sub(call.started_at) / 1000), roster.entries.len()),
+    };
+    let hands = roster
+        .as_ref()
+        .map(|r| r.raised_hands().iter().map(|e| e.participant.name.clone()).collect::<Vec<_>>().join(", "))
+        .unwrap_or_default();
+    let removed = roster.as_ref().map(|r| r.removed.clone()).unwrap_or_default();
+
+    rsx! {
+        div {
+            class: "position-fixed top-0 start-0 w-100 h-100 d-flex flex-column bg-dark text-white p-3",
+            style: "z-index: 1080;",
+            div { class: "text-center mb-3",
+                h4 { class: "mb-1", {call.title.clone()} }
+                small { class: "text-white-50", {status} }
+            }
+            if let Some(notice) = call.notice.clone() {
+                div { class: "alert alert-warning py-2 small", {notice} }
+            }
+            if !hands.is_empty() && !call.ended {
+                div { class: "small mb-2",
+                    i { class: "bi bi-hand-index-thumb text-warning me-1" }
+                    "Raised hands: {hands}"
+                }
+            }
+            if am_host && !removed.is_empty() && !call.ended {
+                div { class: "small mb-2",
+                    "Removed: "
+                    for person in removed {
+                        button {
+                            key: "{person.id}",
+                            class: "btn btn-outline-light btn-sm ms-1",
+                            title: "Let back in",
+                            onclick: {
+                                let id = person.id.clone();
+                                move |_| calls.reinvite(&id)
+                            },
+                            i { class: "bi bi-person-plus me-1" }
+                            {person.name.clone()}
+                        }
+                    }
+                }
+            }
+            div { class: "row g-2 flex-grow-1 overflow-auto align-content-start",
+                for entry in roster.map(|r| r.entries).unwrap_or_default().into_iter().filter(|_| !call.ended) {
+                    div { key: "{entry.participant.id}", class: "col-6 col-md-4",
+                        div {
+                            class: if call.roster.as_ref().and_then(|r| r.active_speaker.as_deref()) == Some(entry.participant.id.as_str()) {
+                                "rounded-3 p-3 text-center bg-secondary bg-opacity-25 border border-2 border-success"
+                            } else {
+                                "rounded-3 p-3 text-center bg-secondary bg-opacity-25 border border-2 border-dark"
+                            },
+                            img {
+                                class: "rounded-circle mb-2",
+                                src: entry.participant.avatar.clone(),
+                                style: "width: 64px; height: 64px; object-fit: cover;"
+                            }
+                            div { class: "small fw-bold text-truncate",
+                                {entry.participant.name.clone()}
+                                if entry.role == RoomRole::Host {
+                                    span { class: "badge text-bg-primary ms-1", "Host" }
+                                }
+                            }
+                            div { class: "d-flex justify-content-center gap-2 small mt-1",
+                                i { class: if entry.muted { "bi bi-mic-mute text-danger" } else { "bi bi-mic" } }
+                                if call.kind == CallKind::Video {
+                                    i { class: if entry.video { "bi bi-camera-video" } else { "bi bi-camera-video-off text-danger" } }
+                                }
+                                if entry.hand_raised_at.is_some() {
+                                    i { class: "bi bi-hand-index-thumb text-warning" }
+                                }
+                            }
+                            if am_host && entry.participant.id != LOCAL_USER_ID {
+                                div { class: "d-flex justify-content-center gap-1 mt-2",
+                                    if !entry.muted {
+                                        button {
+                                            class: "btn btn-outline-light btn-sm",
+                                            title: "Mute",
+                                            onclick: {
+                                                let id = entry.participant.id.clone();
+                                                move |_| calls.mute_participant(&id)
+                                            },
+                                            i { class: "bi bi-mic-mute" }
+                                        }
+                                    }
+                                    button {
+                                        class: "btn btn-outline-danger btn-sm",
+                                        title: "Remove from call",
+                                        onclick: {
+                                            let id = entry.participant.id.clone();
+                                            move |_| calls.remove_participant(&id)
+                                        },
+                                        i { class: "bi bi-person-x" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "d-flex justify-content-center gap-3 py-3",
+                if call.ended {
+                    button {
+                        class: "btn btn-outline-light",
+                        onclick: move |_| calls.dismiss_room(),
+                        "Close"
+                    }
+                } else {
+                    button {
+                        class: if muted { "btn btn-light rounded-circle" } else { "btn btn-outline-light rounded-circle" },
+                        style: "width: 56px; height: 56px;",
+                        title: if muted { "Unmute" } else { "Mute" },
+                        onclick: move |_| calls.set_muted(!muted),
+                        i { class: if muted { "bi bi-mic-mute fs-5" } else { "bi bi-mic fs-5" } }
+                    }
+                    if call.kind == CallKind::Video {
+                        button {
+                            class: if video { "btn btn-outline-light rounded-circle" } else { "btn btn-light rounded-circle" },
+                            style: "width: 56px; height: 56px;",
+                            title: if video { "Turn camera off" } else { "Turn camera on" },
+                            onclick: move |_| calls.set_video(!video),
+                            i { class: if video { "bi bi-camera-video fs-5" } else { "bi bi-camera-video-off fs-5" } }
+                        }
+                    }
+                    button {
+                        class: if hand_raised { "btn btn-warning rounded-circle" } else { "btn btn-outline-light rounded-circle" },
+                        style: "width: 56px; height: 56px;",
+                        title: if hand_raised { "Lower hand" } else { "Raise hand" },
+                        onclick: move |_| calls.raise_hand(!hand_raised),
+                        i { class: "bi bi-hand-index-thumb fs-5" }
+                    }
+                    button {
+                        class: "btn btn-danger rounded-circle",
+                        style: "width: 56px; height: 56px;",
+                        title: "Leave",
+                        onclick: move |_| calls.leave_room(),
+                        i { class: "bi bi-telephone-x fs-5" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use dioxus::prelude::*;
+
+use crate::models::circle::{CircleDetails, Role, Visibility};
+use crate::models::LOCAL_USER_ID;
+use crate::services::discovery::{self, Category, DirectoryEntry, DirectoryQuery, SortOrder};
+use crate::services::repositories::use_circle_repo;
+use crate::utils::sleep;
+
+// Wait this long after the last keystroke before searching
+const SEARCH_DEBOUNCE_MILLIS: u32 = 300;
+
+// Search the public directory and join or leave circles from it. Joining and
+// leaving show straight away; if the server and this device end up
+// disagreeing, the server's view of our membership wins.
+#[component]
+pub fn CircleDiscovery() -> Element {
+    let mut circles = use_circle_repo();
+    let mut text = use_signal(String::new);
+    let mut category = use_signal(|| None::<Category>);
+    let mut sort = use_signal(|| SortOrder::Trending);
+    let mut results = use_signal(Vec::<DirectoryEntry>::new);
+    let mut cursor = use_signal(|| None::<String>);
+    let mut loading = use_signal(|| false);
+    let mut error = use_signal(|| None::<String>);
+    // Joined state we are showing before the server has confirmed it
+    let mut pending = use_signal(HashMap::<String, bool>::new);
+
+    let query = move |cursor: Option<String>| DirectoryQuery { text: text(), category: category(), sort: sort(), cursor };
+
+    // Restarts whenever the query changes, which drops the previous search
+    let _search = use_resource(move || async move {
+        let first_page = query(None);
+        sleep(SEARCH_DEBOUNCE_MILLIS).await;
+        loading.set(true);
+        match discovery::search(&first_page).await {
+            Ok(page) => {
+                results.set(page.circles);
+                cursor.set(page.next_cursor);
+                error.set(None);
+            }
+            Err(e) => error.set(Some(e)),
+        }
+        loading.set(false);
+    });
+
+    let load_more = move |_| {
+        let Some(next) = cursor() else { return };
+        spawn(async move {
+            let page_query = query(Some(next));
+            loading.set(true);
+            let result = discovery::search(&page_query).await;
+            loading.set(false);
+            // The search changed while this page was loading
+            if query(page_query.cursor.clone()) != page_query {
+                return;
+            }
+            match result {
+                Ok(page) => {
+                    results.write().extend(page.circles);
+                    cursor.set(page.next_cursor);
+                }
+                Err(e) => error.set(Some(e)),
+            }
+        });
+    };
+
+    let mut toggle = move |entry: DirectoryEntry, join: bool| {
+        pending.write().insert(entry.id.clone(), join);
+        spawn(async move {
+            let details = CircleDetails {
+                name: entry.name.clone(),
+                description: entry.description.clone(),
+                icon: entry.icon.clone(),
+                visibility: Visibility::Public,
+                parent: None,
+            };
+            let id = entry.id.clone();
+            let is_member = move || circles.read().circle(&id).is_some_and(|c| c.role_of(LOCAL_USER_ID).is_some());
+            let was_member = is_member();
+            // Refuse what we would refuse locally before asking the server
+            if !join && let Err(e) = circles.read().check_leave(&entry.id) {
+                error.set(Some(e));
+                pending.write().remove(&entry.id);
+                return;
+            }
+            let result = if join { discovery::join(&entry.id).await } else { discovery::leave(&entry.id).await };
+            let result = result.and_then(|()| {
+                if join {
+                    circles.write().join_public(&entry.id, details.clone())
+                } else {
+                    circles.write().leave(&entry.id)
+                }
+            });
+            match result {
+                Ok(()) => error.set(None),
+                Err(e) => error.set(Some(e)),
+            }
+            // A failed request may still have reached the server, so ask it
+            // where we stand and follow that
+            if is_member() != join {
+                let settled = discovery::membership(&entry.id)
+                    .await
+                    .and_then(|member| circles.write().set_public_membership(&entry.id, details, member));
+                if let Err(e) = settled {
+                    log::warn!("Couldn't check membership of {}: {}", entry.id, e);
+                }
+            }
+            let now_member = is_member();
+            if now_member != was_member
+                && let Some(listed) = results.write().iter_mut().find(|e| e.id == entry.id)
+            {
+                listed.member_count = if now_member { listed.member_count + 1 } else { listed.member_count.saturating_sub(1) };
+            }
+            pending.write().remove(&entry.id);
+        });
+    };
+
+    let entries: Vec<(DirectoryEntry, bool, Option<Role>)> = results
+        .read()
+        .iter()
+        .map(|entry| {
+            let role = circles.read().circle(&entry.id).and_then(|c| c.role_of(LOCAL_USER_ID));
+            let member = role.is_some();
+            let joined = pending.read().get(&entry.id).copied().unwrap_or(member);
+            let mut entry = entry.clone();
+            // Count ourselves in or out while the change is pending
+            match (member, joined) {
+                (false, true) => entry.member_count += 1,
+                (true, false) => entry.member_count = entry.member_count.saturating_sub(1),
+                _ => {}
+            }
+            (entry, joined, role)
+        })
+        .collect();
+
+    rsx! {
+        div { class: "circle-discovery mt-4",
+            h6 { class: "text-muted mb-2", "Discover" }
+            div { class: "input-group mb-2",
+                span { class: "input-group-text bg-white",
+                    i { class: "bi bi-search" }
+                }
+                input {
+                    class: "form-control",
+                    r#type: "search",
+                    placeholder: "Search public circles",
+                    value: "{text}",
+                    oninput: move |e| text.set(e.value()),
+                }
+            }
+            div { class: "d-flex gap-2 overflow-auto pb-2 mb-2",
+                button {
+                    class: if category().is_none() { "btn btn-primary btn-sm rounded-pill" } else { "btn btn-outline-secondary btn-sm rounded-pill" },
+                    onclick: move |_| category.set(None),
+                    "All"
+                }
+                for option in Category::ALL {
+                    button {
+                        key: "{option.label()}",
+                        class: if category() == Some(option) { "btn btn-primary btn-sm rounded-pill" } else { "btn btn-outline-secondary btn-sm rounded-pill" },
+                        onclick: move |_| category.set(Some(option)),
+                        {option.label()}
+                    }
+                }
+            }
+            div { class: "btn-group btn-group-sm mb-3",
+                button {
+                    class: if sort() == SortOrder::Trending { "btn btn-primary" } else { "btn btn-outline-primary" },
+                    onclick: move |_| sort.set(SortOrder::Trending),
+                    i { class: "bi bi-graph-up-arrow me-1" }
+                    "Trending"
+                }
+                button {
+                    class: if sort() == SortOrder::MostMembers { "btn btn-primary" } else { "btn btn-outline-primary" },
+                    onclick: move |_| sort.set(SortOrder::MostMembers),
+                    i { class: "bi bi-people me-1" }
+                    "Most members"
+                }
+            }
+
+            if let Some(message) = error.read().clone() {
+                div { class: "alert alert-danger py-2 small", {message} }
+            }
+
+            ul { class: "list-group mb-3",
+                for (entry, joined, role) in entries.iter().cloned() {
+                    li { key: "{entry.id}", class: "list-group-item d-flex align-items-center",
+                        i { class: "bi {entry.icon} fs-4 text-secondary" }
+                        div { class: "ms-3 flex-grow-1",
+                            div { class: "fw-bold", {entry.name.clone()} }
+                            div { class: "small text-muted", {entry.description.clone()} }
+                            div { class: "small text-muted",
+                                span { class: "badge text-bg-light me-2", {entry.category.label()} }
+                                "{entry.member_count} members"
+                                if sort() == SortOrder::Trending && entry.weekly_joins > 0 {
+                                    " · +{entry.weekly_joins} this week"
+                                }
+                            }
+                        }
+                        if role == Some(Role::Owner) {
+                            span { class: "badge text-bg-secondary", "Owner" }
+                        } else if joined {
+                            button {
+                                class: "btn btn-outline-secondary btn-sm",
+                                disabled: pending.read().contains_key(&entry.id),
+                                onclick: {
+                                    let entry = entry.clone();
+                                    move |_| toggle(entry.clone(), false)
+                                },
+                                "Leave"
+                            }
+                        } else {
+                            button {
+                                class: "btn btn-primary btn-sm",
+                                disabled: pending.read().contains_key(&entry.id),
+                                onclick: {
+                                    let entry = entry.clone();
+                                    move |_| toggle(entry.clone(), true)
+                                },
+                                "Join"
+                            }
+                        }
+                    }
+                }
+                if entries.is_empty() && !loading() {
+                    li { class: "list-group-item text-center text-muted py-3", "No public circles match your search" }
+                }
+            }
+
+            if loading() {
+                div { class: "text-center text-muted small mb-3",
+                    span { class: "spinner-border spinner-border-sm me-2" }
+                    "Searching…"
+                }
+            } else if cursor.read().is_some() {
+                button { class: "btn btn-outline-primary w-100 mb-3", onclick: load_more, "Load more" }
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use dioxus::prelude::*;
+use crate::models::circle::{CircleDraft, Permission, Visibility, CIRCLE_ICONS, MAX_NAME_LEN};
+use crate::models::LOCAL_USER_ID;
+use crate::services::repositories::use_circle_repo;
+
+// Modal for creating a circle, or editing one when `circle_id` is set
+#[component]
+pub fn CircleEditor(circle_id: Option<String>, visibility: Visibility, onclose: EventHandler<()>) -> Element {
+    let mut circles = use_circle_repo();
+    let editing_id = circle_id.clone();
+    let mut draft = use_signal(move || {
+        circle_id
+            .as_deref()
+            .and_then(|id| circles.peek().circle(id).map(|c| CircleDraft::of(c, LOCAL_USER_ID)))
+            .unwrap_or_else(|| CircleDraft::new(visibility))
+    });
+    let mut error = use_signal(|| None::<String>);
+    let contacts = circles.read().contacts();
+    // A new circle is ours, so everything is allowed
+    let existing = editing_id.as_deref().and_then(|id| circles.read().circle(id).cloned());
+    let can_rename = existing.as_ref().is_none_or(|c| c.can(LOCAL_USER_ID, Permission::Rename));
+    let can_invite = existing.as_ref().is_none_or(|c| c.can(LOCAL_USER_ID, Permission::Invite));
+    let can_remove = |id: &str| {
+        existing.as_ref().is_none_or(|c| c.role_of(id).is_none() || c.can_manage(LOCAL_USER_ID, Permission::Remove, id))
+    };
+    let title = if editing_id.is_some() { "Edit circle" } else { "New circle" };
+    let parent_options = circles.read().parent_options(editing_id.as_deref());
+    let has_parent = draft.read().details.parent.is_some();
+
+    let save = move |_| {
+        let result = match editing_id.as_deref() {
+            Some(id) => circles.write().update(id, draft.read().clone()),
+            None => circles.write().create(draft.read().clone()).map(|_| ()),
+        };
+        match result {
+            Ok(()) => onclose.call(()),
+            Err(e) => error.set(Some(e)),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "modal d-block",
+            style: "background-color: rgba(0, 0, 0, 0.5);",
+            tabindex: "-1",
+            div { class: "modal-dialog modal-dialog-scrollable modal-dialog-centered",
+                div { class: "modal-content",
+                    div { class: "modal-header",
+                        h5 { class: "modal-title", {title} }
+                        button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                    }
+                    div { class: "modal-body",
+                        if let Some(message) = error.read().clone() {
+                            div { class: "alert alert-danger py-2 small", {message} }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "circleName", "Name" }
+                            input {
+                                id: "circleName",
+                                class: "form-control",
+                                maxlength: "{MAX_NAME_LEN}",
+                                disabled: !can_rename,
+                                value: "{draft.read().details.name}",
+                                oninput: move |e| draft.write().details.name = e.value(),
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "circleDescription", "Description" }
+                            textarea {
+                                id: "circleDescription",
+                                class: "form-control",
+                                rows: "2",
+                                disabled: !can_rename,
+                                value: "{draft.read().details.description}",
+                                oninput: move |e| draft.write().details.description = e.value(),
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label d-block", "Icon" }
+                            div { class: "d-flex flex-wrap gap-2",
+                                for icon in CIRCLE_ICONS {
+                                    button {
+                                        key: "{icon}",
+                                        class: if draft.read().details.icon == icon { "btn btn-primary" } else { "btn btn-outline-secondary" },
+                                        disabled: !can_rename,
+                                        onclick: move |_| draft.write().details.icon = icon.to_string(),
+                                        i { class: "bi {icon}" }
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "circleParent", "Inside" }
+                            select {
+                                id: "circleParent",
+                                class: "form-select",
+                                disabled: !can_rename,
+                                onchange: move |e: Event<FormData>| {
+                                    let parent = parent_options.iter().find(|c| c.id == e.value());
+                                    let mut draft = draft.write();
+                                    draft.details.parent = parent.map(|c| c.id.clone());
+                                    // A sub-circle takes its parent's visibility
+                                    if let Some(parent) = parent {
+                                        draft.details.visibility = parent.details.visibility;
+                                    }
+                                },
+                                option { value: "", selected: !has_parent, "Nothing (top-level circle)" }
+                                for option in parent_options.iter() {
+                                    option {
+                                        key: "{option.id}",
+                                        value: option.id.clone(),
+                                        selected: draft.read().details.parent.as_deref() == Some(option.id.as_str()),
+                                        "{option.details.name} ({option.details.visibility.label().to_lowercase()})"
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label d-block", "Visibility" }
+                            div { class: "btn-group w-100",
+                                for option in Visibility::ALL {
+                                    button {
+                                        key: "{option.label()}",
+                                        class: if draft.read().details.visibility == option { "btn btn-primary" } else { "btn btn-outline-primary" },
+                                        disabled: !can_rename || has_parent,
+                                        onclick: move |_| draft.write().details.visibility = option,
+                                        {option.label()}
+                                    }
+                                }
+                            }
+                        }
+                        div {
+                            label { class: "form-label d-block", "Members" }
+                            ul { class: "list-group",
+                                for contact in contacts {
+                                    li {
+                                        key: "{contact.id}",
+                                        class: "list-group-item d-flex align-items-center",
+                                        input {
+                                            class: "form-check-input me-3",
+                                            r#type: "checkbox",
+                                            id: "member-{contact.id}",
+                                            checked: draft.read().has_member(&contact.id),
+                                            disabled: if draft.read().has_member(&contact.id) { !can_remove(&contact.id) } else { !can_invite },
+                                            onchange: {
+                                                let contact = contact.clone();
+                                                move |_| draft.write().toggle_member(contact.clone())
+                                            },
+                                        }
+                                        img {
+                                            class: "rounded-circle me-2",
+                                            src: contact.avatar.clone(),
+                                            style: "width: 32px; height: 32px; object-fit: cover;"
+                                        }
+                                        label { class: "form-check-label", r#for: "member-{contact.id}", {contact.name.clone()} }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "modal-footer",
+                        button { class: "btn btn-secondary", onclick: move |_| onclose.call(()), "Cancel" }
+                        button { class: "btn btn-primary", onclick: save, "Save" }
+                    }
+                }
+            }
+        }
+    }
+}
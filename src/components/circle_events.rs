@@ -0,0 +1,265 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::components::event_editor::EventEditor;
+use crate::models::circle::CircleMember;
+use crate::models::event::{Occurrence, Rsvp, Weekday};
+use crate::models::LOCAL_USER_ID;
+use crate::services::ical;
+use crate::services::repositories::{use_circle_repo, use_event_repo};
+use crate::utils::format::{clock_time, local_offset_millis, short_date};
+use crate::utils::now_millis;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+// How far ahead a circle's agenda looks
+const AGENDA_DAYS: u64 = 60;
+
+// "Thu 12 Mar 2025"
+pub fn day_label(millis: u64) -> String {
+    let day = (millis as i64 + local_offset_millis()).div_euclid(DAY_MILLIS as i64);
+    format!("{} {}", Weekday::of_day(day).label(), short_date(millis))
+}
+
+// One occurrence with our answer. `circle_name` is shown when events of
+// several circles are listed together.
+#[component]
+pub fn EventCard(occurrence: Occurrence, circle_name: Option<String>, onedit: EventHandler<String>) -> Element {
+    let mut events = use_event_repo();
+    let event = occurrence.event.clone();
+    let mine = event.rsvp_of(LOCAL_USER_ID);
+    let going: Vec<CircleMember> = event.answered(Rsvp::Going);
+    let maybe = event.answered(Rsvp::Maybe).len();
+    let when = if event.all_day {
+        "All day".to_string()
+    } else {
+        format!("{} – {}", clock_time(occurrence.starts_at), clock_time(occurrence.ends_at))
+    };
+    let is_organizer = event.organizer.id == LOCAL_USER_ID;
+    let event_id = event.id.clone();
+
+    rsx! {
+        div { class: "card mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-start",
+                    div { class: "flex-grow-1",
+                        div { class: "fw-bold", {event.title.clone()} }
+                        div { class: "small text-muted",
+                            i { class: "bi bi-clock me-1" }
+                            {when}
+                            if let Some(rule) = event.recurrence.as_ref() {
+                                span { class: "ms-2", title: "Repeats",
+                                    i { class: "bi bi-arrow-repeat me-1" }
+                                    {rule.summary()}
+                                }
+                            }
+                        }
+                        if !event.location.is_empty() {
+                            div { class: "small text-muted",
+                                i { class: "bi bi-geo-alt me-1" }
+                                {event.location.clone()}
+                            }
+                        }
+                        if let Some(name) = circle_name {
+                            div { class: "small text-primary", {name} }
+                        }
+                        if !event.description.is_empty() {
+                            div { class: "small mt-1", style: "white-space: pre-line;", {event.description.clone()} }
+                        }
+                    }
+                    if is_organizer {
+                        button {
+                            class: "btn btn-link btn-sm text-secondary",
+                            title: "Edit",
+                            onclick: move |_| onedit.call(event_id.clone()),
+                            i { class: "bi bi-pencil" }
+                        }
+                    }
+                }
+                div { class: "d-flex align-items-center mt-2",
+                    div { class: "btn-group btn-group-sm me-2",
+                        for answer in Rsvp::ALL {
+                            button {
+                                key: "{answer.label()}",
+                                class: if mine == Some(answer) { "btn btn-primary" } else { "btn btn-outline-primary" },
+                                onclick: {
+                                    let id = event.id.clone();
+                                    move |_| {
+                                        let _ = events.write().rsvp(&id, answer);
+                                    }
+                                },
+                                {answer.label()}
+                            }
+                        }
+                    }
+                    div { class: "d-flex align-items-center small text-muted ms-auto",
+                        for member in going.iter().take(3) {
+                            img {
+                                key: "{member.id}",
+                                class: "rounded-circle border border-white",
+                                src: member.avatar.clone(),
+                                title: member.name.clone(),
+                                style: "width: 22px; height: 22px; object-fit: cover; margin-left: -6px;"
+                            }
+                        }
+                        span { class: "ms-1",
+                            "{going.len()} going"
+                            if maybe > 0 { ", {maybe} maybe" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The agenda of one circle: what's coming up in the next weeks, with
+// .ics import and export
+#[component]
+pub fn CircleEvents(id: String) -> Element {
+    let circles = use_circle_repo();
+    let mut events = use_event_repo();
+    // None while closed; Some(None) adds an event, Some(Some(id)) edits one
+    let mut editing = use_signal(|| None::<Option<String>>);
+    let mut notice = use_signal(|| None::<String>);
+    let Some(circle) = circles.read().circle(&id).cloned() else {
+        return rsx! {
+            div { class: "container mt-5",
+                p { "This circle no longer exists." }
+                Link { to: Route::Circles {}, class: "btn btn-primary", "Back to Circles" }
+            }
+        };
+    };
+
+    let now = now_millis();
+    let agenda = events.read().agenda(std::slice::from_ref(&id), now, now + AGENDA_DAYS * DAY_MILLIS);
+    let mut days: Vec<(String, Vec<Occurrence>)> = Vec::new();
+    for occurrence in agenda {
+        let label = day_label(occurrence.starts_at);
+        match days.last_mut() {
+            Some((last, list)) if *last == label => list.push(occurrence),
+            _ => days.push((label, vec![occurrence])),
+        }
+    }
+    let export_url = ical::data_url(&events.read().for_circle(&id), local_offset_millis());
+
+    let import = {
+        let id = id.clone();
+        move |evt: Event<FormData>| {
+            let id = id.clone();
+            async move {
+                let Some(files) = evt.files() else {
+                    return;
+                };
+                let mut messages = Vec::new();
+                for name in files.files() {
+                    let parsed = files
+                        .read_file_to_string(&name)
+                        .await
+                        .ok_or_else(|| "couldn't read the file".to_string())
+                        .and_then(|text| ical::parse(&text, &CircleMember::local(), local_offset_millis()));
+                    match parsed {
+                        Ok(found) => {
+                            let result = events.write().import(&id, found.events, &circles.read());
+                            match result {
+                                Ok(done) => {
+                                    let mut message = format!("{}: added {}, updated {}", name, done.added, done.updated);
+                                    if done.skipped > 0 {
+                                        message.push_str(&format!(", left {} organised by others", done.skipped));
+                                    }
+                                    if found.unrepeated > 0 {
+                                        message.push_str(&format!(
+                                            ", {} with repeat rules we can't follow brought in once",
+                                            found.unrepeated
+                                        ));
+                                    }
+                                    messages.push(message);
+                                }
+                                Err(e) => messages.push(format!("{}: {}", name, e)),
+                            }
+                        }
+                        Err(e) => messages.push(format!("{}: {}", name, e)),
+                    }
+                }
+                notice.set(Some(messages.join("\n")));
+            }
+        }
+    };
+
+    rsx! {
+        div { class: "p-3",
+            div { class: "d-flex align-items-center mb-3",
+                Link { to: Route::CircleFeed { id: id.clone() }, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                div { class: "ms-1 flex-grow-1",
+                    div { class: "fw-bold", "Events" }
+                    div { class: "small text-muted", {circle.details.name.clone()} }
+                }
+                button {
+                    class: "btn btn-primary rounded-circle",
+                    style: "width: 40px; height: 40px; padding: 0;",
+                    title: "New event",
+                    onclick: move |_| editing.set(Some(None)),
+                    i { class: "bi bi-plus", style: "font-size: 1.5rem;" }
+                }
+            }
+
+            div { class: "d-flex gap-2 mb-3",
+                Link { to: Route::Calendar {}, class: "btn btn-outline-secondary btn-sm",
+                    i { class: "bi bi-calendar3 me-1" }
+                    "All circles"
+                }
+                a {
+                    class: "btn btn-outline-secondary btn-sm",
+                    href: export_url,
+                    download: "{circle.details.name}.ics",
+                    i { class: "bi bi-download me-1" }
+                    "Export"
+                }
+                label { class: "btn btn-outline-secondary btn-sm mb-0",
+                    i { class: "bi bi-upload me-1" }
+                    "Import .ics"
+                    input {
+                        class: "d-none",
+                        r#type: "file",
+                        accept: ".ics,text/calendar",
+                        multiple: true,
+                        onchange: import,
+                    }
+                }
+            }
+
+            if let Some(message) = notice.read().clone() {
+                div { class: "alert alert-info py-2 small", style: "white-space: pre-line;", {message} }
+            }
+
+            if days.is_empty() {
+                div { class: "text-center text-muted py-5",
+                    i { class: "bi bi-calendar-event fs-1" }
+                    p { class: "mt-2", "Nothing planned in the next {AGENDA_DAYS} days" }
+                }
+            }
+            for (label, list) in days {
+                div { key: "{label}",
+                    div { class: "small fw-bold text-muted mt-3 mb-2", {label.clone()} }
+                    for occurrence in list {
+                        EventCard {
+                            key: "{occurrence.event.id}-{occurrence.starts_at}",
+                            occurrence: occurrence.clone(),
+                            circle_name: None,
+                            onedit: move |event_id| editing.set(Some(Some(event_id))),
+                        }
+                    }
+                }
+            }
+
+            if let Some(event_id) = editing.read().clone() {
+                EventEditor {
+                    circle_id: id.clone(),
+                    event_id,
+                    date: None,
+                    onclose: move |_| editing.set(None),
+                }
+            }
+        }
+    }
+}
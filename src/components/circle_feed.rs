@@ -0,0 +1,560 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::models::circle::{Circle, Visibility};
+use crate::models::event::{parse_date, parse_time};
+use crate::models::poll::{PollDraft, MAX_OPTION_LEN, MAX_POLL_OPTIONS, MIN_POLL_OPTIONS};
+use crate::models::post::{Post, PostDraft, MAX_IMAGES, MAX_POST_LEN};
+use crate::models::LOCAL_USER_ID;
+use crate::services::polls::{self, BallotUpdate};
+use crate::services::repositories::{use_circle_repo, use_feed_repo};
+use crate::utils::format::{clock_time, local_offset_millis, relative_time, short_date};
+use crate::utils::now_millis;
+
+// "2025-03-12T18:30" from a datetime-local input, as UTC milliseconds
+fn parse_deadline(value: &str) -> Option<u64> {
+    let (date, time) = value.split_once('T')?;
+    let millis = parse_date(date)? * 24 * 60 * 60 * 1000 + parse_time(time)? - local_offset_millis();
+    u64::try_from(millis).ok()
+}
+
+// Options with live results. Votes go to the circles the poll was shared
+// with as they are cast.
+#[component]
+fn PollView(post: Post) -> Element {
+    let mut feed = use_feed_repo();
+    let mut error = use_signal(|| None::<String>);
+    let Some(poll) = post.poll.clone() else {
+        return rsx! {};
+    };
+    let now = now_millis();
+    let open = poll.is_open(now);
+    let mine = poll.choices_of(&post.id, LOCAL_USER_ID);
+    let voters = poll.voter_count();
+    let results = poll.results();
+    let leading = results.iter().map(|r| r.votes).max().unwrap_or(0);
+    let is_mine = post.author.id == LOCAL_USER_ID;
+
+    rsx! {
+        div { class: "mb-2",
+            for result in results {
+                button {
+                    key: "{result.option.id}",
+                    class: "btn btn-light w-100 text-start p-2 mb-1 position-relative overflow-hidden",
+                    disabled: !open,
+                    onclick: {
+                        let post_id = post.id.clone();
+                        let audience = post.audience.clone();
+                        let option_id = result.option.id.clone();
+                        move |_| {
+                            let vote = feed.write().vote(&post_id, &option_id);
+                            match vote {
+                                Ok((key, ballot)) => {
+                                    polls::broadcast(&audience, &BallotUpdate { post_id: post_id.clone(), key, ballot });
+                                    error.set(None);
+                                }
+                                Err(e) => error.set(Some(e)),
+                            }
+                        }
+                    },
+                    div {
+                        class: if result.votes == leading && leading > 0 { "position-absolute top-0 start-0 h-100 bg-primary-subtle" } else { "position-absolute top-0 start-0 h-100 bg-secondary-subtle" },
+                        style: "width: {result.votes * 100 / voters.max(1)}%;",
+                    }
+                    div { class: "position-relative d-flex align-items-center",
+                        i {
+                            class: match (poll.multiple, mine.contains(&result.option.id)) {
+                                (true, true) => "bi bi-check-square-fill text-primary me-2",
+                                (true, false) => "bi bi-square me-2",
+                                (false, true) => "bi bi-record-circle-fill text-primary me-2",
+                                (false, false) => "bi bi-circle me-2",
+                            }
+                        }
+                        span { class: "flex-grow-1", {result.option.text.clone()} }
+                        for voter in result.voters.iter().take(3) {
+                            img {
+                                key: "{voter.id}",
+                                class: "rounded-circle border border-white",
+                                src: voter.avatar.clone(),
+                                title: voter.name.clone(),
+                                style: "width: 20px; height: 20px; object-fit: cover; margin-left: -6px;"
+                            }
+                        }
+                        span { class: "small text-muted ms-2", "{result.votes}" }
+                    }
+                }
+            }
+            if let Some(message) = error.read().clone() {
+                div { class: "small text-danger", {message} }
+            }
+            div { class: "d-flex align-items-center small text-muted",
+                span { class: "flex-grow-1",
+                    "{voters} voted"
+                    if poll.multiple { " · pick any" }
+                    if poll.anonymous { " · anonymous" }
+                    match (open, poll.closes_at) {
+                        (true, Some(at)) => rsx! { " · closes {short_date(at)} {clock_time(at)}" },
+                        (true, None) => rsx! {},
+                        (false, _) => rsx! { " · closed" },
+                    }
+                }
+                if is_mine && open {
+                    button {
+                        class: "btn btn-link btn-sm text-secondary p-0",
+                        onclick: {
+                            let post_id = post.id.clone();
+                            move |_| error.set(feed.write().close_poll(&post_id).err())
+                        },
+                        "Close poll"
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Write a post and choose which of our circles see it
+#[component]
+fn Composer(circle_id: String) -> Element {
+    let mut feed = use_feed_repo();
+    let circles = use_circle_repo();
+    let mut draft = use_signal({
+        let circle_id = circle_id.clone();
+        move || PostDraft::to(&circle_id)
+    });
+    let mut image_url = use_signal(String::new);
+    let mut choosing = use_signal(|| false);
+    let mut deadline = use_signal(String::new);
+    let mut error = use_signal(|| None::<String>);
+    let poll = draft.read().poll.clone();
+    let ours: Vec<Circle> = Visibility::ALL.into_iter().flat_map(|v| circles.read().with_visibility(v)).collect();
+    let audience_names: Vec<String> = ours
+        .iter()
+        .filter(|c| draft.read().audience.contains(&c.id))
+        .map(|c| c.details.name.clone())
+        .collect();
+
+    let add_image = move |_| {
+        let url = image_url.read().trim().to_string();
+        if url.is_empty() {
+            return;
+        }
+        draft.write().images.push(url);
+        image_url.set(String::new());
+    };
+
+    let publish = move |_| {
+        let result = feed.write().publish(draft.read().clone(), &circles.read());
+        match result {
+            Ok(_) => {
+                draft.set(PostDraft::to(&circle_id));
+                deadline.set(String::new());
+                choosing.set(false);
+                error.set(None);
+            }
+            Err(e) => error.set(Some(e)),
+        }
+    };
+
+    rsx! {
+        div { class: "card mb-3",
+            div { class: "card-body",
+                if let Some(message) = error.read().clone() {
+                    div { class: "alert alert-danger py-2 small", {message} }
+                }
+                textarea {
+                    class: "form-control mb-2",
+                    rows: "3",
+                    maxlength: "{MAX_POST_LEN}",
+                    placeholder: if poll.is_some() { "Ask your circles a question" } else { "Share something with your circles" },
+                    value: "{draft.read().body}",
+                    oninput: move |e| draft.write().body = e.value(),
+                }
+                if !draft.read().images.is_empty() {
+                    div { class: "d-flex flex-wrap gap-2 mb-2",
+                        for (index, url) in draft.read().images.iter().cloned().enumerate() {
+                            div { key: "{index}-{url}", class: "position-relative",
+                                img { class: "rounded", src: url.clone(), style: "width: 72px; height: 72px; object-fit: cover;" }
+                                button {
+                                    class: "btn btn-sm btn-dark position-absolute top-0 end-0 py-0 px-1",
+                                    title: "Remove picture",
+                                    onclick: move |_| {
+                                        draft.write().images.remove(index);
+                                    },
+                                    i { class: "bi bi-x" }
+                                }
+                            }
+                        }
+                    }
+                }
+                if let Some(poll) = poll.clone() {
+                    div { class: "border rounded p-2 mb-2",
+                        for (index, text) in poll.options.iter().cloned().enumerate() {
+                            div { key: "{index}", class: "input-group input-group-sm mb-1",
+                                input {
+                                    class: "form-control",
+                                    maxlength: "{MAX_OPTION_LEN}",
+                                    placeholder: "Option {index + 1}",
+                                    value: text,
+                                    oninput: move |e| {
+                                        if let Some(poll) = draft.write().poll.as_mut() {
+                                            poll.options[index] = e.value();
+                                        }
+                                    },
+                                }
+                                if poll.options.len() > MIN_POLL_OPTIONS {
+                                    button {
+                                        class: "btn btn-outline-secondary",
+                                        title: "Remove option",
+                                        onclick: move |_| {
+                                            if let Some(poll) = draft.write().poll.as_mut() {
+                                                poll.options.remove(index);
+                                            }
+                                        },
+                                        i { class: "bi bi-x" }
+                                    }
+                                }
+                            }
+                        }
+                        if poll.options.len() < MAX_POLL_OPTIONS {
+                            button {
+                                class: "btn btn-link btn-sm px-0",
+                                onclick: move |_| {
+                                    if let Some(poll) = draft.write().poll.as_mut() {
+                                        poll.options.push(String::new());
+                                    }
+                                },
+                                i { class: "bi bi-plus me-1" }
+                                "Add option"
+                            }
+                        }
+                        div { class: "form-check small",
+                            input {
+                                id: "pollMultiple",
+                                class: "form-check-input",
+                                r#type: "checkbox",
+                                checked: poll.multiple,
+                                onchange: move |e: Event<FormData>| {
+                                    if let Some(poll) = draft.write().poll.as_mut() {
+                                        poll.multiple = e.checked();
+                                    }
+                                },
+                            }
+                            label { class: "form-check-label", r#for: "pollMultiple", "Allow more than one choice" }
+                        }
+                        div { class: "form-check small mb-1",
+                            input {
+                                id: "pollAnonymous",
+                                class: "form-check-input",
+                                r#type: "checkbox",
+                                checked: poll.anonymous,
+                                onchange: move |e: Event<FormData>| {
+                                    if let Some(poll) = draft.write().poll.as_mut() {
+                                        poll.anonymous = e.checked();
+                                    }
+                                },
+                            }
+                            label { class: "form-check-label", r#for: "pollAnonymous", "Hide who voted for what" }
+                        }
+                        label { class: "form-label small mb-1", r#for: "pollDeadline", "Closes (optional)" }
+                        input {
+                            id: "pollDeadline",
+                            class: "form-control form-control-sm",
+                            r#type: "datetime-local",
+                            value: "{deadline}",
+                            oninput: move |e| {
+                                let closes_at = parse_deadline(&e.value());
+                                deadline.set(e.value());
+                                if let Some(poll) = draft.write().poll.as_mut() {
+                                    poll.closes_at = closes_at;
+                                }
+                            },
+                        }
+                    }
+                }
+                if draft.read().images.len() < MAX_IMAGES {
+                    form { class: "input-group input-group-sm mb-2", onsubmit: add_image,
+                        input {
+                            class: "form-control",
+                            r#type: "url",
+                            placeholder: "Picture address (https://…)",
+                            value: "{image_url}",
+                            oninput: move |e| image_url.set(e.value()),
+                        }
+                        button { class: "btn btn-outline-secondary", r#type: "submit",
+                            i { class: "bi bi-image me-1" }
+                            "Add"
+                        }
+                    }
+                }
+                div { class: "d-flex align-items-center",
+                    button {
+                        class: "btn btn-link btn-sm text-decoration-none px-0 text-start flex-grow-1 text-truncate",
+                        onclick: move |_| choosing.toggle(),
+                        i { class: "bi bi-people me-1" }
+                        if audience_names.is_empty() { "Choose circles" } else { {audience_names.join(", ")} }
+                    }
+                    button {
+                        class: if draft.read().poll.is_some() { "btn btn-secondary btn-sm ms-2" } else { "btn btn-outline-secondary btn-sm ms-2" },
+                        title: "Poll",
+                        onclick: move |_| {
+                            let mut draft = draft.write();
+                            draft.poll = match draft.poll {
+                                Some(_) => None,
+                                None => Some(PollDraft::default()),
+                            };
+                        },
+                        i { class: "bi bi-bar-chart" }
+                    }
+                    button { class: "btn btn-primary btn-sm ms-2", onclick: publish, "Post" }
+                }
+                if *choosing.read() {
+                    div { class: "d-flex flex-wrap gap-2 mt-2",
+                        for circle in ours {
+                            button {
+                                key: "{circle.id}",
+                                class: if draft.read().audience.contains(&circle.id) { "btn btn-primary btn-sm rounded-pill" } else { "btn btn-outline-secondary btn-sm rounded-pill" },
+                                onclick: {
+                                    let id = circle.id.clone();
+                                    move |_| draft.write().toggle_circle(&id)
+                                },
+                                i { class: "bi {circle.details.icon} me-1" }
+                                {circle.details.name.clone()}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn PostCard(post: Post, circle_id: String, unread: bool) -> Element {
+    let mut feed = use_feed_repo();
+    let circles = use_circle_repo();
+    let mut show_comments = use_signal(|| false);
+    let mut reply = use_signal(String::new);
+    let mut error = use_signal(|| None::<String>);
+    let liked = post.liked_by(LOCAL_USER_ID);
+    let is_mine = post.author.id == LOCAL_USER_ID;
+    let comments: Vec<_> = post.visible_comments().into_iter().cloned().collect();
+    // The other circles this post went to that we can see
+    let also_in: Vec<String> = post
+        .audience
+        .iter()
+        .filter(|id| **id != circle_id)
+        .filter_map(|id| circles.read().circle(id).map(|c| c.details.name.clone()))
+        .collect();
+
+    let send_comment = {
+        let post_id = post.id.clone();
+        move |_| {
+            let result = feed.write().comment(&post_id, &reply.read(), &circles.read());
+            match result {
+                Ok(()) => {
+                    reply.set(String::new());
+                    error.set(None);
+                }
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    rsx! {
+        div { class: if unread { "card mb-3 border-primary" } else { "card mb-3" },
+            div { class: "card-body",
+                div { class: "d-flex align-items-center mb-2",
+                    img {
+                        class: "rounded-circle",
+                        src: post.author.avatar.clone(),
+                        style: "width: 40px; height: 40px; object-fit: cover;"
+                    }
+                    div { class: "ms-2 flex-grow-1",
+                        div { class: "fw-bold",
+                            {post.author.name.clone()}
+                            if unread {
+                                span { class: "badge text-bg-primary ms-2", "New" }
+                            }
+                        }
+                        div { class: "small text-muted",
+                            {relative_time(post.posted_at)}
+                            if !also_in.is_empty() {
+                                " · also in {also_in.join(\", \")}"
+                            }
+                        }
+                    }
+                    if is_mine {
+                        button {
+                            class: "btn btn-link text-danger btn-sm",
+                            title: "Delete post",
+                            onclick: {
+                                let post_id = post.id.clone();
+                                move |_| error.set(feed.write().delete(&post_id).err())
+                            },
+                            i { class: "bi bi-trash" }
+                        }
+                    }
+                }
+                if !post.body.is_empty() {
+                    p { class: "card-text", style: "white-space: pre-wrap;", {post.body.clone()} }
+                }
+                if post.poll.is_some() {
+                    PollView { post: post.clone() }
+                }
+                if !post.images.is_empty() {
+                    div { class: "row g-1 mb-2",
+                        for url in post.images.iter() {
+                            div { key: "{url}", class: if post.images.len() == 1 { "col-12" } else { "col-6" },
+                                img { class: "img-fluid rounded w-100", src: url.clone(), style: "object-fit: cover; max-height: 320px;" }
+                            }
+                        }
+                    }
+                }
+                for link in post.links.iter() {
+                    a {
+                        key: "{link}",
+                        class: "d-block border rounded p-2 mb-2 small text-truncate text-decoration-none",
+                        href: link.clone(),
+                        target: "_blank",
+                        rel: "noopener noreferrer",
+                        i { class: "bi bi-link-45deg me-1" }
+                        {link.clone()}
+                    }
+                }
+                if let Some(message) = error.read().clone() {
+                    div { class: "alert alert-danger py-2 small", {message} }
+                }
+                div { class: "d-flex gap-3 border-top pt-2",
+                    button {
+                        class: if liked { "btn btn-link btn-sm text-danger text-decoration-none p-0" } else { "btn btn-link btn-sm text-secondary text-decoration-none p-0" },
+                        onclick: {
+                            let post_id = post.id.clone();
+                            move |_| error.set(feed.write().toggle_like(&post_id).err())
+                        },
+                        i { class: if liked { "bi bi-heart-fill me-1" } else { "bi bi-heart me-1" } }
+                        "{post.like_count()}"
+                    }
+                    button {
+                        class: "btn btn-link btn-sm text-secondary text-decoration-none p-0",
+                        onclick: move |_| show_comments.toggle(),
+                        i { class: "bi bi-chat me-1" }
+                        "{comments.len()}"
+                    }
+                }
+                if *show_comments.read() {
+                    div { class: "mt-2",
+                        for comment in comments {
+                            div { key: "{comment.id}", class: "d-flex align-items-start mb-2",
+                                img {
+                                    class: "rounded-circle",
+                                    src: comment.author.avatar.clone(),
+                                    style: "width: 28px; height: 28px; object-fit: cover;"
+                                }
+                                div { class: "ms-2 bg-body-tertiary rounded px-2 py-1 flex-grow-1 small",
+                                    div { class: "fw-bold",
+                                        {comment.author.name.clone()}
+                                        span { class: "text-muted fw-normal ms-2", {relative_time(comment.posted_at)} }
+                                    }
+                                    {comment.body.clone()}
+                                }
+                                if comment.author.id == LOCAL_USER_ID || is_mine {
+                                    button {
+                                        class: "btn btn-link btn-sm text-secondary",
+                                        title: "Delete comment",
+                                        onclick: {
+                                            let post_id = post.id.clone();
+                                            let comment_id = comment.id.clone();
+                                            move |_| error.set(feed.write().delete_comment(&post_id, &comment_id).err())
+                                        },
+                                        i { class: "bi bi-x" }
+                                    }
+                                }
+                            }
+                        }
+                        form { class: "input-group input-group-sm", onsubmit: send_comment,
+                            input {
+                                class: "form-control",
+                                placeholder: "Write a comment",
+                                value: "{reply}",
+                                oninput: move |e| reply.set(e.value()),
+                            }
+                            button { class: "btn btn-primary", r#type: "submit",
+                                i { class: "bi bi-send" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A circle's posts, newest first. Opening the feed marks it read; posts that
+// were new when we arrived keep their badge until we leave.
+#[component]
+pub fn CircleFeed(id: String) -> Element {
+    let mut feed = use_feed_repo();
+    let circles = use_circle_repo();
+    let read_before = use_hook({
+        let id = id.clone();
+        move || feed.peek().last_read_at(&id)
+    });
+    use_effect({
+        let id = id.clone();
+        move || feed.write().mark_read(&id)
+    });
+
+    let Some(circle) = circles.read().circle(&id).cloned() else {
+        return rsx! {
+            div { class: "container mt-5",
+                p { "This circle no longer exists." }
+                Link { to: Route::Circles {}, class: "btn btn-primary", "Back to Circles" }
+            }
+        };
+    };
+    let posts = feed.read().feed(&id);
+
+    rsx! {
+        div { class: "p-3",
+            div { class: "d-flex align-items-center mb-3",
+                Link { to: Route::Circles {}, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                i { class: "bi {circle.details.icon} fs-4 ms-1" }
+                div { class: "ms-2",
+                    div { class: "fw-bold", {circle.details.name.clone()} }
+                    Link {
+                        to: Route::CircleMembers { id: id.clone() },
+                        class: "small text-muted text-decoration-none",
+                        "{circle.member_count()} members"
+                    }
+                }
+                Link {
+                    to: Route::CircleEvents { id: id.clone() },
+                    class: "btn btn-outline-secondary btn-sm ms-auto",
+                    title: "Events",
+                    i { class: "bi bi-calendar-event" }
+                }
+            }
+
+            if circle.role_of(LOCAL_USER_ID).is_some() {
+                Composer { circle_id: id.clone() }
+            }
+
+            if posts.is_empty() {
+                div { class: "text-center py-3 text-muted",
+                    p { "Nothing here yet" }
+                    p { "Posts shared with this circle will show up here" }
+                }
+            }
+            for post in posts {
+                PostCard {
+                    key: "{post.id}",
+                    unread: post.author.id != LOCAL_USER_ID && post.posted_at > read_before,
+                    post: post.clone(),
+                    circle_id: id.clone(),
+                }
+            }
+        }
+    }
+}
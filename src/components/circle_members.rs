@@ -0,0 +1,353 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::models::circle::{Permission, Role};
+use crate::models::LOCAL_USER_ID;
+use crate::services::invites;
+use crate::services::vcard::{self, VCardVersion};
+use crate::services::repositories::{use_circle_repo, use_tree_repo};
+use crate::utils::format::short_date;
+use crate::utils::now_millis;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+// Create invite links, show them as links and QR codes and revoke them
+#[component]
+fn InvitePanel(circle_id: String) -> Element {
+    let mut circles = use_circle_repo();
+    let mut error = use_signal(|| None::<String>);
+    let mut ttl_days = use_signal(|| 7u64);
+    let mut max_uses = use_signal(|| None::<u32>);
+    // The invite whose link and QR code are on show
+    let mut shown = use_signal(|| None::<String>);
+
+    let Some(circle) = circles.read().circle(&circle_id).cloned() else {
+        return rsx! {};
+    };
+    let can_revoke = circle.can(LOCAL_USER_ID, Permission::RevokeInvites);
+    let now = now_millis();
+    let outstanding: Vec<_> = circle.outstanding_invites(now).into_iter().cloned().collect();
+    let shown_token = shown.read().as_deref().and_then(|invite_id| circles.read().invite_token(&circle_id, invite_id));
+
+    rsx! {
+        div { class: "card mb-3",
+            div { class: "card-body",
+                h6 { class: "card-title", "Invite links" }
+                if let Some(message) = error.read().clone() {
+                    div { class: "alert alert-danger py-2 small", {message} }
+                }
+                div { class: "d-flex gap-2 mb-3",
+                    select {
+                        class: "form-select form-select-sm",
+                        onchange: move |e: Event<FormData>| ttl_days.set(e.value().parse().unwrap_or(7)),
+                        for days in [1u64, 7, 30] {
+                            option { key: "{days}", value: "{days}", selected: *ttl_days.read() == days,
+                                if days == 1 { "Expires in 1 day" } else { "Expires in {days} days" }
+                            }
+                        }
+                    }
+                    select {
+                        class: "form-select form-select-sm",
+                        onchange: move |e: Event<FormData>| max_uses.set(e.value().parse().ok()),
+                        for uses in [Some(1u32), Some(5), Some(25), None] {
+                            option {
+                                key: "{uses:?}",
+                                value: uses.map_or(String::new(), |u| u.to_string()),
+                                selected: *max_uses.read() == uses,
+                                {uses.map_or("Unlimited uses".to_string(), |u| if u == 1 { "1 use".to_string() } else { format!("{} uses", u) })}
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-primary btn-sm text-nowrap",
+                        onclick: {
+                            let circle_id = circle_id.clone();
+                            move |_| {
+                                let ttl = *ttl_days.read() * DAY_MILLIS;
+                                let result = circles.write().create_invite(&circle_id, ttl, *max_uses.read());
+                                match result.and_then(|token| invites::decode(&token)) {
+                                    Ok(token) => {
+                                        shown.set(Some(token.invite_id));
+                                        error.set(None);
+                                    }
+                                    Err(e) => error.set(Some(e)),
+                                }
+                            }
+                        },
+                        "Create link"
+                    }
+                }
+                if let Some(token) = shown_token {
+                    div { class: "text-center mb-3",
+                        div {
+                            class: "mx-auto mb-2",
+                            style: "width: 180px;",
+                            dangerous_inner_html: invites::qr_svg(&invites::share_url(&token)).unwrap_or_default(),
+                        }
+                        input { class: "form-control form-control-sm mb-1", readonly: true, value: invites::share_url(&token) }
+                        input { class: "form-control form-control-sm", readonly: true, value: invites::deep_link(&token) }
+                    }
+                }
+                ul { class: "list-group list-group-flush small",
+                    if outstanding.is_empty() {
+                        li { class: "list-group-item text-muted px-0", "No active invite links" }
+                    }
+                    for invite in outstanding {
+                        li { key: "{invite.id}", class: "list-group-item d-flex align-items-center px-0",
+                            button {
+                                class: "btn btn-link btn-sm p-0 me-2",
+                                title: "Show link",
+                                onclick: {
+                                    let invite_id = invite.id.clone();
+                                    move |_| shown.set(Some(invite_id.clone()))
+                                },
+                                i { class: "bi bi-qr-code" }
+                            }
+                            span { class: "flex-grow-1",
+                                "Expires {short_date(invite.expires_at)} · "
+                                {invite.max_uses.map_or(format!("{} used", invite.used_by.len()), |max| format!("{} of {} used", invite.used_by.len(), max))}
+                            }
+                            if can_revoke {
+                                button {
+                                    class: "btn btn-outline-danger btn-sm",
+                                    onclick: {
+                                        let circle_id = circle_id.clone();
+                                        let invite_id = invite.id.clone();
+                                        move |_| error.set(circles.write().revoke_invite(&circle_id, &invite_id).err())
+                                    },
+                                    "Revoke"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Member management for one circle: roles, removal, invites and what our
+// own role allows
+#[component]
+pub fn CircleMembers(id: String) -> Element {
+    let mut circles = use_circle_repo();
+    let mut error = use_signal(|| None::<String>);
+    let mut inviting = use_signal(|| false);
+    let mut export_version = use_signal(|| VCardVersion::V3);
+    let navigator = use_navigator();
+
+    let Some(circle) = circles.read().circle(&id).cloned() else {
+        return rsx! {
+            div { class: "container mt-5",
+                p { "This circle no longer exists." }
+                Link { to: Route::Circles {}, class: "btn btn-primary", "Back to Circles" }
+            }
+        };
+    };
+    let my_role = circle.role_of(LOCAL_USER_ID);
+    let can_invite = circle.can(LOCAL_USER_ID, Permission::Invite);
+    let assignable = circle.assignable_roles(LOCAL_USER_ID);
+    let export_url = vcard::data_url(&circles.read().cards_for(&id), export_version());
+    // How members in the family tree are related to us
+    let family = use_tree_repo();
+    let kinship_of = |member_id: &str| {
+        let family = family.read();
+        let person = family.tree.person_for_member(member_id)?;
+        Some(family.kinship_to_me(&person.id).title(family.language)).filter(|name| !name.is_empty() && member_id != LOCAL_USER_ID)
+    };
+    let invitable: Vec<_> = circles
+        .read()
+        .contacts()
+        .into_iter()
+        .filter(|c| circle.role_of(&c.id).is_none())
+        .collect();
+
+    rsx! {
+        div { class: "p-3",
+            div { class: "d-flex align-items-center mb-3",
+                Link { to: Route::Circles {}, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                i { class: "bi {circle.details.icon} fs-4 ms-1" }
+                div { class: "ms-2",
+                    div { class: "fw-bold", {circle.details.name.clone()} }
+                    div { class: "small text-muted", "{circle.member_count()} members" }
+                }
+                if can_invite {
+                    button {
+                        class: "btn btn-primary btn-sm ms-auto",
+                        onclick: move |_| inviting.toggle(),
+                        i { class: "bi bi-person-plus me-1" }
+                        "Add"
+                    }
+                }
+            }
+
+            if let Some(message) = error.read().clone() {
+                div { class: "alert alert-danger py-2 small", {message} }
+            }
+
+            if *inviting.read() {
+                div { class: "border rounded-3 mb-3",
+                    if invitable.is_empty() {
+                        p { class: "text-muted small p-3 m-0", "Everyone you know is already here" }
+                    }
+                    for contact in invitable {
+                        div { key: "{contact.id}", class: "d-flex align-items-center px-3 py-2 border-bottom",
+                            img {
+                                class: "rounded-circle",
+                                src: contact.avatar.clone(),
+                                style: "width: 36px; height: 36px; object-fit: cover;"
+                            }
+                            span { class: "ms-3 flex-grow-1", {contact.name.clone()} }
+                            button {
+                                class: "btn btn-outline-primary btn-sm",
+                                onclick: {
+                                    let id = id.clone();
+                                    let contact = contact.clone();
+                                    move |_| error.set(circles.write().invite(&id, contact.clone()).err())
+                                },
+                                "Add"
+                            }
+                        }
+                    }
+                }
+            }
+
+            ul { class: "list-group mb-4",
+                for membership in circle.memberships() {
+                    li { key: "{membership.member.id}", class: "list-group-item d-flex align-items-center",
+                        img {
+                            class: "rounded-circle",
+                            src: membership.member.avatar.clone(),
+                            style: "width: 40px; height: 40px; object-fit: cover;"
+                        }
+                        div { class: "ms-3 flex-grow-1",
+                            div { class: "fw-bold",
+                                {membership.member.name.clone()}
+                                if membership.member.id == LOCAL_USER_ID {
+                                    span { class: "text-muted fw-normal", " (you)" }
+                                }
+                            }
+                            if let Some(kinship) = kinship_of(&membership.member.id) {
+                                div { class: "small text-primary",
+                                    i { class: "bi bi-diagram-3 me-1" }
+                                    {kinship}
+                                }
+                            }
+                            if circle.can_manage(LOCAL_USER_ID, Permission::ChangeRoles, &membership.member.id) {
+                                select {
+                                    class: "form-select form-select-sm mt-1",
+                                    style: "max-width: 10rem;",
+                                    onchange: {
+                                        let id = id.clone();
+                                        let member_id = membership.member.id.clone();
+                                        move |e: Event<FormData>| {
+                                            let role = Role::ALL.into_iter().find(|r| r.label() == e.value());
+                                            if let Some(role) = role {
+                                                error.set(circles.write().set_role(&id, &member_id, role).err());
+                                            }
+                                        }
+                                    },
+                                    for role in assignable.iter() {
+                                        option {
+                                            key: "{role.label()}",
+                                            value: role.label(),
+                                            selected: *role == membership.role,
+                                            {role.label()}
+                                        }
+                                    }
+                                }
+                            } else {
+                                span { class: "badge text-bg-secondary", {membership.role.label()} }
+                            }
+                        }
+                        if circle.can_manage(LOCAL_USER_ID, Permission::Remove, &membership.member.id) {
+                            button {
+                                class: "btn btn-outline-danger btn-sm",
+                                title: "Remove from circle",
+                                onclick: {
+                                    let id = id.clone();
+                                    let member_id = membership.member.id.clone();
+                                    move |_| error.set(circles.write().remove_member(&id, &member_id).err())
+                                },
+                                i { class: "bi bi-person-dash" }
+                            }
+                        }
+                    }
+                }
+            }
+
+            if can_invite {
+                InvitePanel { circle_id: id.clone() }
+            }
+
+            div { class: "card mb-3",
+                div { class: "card-body",
+                    h6 { class: "card-title", "Address book" }
+                    div { class: "d-flex gap-2",
+                        select {
+                            class: "form-select form-select-sm",
+                            style: "max-width: 8rem;",
+                            onchange: move |e: Event<FormData>| {
+                                if let Some(version) = VCardVersion::ALL.into_iter().find(|v| v.label() == e.value()) {
+                                    export_version.set(version);
+                                }
+                            },
+                            for version in VCardVersion::ALL {
+                                option {
+                                    key: "{version.label()}",
+                                    value: version.label(),
+                                    selected: export_version() == version,
+                                    "vCard {version.label()}"
+                                }
+                            }
+                        }
+                        a {
+                            class: "btn btn-outline-secondary btn-sm",
+                            href: export_url,
+                            download: "{circle.details.name}.vcf",
+                            i { class: "bi bi-download me-1" }
+                            "Export"
+                        }
+                        if can_invite {
+                            Link {
+                                to: Route::ImportContacts { id: id.clone() },
+                                class: "btn btn-outline-secondary btn-sm",
+                                i { class: "bi bi-upload me-1" }
+                                "Import"
+                            }
+                        }
+                    }
+                }
+            }
+
+            if let Some(role) = my_role {
+                div { class: "card mb-3",
+                    div { class: "card-body",
+                        h6 { class: "card-title", "As {role.label().to_lowercase()} you can" }
+                        ul { class: "list-unstyled small mb-0",
+                            for permission in Permission::ALL {
+                                li { key: "{permission.label()}",
+                                    i { class: if role.can(permission) { "bi bi-check-circle text-success me-2" } else { "bi bi-x-circle text-muted me-2" } }
+                                    {permission.label()}
+                                }
+                            }
+                        }
+                    }
+                }
+                if role != Role::Owner {
+                    button {
+                        class: "btn btn-outline-danger w-100",
+                        onclick: move |_| match circles.write().leave(&id) {
+                            Ok(()) => {
+                                navigator.push(Route::Circles {});
+                            }
+                            Err(e) => error.set(Some(e)),
+                        },
+                        "Leave circle"
+                    }
+                }
+            }
+        }
+    }
+}
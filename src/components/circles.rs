@@ -1,20 +1,162 @@
+use std::collections::BTreeSet;
+
 use dioxus::prelude::*;
+use crate::components::circle_discovery::CircleDiscovery;
+use crate::components::circle_editor::CircleEditor;
+use crate::models::call::{CallKind, CallParticipant};
+use crate::models::circle::{Circle, Permission, Visibility};
+use crate::models::LOCAL_USER_ID;
+use crate::Route;
+use crate::services::calls::use_calls;
+use crate::services::repositories::{use_circle_repo, use_feed_repo};
 
+// One row of the circle tree. Sub-circles are indented under their parent,
+// which can fold them away.
 #[component]
-fn CirclePreview(name: String, description: String, member_count: i32, icon: String) -> Element {
+fn CirclePreview(
+    circle: Circle,
+    depth: usize,
+    sub_circles: usize,
+    collapsed: bool,
+    ontoggle: EventHandler<String>,
+    onedit: EventHandler<String>,
+) -> Element {
+    let mut calls = use_calls();
+    let mut circles = use_circle_repo();
+    let feed = use_feed_repo();
+    let mut menu_open = use_signal(|| false);
+    let mut confirming = use_signal(|| false);
+    let id = circle.id.clone();
+    let title = circle.details.name.clone();
+    let members: Vec<CallParticipant> = circle
+        .active_members()
+        .into_iter()
+        .filter(|m| m.id != LOCAL_USER_ID)
+        .map(|m| CallParticipant { id: m.id, name: m.name, avatar: m.avatar })
+        .collect();
+    let member_count = circles.read().member_count(&circle.id);
+    let can_edit = circle.can(LOCAL_USER_ID, Permission::Rename) || circle.can(LOCAL_USER_ID, Permission::Invite);
+    let can_delete = circle.can(LOCAL_USER_ID, Permission::Delete);
+    let role = circle.role_of(LOCAL_USER_ID).map(|r| r.label()).unwrap_or_default();
+    let unread = feed.read().unread_count(&circle.id);
+
     rsx! {
-        div { class: "circle-preview",
+        div {
+            class: "circle-preview position-relative",
+            style: "padding-left: {15 + depth * 24}px;",
+            if sub_circles > 0 {
+                button {
+                    class: "btn btn-link btn-sm text-secondary px-1",
+                    title: if collapsed { "Show sub-circles" } else { "Hide sub-circles" },
+                    onclick: {
+                        let id = id.clone();
+                        move |_| ontoggle.call(id.clone())
+                    },
+                    i { class: if collapsed { "bi bi-chevron-right" } else { "bi bi-chevron-down" } }
+                }
+            } else {
+                span { class: "d-inline-block", style: "width: 26px;" }
+            }
             div { class: "circle-icon",
-                i { class: "bi {icon}" }
+                i { class: "bi {circle.details.icon}" }
             }
             div { class: "circle-content",
                 div { class: "circle-header",
-                    span { class: "name", {name} }
-                    span { class: "member-count", "{member_count} members" }
+                    Link {
+                        to: Route::CircleFeed { id: id.clone() },
+                        class: "name text-reset text-decoration-none",
+                        {circle.details.name.clone()}
+                        if unread > 0 {
+                            span { class: "badge rounded-pill text-bg-primary ms-2", "{unread}" }
+                        }
+                    }
+                    Link {
+                        to: Route::CircleMembers { id: id.clone() },
+                        class: "member-count text-decoration-none",
+                        "{member_count} members · {role}"
+                    }
                 }
-                p { class: "description", {description} }
+                p { class: "description", {circle.details.description.clone()} }
             }
-            
+            button {
+                class: "btn btn-link text-primary",
+                title: "Start group call",
+                onclick: {
+                    let room_id = format!("circle-{}", id);
+                    move |_| calls.join_room(&room_id, &title, CallKind::Video, members.clone())
+                },
+                i { class: "bi bi-camera-video fs-5" }
+            }
+            button {
+                class: "btn btn-link text-secondary",
+                title: "More",
+                onclick: move |_| {
+                    menu_open.toggle();
+                    confirming.set(false);
+                },
+                i { class: "bi bi-three-dots-vertical fs-5" }
+            }
+            if *menu_open.read() {
+                div {
+                    class: "dropdown-menu show position-absolute end-0 top-100 shadow-sm",
+                    style: "z-index: 10;",
+                    if *confirming.read() {
+                        div { class: "px-3 py-2 small",
+                            p { class: "mb-2", "Delete this circle for everyone?" }
+                            div { class: "d-flex gap-2",
+                                button {
+                                    class: "btn btn-danger btn-sm",
+                                    onclick: {
+                                        let id = id.clone();
+                                        move |_| {
+                                            if let Err(e) = circles.write().delete(&id) {
+                                                log::warn!("Could not delete circle: {}", e);
+                                            }
+                                            menu_open.set(false);
+                                        }
+                                    },
+                                    "Delete"
+                                }
+                                button {
+                                    class: "btn btn-light btn-sm",
+                                    onclick: move |_| confirming.set(false),
+                                    "Cancel"
+                                }
+                            }
+                        }
+                    } else {
+                        if can_edit {
+                            button {
+                                class: "dropdown-item",
+                                onclick: {
+                                    let id = id.clone();
+                                    move |_| {
+                                        menu_open.set(false);
+                                        onedit.call(id.clone());
+                                    }
+                                },
+                                i { class: "bi bi-pencil me-2" }
+                                "Edit"
+                            }
+                        }
+                        Link {
+                            class: "dropdown-item",
+                            to: Route::CircleMembers { id: id.clone() },
+                            i { class: "bi bi-people me-2" }
+                            "Members"
+                        }
+                        if can_delete {
+                            button {
+                                class: "dropdown-item text-danger",
+                                onclick: move |_| confirming.set(true),
+                                i { class: "bi bi-trash me-2" }
+                                "Delete"
+                            }
+                        }
+                    }
+                }
+            }
+
             style {
                 "
                 .circle-preview {{
@@ -61,78 +203,64 @@ fn CirclePreview(name: String, description: String, member_count: i32, icon: Str
 }
 
 #[component]
-fn PersonalCirclesTab() -> Element {
-    rsx! {
-        div { class: "circles-tab",
-            CirclePreview {
-                name: "Contacts".to_string(),
-                description: "Your personal contacts list".to_string(),
-                member_count: 42,
-                icon: "bi-person-lines-fill".to_string()
-            }
-            CirclePreview {
-                name: "Family".to_string(),
-                description: "Close family members".to_string(),
-                member_count: 8,
-                icon: "bi-house-heart".to_string()
-            }
-            CirclePreview {
-                name: "Work".to_string(),
-                description: "Professional contacts".to_string(),
-                member_count: 17,
-                icon: "bi-briefcase".to_string()
-            }
-        }
-    }
-}
+fn CircleList(visibility: Visibility, onedit: EventHandler<String>) -> Element {
+    let circles = use_circle_repo();
+    let mut collapsed = use_signal(BTreeSet::<String>::new);
+    let list = circles.read().with_visibility(visibility);
+    let (empty_title, empty_text) = match visibility {
+        Visibility::Personal => ("You don't have any personal circles yet", "Group your contacts with the + button above"),
+        Visibility::Private => ("You don't have any private circles yet", "Create one with the + button above"),
+        Visibility::Public => ("You haven't joined any public circles yet", "Find one to join below"),
+    };
 
-#[component]
-fn PrivateCirclesTab() -> Element {
-    rsx! {
-        div { class: "circles-tab",
-            CirclePreview {
-                name: "Project Alpha".to_string(),
-                description: "Development team for Project Alpha".to_string(),
-                member_count: 6,
-                icon: "bi-lock".to_string()
-            }
-            CirclePreview {
-                name: "Book Club".to_string(),
-                description: "Monthly book discussions".to_string(),
-                member_count: 12,
-                icon: "bi-book".to_string()
-            }
-            
-            // Empty state if no private circles
-            div { class: "text-center py-3 text-muted d-none",
-                p { "You don't have any private circles yet" }
-                p { "Create one with the + button above" }
+    // Flatten the tree depth first, leaving out folded branches. A circle
+    // whose parent we're not in starts a tree of its own.
+    let mut rows: Vec<(Circle, usize, usize)> = Vec::new();
+    {
+        let repo = circles.read();
+        let shown: BTreeSet<&str> = list.iter().map(|c| c.id.as_str()).collect();
+        let mut stack: Vec<(Circle, usize)> = list
+            .iter()
+            .filter(|c| repo.parent_of(&c.id).is_none_or(|p| !shown.contains(p.id.as_str())))
+            .rev()
+            .map(|c| (c.clone(), 0))
+            .collect();
+        while let Some((circle, depth)) = stack.pop() {
+            let children: Vec<Circle> = repo
+                .children(&circle.id)
+                .into_iter()
+                .filter(|c| shown.contains(c.id.as_str()) && !rows.iter().any(|(r, _, _)| r.id == c.id))
+                .collect();
+            if !collapsed.read().contains(&circle.id) {
+                stack.extend(children.iter().rev().map(|c| (c.clone(), depth + 1)));
             }
+            rows.push((circle, depth, children.len()));
         }
     }
-}
 
-#[component]
-fn PublicCirclesTab() -> Element {
     rsx! {
         div { class: "circles-tab",
-            CirclePreview {
-                name: "Tech News".to_string(),
-                description: "Latest in technology and development".to_string(),
-                member_count: 156,
-                icon: "bi-globe".to_string()
-            }
-            CirclePreview {
-                name: "Rust Community".to_string(),
-                description: "Discussions about Rust programming".to_string(),
-                member_count: 324,
-                icon: "bi-gear".to_string()
+            for (circle, depth, sub_circles) in rows {
+                CirclePreview {
+                    key: "{circle.id}",
+                    collapsed: collapsed.read().contains(&circle.id),
+                    circle: circle.clone(),
+                    depth,
+                    sub_circles,
+                    ontoggle: move |id: String| {
+                        let mut collapsed = collapsed.write();
+                        if !collapsed.remove(&id) {
+                            collapsed.insert(id);
+                        }
+                    },
+                    onedit,
+                }
             }
-            
-            // Empty state if no public circles
-            div { class: "text-center py-3 text-muted d-none",
-                p { "You haven't joined any public circles yet" }
-                p { "Discover public circles with the search feature" }
+            if list.is_empty() {
+                div { class: "text-center py-3 text-muted",
+                    p { {empty_title} }
+                    p { {empty_text} }
+                }
             }
         }
     }
@@ -141,6 +269,13 @@ fn PublicCirclesTab() -> Element {
 #[component]
 pub fn Circles() -> Element {
     let mut active_tab = use_signal(|| "personal");
+    // None while closed; Some(None) creates a circle, Some(Some(id)) edits one
+    let mut editing = use_signal(|| None::<Option<String>>);
+    let visibility = match *active_tab.read() {
+        "private" => Visibility::Private,
+        "public" => Visibility::Public,
+        _ => Visibility::Personal,
+    };
     
     rsx! {
         div { class: "circles-page p-3",
@@ -168,29 +303,34 @@ pub fn Circles() -> Element {
                     }
                 }
                 
-                // Plus icon button
+                // Calendar of every circle, then the plus icon button
+                Link {
+                    to: Route::Calendar {},
+                    class: "btn btn-outline-secondary rounded-circle ms-auto me-2",
+                    style: "width: 40px; height: 40px; padding: 0; line-height: 38px;",
+                    title: "Calendar",
+                    i { class: "bi bi-calendar3" }
+                }
                 button {
                     class: "btn btn-primary rounded-circle",
                     style: "width: 40px; height: 40px; padding: 0;",
-                    onclick: move |_| {
-                        match *active_tab.read() {
-                            "personal" => log::info!("Create personal circle"),
-                            "private" => log::info!("Create private circle"),
-                            "public" => log::info!("Join public circle"),
-                            _ => {}
-                        }
-                    },
+                    onclick: move |_| editing.set(Some(None)),
                     i { class: "bi bi-plus", style: "font-size: 1.5rem;" }
                 }
             }
             
             // Tab content
-            if *active_tab.read() == "personal" {
-                PersonalCirclesTab {}
-            } else if *active_tab.read() == "private" {
-                PrivateCirclesTab {}
-            } else {
-                PublicCirclesTab {}
+            CircleList { visibility, onedit: move |id| editing.set(Some(Some(id))) }
+            if visibility == Visibility::Public {
+                CircleDiscovery {}
+            }
+
+            if let Some(circle_id) = editing.read().clone() {
+                CircleEditor {
+                    circle_id,
+                    visibility,
+                    onclose: move |_| editing.set(None),
+                }
             }
             
             style {
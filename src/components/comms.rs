@@ -1,4 +1,9 @@
 use dioxus::prelude::*;
+use crate::Route;
+use crate::models::call::{CallDirection, CallGroup, CallKind, CallOutcome, CallParticipant};
+use crate::services::calls::use_calls;
+use crate::services::repositories::{use_call_repo, use_message_repo};
+use crate::utils::format::{duration, relative_time};
 
 #[component]
 fn MessagePreview(name: String, message: String, msg_time: String, unread: bool, avatar: String) -> Element {
@@ -50,90 +55,210 @@ fn MessagePreview(name: String, message: String, msg_time: String, unread: bool,
 
 #[component]
 fn MessagesTab() -> Element {
+    let repo = use_message_repo();
+    let repo = repo.read();
+
     rsx! {
         div { class: "messages-tab",
-            MessagePreview {
-                name: "Sarah Johnson",
-                message: "Hey, how's the project coming along?",
-                msg_time: "10:30 AM",
-                unread: true,
-                avatar: "https://i.pravatar.cc/150?img=1"
+            for conversation in repo.recent_conversations() {
+                Link {
+                    key: "{conversation.id}",
+                    to: Route::Conversation { id: conversation.id.clone() },
+                    class: "text-reset text-decoration-none",
+                    MessagePreview {
+                        name: conversation.name.clone(),
+                        message: repo.last_message(&conversation.id).map_or(String::new(), |m| {
+                            if m.is_deleted() { "Message deleted".to_string() } else { m.text().to_string() }
+                        }),
+                        msg_time: repo.last_message(&conversation.id).map_or(String::new(), |m| relative_time(m.sent_at)),
+                        unread: repo.has_unread(&conversation.id),
+                        avatar: conversation.avatar.clone()
+                    }
+                }
             }
-            MessagePreview {
-                name: "David Lee",
-                message: "I've sent you the files you requested",
-                msg_time: "Yesterday",
-                unread: false,
-                avatar: "https://i.pravatar.cc/150?img=2"
+        }
+    }
+}
+
+#[component]
+fn CallLogEntry(group: CallGroup) -> Element {
+    let mut calls = use_calls();
+    let call = group.latest;
+    let missed = call.direction == CallDirection::Incoming && call.outcome == CallOutcome::Missed;
+    let (icon, label) = match (call.direction, call.outcome) {
+        (CallDirection::Incoming, CallOutcome::Missed) => ("bi-telephone-x text-danger", "Missed"),
+        (CallDirection::Incoming, CallOutcome::Declined) => ("bi-telephone-minus text-warning", "Declined"),
+        (CallDirection::Incoming, CallOutcome::Completed) => ("bi-telephone-inbound text-success", "Incoming"),
+        (CallDirection::Outgoing, CallOutcome::Completed) => ("bi-telephone-outbound text-success", "Outgoing"),
+        (CallDirection::Outgoing, _) => ("bi-telephone-outbound text-muted", "No answer"),
+    };
+    let detail = if call.outcome == CallOutcome::Completed {
+        format!("{} · {}", label, duration(call.duration_secs))
+    } else {
+        label.to_string()
+    };
+    let avatar = call.participants.first().map(|p| p.avatar.clone()).unwrap_or_default();
+    let title = if group.count > 1 { format!("{} ({})", call.title(), group.count) } else { call.title() };
+    let call_back = call.participants.first().cloned();
+    let kind = call.kind;
+    let call_back_icon = match call.kind {
+        CallKind::Audio => "bi bi-telephone",
+        CallKind::Video => "bi bi-camera-video",
+    };
+
+    rsx! {
+        div {
+            class: "d-flex align-items-center p-2 border-bottom",
+            img {
+                class: "rounded-circle",
+                src: avatar,
+                style: "width: 50px; height: 50px; object-fit: cover;"
             }
-            MessagePreview {
-                name: "Tech Team",
-                message: "Meeting scheduled for tomorrow at 2 PM",
-                msg_time: "Yesterday",
-                unread: false,
-                avatar: "https://i.pravatar.cc/150?img=3"
+            div {
+                class: "ms-3 flex-grow-1",
+                span {
+                    class: if missed { "fw-bold text-danger" } else { "fw-bold" },
+                    {title}
+                }
+                div {
+                    class: "small text-muted",
+                    i { class: "bi {icon} me-1" }
+                    {detail}
+                    " · "
+                    {relative_time(call.started_at)}
+                }
             }
-            MessagePreview {
-                name: "Alex Wong",
-                message: "Thanks for your help!",
-                msg_time: "Monday",
-                unread: false,
-                avatar: "https://i.pravatar.cc/150?img=4"
+            button {
+                class: "btn btn-link text-primary",
+                title: "Call back",
+                onclick: move |_| {
+                    if let Some(peer) = call_back.clone() {
+                        calls.dial(peer, kind);
+                    }
+                },
+                i { class: "{call_back_icon} fs-5" }
             }
         }
     }
 }
 
 #[component]
-fn AudioCallsTab() -> Element {
+fn CallLog(kind: CallKind) -> Element {
+    let calls = use_call_repo();
+    let groups = calls.read().log(kind);
+    let (empty_icon, empty_title, empty_text) = match kind {
+        CallKind::Audio => ("bi-telephone-x", "No recent audio calls", "Your audio call history will appear here"),
+        CallKind::Video => ("bi-camera-video-off", "No recent video calls", "Your video call history will appear here"),
+    };
+
     rsx! {
-        div { class: "p-3",
-            // Placeholder for audio call history
-            div {
-                class: "text-center py-5",
-                i {
-                    class: "bi bi-telephone-x display-1 text-muted"
-                }
-                p {
-                    class: "mt-3 fw-bold",
-                    "No recent audio calls"
+        if groups.is_empty() {
+            div { class: "p-3",
+                div {
+                    class: "text-center py-5",
+                    i {
+                        class: "bi {empty_icon} display-1 text-muted"
+                    }
+                    p {
+                        class: "mt-3 fw-bold",
+                        {empty_title}
+                    }
+                    p {
+                        class: "text-muted",
+                        {empty_text}
+                    }
                 }
-                p {
-                    class: "text-muted",
-                    "Your audio call history will appear here"
+            }
+        } else {
+            div { class: "calls-tab",
+                for group in groups {
+                    CallLogEntry { key: "{group.latest.id}", group: group.clone() }
                 }
             }
         }
     }
 }
 
+// Choose who to call from the people we have conversations with
 #[component]
-fn VideoCallsTab() -> Element {
+fn ContactPicker(kind: CallKind, onclose: EventHandler<()>) -> Element {
+    let repo = use_message_repo();
+    let mut calls = use_calls();
+    let title = match kind {
+        CallKind::Audio => "New audio call",
+        CallKind::Video => "New video call",
+    };
+
     rsx! {
-        div { class: "p-3",
-            // Placeholder for video call history
-            div {
-                class: "text-center py-5",
-                i {
-                    class: "bi bi-camera-video-off display-1 text-muted"
-                }
-                p {
-                    class: "mt-3 fw-bold",
-                    "No recent video calls"
+        div { class: "mx-3 mb-3 border rounded-3 overflow-hidden",
+            div { class: "d-flex justify-content-between align-items-center px-3 py-2 border-bottom",
+                span { class: "fw-bold", {title} }
+                button { class: "btn btn-link btn-sm text-muted", onclick: move |_| onclose.call(()),
+                    i { class: "bi bi-x fs-5" }
                 }
-                p {
-                    class: "text-muted",
-                    "Your video call history will appear here"
+            }
+            for conversation in repo.read().recent_conversations() {
+                button {
+                    key: "{conversation.id}",
+                    class: "list-group-item list-group-item-action d-flex align-items-center w-100 border-0 px-3 py-2",
+                    onclick: {
+                        let peer = CallParticipant {
+                            id: conversation.id.clone(),
+                            name: conversation.name.clone(),
+                            avatar: conversation.avatar.clone(),
+                        };
+                        move |_| {
+                            calls.dial(peer.clone(), kind);
+                            onclose.call(());
+                        }
+                    },
+                    img {
+                        class: "rounded-circle",
+                        src: conversation.avatar.clone(),
+                        style: "width: 36px; height: 36px; object-fit: cover;"
+                    }
+                    span { class: "ms-3", {conversation.name.clone()} }
                 }
             }
         }
     }
 }
 
+#[component]
+fn AudioCallsTab() -> Element {
+    rsx! {
+        CallLog { kind: CallKind::Audio }
+    }
+}
+
+#[component]
+fn VideoCallsTab() -> Element {
+    rsx! {
+        CallLog { kind: CallKind::Video }
+    }
+}
+
+#[component]
+fn MissedBadge(count: usize) -> Element {
+    rsx! {
+        if count > 0 {
+            span {
+                class: "position-absolute top-0 start-100 translate-middle badge rounded-pill bg-danger",
+                style: "font-size: 0.6rem;",
+                "{count}"
+            }
+        }
+    }
+}
+
 // Add this component to export Comms for the router
 #[component]
 pub fn Comms() -> Element {
     let mut active_tab = use_signal(|| "messages");
+    let mut picking = use_signal(|| false);
+    let mut calls = use_call_repo();
+    let missed_audio = calls.read().unseen_missed(CallKind::Audio);
+    let missed_video = calls.read().unseen_missed(CallKind::Video);
 
     rsx! {
         div {
@@ -149,7 +274,10 @@ pub fn Comms() -> Element {
                         } else {
                             "nav-link text-secondary"
                         },
-                        onclick: move |_| active_tab.set("messages"),
+                        onclick: move |_| {
+                            active_tab.set("messages");
+                            picking.set(false);
+                        },
                         i { class: "bi bi-chat-dots fs-5" }
                     }
                     button {
@@ -158,8 +286,14 @@ pub fn Comms() -> Element {
                         } else {
                             "nav-link text-secondary"
                         },
-                        onclick: move |_| active_tab.set("audio"),
-                        i { class: "bi bi-telephone fs-5" }
+                        onclick: move |_| {
+                            active_tab.set("audio");
+                            calls.write().mark_missed_seen(CallKind::Audio);
+                        },
+                        span { class: "position-relative",
+                            i { class: "bi bi-telephone fs-5" }
+                            MissedBadge { count: missed_audio }
+                        }
                     }
                     button {
                         class: if *active_tab.read() == "video" {
@@ -167,8 +301,14 @@ pub fn Comms() -> Element {
                         } else {
                             "nav-link text-secondary"
                         },
-                        onclick: move |_| active_tab.set("video"),
-                        i { class: "bi bi-camera-video fs-5" }
+                        onclick: move |_| {
+                            active_tab.set("video");
+                            calls.write().mark_missed_seen(CallKind::Video);
+                        },
+                        span { class: "position-relative",
+                            i { class: "bi bi-camera-video fs-5" }
+                            MissedBadge { count: missed_video }
+                        }
                     }
                 }
 
@@ -179,8 +319,7 @@ pub fn Comms() -> Element {
                     onclick: move |_| {
                         match *active_tab.read() {
                             "messages" => log::info!("New message button clicked"),
-                            "audio" => log::info!("New audio call button clicked"),
-                            "video" => log::info!("New video call button clicked"),
+                            "audio" | "video" => picking.toggle(),
                             _ => {}
                         }
                     },
@@ -188,6 +327,13 @@ pub fn Comms() -> Element {
                 }
             }
 
+            if *picking.read() {
+                ContactPicker {
+                    kind: if *active_tab.read() == "video" { CallKind::Video } else { CallKind::Audio },
+                    onclose: move |_| picking.set(false),
+                }
+            }
+
             // Tab content container with appropriate background
             div {
                 class: "rounded-3 mx-3 overflow-hidden bg-body-tertiary",
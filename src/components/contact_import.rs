@@ -0,0 +1,180 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::components::csv_import::CsvWizard;
+use crate::services::repositories::circle_repo::{ImportAction, ImportCandidate, ImportSummary};
+use crate::services::repositories::use_circle_repo;
+use crate::services::vcard;
+
+// Bring contacts in from .vcf or spreadsheet .csv files: pick files, map the
+// spreadsheet's columns, review possible duplicates, then add the rest to the
+// circle
+#[component]
+pub fn ImportContacts(id: String) -> Element {
+    let mut circles = use_circle_repo();
+    let mut candidates = use_signal(|| None::<Vec<ImportCandidate>>);
+    let mut summary = use_signal(|| None::<ImportSummary>);
+    let mut error = use_signal(|| None::<String>);
+    let mut spreadsheet = use_signal(|| None::<(String, Vec<u8>)>);
+    // The wizard can send spreadsheet rows to another circle
+    let mut target = use_signal(|| id.clone());
+    let Some(circle) = circles.read().circle(&target()).cloned() else {
+        return rsx! {
+            div { class: "container mt-5",
+                p { "This circle no longer exists." }
+                Link { to: Route::Circles {}, class: "btn btn-primary", "Back to Circles" }
+            }
+        };
+    };
+
+    let read_files = move |evt: Event<FormData>| async move {
+        let Some(files) = evt.files() else {
+            return;
+        };
+        let mut cards = Vec::new();
+        let mut problems = Vec::new();
+        for name in files.files() {
+            let lower = name.to_lowercase();
+            if [".csv", ".tsv", ".txt"].iter().any(|ext| lower.ends_with(ext)) {
+                match files.read_file(&name).await {
+                    Some(bytes) => spreadsheet.set(Some((name, bytes))),
+                    None => problems.push(format!("{}: couldn't read the file", name)),
+                }
+                continue;
+            }
+            match files.read_file_to_string(&name).await.map(|text| vcard::parse(&text)) {
+                Some(Ok(mut parsed)) => cards.append(&mut parsed),
+                Some(Err(e)) => problems.push(format!("{}: {}", name, e)),
+                None => problems.push(format!("{}: couldn't read the file", name)),
+            }
+        }
+        error.set((!problems.is_empty()).then(|| problems.join("\n")));
+        if !cards.is_empty() {
+            candidates.set(Some(circles.read().review_import(cards)));
+        }
+    };
+
+    let import = move |_| {
+        let Some(reviewed) = candidates.take() else {
+            return;
+        };
+        let result = circles.write().import(&target(), reviewed);
+        match result {
+            Ok(done) => summary.set(Some(done)),
+            Err(e) => error.set(Some(e)),
+        }
+    };
+
+    let to_import = candidates
+        .read()
+        .as_ref()
+        .map_or(0, |c| c.iter().filter(|c| c.action != ImportAction::Skip).count());
+
+    rsx! {
+        div { class: "p-3",
+            div { class: "d-flex align-items-center mb-3",
+                Link { to: Route::CircleMembers { id: id.clone() }, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                div { class: "ms-1",
+                    div { class: "fw-bold", "Import contacts" }
+                    div { class: "small text-muted", "into {circle.details.name}" }
+                }
+            }
+
+            if let Some(message) = error.read().clone() {
+                div { class: "alert alert-warning py-2 small", style: "white-space: pre-line;", {message} }
+            }
+
+            if let Some(done) = summary() {
+                div { class: "text-center py-4",
+                    i { class: "bi bi-check-circle text-success fs-1" }
+                    p { class: "mt-2",
+                        "Added {done.added}, merged {done.merged}, skipped {done.skipped}"
+                    }
+                    Link { to: Route::CircleMembers { id: target() }, class: "btn btn-primary", "See members" }
+                }
+            } else if let Some(list) = candidates.read().clone() {
+                p { class: "small text-muted",
+                    "{list.len()} contacts found. Check the ones that look like people you already have."
+                }
+                ul { class: "list-group mb-3",
+                    for (index, candidate) in list.into_iter().enumerate() {
+                        li { key: "{index}", class: "list-group-item",
+                            div { class: "d-flex align-items-center",
+                                div { class: "flex-grow-1",
+                                    div { class: "fw-bold", {candidate.card.name.clone()} }
+                                    div { class: "small text-muted",
+                                        {candidate.card.phones.iter().chain(candidate.card.emails.iter()).cloned().collect::<Vec<_>>().join(" · ")}
+                                    }
+                                }
+                                select {
+                                    class: "form-select form-select-sm",
+                                    style: "max-width: 9rem;",
+                                    onchange: move |e: Event<FormData>| {
+                                        let action = ImportAction::ALL.into_iter().find(|a| a.label() == e.value());
+                                        if let (Some(action), Some(list)) = (action, candidates.write().as_mut()) {
+                                            list[index].action = action;
+                                        }
+                                    },
+                                    for action in ImportAction::ALL {
+                                        if action != ImportAction::Merge || candidate.duplicate.is_some() {
+                                            option {
+                                                key: "{action.label()}",
+                                                value: action.label(),
+                                                selected: candidate.action == action,
+                                                {action.label()}
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            if let Some((member, reason)) = candidate.duplicate.clone() {
+                                div { class: "d-flex align-items-center small mt-2 text-warning-emphasis",
+                                    img {
+                                        class: "rounded-circle me-2",
+                                        src: member.avatar.clone(),
+                                        style: "width: 24px; height: 24px; object-fit: cover;"
+                                    }
+                                    "Looks like {member.name} · {reason.label().to_lowercase()}"
+                                }
+                            }
+                        }
+                    }
+                }
+                div { class: "d-flex gap-2",
+                    button { class: "btn btn-secondary flex-grow-1", onclick: move |_| candidates.set(None), "Cancel" }
+                    button {
+                        class: "btn btn-primary flex-grow-1",
+                        disabled: to_import == 0,
+                        onclick: import,
+                        "Import {to_import}"
+                    }
+                }
+            } else if let Some((file_name, bytes)) = spreadsheet() {
+                CsvWizard {
+                    file_name,
+                    bytes,
+                    circle_id: target(),
+                    ondone: move |(circle_id, cards): (String, Vec<_>)| {
+                        target.set(circle_id);
+                        spreadsheet.set(None);
+                        candidates.set(Some(circles.read().review_import(cards)));
+                    },
+                    oncancel: move |_| spreadsheet.set(None),
+                }
+            } else {
+                div { class: "border rounded-3 p-4 text-center",
+                    i { class: "bi bi-person-vcard fs-1 text-secondary" }
+                    p { class: "mt-2", "Choose vCard (.vcf) files from your phone or address book, or a .csv spreadsheet" }
+                    input {
+                        class: "form-control",
+                        r#type: "file",
+                        accept: ".vcf,.csv,.tsv,text/vcard,text/x-vcard,text/csv",
+                        multiple: true,
+                        onchange: read_files,
+                    }
+                }
+            }
+        }
+    }
+}
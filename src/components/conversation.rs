@@ -0,0 +1,345 @@
+use dioxus::prelude::*;
+use crate::Route;
+use crate::models::LOCAL_USER_ID;
+use crate::models::message::{Message, QUICK_REACTIONS};
+use crate::services::crypto::safety_number::display_groups;
+use crate::services::repositories::{use_key_repo, use_message_repo};
+use crate::utils::format::{clock_time, relative_time};
+
+#[component]
+fn MessageBubble(
+    message: Message,
+    on_reply: EventHandler<String>,
+    on_edit: EventHandler<String>,
+) -> Element {
+    let mut repo = use_message_repo();
+    let mut show_reactions = use_signal(|| false);
+    let mut show_history = use_signal(|| false);
+
+    let mine = message.sender_id == LOCAL_USER_ID;
+    let id = message.id.clone();
+    let align = if mine { "align-items-end" } else { "align-items-start" };
+    let bubble = if mine { "bg-primary text-white" } else { "bg-body border" };
+
+    if message.is_deleted() {
+        return rsx! {
+            div { class: "d-flex flex-column {align} mb-2",
+                div { class: "rounded-3 px-3 py-2 border text-muted fst-italic small",
+                    i { class: "bi bi-slash-circle me-1" }
+                    if mine { "You deleted this message" } else { "This message was deleted" }
+                }
+            }
+        };
+    }
+
+    // The quoted message may have been deleted since the reply was written
+    let quote = message.reply_to.clone().map(|q| {
+        let gone = repo.read().message(&q.message_id).is_some_and(|m| m.is_deleted());
+        (q.sender_name, if gone { "Original message deleted".to_string() } else { q.excerpt })
+    });
+    let reactions = message.reaction_counts(LOCAL_USER_ID);
+    let history: Vec<(u64, String)> = message
+        .history()
+        .into_iter()
+        .map(|(at, body)| (at, body.to_string()))
+        .collect();
+
+    rsx! {
+        div { class: "d-flex flex-column {align} mb-2",
+            if !mine {
+                span { class: "small text-muted ms-1", {message.sender_name.clone()} }
+            }
+            div { class: "rounded-3 px-3 py-2 {bubble}", style: "max-width: 75%;",
+                if let Some((sender, excerpt)) = quote {
+                    div { class: "border-start border-3 ps-2 mb-1 small opacity-75",
+                        div { class: "fw-bold", {sender} }
+                        div { class: "text-truncate", {excerpt} }
+                    }
+                }
+                span { {message.text().to_string()} }
+            }
+            div { class: "d-flex align-items-center gap-2 small text-muted mt-1",
+                span { {clock_time(message.sent_at)} }
+                if message.is_edited() {
+                    button {
+                        class: "btn btn-link btn-sm p-0 text-muted text-decoration-none small",
+                        title: "Show edit history",
+                        onclick: move |_| {
+                            let current = *show_history.read();
+                            show_history.set(!current);
+                        },
+                        "edited"
+                    }
+                }
+                for reaction in reactions {
+                    button {
+                        class: if reaction.mine { "badge rounded-pill text-bg-primary border-0" } else { "badge rounded-pill text-bg-light border" },
+                        onclick: {
+                            let id = id.clone();
+                            let emoji = reaction.emoji.clone();
+                            move |_| repo.write().toggle_reaction(&id, &emoji)
+                        },
+                        "{reaction.emoji} {reaction.count}"
+                    }
+                }
+                button {
+                    class: "btn btn-link btn-sm p-0 text-muted",
+                    title: "React",
+                    onclick: move |_| {
+                        let current = *show_reactions.read();
+                        show_reactions.set(!current);
+                    },
+                    i { class: "bi bi-emoji-smile" }
+                }
+                button {
+                    class: "btn btn-link btn-sm p-0 text-muted",
+                    title: "Reply",
+                    onclick: {
+                        let id = id.clone();
+                        move |_| on_reply.call(id.clone())
+                    },
+                    i { class: "bi bi-reply" }
+                }
+                if mine {
+                    button {
+                        class: "btn btn-link btn-sm p-0 text-muted",
+                        title: "Edit",
+                        onclick: {
+                            let id = id.clone();
+                            move |_| on_edit.call(id.clone())
+                        },
+                        i { class: "bi bi-pencil" }
+                    }
+                    button {
+                        class: "btn btn-link btn-sm p-0 text-muted",
+                        title: "Delete for everyone",
+                        onclick: {
+                            let id = id.clone();
+                            move |_| repo.write().delete_for_everyone(&id)
+                        },
+                        i { class: "bi bi-trash" }
+                    }
+                }
+            }
+            if *show_reactions.read() {
+                div { class: "d-flex gap-1 mt-1",
+                    for emoji in QUICK_REACTIONS {
+                        button {
+                            class: "btn btn-light btn-sm",
+                            onclick: {
+                                let id = id.clone();
+                                move |_| {
+                                    repo.write().toggle_reaction(&id, emoji);
+                                    show_reactions.set(false);
+                                }
+                            },
+                            {emoji}
+                        }
+                    }
+                }
+            }
+            if *show_history.read() {
+                ul { class: "list-group list-group-flush small mt-1", style: "max-width: 75%;",
+                    for (at, body) in history {
+                        li { class: "list-group-item bg-transparent px-1 py-1",
+                            span { class: "text-muted me-2", {relative_time(at)} }
+                            {body}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn Conversation(id: String) -> Element {
+    let mut repo = use_message_repo();
+    let mut draft = use_signal(String::new);
+    let mut replying_to = use_signal(|| None::<String>);
+    let mut editing = use_signal(|| None::<String>);
+    let mut keys = use_key_repo();
+    let mut show_safety_number = use_signal(|| false);
+
+    // Opening the conversation marks it as read
+    let read_id = id.clone();
+    use_effect(use_reactive!(|read_id| repo.write().mark_read(&read_id)));
+
+    let Some(conversation) = repo.read().conversation(&id).cloned() else {
+        return rsx! {
+            div { class: "container mt-5",
+                p { "This conversation no longer exists." }
+                Link { to: Route::Comms {}, class: "btn btn-primary", "Back to Comms" }
+            }
+        };
+    };
+    let thread = repo.read().thread(&id);
+    // Only available once a session has given us the contact's identity key
+    let safety_number = keys.read().material().and_then(|m| {
+        m.safety_number_with(LOCAL_USER_ID, &id)
+            .map(|number| (display_groups(&number), m.is_verified(&id)))
+    });
+    // None while the keys are locked; messages wait in the outbox until then
+    let sessions_ready = keys
+        .read()
+        .material()
+        .map(|m| conversation.recipients().iter().all(|peer| m.has_session(peer)));
+    let reply_preview = replying_to
+        .read()
+        .as_ref()
+        .and_then(|m| repo.read().message(m).cloned());
+
+    let submit = {
+        let id = id.clone();
+        move |_| {
+            let text = draft.read().trim().to_string();
+            if text.is_empty() {
+                return;
+            }
+            if let Some(message_id) = editing.take() {
+                repo.write().edit(&message_id, &text);
+            } else {
+                let reply = replying_to.take();
+                repo.write().send(&id, &text, reply.as_deref());
+            }
+            draft.set(String::new());
+        }
+    };
+
+    rsx! {
+        div { class: "d-flex flex-column h-100",
+            // Header
+            div { class: "d-flex align-items-center p-2 border-bottom",
+                Link { to: Route::Comms {}, class: "btn btn-link text-secondary",
+                    i { class: "bi bi-chevron-left fs-5" }
+                }
+                img {
+                    class: "rounded-circle",
+                    src: conversation.avatar.clone(),
+                    style: "width: 40px; height: 40px; object-fit: cover;"
+                }
+                span { class: "ms-2 fw-bold", {conversation.name.clone()} }
+                if let Some((_, verified)) = &safety_number {
+                    button {
+                        class: "btn btn-link ms-auto",
+                        title: "Verify safety number",
+                        onclick: move |_| {
+                            let current = *show_safety_number.read();
+                            show_safety_number.set(!current);
+                        },
+                        i { class: if *verified { "bi bi-shield-check text-success fs-5" } else { "bi bi-shield-exclamation text-secondary fs-5" } }
+                    }
+                }
+            }
+
+            // Safety number verification
+            if let Some((groups, verified)) = safety_number.filter(|_| *show_safety_number.read()) {
+                div { class: "border-bottom p-3 small",
+                    p { class: "text-muted mb-2",
+                        "Compare these numbers with {conversation.name} in person or over a call. If they match, the keys your messages are encrypted with belong to {conversation.name} and not to someone in between."
+                    }
+                    div { class: "font-monospace d-flex flex-wrap gap-2 fs-6 mb-2",
+                        for group in groups {
+                            span { {group} }
+                        }
+                    }
+                    div { class: "form-check form-switch",
+                        input {
+                            class: "form-check-input",
+                            r#type: "checkbox",
+                            id: "markVerified",
+                            checked: verified,
+                            onchange: {
+                                let id = id.clone();
+                                move |evt: FormEvent| {
+                                    if let Err(e) = keys.write().update(|m| m.set_verified(&id, evt.checked())) {
+                                        log::error!("Failed to save verification: {}", e);
+                                    }
+                                }
+                            }
+                        }
+                        label { class: "form-check-label", r#for: "markVerified", "Mark as verified" }
+                    }
+                }
+            }
+
+            // Encryption status
+            match sessions_ready {
+                None => rsx! {
+                    div { class: "border-bottom px-3 py-2 small text-muted",
+                        i { class: "bi bi-lock me-1" }
+                        "Messages are kept here until you unlock your encryption keys in "
+                        Link { to: Route::Settings {}, "Settings" }
+                        "."
+                    }
+                },
+                Some(false) => rsx! {
+                    div { class: "border-bottom px-3 py-2 small text-muted",
+                        i { class: "bi bi-hourglass-split me-1" }
+                        "Messages go out once an encrypted session with {conversation.name} is set up."
+                    }
+                },
+                Some(true) => rsx! {},
+            }
+
+            // Thread
+            div { class: "flex-grow-1 overflow-auto p-3",
+                for message in thread {
+                    MessageBubble {
+                        key: "{message.id}",
+                        message: message.clone(),
+                        on_reply: move |message_id| {
+                            editing.set(None);
+                            replying_to.set(Some(message_id));
+                        },
+                        on_edit: move |message_id: String| {
+                            let text = repo.read().message(&message_id).map(|m| m.text().to_string());
+                            replying_to.set(None);
+                            draft.set(text.unwrap_or_default());
+                            editing.set(Some(message_id));
+                        },
+                    }
+                }
+            }
+
+            // Composer
+            div { class: "border-top p-2",
+                if let Some(original) = reply_preview {
+                    div { class: "d-flex justify-content-between align-items-center small border-start border-3 border-primary ps-2 mb-2",
+                        div { class: "text-truncate",
+                            span { class: "fw-bold me-1", "Replying to {original.sender_name}:" }
+                            {original.text().to_string()}
+                        }
+                        button { class: "btn btn-link btn-sm text-muted", onclick: move |_| replying_to.set(None),
+                            i { class: "bi bi-x" }
+                        }
+                    }
+                }
+                if editing.read().is_some() {
+                    div { class: "d-flex justify-content-between align-items-center small text-muted mb-2",
+                        span { i { class: "bi bi-pencil me-1" } "Editing message" }
+                        button {
+                            class: "btn btn-link btn-sm text-muted",
+                            onclick: move |_| {
+                                editing.set(None);
+                                draft.set(String::new());
+                            },
+                            i { class: "bi bi-x" }
+                        }
+                    }
+                }
+                form { class: "d-flex gap-2", onsubmit: submit,
+                    input {
+                        class: "form-control",
+                        placeholder: "Message",
+                        value: "{draft.read()}",
+                        oninput: move |evt| draft.set(evt.value())
+                    }
+                    button { class: "btn btn-primary", r#type: "submit",
+                        i { class: if editing.read().is_some() { "bi bi-check-lg" } else { "bi bi-send" } }
+                    }
+                }
+            }
+        }
+    }
+}
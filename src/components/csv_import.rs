@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+
+use dioxus::prelude::*;
+use crate::models::circle::{Circle, Permission, Visibility};
+use crate::models::contact::ContactCard;
+use crate::models::LOCAL_USER_ID;
+use crate::services::csv::{self, ContactField, Delimiter, Encoding};
+use crate::services::repositories::use_circle_repo;
+
+// Rows shown on the preview step; the rest are counted
+const PREVIEW_ROWS: usize = 50;
+
+// Turn a spreadsheet export into contact cards: check how the file was read,
+// say which column holds what, then see which rows are ready to import
+#[component]
+pub fn CsvWizard(
+    file_name: String,
+    bytes: Vec<u8>,
+    circle_id: String,
+    ondone: EventHandler<(String, Vec<ContactCard>)>,
+    oncancel: EventHandler<()>,
+) -> Element {
+    let circles = use_circle_repo();
+    let detected = Encoding::detect(&bytes);
+    let mut encoding = use_signal(|| detected);
+    let mut delimiter = {
+        let bytes = bytes.clone();
+        use_signal(move || Delimiter::detect(&detected.decode(&bytes)))
+    };
+    let mut has_header = use_signal(|| true);
+    // Columns whose field was changed by hand; the rest use the guess
+    let mut chosen = use_signal(BTreeMap::<usize, ContactField>::new);
+    let mut previewing = use_signal(|| false);
+    let mut target = use_signal(|| circle_id.clone());
+
+    let rows = csv::parse(&encoding().decode(&bytes), delimiter());
+    let columns = rows.iter().map(|r| r.fields.len()).max().unwrap_or(0);
+    let cell = |row: usize, column: usize| rows.get(row).and_then(|r| r.fields.get(column)).cloned().unwrap_or_default();
+    let first_data = usize::from(has_header());
+    let mapping: Vec<ContactField> = (0..columns)
+        .map(|column| {
+            chosen.read().get(&column).copied().unwrap_or_else(|| match (has_header(), column) {
+                (true, _) => ContactField::guess(&cell(0, column)),
+                (false, 0) => ContactField::Name,
+                (false, _) => ContactField::Ignore,
+            })
+        })
+        .collect();
+    let headings: Vec<String> = (0..columns)
+        .map(|column| match has_header() {
+            true if !cell(0, column).trim().is_empty() => cell(0, column),
+            _ => format!("Column {}", column + 1),
+        })
+        .collect();
+    let samples: Vec<String> = (0..columns).map(|column| cell(first_data, column)).collect();
+    let checks = csv::check_rows(&rows, &mapping, has_header());
+    let ready = checks.iter().filter(|c| c.is_valid()).count();
+    let has_name = mapping
+        .iter()
+        .any(|f| matches!(f, ContactField::Name | ContactField::GivenName | ContactField::FamilyName));
+
+    let destinations: Vec<Circle> = Visibility::ALL
+        .into_iter()
+        .flat_map(|v| circles.read().with_visibility(v))
+        .filter(|c| c.can(LOCAL_USER_ID, Permission::Invite))
+        .collect();
+
+    let finish = {
+        let cards: Vec<ContactCard> = checks.iter().filter(|c| c.is_valid()).map(|c| c.card.clone()).collect();
+        move |_| ondone.call((target(), cards.clone()))
+    };
+
+    rsx! {
+        div { class: "small text-muted mb-2",
+            i { class: "bi bi-filetype-csv me-1" }
+            "{file_name} · {rows.len().saturating_sub(first_data)} rows"
+        }
+
+        if columns == 0 {
+            div { class: "alert alert-warning py-2 small", "This file doesn't have any rows." }
+            button { class: "btn btn-secondary w-100", onclick: move |_| oncancel.call(()), "Choose another file" }
+        } else if !previewing() {
+            div { class: "row g-2 mb-3",
+                div { class: "col-6",
+                    label { class: "form-label small", "Encoding" }
+                    select {
+                        class: "form-select form-select-sm",
+                        onchange: move |e: Event<FormData>| {
+                            if let Some(found) = Encoding::ALL.into_iter().find(|c| c.label() == e.value()) {
+                                encoding.set(found);
+                            }
+                        },
+                        for option_encoding in Encoding::ALL {
+                            option {
+                                key: "{option_encoding.label()}",
+                                value: option_encoding.label(),
+                                selected: encoding() == option_encoding,
+                                {option_encoding.label()}
+                            }
+                        }
+                    }
+                }
+                div { class: "col-6",
+                    label { class: "form-label small", "Separated by" }
+                    select {
+                        class: "form-select form-select-sm",
+                        onchange: move |e: Event<FormData>| {
+                            if let Some(found) = Delimiter::ALL.into_iter().find(|d| d.label() == e.value()) {
+                                delimiter.set(found);
+                                chosen.write().clear();
+                            }
+                        },
+                        for option_delimiter in Delimiter::ALL {
+                            option {
+                                key: "{option_delimiter.label()}",
+                                value: option_delimiter.label(),
+                                selected: delimiter() == option_delimiter,
+                                {option_delimiter.label()}
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "form-check mb-3",
+                input {
+                    class: "form-check-input",
+                    r#type: "checkbox",
+                    id: "csv-has-header",
+                    checked: has_header(),
+                    onchange: move |e: Event<FormData>| {
+                        has_header.set(e.checked());
+                        chosen.write().clear();
+                    },
+                }
+                label { class: "form-check-label small", r#for: "csv-has-header", "The first row holds column names" }
+            }
+
+            p { class: "small text-muted mb-2", "Match each column to a contact field" }
+            ul { class: "list-group mb-3",
+                for column in 0..columns {
+                    li { key: "{column}", class: "list-group-item d-flex align-items-center",
+                        div { class: "flex-grow-1 me-2", style: "min-width: 0;",
+                            div { class: "fw-bold text-truncate", {headings[column].clone()} }
+                            div { class: "small text-muted text-truncate",
+                                if samples[column].is_empty() { "(empty)" } else { {samples[column].clone()} }
+                            }
+                        }
+                        select {
+                            class: "form-select form-select-sm",
+                            style: "max-width: 10rem;",
+                            onchange: move |e: Event<FormData>| {
+                                if let Some(field) = ContactField::ALL.into_iter().find(|f| f.label() == e.value()) {
+                                    chosen.write().insert(column, field);
+                                }
+                            },
+                            for field in ContactField::ALL {
+                                option {
+                                    key: "{field.label()}",
+                                    value: field.label(),
+                                    selected: mapping[column] == field,
+                                    {field.label()}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if !has_name {
+                div { class: "small text-danger mb-2", "Choose which column holds the name" }
+            }
+            div { class: "d-flex gap-2",
+                button { class: "btn btn-secondary flex-grow-1", onclick: move |_| oncancel.call(()), "Cancel" }
+                button {
+                    class: "btn btn-primary flex-grow-1",
+                    disabled: !has_name,
+                    onclick: move |_| previewing.set(true),
+                    "Preview"
+                }
+            }
+        } else {
+            p { class: "small mb-2",
+                span { class: "text-success", "{ready} ready" }
+                if ready < checks.len() {
+                    span { class: "text-danger", " · {checks.len() - ready} with problems will be left out" }
+                }
+            }
+            ul { class: "list-group mb-2",
+                for check in checks.iter().take(PREVIEW_ROWS) {
+                    li {
+                        key: "{check.line}",
+                        class: if check.is_valid() { "list-group-item" } else { "list-group-item list-group-item-danger" },
+                        div { class: "d-flex",
+                            span { class: "small text-muted me-2", "{check.line}" }
+                            div { class: "flex-grow-1",
+                                div { class: "fw-bold",
+                                    if check.card.name.is_empty() { "—" } else { {check.card.name.clone()} }
+                                }
+                                div { class: "small text-muted",
+                                    {check.card.phones.iter().chain(check.card.emails.iter()).cloned().collect::<Vec<_>>().join(" · ")}
+                                }
+                                for (index, problem) in check.errors.iter().enumerate() {
+                                    div { key: "{index}", class: "small text-danger",
+                                        i { class: "bi bi-exclamation-circle me-1" }
+                                        {problem.clone()}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if checks.len() > PREVIEW_ROWS {
+                p { class: "small text-muted", "and {checks.len() - PREVIEW_ROWS} more rows" }
+            }
+            div { class: "mb-3",
+                label { class: "form-label small", "Add to" }
+                select {
+                    class: "form-select",
+                    onchange: move |e: Event<FormData>| target.set(e.value()),
+                    for circle in destinations {
+                        option {
+                            key: "{circle.id}",
+                            value: circle.id.clone(),
+                            selected: circle.id == target(),
+                            {circle.details.name.clone()}
+                        }
+                    }
+                }
+            }
+            div { class: "d-flex gap-2",
+                button { class: "btn btn-secondary flex-grow-1", onclick: move |_| previewing.set(false), "Back" }
+                button {
+                    class: "btn btn-primary flex-grow-1",
+                    disabled: ready == 0,
+                    onclick: finish,
+                    "Continue with {ready}"
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use dioxus::prelude::*;
+use crate::models::circle::{Circle, Visibility};
+use crate::models::event::{local_date_time, EventDraft, Frequency, Weekday, MAX_TITLE_LEN};
+use crate::services::repositories::{use_circle_repo, use_event_repo};
+use crate::utils::format::local_offset_millis;
+use crate::utils::now_millis;
+
+// Modal for adding an event to a circle, or editing one when `event_id` is
+// set. New events start on `date` (YYYY-MM-DD), or today, and can be moved to
+// another of our circles before saving.
+#[component]
+pub fn EventEditor(circle_id: String, event_id: Option<String>, date: Option<String>, onclose: EventHandler<()>) -> Element {
+    let circles = use_circle_repo();
+    let mut events = use_event_repo();
+    let mut target = use_signal(|| circle_id.clone());
+    let ours: Vec<Circle> = Visibility::ALL.into_iter().flat_map(|v| circles.read().with_visibility(v)).collect();
+    let editing_id = event_id.clone();
+    let mut draft = use_signal(move || {
+        let offset = local_offset_millis();
+        event_id
+            .as_deref()
+            .and_then(|id| events.peek().event(id).map(|e| EventDraft::from_event(e, offset)))
+            .unwrap_or_else(|| EventDraft::new(date.unwrap_or_else(|| local_date_time(now_millis(), offset).0)))
+    });
+    let mut error = use_signal(|| None::<String>);
+    let is_editing = editing_id.is_some();
+    let title = if is_editing { "Edit event" } else { "New event" };
+    let repeat = draft.read().repeat;
+
+    let save = {
+        let editing_id = editing_id.clone();
+        move |_| {
+            let result = match editing_id.as_deref() {
+                Some(id) => events.write().update(id, &draft.read()),
+                None => events.write().create(&target(), &draft.read(), &circles.read()).map(|_| ()),
+            };
+            match result {
+                Ok(()) => onclose.call(()),
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    let cancel_event = move |_| {
+        let Some(id) = editing_id.as_deref() else {
+            return;
+        };
+        let result = events.write().delete(id);
+        match result {
+            Ok(()) => onclose.call(()),
+            Err(e) => error.set(Some(e)),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "modal d-block",
+            style: "background-color: rgba(0, 0, 0, 0.5);",
+            tabindex: "-1",
+            div { class: "modal-dialog modal-dialog-scrollable modal-dialog-centered",
+                div { class: "modal-content",
+                    div { class: "modal-header",
+                        h5 { class: "modal-title", {title} }
+                        button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                    }
+                    div { class: "modal-body",
+                        if let Some(message) = error.read().clone() {
+                            div { class: "alert alert-danger py-2 small", {message} }
+                        }
+                        if !is_editing {
+                            div { class: "mb-3",
+                                label { class: "form-label", r#for: "eventCircle", "Circle" }
+                                select {
+                                    id: "eventCircle",
+                                    class: "form-select",
+                                    onchange: move |e: Event<FormData>| target.set(e.value()),
+                                    for circle in ours {
+                                        option {
+                                            key: "{circle.id}",
+                                            value: circle.id.clone(),
+                                            selected: circle.id == target(),
+                                            {circle.details.name.clone()}
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "eventTitle", "Title" }
+                            input {
+                                id: "eventTitle",
+                                class: "form-control",
+                                maxlength: "{MAX_TITLE_LEN}",
+                                value: "{draft.read().title}",
+                                oninput: move |e| draft.write().title = e.value(),
+                            }
+                        }
+                        div { class: "row g-2 mb-2",
+                            div { class: "col-6",
+                                label { class: "form-label", r#for: "eventDate", "Date" }
+                                input {
+                                    id: "eventDate",
+                                    class: "form-control",
+                                    r#type: "date",
+                                    value: "{draft.read().date}",
+                                    oninput: move |e| draft.write().date = e.value(),
+                                }
+                            }
+                            div { class: "col-6 d-flex align-items-end",
+                                div { class: "form-check mb-2",
+                                    input {
+                                        id: "eventAllDay",
+                                        class: "form-check-input",
+                                        r#type: "checkbox",
+                                        checked: draft.read().all_day,
+                                        onchange: move |e: Event<FormData>| draft.write().all_day = e.checked(),
+                                    }
+                                    label { class: "form-check-label", r#for: "eventAllDay", "All day" }
+                                }
+                            }
+                        }
+                        if !draft.read().all_day {
+                            div { class: "row g-2 mb-3",
+                                div { class: "col-6",
+                                    label { class: "form-label", r#for: "eventStart", "Starts" }
+                                    input {
+                                        id: "eventStart",
+                                        class: "form-control",
+                                        r#type: "time",
+                                        value: "{draft.read().start_time}",
+                                        oninput: move |e| draft.write().start_time = e.value(),
+                                    }
+                                }
+                                div { class: "col-6",
+                                    label { class: "form-label", r#for: "eventEnd", "Ends" }
+                                    input {
+                                        id: "eventEnd",
+                                        class: "form-control",
+                                        r#type: "time",
+                                        value: "{draft.read().end_time}",
+                                        oninput: move |e| draft.write().end_time = e.value(),
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "eventLocation", "Location" }
+                            input {
+                                id: "eventLocation",
+                                class: "form-control",
+                                value: "{draft.read().location}",
+                                oninput: move |e| draft.write().location = e.value(),
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "eventDescription", "Details" }
+                            textarea {
+                                id: "eventDescription",
+                                class: "form-control",
+                                rows: "2",
+                                value: "{draft.read().description}",
+                                oninput: move |e| draft.write().description = e.value(),
+                            }
+                        }
+                        div { class: "mb-2",
+                            label { class: "form-label", r#for: "eventRepeat", "Repeats" }
+                            select {
+                                id: "eventRepeat",
+                                class: "form-select",
+                                onchange: move |e: Event<FormData>| {
+                                    draft.write().repeat = Frequency::ALL.into_iter().find(|f| f.label() == e.value());
+                                },
+                                option { value: "", selected: repeat.is_none(), "Never" }
+                                for frequency in Frequency::ALL {
+                                    option {
+                                        key: "{frequency.label()}",
+                                        value: frequency.label(),
+                                        selected: repeat == Some(frequency),
+                                        {frequency.label()}
+                                    }
+                                }
+                            }
+                        }
+                        if repeat.is_some() {
+                            div { class: "row g-2 mb-2",
+                                div { class: "col-4",
+                                    label { class: "form-label small", r#for: "eventInterval", "Every" }
+                                    input {
+                                        id: "eventInterval",
+                                        class: "form-control",
+                                        r#type: "number",
+                                        min: "1",
+                                        value: "{draft.read().interval}",
+                                        oninput: move |e| draft.write().interval = e.value().parse().unwrap_or(1),
+                                    }
+                                }
+                                div { class: "col-4",
+                                    label { class: "form-label small", r#for: "eventCount", "Times" }
+                                    input {
+                                        id: "eventCount",
+                                        class: "form-control",
+                                        r#type: "number",
+                                        min: "1",
+                                        placeholder: "No limit",
+                                        value: draft.read().count.map(|c| c.to_string()).unwrap_or_default(),
+                                        oninput: move |e| draft.write().count = e.value().parse().ok(),
+                                    }
+                                }
+                                div { class: "col-4",
+                                    label { class: "form-label small", r#for: "eventUntil", "Until" }
+                                    input {
+                                        id: "eventUntil",
+                                        class: "form-control",
+                                        r#type: "date",
+                                        value: "{draft.read().until}",
+                                        oninput: move |e| draft.write().until = e.value(),
+                                    }
+                                }
+                            }
+                        }
+                        if repeat == Some(Frequency::Weekly) {
+                            div { class: "btn-group w-100 mb-2",
+                                for day in Weekday::ALL {
+                                    button {
+                                        key: "{day.label()}",
+                                        class: if draft.read().by_day.contains(&day) { "btn btn-sm btn-primary" } else { "btn btn-sm btn-outline-primary" },
+                                        onclick: move |_| draft.write().toggle_day(day),
+                                        {day.label()}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "modal-footer",
+                        if is_editing {
+                            button { class: "btn btn-outline-danger me-auto", onclick: cancel_event, "Cancel event" }
+                        }
+                        button { class: "btn btn-secondary", onclick: move |_| onclose.call(()), "Close" }
+                        button { class: "btn btn-primary", onclick: save, "Save" }
+                    }
+                }
+            }
+        }
+    }
+}
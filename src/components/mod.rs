@@ -5,8 +5,23 @@ mod error_boundary;
 mod home;
 mod profile;
 mod comms;
+mod call_screen;
+mod conversation;
 mod circles;
+mod circle_discovery;
+mod circle_editor;
+mod circle_events;
+mod calendar;
+mod event_editor;
+mod circle_feed;
+mod circle_members;
+mod contact_import;
+mod csv_import;
+mod accept_invite;
 mod tree;
+mod person_editor;
+mod person_merge;
+mod tree_chart;
 mod settings;
 mod system_info;
 
@@ -16,7 +31,15 @@ pub use bottom_nav::BottomNav;
 pub use home::Home;
 pub use profile::Profile;
 pub use comms::Comms;
+pub use call_screen::CallScreen;
+pub use conversation::Conversation;
 pub use circles::Circles;
+pub use circle_feed::CircleFeed;
+pub use circle_members::CircleMembers;
+pub use circle_events::CircleEvents;
+pub use calendar::Calendar;
+pub use contact_import::ImportContacts;
+pub use accept_invite::AcceptInvite;
 pub use tree::Tree;
 pub use settings::Settings;
 pub use system_info::SystemInfo;
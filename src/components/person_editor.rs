@@ -0,0 +1,499 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dioxus::prelude::*;
+use serde::Deserialize;
+
+use crate::models::family::{Gender, PersonDraft, RelationKind, Relative};
+use crate::models::fuzzy_date::{Calendar, FuzzyDate};
+use crate::services::repositories::use_tree_repo;
+
+// Uploaded photos are kept inline in the tree, which shares one storage
+// entry of a few megabytes, so they are shrunk to a small JPEG first
+const MAX_UPLOAD_BYTES: usize = 20 * 1024 * 1024;
+const PHOTO_SIDE: u32 = 256;
+const MAX_PHOTO_BYTES: usize = 64 * 1024;
+
+// The relative a role and kind picked in the form stand for
+fn relative_from(role: &str, kind: RelationKind) -> Relative {
+    match role {
+        "parent" => Relative::Parent(kind),
+        "child" => Relative::Child(kind),
+        _ => Relative::Spouse,
+    }
+}
+
+// How a typed date was read, and the Gregorian date for other calendars.
+// Dates that can't be read are kept as typed.
+fn date_note(text: &str) -> Element {
+    if text.trim().is_empty() {
+        return rsx! {};
+    }
+    match FuzzyDate::parse(text) {
+        Ok(date) if date.calendar() != Calendar::Gregorian => rsx! {
+            div { class: "form-text", "{date} ({date.calendar().label()}) = {date.gregorian()}" }
+        },
+        Ok(date) => rsx! {
+            div { class: "form-text", "Read as {date}" }
+        },
+        Err(e) => rsx! {
+            div { class: "form-text text-warning", "{e}; kept as typed" }
+        },
+    }
+}
+
+// Age at death, or today for the living
+fn age_note(born: &str, died: &str) -> Option<String> {
+    let born = FuzzyDate::parse(born).ok()?;
+    if died.trim().is_empty() {
+        return Some(format!("Age {} today", born.age_at(&FuzzyDate::today())));
+    }
+    let died = FuzzyDate::parse(died).ok()?;
+    Some(format!("Died aged {}", born.age_at(&died)))
+}
+
+fn photo_data_url(name: &str, bytes: &[u8]) -> Result<String, String> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(format!("Photos can be at most {} MB", MAX_UPLOAD_BYTES / 1024 / 1024));
+    }
+    let kind = match name.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("png") => "png",
+        Some("gif") => "gif",
+        Some("webp") => "webp",
+        Some("svg") => "svg+xml",
+        _ => "jpeg",
+    };
+    Ok(format!("data:image/{};base64,{}", kind, STANDARD.encode(bytes)))
+}
+
+const THUMBNAIL_JS: &str = r#"
+    const [url, side] = await dioxus.recv();
+    try {
+        const image = new Image();
+        await new Promise((done, fail) => {
+            image.onload = done;
+            image.onerror = () => fail(new Error('That file is not a picture we can read'));
+            image.src = url;
+        });
+        const scale = Math.min(1, side / Math.max(image.naturalWidth, image.naturalHeight));
+        const canvas = document.createElement('canvas');
+        canvas.width = Math.max(1, Math.round(image.naturalWidth * scale));
+        canvas.height = Math.max(1, Math.round(image.naturalHeight * scale));
+        const context = canvas.getContext('2d');
+        context.fillStyle = '#ffffff';
+        context.fillRect(0, 0, canvas.width, canvas.height);
+        context.drawImage(image, 0, 0, canvas.width, canvas.height);
+        dioxus.send({ url: canvas.toDataURL('image/jpeg', 0.85) });
+    } catch (e) {
+        dioxus.send({ error: String(e.message || e) });
+    }
+"#;
+
+#[derive(Deserialize)]
+struct Thumbnail {
+    url: Option<String>,
+    error: Option<String>,
+}
+
+// The photo at `url` scaled down to fit PHOTO_SIDE pixels, as a JPEG
+async fn thumbnail(url: &str) -> Result<String, String> {
+    let mut eval = document::eval(THUMBNAIL_JS);
+    eval.send((url, PHOTO_SIDE)).map_err(|e| e.to_string())?;
+    let drawn: Thumbnail = eval.recv().await.map_err(|e| e.to_string())?;
+    let url = match (drawn.url, drawn.error) {
+        (Some(url), _) if url.starts_with("data:image/") => url,
+        (_, Some(error)) => return Err(error),
+        _ => return Err("Couldn't shrink the photo".to_string()),
+    };
+    match url.len() > MAX_PHOTO_BYTES {
+        true => Err("That photo is still too big once shrunk".to_string()),
+        false => Ok(url),
+    }
+}
+
+// Modal for editing someone in the family tree when `person_id` is set, or
+// adding someone new, as `relative` of `of` when both are given
+#[component]
+pub fn PersonEditor(person_id: Option<String>, of: Option<String>, relative: Option<Relative>, onclose: EventHandler<()>) -> Element {
+    let mut tree = use_tree_repo();
+    let editing_id = person_id.clone();
+    let mut draft = use_signal(move || {
+        person_id
+            .as_deref()
+            .and_then(|id| tree.peek().tree.person(id).map(PersonDraft::from_person))
+            .unwrap_or_default()
+    });
+    let mut error = use_signal(|| None::<String>);
+    // How a new parent or child is one
+    let mut new_kind = use_signal(|| match relative {
+        Some(Relative::Parent(kind)) | Some(Relative::Child(kind)) => kind,
+        _ => RelationKind::Parent,
+    });
+    let mut link_to = use_signal(String::new);
+    let mut link_role = use_signal(|| "parent".to_string());
+    let mut link_kind = use_signal(|| RelationKind::Parent);
+
+    let of_name = of.as_deref().and_then(|id| tree.read().tree.person(id).map(|p| p.full_name())).unwrap_or_default();
+    let title = match (&editing_id, relative) {
+        (Some(_), _) => format!("Edit {}", draft.read().given_name),
+        (None, Some(relative)) => format!("New {} of {}", relative.label().to_lowercase(), of_name),
+        (None, None) => "New person".to_string(),
+    };
+    let is_home = editing_id.as_deref() == Some(tree.read().home_id.as_str());
+    let preview = PersonDraft::to_person(&draft.read()).avatar();
+
+    // Everyone else, and this person's current links
+    let mut others: Vec<(String, String)> = tree
+        .read()
+        .tree
+        .people()
+        .filter(|p| Some(&p.id) != editing_id.as_ref())
+        .map(|p| (p.id.clone(), p.full_name()))
+        .collect();
+    others.sort_by(|a, b| a.1.cmp(&b.1));
+    let links: Vec<(String, String, String)> = editing_id
+        .as_deref()
+        .map(|id| {
+            let tree = tree.read();
+            tree.tree
+                .links_of(id)
+                .into_iter()
+                .filter_map(|r| {
+                    let other = if r.from == id { &r.to } else { &r.from };
+                    let role = match (r.kind, r.from == id) {
+                        (RelationKind::Spouse, _) => "Spouse".to_string(),
+                        (kind, false) => format!("{} parent", kind.label()),
+                        (kind, true) => format!("{} child", kind.label()),
+                    };
+                    tree.tree.person(other).map(|p| (r.id.clone(), p.full_name(), role))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let save = {
+        let editing_id = editing_id.clone();
+        let of = of.clone();
+        move |_| {
+            let result = match (editing_id.as_deref(), of.as_deref(), relative) {
+                (Some(id), _, _) => tree.write().save_person(Some(id), &draft.read()),
+                (None, Some(of), Some(relative)) => {
+                    let relative = match relative {
+                        Relative::Parent(_) => Relative::Parent(new_kind()),
+                        Relative::Child(_) => Relative::Child(new_kind()),
+                        Relative::Spouse => Relative::Spouse,
+                    };
+                    tree.write().add_relative(of, relative, &draft.read())
+                }
+                _ => tree.write().save_person(None, &draft.read()),
+            };
+            match result {
+                Ok(_) => onclose.call(()),
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    let link = {
+        let editing_id = editing_id.clone();
+        move |_| {
+            let Some(id) = editing_id.as_deref() else {
+                return;
+            };
+            if link_to().is_empty() {
+                error.set(Some("Choose who to link".to_string()));
+                return;
+            }
+            let result = tree.write().link(id, relative_from(&link_role(), link_kind()), &link_to());
+            match result {
+                Ok(()) => {
+                    link_to.set(String::new());
+                    error.set(None);
+                }
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    let delete = {
+        let editing_id = editing_id.clone();
+        move |_| {
+            let Some(id) = editing_id.as_deref() else {
+                return;
+            };
+            let result = tree.write().delete_person(id);
+            match result {
+                Ok(()) => onclose.call(()),
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    let upload = move |evt: Event<FormData>| async move {
+        let Some(files) = evt.files() else {
+            return;
+        };
+        let Some(name) = files.files().into_iter().next() else {
+            return;
+        };
+        let read = files
+            .read_file(&name)
+            .await
+            .ok_or_else(|| "Couldn't read the photo".to_string())
+            .and_then(|bytes| photo_data_url(&name, &bytes));
+        let read = match read {
+            Ok(url) => thumbnail(&url).await,
+            Err(e) => Err(e),
+        };
+        match read {
+            Ok(url) => draft.write().photo = url,
+            Err(e) => error.set(Some(e)),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "modal d-block",
+            style: "background-color: rgba(0, 0, 0, 0.5);",
+            tabindex: "-1",
+            div { class: "modal-dialog modal-dialog-scrollable modal-dialog-centered",
+                div { class: "modal-content",
+                    div { class: "modal-header",
+                        h5 { class: "modal-title", {title} }
+                        button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                    }
+                    div { class: "modal-body",
+                        if let Some(message) = error.read().clone() {
+                            div { class: "alert alert-danger py-2 small", {message} }
+                        }
+                        if matches!(relative, Some(Relative::Parent(_)) | Some(Relative::Child(_))) && editing_id.is_none() {
+                            div { class: "mb-3",
+                                label { class: "form-label", r#for: "personNewKind", "Kind" }
+                                select {
+                                    id: "personNewKind",
+                                    class: "form-select",
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Some(kind) = RelationKind::ALL.into_iter().find(|k| k.label() == e.value()) {
+                                            new_kind.set(kind);
+                                        }
+                                    },
+                                    for kind in RelationKind::ALL.into_iter().filter(|k| k.is_parental()) {
+                                        option { key: "{kind.label()}", value: kind.label(), selected: kind == new_kind(), {kind.label()} }
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "d-flex align-items-center mb-3",
+                            img {
+                                class: "rounded-circle me-3",
+                                src: "{preview}",
+                                alt: "Photo",
+                                style: "width: 64px; height: 64px; object-fit: cover;",
+                            }
+                            div { class: "flex-grow-1",
+                                input {
+                                    class: "form-control form-control-sm mb-1",
+                                    placeholder: "Photo address",
+                                    value: "{draft.read().photo}",
+                                    oninput: move |e| draft.write().photo = e.value(),
+                                }
+                                label { class: "btn btn-outline-secondary btn-sm mb-0 me-2",
+                                    i { class: "bi bi-image me-1" }
+                                    "Upload"
+                                    input { class: "d-none", r#type: "file", accept: "image/*", onchange: upload }
+                                }
+                                if !draft.read().photo.is_empty() {
+                                    button {
+                                        class: "btn btn-link btn-sm text-danger p-0",
+                                        onclick: move |_| draft.write().photo.clear(),
+                                        "Remove photo"
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "row g-2 mb-2",
+                            div { class: "col-6",
+                                label { class: "form-label", r#for: "personGiven", "Given name" }
+                                input {
+                                    id: "personGiven",
+                                    class: "form-control",
+                                    value: "{draft.read().given_name}",
+                                    oninput: move |e| draft.write().given_name = e.value(),
+                                }
+                            }
+                            div { class: "col-6",
+                                label { class: "form-label", r#for: "personSurname", "Surname" }
+                                input {
+                                    id: "personSurname",
+                                    class: "form-control",
+                                    value: "{draft.read().surname}",
+                                    oninput: move |e| draft.write().surname = e.value(),
+                                }
+                            }
+                        }
+                        div { class: "mb-2",
+                            label { class: "form-label", r#for: "personAlternate", "Other spellings" }
+                            input {
+                                id: "personAlternate",
+                                class: "form-control",
+                                placeholder: "Separate with commas",
+                                value: "{draft.read().alternate_names}",
+                                oninput: move |e| draft.write().alternate_names = e.value(),
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "personGender", "Gender" }
+                            select {
+                                id: "personGender",
+                                class: "form-select",
+                                onchange: move |e: Event<FormData>| {
+                                    if let Some(gender) = Gender::ALL.into_iter().find(|g| g.label() == e.value()) {
+                                        draft.write().gender = gender;
+                                    }
+                                },
+                                for gender in Gender::ALL {
+                                    option {
+                                        key: "{gender.label()}",
+                                        value: gender.label(),
+                                        selected: gender == draft.read().gender,
+                                        {gender.label()}
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "row g-2 mb-2",
+                            div { class: "col-5",
+                                label { class: "form-label", r#for: "personBorn", "Born" }
+                                input {
+                                    id: "personBorn",
+                                    class: "form-control",
+                                    placeholder: "12 Mar 1970, abt 1970, ১২ বৈশাখ ১৩৭৭",
+                                    value: "{draft.read().birth_date}",
+                                    oninput: move |e| draft.write().birth_date = e.value(),
+                                }
+                                {date_note(&draft.read().birth_date)}
+                            }
+                            div { class: "col-7",
+                                label { class: "form-label", r#for: "personBornAt", "Place" }
+                                input {
+                                    id: "personBornAt",
+                                    class: "form-control",
+                                    value: "{draft.read().birth_place}",
+                                    oninput: move |e| draft.write().birth_place = e.value(),
+                                }
+                            }
+                        }
+                        div { class: "row g-2 mb-2",
+                            div { class: "col-5",
+                                label { class: "form-label", r#for: "personDied", "Died" }
+                                input {
+                                    id: "personDied",
+                                    class: "form-control",
+                                    value: "{draft.read().death_date}",
+                                    oninput: move |e| draft.write().death_date = e.value(),
+                                }
+                                {date_note(&draft.read().death_date)}
+                            }
+                            div { class: "col-7",
+                                label { class: "form-label", r#for: "personDiedAt", "Place" }
+                                input {
+                                    id: "personDiedAt",
+                                    class: "form-control",
+                                    value: "{draft.read().death_place}",
+                                    oninput: move |e| draft.write().death_place = e.value(),
+                                }
+                            }
+                        }
+                        if let Some(age) = age_note(&draft.read().birth_date, &draft.read().death_date) {
+                            div { class: "form-text mb-2", {age} }
+                        }
+                        div { class: "mb-2",
+                            label { class: "form-label", r#for: "personOccupation", "Occupation" }
+                            input {
+                                id: "personOccupation",
+                                class: "form-control",
+                                value: "{draft.read().occupation}",
+                                oninput: move |e| draft.write().occupation = e.value(),
+                            }
+                        }
+                        div { class: "mb-3",
+                            label { class: "form-label", r#for: "personNotes", "Notes" }
+                            textarea {
+                                id: "personNotes",
+                                class: "form-control",
+                                rows: "3",
+                                value: "{draft.read().notes}",
+                                oninput: move |e| draft.write().notes = e.value(),
+                            }
+                        }
+                        if editing_id.is_some() {
+                            h6 { "Relatives" }
+                            ul { class: "list-group mb-2",
+                                for (relationship_id, name, role) in links {
+                                    li { key: "{relationship_id}", class: "list-group-item d-flex align-items-center py-1",
+                                        div { class: "flex-grow-1",
+                                            {name}
+                                            span { class: "text-muted small ms-2", {role} }
+                                        }
+                                        button {
+                                            class: "btn btn-link btn-sm text-danger",
+                                            title: "Unlink",
+                                            onclick: {
+                                                let relationship_id = relationship_id.clone();
+                                                move |_| {
+                                                    let result = tree.write().unlink(&relationship_id);
+                                                    if let Err(e) = result {
+                                                        error.set(Some(e));
+                                                    }
+                                                }
+                                            },
+                                            i { class: "bi bi-x-lg" }
+                                        }
+                                    }
+                                }
+                            }
+                            div { class: "input-group input-group-sm",
+                                select {
+                                    class: "form-select",
+                                    value: "{link_to}",
+                                    onchange: move |e: Event<FormData>| link_to.set(e.value()),
+                                    option { value: "", "Link someone already here…" }
+                                    for (id, name) in others {
+                                        option { key: "{id}", value: "{id}", selected: id == link_to(), {name} }
+                                    }
+                                }
+                                select {
+                                    class: "form-select",
+                                    onchange: move |e: Event<FormData>| link_role.set(e.value()),
+                                    option { value: "parent", "as parent" }
+                                    option { value: "child", "as child" }
+                                    option { value: "spouse", "as spouse" }
+                                }
+                                if link_role() != "spouse" {
+                                    select {
+                                        class: "form-select",
+                                        onchange: move |e: Event<FormData>| {
+                                            if let Some(kind) = RelationKind::ALL.into_iter().find(|k| k.label() == e.value()) {
+                                                link_kind.set(kind);
+                                            }
+                                        },
+                                        for kind in RelationKind::ALL.into_iter().filter(|k| k.is_parental()) {
+                                            option { key: "{kind.label()}", value: kind.label(), selected: kind == link_kind(), {kind.label()} }
+                                        }
+                                    }
+                                }
+                                button { class: "btn btn-outline-primary", onclick: link, "Link" }
+                            }
+                        }
+                    }
+                    div { class: "modal-footer",
+                        if editing_id.is_some() && !is_home {
+                            button { class: "btn btn-outline-danger me-auto", onclick: delete, "Delete" }
+                        }
+                        button { class: "btn btn-secondary", onclick: move |_| onclose.call(()), "Close" }
+                        button { class: "btn btn-primary", onclick: save, "Save" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use dioxus::prelude::*;
+
+use crate::models::family::PersonDraft;
+use crate::models::person_match::PersonField;
+use crate::services::repositories::use_tree_repo;
+
+// Modal comparing two records of what may be the same person side by side.
+// Each property is taken from one or the other, and the second record is
+// folded into the first.
+#[component]
+pub fn PersonMerge(first: String, second: String, onclose: EventHandler<()>) -> Element {
+    let mut tree = use_tree_repo();
+    let drafts = {
+        let repo = tree.read();
+        repo.tree.person(&first).map(PersonDraft::from_person).zip(repo.tree.person(&second).map(PersonDraft::from_person))
+    };
+    // Which properties come from the second record; empty ones lose by default
+    let mut picks = use_signal({
+        let drafts = drafts.clone();
+        move || match drafts {
+            Some((left, right)) => PersonField::ALL.map(|field| field.value(&left).is_empty() && !field.value(&right).is_empty()),
+            None => [false; PersonField::ALL.len()],
+        }
+    });
+    let mut error = use_signal(|| None::<String>);
+    // Hooks come first so they run on every render, even once a record is gone
+    let Some((left, right)) = drafts else {
+        return rsx! {};
+    };
+    let relatives = |id: &str| {
+        let repo = tree.read();
+        repo.tree.links_of(id).len()
+    };
+    let (left_links, right_links) = (relatives(&first), relatives(&second));
+
+    let merge = {
+        let (left, right) = (left.clone(), right.clone());
+        move |_| {
+            let mut merged = left.clone();
+            for (field, take_right) in PersonField::ALL.into_iter().zip(picks()) {
+                if take_right {
+                    field.copy(&right, &mut merged);
+                }
+            }
+            let result = tree.write().merge_people(&first, &second, &merged);
+            match result {
+                Ok(()) => onclose.call(()),
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    rsx! {
+        div {
+            class: "modal d-block",
+            style: "background-color: rgba(0, 0, 0, 0.5);",
+            tabindex: "-1",
+            div { class: "modal-dialog modal-lg modal-dialog-scrollable modal-dialog-centered",
+                div { class: "modal-content",
+                    div { class: "modal-header",
+                        h5 { class: "modal-title", "Merge duplicates" }
+                        button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                    }
+                    div { class: "modal-body",
+                        if let Some(message) = error.read().clone() {
+                            div { class: "alert alert-danger py-2 small", {message} }
+                        }
+                        p { class: "small text-muted",
+                            "Pick which record each detail should come from. Relatives from both are kept, and both names are kept as other spellings."
+                        }
+                        table { class: "table table-sm small align-middle",
+                            thead {
+                                tr {
+                                    th {}
+                                    th { "First record · {left_links} links" }
+                                    th { "Second record · {right_links} links" }
+                                }
+                            }
+                            tbody {
+                                for (index, field) in PersonField::ALL.into_iter().enumerate() {
+                                    tr { key: "{field.label()}",
+                                        th { {field.label()} }
+                                        for (side, draft) in [(false, &left), (true, &right)] {
+                                            td { key: "{side}",
+                                                label { class: "form-check mb-0",
+                                                    input {
+                                                        class: "form-check-input",
+                                                        r#type: "radio",
+                                                        name: "mergeField{index}",
+                                                        checked: picks()[index] == side,
+                                                        onchange: move |_| picks.write()[index] = side,
+                                                    }
+                                                    span { class: "form-check-label text-break",
+                                                        match (field, field.value(draft)) {
+                                                            (_, value) if value.is_empty() => rsx! { span { class: "text-muted", "—" } },
+                                                            (PersonField::Photo, value) => rsx! {
+                                                                img { class: "rounded-circle", src: "{value}", alt: "", style: "width: 40px; height: 40px; object-fit: cover;" }
+                                                            },
+                                                            (_, value) => rsx! { {value} },
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "modal-footer",
+                        button { class: "btn btn-secondary", onclick: move |_| onclose.call(()), "Close" }
+                        button { class: "btn btn-primary", onclick: merge, "Merge" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use dioxus::prelude::*;
+use crate::models::family::TreePrivacy;
+use crate::services::repositories::use_tree_repo;
 
 #[component]
 pub fn Profile() -> Element {
@@ -8,6 +10,8 @@ pub fn Profile() -> Element {
     let mut location = use_signal(|| String::from("San Francisco, CA"));
     let joined_date = use_signal(|| String::from("April 2025"));
     let mut edit_mode = use_signal(|| false);
+    let mut tree = use_tree_repo();
+    let privacy = tree.read().privacy.clone();
     
     // Stats
     let connections = 127;
@@ -189,10 +193,46 @@ pub fn Profile() -> Element {
                             label { class: "form-check-label", for: "shareActivity", "Share activity updates" }
                         }
                         
-                        div { class: "form-check form-switch",
+                        div { class: "form-check form-switch mb-2",
                             input { class: "form-check-input", type: "checkbox", id: "shareLocation", checked: false }
                             label { class: "form-check-label", for: "shareLocation", "Share location with family members" }
                         }
+
+                        div { class: "form-check form-switch",
+                            input {
+                                class: "form-check-input",
+                                r#type: "checkbox",
+                                id: "hideLiving",
+                                checked: privacy.hide_living,
+                                onchange: move |evt| {
+                                    let privacy = TreePrivacy { hide_living: evt.checked(), ..tree.read().privacy.clone() };
+                                    tree.write().set_privacy(privacy);
+                                }
+                            }
+                            label { class: "form-check-label", r#for: "hideLiving", "Hide living relatives in shared family trees" }
+                        }
+                        if privacy.hide_living {
+                            div { class: "d-flex align-items-center gap-2 small text-muted mt-1 ms-5",
+                                "Count as living if born within"
+                                input {
+                                    class: "form-control form-control-sm",
+                                    style: "width: 5em;",
+                                    r#type: "number",
+                                    min: "1",
+                                    max: "150",
+                                    value: "{privacy.living_years}",
+                                    onchange: move |evt| {
+                                        if let Ok(years) = evt.value().parse::<i64>()
+                                            && (1..=150).contains(&years)
+                                        {
+                                            let privacy = TreePrivacy { living_years: years, ..tree.read().privacy.clone() };
+                                            tree.write().set_privacy(privacy);
+                                        }
+                                    }
+                                }
+                                "years"
+                            }
+                        }
                     }
                 }
             }
@@ -1,5 +1,8 @@
 use dioxus::prelude::*;
 use crate::state::{use_app_state, Theme, toggle_theme};
+use crate::models::LOCAL_USER_ID;
+use crate::services::crypto::safety_number::{display_groups, fingerprint};
+use crate::services::repositories::{use_key_repo, KeyState};
 
 #[component]
 pub fn Settings() -> Element {
@@ -14,6 +17,31 @@ pub fn Settings() -> Element {
         toggle_theme(state);
     };
 
+    // Encryption keys
+    let mut keys = use_key_repo();
+    let mut passphrase = use_signal(String::new);
+    let mut key_error = use_signal(|| None::<String>);
+    let key_state = keys.read().state();
+    let own_fingerprint = keys
+        .read()
+        .material()
+        .map(|m| display_groups(&fingerprint(LOCAL_USER_ID, &m.public_identity())));
+
+    let submit_passphrase = move |_| {
+        let secret = passphrase.read().clone();
+        if secret.chars().count() < 8 {
+            key_error.set(Some("Use at least 8 characters.".to_string()));
+            return;
+        }
+        let state = keys.read().state();
+        let result = match state {
+            KeyState::NotSetUp => keys.write().set_up(&secret).map_err(|e| format!("Could not create keys: {}", e)),
+            _ => keys.write().unlock(&secret).map_err(|e| format!("Could not unlock keys: {}", e)),
+        };
+        key_error.set(result.err());
+        passphrase.set(String::new());
+    };
+
     rsx! {
         div { class: "container mt-2",
             div { class: "card",
@@ -62,6 +90,42 @@ pub fn Settings() -> Element {
                                 p { class: "text-muted small", "Select your time zone for accurate time displays." }
                             }
 
+                            // End-to-end encryption
+                            div { class: "mb-4",
+                                h5 { "Secure Messaging" }
+                                if key_state == KeyState::Unlocked {
+                                    div { class: "d-flex align-items-center mb-2",
+                                        span { class: "badge bg-success me-2", i { class: "bi bi-shield-lock me-1" } "Unlocked" }
+                                        button { class: "btn btn-sm btn-outline-secondary ms-auto", onclick: move |_| keys.write().lock(), "Lock" }
+                                    }
+                                    if let Some(groups) = own_fingerprint {
+                                        div { class: "small text-muted", "Your fingerprint" }
+                                        div { class: "font-monospace d-flex flex-wrap gap-2",
+                                            for group in groups {
+                                                span { {group} }
+                                            }
+                                        }
+                                    }
+                                } else {
+                                    form { class: "d-flex gap-2", onsubmit: submit_passphrase,
+                                        input {
+                                            class: "form-control",
+                                            r#type: "password",
+                                            placeholder: if key_state == KeyState::NotSetUp { "Choose a passphrase" } else { "Passphrase" },
+                                            value: "{passphrase.read()}",
+                                            oninput: move |evt| passphrase.set(evt.value())
+                                        }
+                                        button { class: "btn btn-primary text-nowrap", r#type: "submit",
+                                            if key_state == KeyState::NotSetUp { "Set up" } else { "Unlock" }
+                                        }
+                                    }
+                                    if let Some(error) = key_error.read().clone() {
+                                        div { class: "text-danger small mt-1", {error} }
+                                    }
+                                }
+                                p { class: "text-muted small", "Your keys are stored on this device, encrypted with your passphrase. Messages stay on this device and are not end-to-end encrypted until the messaging server is connected." }
+                            }
+
                             // Accessibility settings
                             div {
                                 h5 { "Accessibility" }
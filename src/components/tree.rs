@@ -1,257 +1,741 @@
 use dioxus::prelude::*;
+use crate::components::person_editor::PersonEditor;
+use crate::components::person_merge::PersonMerge;
+use crate::components::tree_chart::TreeChart;
+use crate::models::family::{Branch, RelationKind, Relative};
+use crate::models::fuzzy_date;
+use crate::models::kinship::Language;
+use crate::models::person_match;
+use crate::models::tree_health::Issue;
+use crate::services::chart_export;
+use crate::services::gedcom::{self, ExportOptions, ImportReport};
+use crate::services::repositories::use_tree_repo;
+use crate::services::tree_layout;
 
+// What a GEDCOM import brought in and what it had to leave out
 #[component]
-pub fn Tree() -> Element {
-    let family_tree_js = r###"
-        // Execute immediately instead of waiting for DOMContentLoaded
-        (function() {
-            // Add CSS for family-chart
-            const link = document.createElement('link');
-            link.rel = 'stylesheet';
-            link.href = 'https://unpkg.com/family-chart@0.2.1/dist/styles/family-chart.css';
-            document.head.appendChild(link);
-            
-            // Load D3 first, then family-chart
-            loadScript('https://unpkg.com/d3@6', function() {
-                loadScript('https://unpkg.com/family-chart@0.2.1', function() {
-                    // Small delay to ensure DOM is ready
-                    setTimeout(() => {
-                        try {
-                            create(getData());
-                            console.log("Family chart initialized");
-                            
-                            // Apply custom styles AFTER chart is initialized
-                            applyCustomStyles();
-                        } catch (error) {
-                            console.error('Error initializing family chart:', error);
-                            const container = document.getElementById('FamilyChart');
-                            if (container) {
-                                container.innerHTML = '<div class="alert alert-danger">Error initializing family chart: ' + error.message + '</div>';
+fn ReportCard(name: String, report: ImportReport, onclose: EventHandler<()>) -> Element {
+    let unsupported: Vec<String> = report.unsupported.iter().map(|(tag, count)| format!("{} ×{}", tag, count)).collect();
+
+    rsx! {
+        div { class: "card shadow-sm small",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-start",
+                    div { class: "flex-grow-1",
+                        div { class: "fw-bold", {name} }
+                        div { class: "text-muted", "GEDCOM {report.version}, {report.encoding}" }
+                    }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                div { class: "mt-1",
+                    "Added {report.persons} people and {report.relationships} relationships."
+                }
+                if !report.lossy.is_empty() {
+                    div { class: "fw-bold mt-2", "Changed on the way in" }
+                    ul { class: "mb-0 ps-3",
+                        for (what, count) in report.lossy.iter() {
+                            li { key: "{what}",
+                                {what.clone()}
+                                if *count > 1 { " ({count})" }
                             }
                         }
-                    }, 100);
-                });
-            });
-        })();
+                    }
+                }
+                if !unsupported.is_empty() {
+                    div { class: "fw-bold mt-2", "Skipped tags" }
+                    div { class: "text-muted", {unsupported.join(", ")} }
+                }
+            }
+        }
+    }
+}
+
+// Choosing which part of the tree to share before downloading it
+#[component]
+fn ExportCard(onclose: EventHandler<()>) -> Element {
+    let tree = use_tree_repo();
+    let privacy = tree.read().privacy.clone();
+    let mut scope = use_signal(|| "everyone".to_string());
+    let mut root = use_signal(|| tree.read().home_id.clone());
+    let mut hide_living = use_signal(|| privacy.hide_living);
+
+    let mut people: Vec<(String, String)> = tree.read().tree.people().map(|p| (p.id.clone(), p.full_name())).collect();
+    people.sort_by(|a, b| a.1.cmp(&b.1));
+    let branch = match scope().as_str() {
+        "descendants" => Branch::Descendants(root()),
+        "ancestors" => Branch::Ancestors(root()),
+        _ => Branch::Everyone,
+    };
+    let count = tree.read().tree.branch(&branch).len();
+    let options = ExportOptions { branch, hide_living: hide_living(), living_years: privacy.living_years };
+    let url = gedcom::data_url(&tree.read().tree, &options);
 
-        function applyCustomStyles() {
-            // Add custom CSS to fix styling issues
-            const customStyle = document.createElement('style');
-            customStyle.textContent = `
-                /* Different colors for male and female cards */
-                rect.card-male card.male {
-                    fill: #d4e6f9;  /* Light blue for males */
-                }
-                rect.card-female card.female {
-                    fill: #f9d4d4;  /* Light pink for females */
-                }
-            `;
-            document.head.appendChild(customStyle);
-            
-            // Also directly apply styles to existing elements
-            document.querySelectorAll('rect.card-male').forEach(el => {
-                el.style.fill = '#d4e6f9';
-            });
-            
-            document.querySelectorAll('rect.card-female').forEach(el => {
-                el.style.fill = '#f9d4d4';
-            });
+    rsx! {
+        div { class: "card shadow-sm small mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-center mb-2",
+                    div { class: "fw-bold flex-grow-1", "Export GEDCOM" }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                select {
+                    class: "form-select form-select-sm mb-2",
+                    value: "{scope}",
+                    onchange: move |evt| scope.set(evt.value()),
+                    option { value: "everyone", "Everyone" }
+                    option { value: "descendants", "Descendants of…" }
+                    option { value: "ancestors", "Ancestors of…" }
+                }
+                if scope() != "everyone" {
+                    select {
+                        class: "form-select form-select-sm mb-2",
+                        value: "{root}",
+                        onchange: move |evt| root.set(evt.value()),
+                        for (id, name) in people {
+                            option { key: "{id}", value: "{id}", {name} }
+                        }
+                    }
+                }
+                div { class: "form-check mb-1",
+                    input {
+                        class: "form-check-input",
+                        r#type: "checkbox",
+                        id: "exportHideLiving",
+                        checked: hide_living(),
+                        onchange: move |evt| hide_living.set(evt.checked()),
+                    }
+                    label { class: "form-check-label", r#for: "exportHideLiving", "Hide living people" }
+                }
+                if hide_living() {
+                    p { class: "text-muted mb-2",
+                        "Anyone born in the last {privacy.living_years} years, or with no birth date, and no recorded death is shared only as \"Living\"."
+                    }
+                }
+                a {
+                    class: "btn btn-primary btn-sm w-100",
+                    href: url,
+                    download: "family-tree.ged",
+                    i { class: "bi bi-download me-1" }
+                    "Download {count} people"
+                }
+            }
         }
+    }
+}
+
+// Saving the chart as a picture or a printable PDF, of what's on screen or
+// of one person's ancestors or descendants
+#[component]
+fn ChartExportCard(selected: Signal<String>, onclose: EventHandler<()>) -> Element {
+    let tree = use_tree_repo();
+    let mut scope = use_signal(|| "view".to_string());
+    let mut root = use_signal(|| selected.read().clone());
+    let mut format = use_signal(|| "pdf".to_string());
+    let mut fit = use_signal(|| false);
+    let mut title = use_signal(|| "Family tree".to_string());
+    let mut busy = use_signal(|| false);
+    let mut error = use_signal(|| None::<String>);
+    // The finished file and its name, until an option changes
+    let mut ready = use_signal(|| None::<(String, String)>);
+
+    let mut people: Vec<(String, String)> = tree.read().tree.people().map(|p| (p.id.clone(), p.full_name())).collect();
+    people.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut changed = move || {
+        ready.set(None);
+        error.set(None);
+    };
 
-        function loadScript(url, callback) {
-            const script = document.createElement('script');
-            script.type = url.includes('family-chart') ? 'module' : 'text/javascript';
-            script.src = url;
-            script.onload = callback;
-            document.head.appendChild(script);
+    let prepare = move |_| {
+        let (body, layout, subtitle) = {
+            let repo = tree.read();
+            let (part, layout, what) = match scope().as_str() {
+                "view" => (repo.tree.clone(), tree_layout::around(&repo.tree, &repo.home_id, &selected()), "Family tree".to_string()),
+                kind => {
+                    let (branch, what) = if kind == "ancestors" {
+                        (Branch::Ancestors(root()), "Ancestors")
+                    } else {
+                        (Branch::Descendants(root()), "Descendants")
+                    };
+                    let part = repo.tree.only(&repo.tree.branch(&branch));
+                    let name = part.person(&root()).map(|p| p.full_name()).unwrap_or_default();
+                    let layout = tree_layout::layout(&part, &root());
+                    (part, layout, format!("{} of {}", what, name))
+                }
+            };
+            let index = repo.tree.index();
+            let body = chart_export::chart_body(&part, &layout, |id| index.kinship(&repo.home_id, id).title(repo.language));
+            let subtitle = format!("{} · {} people", what, layout.cards.len());
+            (body, layout, subtitle)
+        };
+        let heading = title();
+        let kind = format();
+        busy.set(true);
+        changed();
+        spawn(async move {
+            let made = match kind.as_str() {
+                "svg" => {
+                    let (svg, _, _) = chart_export::svg(&body, &layout, &heading, &subtitle);
+                    Ok((chart_export::data_url("image/svg+xml", svg.as_bytes()), "svg"))
+                }
+                "png" => {
+                    let (svg, width, height) = chart_export::svg(&body, &layout, &heading, &subtitle);
+                    let (width, height) = chart_export::pixels(width, height);
+                    chart_export::rasterize(&svg, width, height, "image/png").await.map(|url| (url, "png"))
+                }
+                _ => {
+                    let (width, height) = chart_export::pixels(chart_export::PAGE_WIDTH, chart_export::PAGE_HEIGHT);
+                    let mut jpegs = Vec::new();
+                    let mut failed = None;
+                    for page in chart_export::pages(&body, &layout, &heading, &subtitle, fit()) {
+                        match chart_export::rasterize(&page, width, height, "image/jpeg").await.and_then(|url| chart_export::decode(&url)) {
+                            Ok(jpeg) => jpegs.push(jpeg),
+                            Err(e) => {
+                                failed = Some(e);
+                                break;
+                            }
+                        }
+                    }
+                    match failed {
+                        Some(e) => Err(e),
+                        None => Ok((chart_export::data_url("application/pdf", &chart_export::pdf(&jpegs, width, height, &heading)), "pdf")),
+                    }
+                }
+            };
+            busy.set(false);
+            match made {
+                Ok((url, extension)) => ready.set(Some((url, format!("family-tree.{}", extension)))),
+                Err(e) => error.set(Some(format!("Couldn't draw the chart: {}", e))),
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "card shadow-sm small mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-center mb-2",
+                    div { class: "fw-bold flex-grow-1", "Export chart" }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                if let Some(message) = error.read().clone() {
+                    div { class: "alert alert-danger py-2 small", {message} }
+                }
+                input {
+                    class: "form-control form-control-sm mb-2",
+                    placeholder: "Title",
+                    value: "{title}",
+                    oninput: move |evt| {
+                        title.set(evt.value());
+                        changed();
+                    },
+                }
+                select {
+                    class: "form-select form-select-sm mb-2",
+                    value: "{scope}",
+                    onchange: move |evt| {
+                        scope.set(evt.value());
+                        changed();
+                    },
+                    option { value: "view", "What's on screen" }
+                    option { value: "descendants", "Descendants of…" }
+                    option { value: "ancestors", "Ancestors of…" }
+                }
+                if scope() != "view" {
+                    select {
+                        class: "form-select form-select-sm mb-2",
+                        value: "{root}",
+                        onchange: move |evt| {
+                            root.set(evt.value());
+                            changed();
+                        },
+                        for (id, name) in people {
+                            option { key: "{id}", value: "{id}", {name} }
+                        }
+                    }
+                }
+                div { class: "btn-group btn-group-sm w-100 mb-2",
+                    for (value, label) in [("svg", "SVG"), ("png", "PNG"), ("pdf", "PDF")] {
+                        button {
+                            key: "{value}",
+                            class: if format() == value { "btn btn-primary" } else { "btn btn-outline-primary" },
+                            onclick: move |_| {
+                                format.set(value.to_string());
+                                changed();
+                            },
+                            {label}
+                        }
+                    }
+                }
+                if format() == "pdf" {
+                    div { class: "form-check mb-2",
+                        input {
+                            class: "form-check-input",
+                            r#type: "checkbox",
+                            id: "chartFitPage",
+                            checked: fit(),
+                            onchange: move |evt| {
+                                fit.set(evt.checked());
+                                changed();
+                            },
+                        }
+                        label { class: "form-check-label", r#for: "chartFitPage", "Shrink onto one page" }
+                    }
+                    if !fit() {
+                        p { class: "text-muted mb-2", "Large charts are split across A4 pages to tape together." }
+                    }
+                }
+                match ready() {
+                    Some((url, name)) => rsx! {
+                        a {
+                            class: "btn btn-success btn-sm w-100",
+                            href: url,
+                            download: "{name}",
+                            i { class: "bi bi-download me-1" }
+                            "Download {name}"
+                        }
+                    },
+                    None => rsx! {
+                        button { class: "btn btn-primary btn-sm w-100", disabled: busy(), onclick: prepare,
+                            if busy() {
+                                span { class: "spinner-border spinner-border-sm me-1" }
+                                "Drawing…"
+                            } else {
+                                i { class: "bi bi-printer me-1" }
+                                "Prepare"
+                            }
+                        }
+                    },
+                }
+            }
         }
+    }
+}
 
-        function create(data) {
-            const cont = document.querySelector("#FamilyChart");
-            if (!cont) {
-                console.error("FamilyChart container not found");
-                return;
+// How any two people in the tree are related
+#[component]
+fn KinshipCard(onclose: EventHandler<()>) -> Element {
+    let mut tree = use_tree_repo();
+    let language = tree.read().language;
+    let first = use_signal(|| tree.read().home_id.clone());
+    let second = use_signal(|| tree.read().tree.people().next().map(|p| p.id.clone()).unwrap_or_default());
+
+    let mut people: Vec<(String, String)> = tree.read().tree.people().map(|p| (p.id.clone(), p.full_name())).collect();
+    people.sort_by(|a, b| a.1.cmp(&b.1));
+    let name_of = |id: &str| people.iter().find(|(p, _)| p == id).map(|(_, name)| name.clone()).unwrap_or_default();
+    let kinship = tree.read().tree.kinship(&first(), &second()).name(language);
+    let answer = match (kinship.is_empty(), language) {
+        (true, Language::Bengali) => "কোনো জানা সম্পর্ক নেই".to_string(),
+        (true, Language::English) => "No known relation".to_string(),
+        (false, Language::Bengali) => format!("{} হলেন {}-এর {}", name_of(&second()), name_of(&first()), kinship),
+        (false, Language::English) => format!("{} is {}'s {}", name_of(&second()), name_of(&first()), kinship),
+    };
+
+    rsx! {
+        div { class: "card shadow-sm small mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-center mb-2",
+                    div { class: "fw-bold flex-grow-1", "Relationship" }
+                    select {
+                        class: "form-select form-select-sm me-2",
+                        style: "width: auto;",
+                        onchange: move |evt| {
+                            if let Some(chosen) = Language::ALL.into_iter().find(|l| l.label() == evt.value()) {
+                                tree.write().set_language(chosen);
+                            }
+                        },
+                        for option_language in Language::ALL {
+                            option {
+                                key: "{option_language.label()}",
+                                value: option_language.label(),
+                                selected: option_language == language,
+                                {option_language.label()}
+                            }
+                        }
+                    }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                for (index, mut chosen) in [first, second].into_iter().enumerate() {
+                    select {
+                        key: "{index}",
+                        class: "form-select form-select-sm mb-2",
+                        value: "{chosen}",
+                        onchange: move |evt| chosen.set(evt.value()),
+                        for (id, name) in people.iter() {
+                            option { key: "{id}", value: "{id}", selected: *id == chosen(), {name.clone()} }
+                        }
+                    }
+                }
+                div { class: "fw-bold", {answer} }
             }
-            
-            // Clear any existing content
-            cont.innerHTML = '';
-            
-            // Set background color for the container
-            cont.style.backgroundColor = "#f0f2f5";
-            
-            const store = f3.createStore({
-                data,
-                node_separation: 250,
-                level_separation: 150
-            });
-            
-            const svg = f3.createSvg(cont);
-            
-            const Card = f3.elements.Card({
-                store,
-                svg,
-                card_dim: {w:220, h:100, text_x:75, text_y:15, img_w:60, img_h:60, img_x:5, img_y:5},
-                card_display: [
-                    d => `${d.data["first name"]} ${d.data["last name"]}`,
-                    d => `${d.data["birthday"]}`,
-                    d => `${d.data["role"] || ""}`,
-                    d => `${d.data["info"] || ""}`
-                ],
-                mini_tree: true,
-                link_break: false
-            });
+        }
+    }
+}
 
-            store.setOnUpdate(props => {
-                f3.view(store.getTree(), svg, Card, props || {});
-                
-                // After rendering, fix specific issues
-                setTimeout(() => {
-                    // Fix link visibility
-                    document.querySelectorAll('.link, .marriage-link').forEach(link => {
-                        link.style.stroke = '#6c757d';
-                        link.style.strokeWidth = '2px';
-                        link.style.opacity = '1';
-                    });
-                    
-                    // Remove text overflow masks
-                    document.querySelectorAll('.text-overflow-mask').forEach(mask => {
-                        mask.style.display = 'none';
-                    });
-                }, 100);
-            });
-            
-            store.updateTree({initial: true});
+// Problems the validator found, each with links to the people involved
+#[component]
+fn HealthCard(onjump: EventHandler<String>, onclose: EventHandler<()>) -> Element {
+    let tree = use_tree_repo();
+    let issues: Vec<(Issue, Vec<(String, String)>)> = {
+        let repo = tree.read();
+        repo.health
+            .iter()
+            .map(|issue| {
+                let people = issue.people.iter().filter_map(|id| repo.tree.person(id)).map(|p| (p.id.clone(), p.full_name())).collect();
+                (issue.clone(), people)
+            })
+            .collect()
+    };
+
+    rsx! {
+        div { class: "card shadow-sm small mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-center mb-2",
+                    div { class: "fw-bold flex-grow-1", "Tree health" }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                if issues.is_empty() {
+                    div { class: "text-success",
+                        i { class: "bi bi-check-circle me-1" }
+                        "No problems found."
+                    }
+                }
+                ul { class: "list-unstyled mb-0", style: "max-height: 40vh; overflow-y: auto;",
+                    for (index, (issue, people)) in issues.into_iter().enumerate() {
+                        li { key: "{index}", class: "border-top py-1",
+                            div { class: "fw-bold text-danger", {issue.problem.label()} }
+                            div { {issue.message.clone()} }
+                            for (id, name) in people {
+                                button {
+                                    key: "{id}",
+                                    class: "btn btn-link btn-sm p-0 me-2",
+                                    onclick: {
+                                        let id = id.clone();
+                                        move |_| onjump.call(id.clone())
+                                    },
+                                    i { class: "bi bi-box-arrow-up-right me-1" }
+                                    {name}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         }
+    }
+}
 
-        // Generate SVG placeholder for missing images
-        function generateAvatarSvg(name, gender) {
-            const colors = {
-                M: "#d4e6f9", // Blue for male
-                F: "#f9d4d4", // Red for female
-                O: "#34A853"  // Green for other
-            };
-            const color = colors[gender] || "#FBBC05"; // Yellow default
-            const initials = name.split(' ').map(n => n[0]).join('').toUpperCase();
-            
-            return `data:image/svg+xml;utf8,<svg xmlns="http://www.w3.org/2000/svg" width="60" height="60" viewBox="0 0 60 60"><circle cx="30" cy="30" r="30" fill="${color}"/><text x="30" y="35" font-family="Arial" font-size="20" fill="white" text-anchor="middle">${initials}</text></svg>`;
+// People who may be in the tree twice, and the merge that can be undone
+#[component]
+fn DuplicatesCard(oncompare: EventHandler<(String, String)>, onclose: EventHandler<()>) -> Element {
+    let mut tree = use_tree_repo();
+    let mut error = use_signal(|| None::<String>);
+    let (candidates, last_merge) = {
+        let repo = tree.read();
+        let name_of = |id: &str| repo.tree.persons.iter().find(|p| p.id == id).map(|p| p.full_name()).unwrap_or_default();
+        let candidates: Vec<(person_match::Candidate, String, String)> = repo
+            .duplicates()
+            .iter()
+            .map(|c| (c.clone(), name_of(&c.first), name_of(&c.second)))
+            .collect();
+        let last_merge = repo.merges.last().map(|m| format!("Merged {} into {}", name_of(&m.removed), name_of(&m.kept)));
+        (candidates, last_merge)
+    };
+
+    rsx! {
+        div { class: "card shadow-sm small mb-2",
+            div { class: "card-body py-2",
+                div { class: "d-flex align-items-center mb-2",
+                    div { class: "fw-bold flex-grow-1", "Possible duplicates" }
+                    button { class: "btn-close", onclick: move |_| onclose.call(()) }
+                }
+                if let Some(message) = last_merge {
+                    div { class: "alert alert-secondary d-flex align-items-center py-1 px-2 mb-2",
+                        span { class: "flex-grow-1", {message} }
+                        button {
+                            class: "btn btn-link btn-sm p-0",
+                            onclick: move |_| {
+                                let result = tree.write().undo_merge();
+                                error.set(result.err());
+                            },
+                            i { class: "bi bi-arrow-counterclockwise me-1" }
+                            "Undo"
+                        }
+                    }
+                }
+                if let Some(message) = error.read().clone() {
+                    div { class: "alert alert-danger py-1 px-2 mb-2", {message} }
+                }
+                if candidates.is_empty() {
+                    div { class: "text-success",
+                        i { class: "bi bi-check-circle me-1" }
+                        "Nobody seems to be in the tree twice."
+                    }
+                }
+                ul { class: "list-unstyled mb-0", style: "max-height: 40vh; overflow-y: auto;",
+                    for (candidate, first_name, second_name) in candidates {
+                        li { key: "{candidate.first}-{candidate.second}", class: "border-top py-1",
+                            div { class: "d-flex align-items-center",
+                                div { class: "fw-bold flex-grow-1", "{first_name} · {second_name}" }
+                                span { class: "badge bg-secondary", "{candidate.score}" }
+                            }
+                            div { class: "text-muted", {candidate.reasons.join(", ")} }
+                            div {
+                                button {
+                                    class: "btn btn-link btn-sm p-0 me-3",
+                                    onclick: {
+                                        let pair = (candidate.first.clone(), candidate.second.clone());
+                                        move |_| oncompare.call(pair.clone())
+                                    },
+                                    i { class: "bi bi-layout-split me-1" }
+                                    "Compare"
+                                }
+                                button {
+                                    class: "btn btn-link btn-sm p-0 text-muted",
+                                    onclick: {
+                                        let (first, second) = (candidate.first.clone(), candidate.second.clone());
+                                        move |_| tree.write().mark_distinct(&first, &second)
+                                    },
+                                    "Not the same"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         }
+    }
+}
 
-        function getData() {
-            return [
-                {
-                    "id": "0",
-                    "rels": {
-                        "spouses": ["1"],
-                        "children": ["2", "3"]
-                    },
-                    "data": {
-                        "first name": "John",
-                        "last name": "Doe",
-                        "birthday": "1970",
-                        "role": "Father",
-                        "info": "Engineer",
-                        "avatar": generateAvatarSvg("John Doe", "M"),
-                        "gender": "M"
+// What the person editor is open for
+#[derive(Clone, PartialEq)]
+enum Editing {
+    Person(String),
+    Relative(String, Relative),
+    New,
+}
+
+// The person picked on the chart, with the quick actions for them
+#[component]
+fn PersonBar(selected: Signal<String>, onedit: EventHandler<Editing>) -> Element {
+    let tree = use_tree_repo();
+    let language = tree.read().language;
+    let mut people: Vec<(String, String)> = tree.read().tree.people().map(|p| (p.id.clone(), p.full_name())).collect();
+    people.sort_by(|a, b| a.1.cmp(&b.1));
+    // Fall back to us when the pick was deleted
+    let person = {
+        let repo = tree.read();
+        repo.tree.person(&selected()).or_else(|| repo.tree.person(&repo.home_id)).cloned()
+    };
+    let Some(person) = person else {
+        return rsx! {};
+    };
+    let title = tree.read().kinship_to_me(&person.id).title(language);
+    let lifespan = match (person.birth_date.is_empty(), person.death_date.is_empty()) {
+        (true, true) => String::new(),
+        (false, true) => format!("b. {}", fuzzy_date::tidy(&person.birth_date)),
+        (true, false) => format!("d. {}", fuzzy_date::tidy(&person.death_date)),
+        (false, false) => format!("{} – {}", fuzzy_date::tidy(&person.birth_date), fuzzy_date::tidy(&person.death_date)),
+    };
+    let actions = [
+        ("bi-person-up", Relative::Parent(RelationKind::Parent)),
+        ("bi-person-down", Relative::Child(RelationKind::Parent)),
+        ("bi-heart", Relative::Spouse),
+    ];
+
+    rsx! {
+        div { class: "card shadow-sm small",
+            div { class: "card-body py-2",
+                select {
+                    class: "form-select form-select-sm mb-2",
+                    value: "{person.id}",
+                    onchange: move |evt| selected.set(evt.value()),
+                    for (id, name) in people {
+                        option { key: "{id}", value: "{id}", selected: id == person.id, {name} }
                     }
-                },
-                {
-                    "id": "1",
-                    "data": {
-                        "gender": "F",
-                        "first name": "Jane",
-                        "last name": "Smith",
-                        "birthday": "1972",
-                        "role": "Mother",
-                        "info": "Doctor",
-                        "avatar": generateAvatarSvg("Jane Smith", "F")
-                    },
-                    "rels": {
-                        "spouses": ["0"],
-                        "children": ["2", "3"]
+                }
+                div { class: "d-flex align-items-center mb-2",
+                    img {
+                        class: "rounded-circle me-2",
+                        src: "{person.avatar()}",
+                        alt: "",
+                        style: "width: 40px; height: 40px; object-fit: cover;",
                     }
-                },
-                {
-                    "id": "2",
-                    "data": {
-                        "gender": "M",
-                        "first name": "Bob",
-                        "last name": "Doe",
-                        "birthday": "1995",
-                        "role": "Son",
-                        "info": "Teacher",
-                        "avatar": generateAvatarSvg("Bob Doe", "M")
-                    },
-                    "rels": {
-                        "father": "0",
-                        "mother": "1",
-                        "spouses": ["4"],
-                        "children": ["5"]
+                    div { class: "flex-grow-1",
+                        div { class: "fw-bold", {person.full_name()} }
+                        div { class: "text-muted",
+                            {title}
+                            if !lifespan.is_empty() { " · {lifespan}" }
+                        }
                     }
-                },
-                {
-                    "id": "3",
-                    "data": {
-                        "gender": "F",
-                        "first name": "Alice",
-                        "last name": "Doe",
-                        "birthday": "1997",
-                        "role": "Daughter",
-                        "info": "Artist",
-                        "avatar": generateAvatarSvg("Alice Doe", "F")
-                    },
-                    "rels": {
-                        "father": "0",
-                        "mother": "1"
+                }
+                div { class: "d-flex flex-wrap gap-1",
+                    button {
+                        class: "btn btn-outline-primary btn-sm",
+                        onclick: {
+                            let id = person.id.clone();
+                            move |_| onedit.call(Editing::Person(id.clone()))
+                        },
+                        i { class: "bi bi-pencil me-1" }
+                        "Edit"
                     }
-                },
-                {
-                    "id": "4",
-                    "data": {
-                        "gender": "F",
-                        "first name": "Carol",
-                        "last name": "Johnson",
-                        "birthday": "1996",
-                        "role": "Daughter-in-law",
-                        "info": "Architect",
-                        "avatar": generateAvatarSvg("Carol Johnson", "F")
-                    },
-                    "rels": {
-                        "spouses": ["2"],
-                        "children": ["5"]
+                    for (icon, relative) in actions {
+                        button {
+                            key: "{icon}",
+                            class: "btn btn-outline-secondary btn-sm",
+                            onclick: {
+                                let id = person.id.clone();
+                                move |_| onedit.call(Editing::Relative(id.clone(), relative))
+                            },
+                            i { class: "bi {icon} me-1" }
+                            "{relative.label()}"
+                        }
                     }
-                },
-                {
-                    "id": "5",
-                    "data": {
-                        "gender": "M",
-                        "first name": "David",
-                        "last name": "Doe",
-                        "birthday": "2020",
-                        "role": "Grandson",
-                        "info": "Student",
-                        "avatar": generateAvatarSvg("David Doe", "M")
-                    },
-                    "rels": {
-                        "father": "2",
-                        "mother": "4"
+                    button {
+                        class: "btn btn-outline-secondary btn-sm",
+                        onclick: move |_| onedit.call(Editing::New),
+                        i { class: "bi bi-person-plus me-1" }
+                        "New person"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn Tree() -> Element {
+    let mut tree = use_tree_repo();
+    let mut report = use_signal(|| None::<Result<(String, ImportReport), String>>);
+    let mut exporting = use_signal(|| false);
+    let mut drawing = use_signal(|| false);
+    let mut relating = use_signal(|| false);
+    let mut checking = use_signal(|| false);
+    let mut deduping = use_signal(|| false);
+    let mut comparing = use_signal(|| None::<(String, String)>);
+    let mut selected = use_signal(|| tree.read().home_id.clone());
+    let mut editing = use_signal(|| None::<Editing>);
+    let problems = tree.read().health.len();
+
+    let import = move |evt: Event<FormData>| async move {
+        let Some(files) = evt.files() else {
+            return;
+        };
+        for name in files.files() {
+            let read = files
+                .read_file(&name)
+                .await
+                .ok_or_else(|| "couldn't read the file".to_string())
+                .and_then(|bytes| gedcom::parse(&bytes));
+            match read {
+                Ok((found, done)) => {
+                    tree.write().import(found);
+                    report.set(Some(Ok((name, done))));
+                    // The same ancestor often comes in twice
+                    if !tree.read().duplicates().is_empty() {
+                        deduping.set(true);
                     }
                 }
-            ];
+                Err(e) => report.set(Some(Err(format!("{}: {}", name, e)))),
+            }
         }
-    "###;
+    };
 
     rsx! {
         div {
-            class: "container-fluid p-0 h-100",
-            // Tree Container - full height and width with visible border for debugging
-            div {
-                id: "FamilyChart",
-                class: "f3",
-                style: "width: 100%; height: 100vh; background-color: #ffffff;"
+            class: "container-fluid p-0 h-100 position-relative",
+            div { class: "position-absolute top-0 end-0 p-2", style: "z-index: 10; max-width: 360px;",
+                div { class: "text-end mb-2",
+                    button {
+                        class: "btn btn-light btn-sm shadow-sm me-2",
+                        onclick: move |_| checking.toggle(),
+                        i { class: "bi bi-heart-pulse me-1" }
+                        "Tree health"
+                        span { class: if problems > 0 { "badge bg-danger ms-1" } else { "badge bg-success ms-1" }, "{problems}" }
+                    }
+                    button {
+                        class: "btn btn-light btn-sm shadow-sm me-2",
+                        onclick: move |_| deduping.toggle(),
+                        i { class: "bi bi-people me-1" }
+                        "Duplicates"
+                    }
+                    button {
+                        class: "btn btn-light btn-sm shadow-sm me-2",
+                        onclick: move |_| relating.toggle(),
+                        i { class: "bi bi-diagram-3 me-1" }
+                        "Relationship"
+                    }
+                    button {
+                        class: "btn btn-light btn-sm shadow-sm me-2",
+                        onclick: move |_| exporting.toggle(),
+                        i { class: "bi bi-download me-1" }
+                        "Export GEDCOM"
+                    }
+                    button {
+                        class: "btn btn-light btn-sm shadow-sm me-2",
+                        onclick: move |_| drawing.toggle(),
+                        i { class: "bi bi-printer me-1" }
+                        "Export chart"
+                    }
+                    label { class: "btn btn-light btn-sm shadow-sm mb-0",
+                        i { class: "bi bi-upload me-1" }
+                        "Import GEDCOM"
+                        input {
+                            class: "d-none",
+                            r#type: "file",
+                            accept: ".ged,.gedcom",
+                            onchange: import,
+                        }
+                    }
+                }
+                div { class: "mb-2",
+                    PersonBar { selected, onedit: move |what| editing.set(Some(what)) }
+                }
+                if checking() {
+                    HealthCard {
+                        onjump: move |id| selected.set(id),
+                        onclose: move |_| checking.set(false),
+                    }
+                }
+                if deduping() {
+                    DuplicatesCard {
+                        oncompare: move |pair| comparing.set(Some(pair)),
+                        onclose: move |_| deduping.set(false),
+                    }
+                }
+                if relating() {
+                    KinshipCard { onclose: move |_| relating.set(false) }
+                }
+                if exporting() {
+                    ExportCard { onclose: move |_| exporting.set(false) }
+                }
+                if drawing() {
+                    ChartExportCard { selected, onclose: move |_| drawing.set(false) }
+                }
+                match report.read().clone() {
+                    Some(Ok((name, done))) => rsx! {
+                        ReportCard { name, report: done, onclose: move |_| report.set(None) }
+                    },
+                    Some(Err(message)) => rsx! {
+                        div { class: "alert alert-danger py-2 small", {message} }
+                    },
+                    None => rsx! {},
+                }
+            }
+            match editing() {
+                Some(Editing::Person(id)) => rsx! {
+                    PersonEditor { person_id: id, onclose: move |_| editing.set(None) }
+                },
+                Some(Editing::Relative(of, relative)) => rsx! {
+                    PersonEditor { of, relative, onclose: move |_| editing.set(None) }
+                },
+                Some(Editing::New) => rsx! {
+                    PersonEditor { onclose: move |_| editing.set(None) }
+                },
+                None => rsx! {},
+            }
+            if let Some((first, second)) = comparing() {
+                PersonMerge { first, second, onclose: move |_| comparing.set(None) }
             }
-            // Add script tag with the family tree JavaScript
-            script {
-                dangerous_inner_html: family_tree_js
+            div { style: "width: 100%; height: 100vh;",
+                TreeChart { selected }
             }
         }
     }
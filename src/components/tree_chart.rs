@@ -0,0 +1,188 @@
+use dioxus::prelude::*;
+
+use crate::models::family::Gender;
+use crate::services::repositories::use_tree_repo;
+use crate::services::tree_layout::{self, CardBox, CARD_HEIGHT, CARD_WIDTH};
+
+const MIN_ZOOM: f64 = 0.2;
+const MAX_ZOOM: f64 = 3.0;
+// Pointer movement before a press counts as dragging rather than a click
+const DRAG_SLOP: f64 = 4.0;
+
+// Where the chart is looked at: its centre in chart units, and the zoom
+#[derive(Clone, Copy, PartialEq)]
+struct View {
+    x: f64,
+    y: f64,
+    zoom: f64,
+}
+
+// What one card shows
+#[derive(Clone, PartialEq)]
+struct Face {
+    card: CardBox,
+    gender: Gender,
+    avatar: String,
+    // Name, dates and relationship to us
+    text: [String; 3],
+}
+
+// The family tree drawn as SVG from the Rust layout, so it needs nothing
+// from the network. Drag to pan, scroll or use the buttons to zoom, and
+// click a card to pick that person; the view follows whoever is picked.
+#[component]
+pub fn TreeChart(selected: Signal<String>) -> Element {
+    let tree = use_tree_repo();
+    let layout = use_memo(move || {
+        let repo = tree.read();
+        tree_layout::around(&repo.tree, &repo.home_id, &selected())
+    });
+    // Kept until the tree or the layout changes, rather than naming
+    // everyone's relationship again on every pan and zoom
+    let faces = use_memo(move || {
+        let repo = tree.read();
+        let index = repo.tree.index();
+        let drawn = layout.read();
+        drawn
+            .cards
+            .iter()
+            .filter_map(|card| {
+                let person = index.person(&card.id)?;
+                let title = index.kinship(&repo.home_id, &card.id).title(repo.language);
+                Some(Face { card: card.clone(), gender: person.gender, avatar: person.avatar(), text: tree_layout::card_text(person, &title) })
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut size = use_signal(|| (1200.0, 800.0));
+    let mut view = use_signal(|| View { x: 0.0, y: 0.0, zoom: 1.0 });
+    // Where a drag started, and the view centre then
+    let mut drag = use_signal(|| None::<(f64, f64, f64, f64)>);
+    let mut dragged = use_signal(|| false);
+
+    use_effect(move || {
+        if let Some(card) = layout.read().card(&selected()) {
+            let (x, y) = card.centre();
+            let mut current = view.write();
+            current.x = x;
+            current.y = y;
+        }
+    });
+
+    let current = view();
+    let (width, height) = (size().0 / current.zoom, size().1 / current.zoom);
+    let view_box = format!("{} {} {} {}", current.x - width / 2.0, current.y - height / 2.0, width, height);
+    let mut zoom_by = move |factor: f64| {
+        let mut current = view.write();
+        current.zoom = (current.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    };
+    let drawn = layout.read();
+
+    rsx! {
+        div { class: "position-relative w-100 h-100",
+            svg {
+                class: "w-100 h-100 d-block",
+                style: "background-color: #f0f2f5; touch-action: none; cursor: grab;",
+                view_box: "{view_box}",
+                onresize: move |evt| {
+                    if let Ok(box_size) = evt.get_border_box_size() {
+                        size.set((box_size.width.max(1.0), box_size.height.max(1.0)));
+                    }
+                },
+                onpointerdown: move |evt| {
+                    let point = evt.client_coordinates();
+                    drag.set(Some((point.x, point.y, view().x, view().y)));
+                    dragged.set(false);
+                },
+                onpointermove: move |evt| {
+                    let Some((start_x, start_y, centre_x, centre_y)) = drag() else {
+                        return;
+                    };
+                    let point = evt.client_coordinates();
+                    let (dx, dy) = (point.x - start_x, point.y - start_y);
+                    if dx.abs() + dy.abs() > DRAG_SLOP {
+                        dragged.set(true);
+                    }
+                    let mut current = view.write();
+                    current.x = centre_x - dx / current.zoom;
+                    current.y = centre_y - dy / current.zoom;
+                },
+                onpointerup: move |_| drag.set(None),
+                onpointerleave: move |_| drag.set(None),
+                onwheel: move |evt| {
+                    evt.prevent_default();
+                    let delta = evt.delta().strip_units().y;
+                    zoom_by(if delta < 0.0 { 1.1 } else { 1.0 / 1.1 });
+                },
+                defs {
+                    clipPath { id: "treeAvatarClip", clip_path_units: "objectBoundingBox",
+                        circle { cx: "0.5", cy: "0.5", r: "0.5" }
+                    }
+                }
+                for (index, line) in drawn.lines.iter().enumerate() {
+                    polyline {
+                        key: "{index}",
+                        points: line.points.iter().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(" "),
+                        fill: "none",
+                        stroke: tree_layout::line_stroke(line.kind),
+                        stroke_width: "2",
+                        stroke_dasharray: tree_layout::line_dash(line.kind),
+                    }
+                }
+                for Face { card, gender, avatar, text: [name, dates, title] } in faces() {
+                    g {
+                        key: "{card.id}",
+                        transform: "translate({card.x}, {card.y})",
+                        style: "cursor: pointer;",
+                        onclick: {
+                            let id = card.id.clone();
+                            move |_| {
+                                if !dragged() {
+                                    selected.set(id.clone());
+                                }
+                            }
+                        },
+                        rect {
+                            width: "{CARD_WIDTH}",
+                            height: "{CARD_HEIGHT}",
+                            rx: "10",
+                            fill: tree_layout::card_fill(gender),
+                            stroke: if card.id == selected() { "#0d6efd" } else { "#adb5bd" },
+                            stroke_width: if card.id == selected() { "3" } else { "1" },
+                        }
+                        image {
+                            href: "{avatar}",
+                            x: "10",
+                            y: "10",
+                            width: "60",
+                            height: "60",
+                            clip_path: "url(#treeAvatarClip)",
+                            preserve_aspect_ratio: "xMidYMid slice",
+                        }
+                        text { x: "80", y: "26", font_size: "14", font_weight: "bold", fill: "#212529", {name} }
+                        text { x: "80", y: "46", font_size: "12", fill: "#495057", {dates} }
+                        text { x: "80", y: "64", font_size: "12", fill: "#6c757d", {title} }
+                    }
+                }
+            }
+            div { class: "position-absolute bottom-0 start-0 p-2 btn-group-vertical", style: "margin-bottom: 70px;",
+                button { class: "btn btn-light btn-sm shadow-sm", title: "Zoom in", onclick: move |_| zoom_by(1.25),
+                    i { class: "bi bi-zoom-in" }
+                }
+                button { class: "btn btn-light btn-sm shadow-sm", title: "Zoom out", onclick: move |_| zoom_by(0.8),
+                    i { class: "bi bi-zoom-out" }
+                }
+                button {
+                    class: "btn btn-light btn-sm shadow-sm",
+                    title: "Back to the picked person",
+                    onclick: move |_| {
+                        if let Some(card) = layout.read().card(&selected()) {
+                            let (x, y) = card.centre();
+                            view.set(View { x, y, zoom: 1.0 });
+                        }
+                    },
+                    i { class: "bi bi-bullseye" }
+                }
+            }
+        }
+    }
+}
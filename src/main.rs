@@ -1,9 +1,16 @@
 use dioxus::prelude::*;
 
 mod components;
+mod models;
+mod services;
 mod state;
+mod utils;
 
-use components::{NavBar, BottomNav, Home, Profile, Comms, Circles, Tree, Settings, SystemInfo};
+use components::{NavBar, BottomNav, CallScreen, Home, Profile, Comms, Conversation, Circles, CircleFeed, CircleMembers, CircleEvents, Calendar, ImportContacts, AcceptInvite, Tree, Settings, SystemInfo};
+use services::calls::use_calls_provider;
+use services::messaging::use_message_delivery;
+use services::polls::use_live_polls;
+use services::repositories::use_repositories_provider;
 use state::{use_app_state, Theme};
 use dioxus::prelude::{ErrorBoundary, VNode};
 
@@ -20,9 +27,30 @@ enum Route {
     #[route("/comms")]
     Comms {},
 
+    #[route("/comms/:id")]
+    Conversation { id: String },
+
     #[route("/circles")]
     Circles {},
 
+    #[route("/circles/:id")]
+    CircleFeed { id: String },
+
+    #[route("/circles/:id/members")]
+    CircleMembers { id: String },
+
+    #[route("/circles/:id/events")]
+    CircleEvents { id: String },
+
+    #[route("/circles/:id/import")]
+    ImportContacts { id: String },
+
+    #[route("/calendar")]
+    Calendar {},
+
+    #[route("/invite/:token")]
+    AcceptInvite { token: String },
+
     #[route("/trees")]
     Tree {},
 
@@ -159,6 +187,7 @@ fn MainLayout() -> Element {
                 Outlet::<Route> {}
             }
             BottomNav {}
+            CallScreen {}
         }
     }
 }
@@ -169,11 +198,17 @@ fn App() -> Element {
     // Get the app state to determine initial theme
     let state = use_app_state();
 
+    // Load the repositories once and share them with every page
+    use_repositories_provider();
+    use_calls_provider();
+    use_live_polls();
+    use_message_delivery();
+
     // React to theme changes and update the <html> element's data-bs-theme attribute
     // This will work for web and mobile (WebView)
     #[cfg(feature = "web")]
     use_effect(move || {
-        let app_state = state.clone(); // Clone the signal for use in the effect
+        let app_state = state; // Signal is Copy, so it can be moved into the effect
         let current_theme_attr = match app_state.read().theme {
             Theme::Light => "light",
             Theme::Dark => "dark",
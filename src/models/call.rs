@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::services::sync::{Identified, Merge};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallKind {
+    Audio,
+    Video,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallDirection {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallOutcome {
+    Missed,
+    Declined,
+    Completed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallParticipant {
+    pub id: String,
+    pub name: String,
+    pub avatar: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Call {
+    pub id: String,
+    pub kind: CallKind,
+    pub direction: CallDirection,
+    // Everyone on the call other than us
+    pub participants: Vec<CallParticipant>,
+    pub started_at: u64,
+    pub duration_secs: u64,
+    pub outcome: CallOutcome,
+    // Whether a missed call has been looked at
+    #[serde(default)]
+    pub seen: bool,
+}
+
+impl Call {
+    pub fn is_unseen_missed(&self) -> bool {
+        self.direction == CallDirection::Incoming && self.outcome == CallOutcome::Missed && !self.seen
+    }
+
+    pub fn title(&self) -> String {
+        self.participants
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // Calls with the same people, direction and outcome on the same local day
+    // are shown as one log entry; offset is local time minus UTC
+    pub fn groups_with(&self, other: &Call, offset: i64) -> bool {
+        let day = |millis: u64| (millis as i64 + offset).div_euclid(DAY_MILLIS);
+        self.kind == other.kind
+            && self.direction == other.direction
+            && self.outcome == other.outcome
+            && self.participants == other.participants
+            && day(self.started_at) == day(other.started_at)
+    }
+}
+
+impl Identified for Call {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Call {
+    fn merge(&mut self, other: Self) {
+        self.seen |= other.seen;
+        self.duration_secs = self.duration_secs.max(other.duration_secs);
+    }
+}
+
+// Consecutive calls that belong together, newest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallGroup {
+    pub latest: Call,
+    pub count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    fn call(started_at: u64) -> Call {
+        Call {
+            id: started_at.to_string(),
+            kind: CallKind::Audio,
+            direction: CallDirection::Incoming,
+            participants: vec![CallParticipant { id: "p1".into(), name: "Rina".into(), avatar: String::new() }],
+            started_at,
+            duration_secs: 0,
+            outcome: CallOutcome::Missed,
+            seen: false,
+        }
+    }
+
+    #[test]
+    fn calls_group_by_local_day() {
+        // 23:00 and 01:00 UTC fall on one day in Dhaka (+6) but not in UTC
+        let late = call(10 * 24 * HOUR + 23 * HOUR);
+        let early = call(11 * 24 * HOUR + HOUR);
+        let dhaka = 6 * HOUR as i64;
+        assert!(!late.groups_with(&early, 0));
+        assert!(late.groups_with(&early, dhaka));
+
+        // 17:00 and 20:00 UTC share a UTC day but Dhaka's midnight falls between
+        let evening = call(10 * 24 * HOUR + 20 * HOUR);
+        let before = call(10 * 24 * HOUR + 17 * HOUR);
+        assert!(before.groups_with(&evening, 0));
+        assert!(!before.groups_with(&evening, dhaka));
+        assert!(evening.groups_with(&late, dhaka));
+    }
+
+    #[test]
+    fn different_outcomes_stay_apart() {
+        let mut completed = call(HOUR);
+        completed.outcome = CallOutcome::Completed;
+        assert!(!call(HOUR).groups_with(&completed, 0));
+    }
+}
@@ -0,0 +1,523 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::message::Tombstone;
+use crate::models::{LOCAL_USER_AVATAR, LOCAL_USER_ID, LOCAL_USER_NAME};
+use crate::services::sync::{Identified, Merge, Stamp};
+use crate::utils::{new_id, now_millis, random_bytes, to_hex};
+
+// Icons a circle can use
+pub const CIRCLE_ICONS: [&str; 7] = [
+    "bi-person-lines-fill",
+    "bi-house-heart",
+    "bi-briefcase",
+    "bi-lock",
+    "bi-book",
+    "bi-globe",
+    "bi-gear",
+];
+
+pub const MAX_NAME_LEN: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    // Only visible to us, like an address book group
+    Personal,
+    // Invite only
+    Private,
+    // Anyone can find and join
+    Public,
+}
+
+impl Visibility {
+    pub const ALL: [Visibility; 3] = [Visibility::Personal, Visibility::Private, Visibility::Public];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Visibility::Personal => "Personal",
+            Visibility::Private => "Private",
+            Visibility::Public => "Public",
+        }
+    }
+}
+
+// Ordered from least to most powerful
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Role {
+    #[default]
+    Member,
+    Moderator,
+    Admin,
+    Owner,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    Invite,
+    Remove,
+    Post,
+    Rename,
+    Delete,
+    ChangeRoles,
+    RevokeInvites,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Owner, Role::Admin, Role::Moderator, Role::Member];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Owner => "Owner",
+            Role::Admin => "Admin",
+            Role::Moderator => "Moderator",
+            Role::Member => "Member",
+        }
+    }
+
+    // The permission matrix
+    pub fn can(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Post => true,
+            Permission::Invite | Permission::Remove => *self >= Role::Moderator,
+            Permission::Rename | Permission::ChangeRoles | Permission::RevokeInvites => *self >= Role::Admin,
+            Permission::Delete => *self == Role::Owner,
+        }
+    }
+}
+
+impl Permission {
+    pub const ALL: [Permission; 7] = [
+        Permission::Invite,
+        Permission::Remove,
+        Permission::Post,
+        Permission::Rename,
+        Permission::Delete,
+        Permission::ChangeRoles,
+        Permission::RevokeInvites,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Permission::Invite => "Invite members",
+            Permission::Remove => "Remove members",
+            Permission::Post => "Post",
+            Permission::Rename => "Edit name and details",
+            Permission::Delete => "Delete the circle",
+            Permission::ChangeRoles => "Change roles",
+            Permission::RevokeInvites => "Revoke invites",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircleMember {
+    pub id: String,
+    pub name: String,
+    pub avatar: String,
+}
+
+impl CircleMember {
+    pub fn local() -> Self {
+        Self {
+            id: LOCAL_USER_ID.to_string(),
+            name: LOCAL_USER_NAME.to_string(),
+            avatar: LOCAL_USER_AVATAR.to_string(),
+        }
+    }
+}
+
+// Membership of one person, last writer wins
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Membership {
+    pub member: CircleMember,
+    #[serde(default)]
+    pub role: Role,
+    pub active: bool,
+    pub stamp: Stamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteStatus {
+    Active,
+    Expired,
+    UsedUp,
+    Revoked,
+}
+
+impl InviteStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            InviteStatus::Active => "Active",
+            InviteStatus::Expired => "Expired",
+            InviteStatus::UsedUp => "Used up",
+            InviteStatus::Revoked => "Revoked",
+        }
+    }
+}
+
+// An invite link. Uses and revocation only ever grow, so copies merge by union.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Invite {
+    pub id: String,
+    pub created_by: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    // None for unlimited
+    pub max_uses: Option<u32>,
+    pub used_by: BTreeSet<String>,
+    pub revoked: bool,
+}
+
+impl Invite {
+    pub fn status(&self, now: u64) -> InviteStatus {
+        if self.revoked {
+            InviteStatus::Revoked
+        } else if now >= self.expires_at {
+            InviteStatus::Expired
+        } else if self.max_uses.is_some_and(|max| self.used_by.len() >= max as usize) {
+            InviteStatus::UsedUp
+        } else {
+            InviteStatus::Active
+        }
+    }
+
+    fn merge(&mut self, other: Invite) {
+        self.used_by.extend(other.used_by);
+        self.revoked |= other.revoked;
+    }
+}
+
+// A key invite tokens are signed with. A device that needs one and finds
+// none makes its own, so copies can end up with several; tokens name the key
+// they were signed with and every key stays valid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InviteKey {
+    pub secret: String,
+    pub stamp: Stamp,
+}
+
+// The editable fields of a circle, changed together
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CircleDetails {
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+    pub visibility: Visibility,
+    // Sub-circles name their parent and share its visibility
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    pub id: String,
+    pub details: CircleDetails,
+    pub edited: Stamp,
+    // Keyed by member id; people who left stay as inactive entries
+    pub members: BTreeMap<String, Membership>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub deleted: Option<Tombstone>,
+    #[serde(default)]
+    pub invites: BTreeMap<String, Invite>,
+    // Keys invite tokens are signed with, by key id. Only copies held by
+    // people who may invite keep them.
+    #[serde(default)]
+    pub invite_keys: BTreeMap<String, InviteKey>,
+}
+
+// What the create/edit form produces. `members` leaves out whoever is
+// editing, since nobody adds or removes themselves through the form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CircleDraft {
+    pub details: CircleDetails,
+    pub members: Vec<CircleMember>,
+}
+
+impl CircleDraft {
+    pub fn new(visibility: Visibility) -> Self {
+        Self {
+            details: CircleDetails {
+                name: String::new(),
+                description: String::new(),
+                icon: CIRCLE_ICONS[0].to_string(),
+                visibility,
+                parent: None,
+            },
+            members: Vec::new(),
+        }
+    }
+
+    pub fn of(circle: &Circle, editor: &str) -> Self {
+        Self {
+            details: circle.details.clone(),
+            members: circle.active_members().into_iter().filter(|m| m.id != editor).collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.details.name.trim();
+        if name.is_empty() {
+            return Err("Give the circle a name".to_string());
+        }
+        if name.chars().count() > MAX_NAME_LEN {
+            return Err(format!("Names can be at most {} characters", MAX_NAME_LEN));
+        }
+        if !CIRCLE_ICONS.contains(&self.details.icon.as_str()) {
+            return Err("Pick one of the available icons".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn has_member(&self, id: &str) -> bool {
+        self.members.iter().any(|m| m.id == id)
+    }
+
+    pub fn toggle_member(&mut self, member: CircleMember) {
+        match self.members.iter().position(|m| m.id == member.id) {
+            Some(index) => {
+                self.members.remove(index);
+            }
+            None => self.members.push(member),
+        }
+    }
+}
+
+impl Circle {
+    pub fn create(draft: CircleDraft, owner: CircleMember) -> Self {
+        let mut circle = Self {
+            id: new_id(),
+            details: CircleDetails { name: draft.details.name.trim().to_string(), ..draft.details },
+            edited: Stamp::now(),
+            members: BTreeMap::new(),
+            created_at: now_millis(),
+            deleted: None,
+            invites: BTreeMap::new(),
+            // Made with the first invite
+            invite_keys: BTreeMap::new(),
+        };
+        circle.add_member(owner.clone(), Role::Owner);
+        for member in draft.members {
+            circle.add_member(member, Role::Member);
+        }
+        circle
+    }
+
+    // A circle known only from the directory or an invite link; the rest
+    // of its members arrive when it syncs
+    pub fn listed(id: &str, details: CircleDetails) -> Self {
+        Self {
+            id: id.to_string(),
+            details,
+            edited: Stamp { millis: 0, device: String::new() },
+            members: BTreeMap::new(),
+            created_at: now_millis(),
+            deleted: None,
+            invites: BTreeMap::new(),
+            invite_keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    // Current members, most senior first
+    pub fn memberships(&self) -> Vec<&Membership> {
+        let mut members: Vec<&Membership> = self.members.values().filter(|m| m.active).collect();
+        members.sort_by(|a, b| b.role.cmp(&a.role).then_with(|| a.member.name.cmp(&b.member.name)));
+        members
+    }
+
+    pub fn active_members(&self) -> Vec<CircleMember> {
+        self.memberships().into_iter().map(|m| m.member.clone()).collect()
+    }
+
+    pub fn member_count(&self) -> usize {
+        self.members.values().filter(|m| m.active).count()
+    }
+
+    pub fn role_of(&self, user_id: &str) -> Option<Role> {
+        self.members.get(user_id).filter(|m| m.active).map(|m| m.role)
+    }
+
+    pub fn can(&self, user_id: &str, permission: Permission) -> bool {
+        self.role_of(user_id).is_some_and(|role| role.can(permission))
+    }
+
+    // Removing someone or changing their role needs the permission and a
+    // more senior role than theirs
+    pub fn can_manage(&self, user_id: &str, permission: Permission, target: &str) -> bool {
+        match (self.role_of(user_id), self.role_of(target)) {
+            (Some(actor), Some(target_role)) => actor.can(permission) && actor > target_role,
+            _ => false,
+        }
+    }
+
+    // Roles `user_id` may hand out
+    pub fn assignable_roles(&self, user_id: &str) -> Vec<Role> {
+        match self.role_of(user_id) {
+            Some(actor) if actor.can(Permission::ChangeRoles) => {
+                Role::ALL.into_iter().filter(|r| *r < actor).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Apply an edit made by `editor`, checking each part against their role
+    // and only stamping what actually changed
+    pub fn apply(&mut self, draft: CircleDraft, editor: &str) -> Result<(), String> {
+        let details = CircleDetails { name: draft.details.name.trim().to_string(), ..draft.details };
+        if details != self.details && !self.can(editor, Permission::Rename) {
+            return Err("Only admins can change the circle's details".to_string());
+        }
+        let leaving: Vec<String> = self
+            .memberships()
+            .iter()
+            .map(|m| m.member.id.clone())
+            .filter(|id| id != editor && !draft.members.iter().any(|m| m.id == *id))
+            .collect();
+        if leaving.iter().any(|id| !self.can_manage(editor, Permission::Remove, id)) {
+            return Err("You can't remove some of these members".to_string());
+        }
+        let joining: Vec<CircleMember> =
+            draft.members.into_iter().filter(|m| self.role_of(&m.id).is_none()).collect();
+        if !joining.is_empty() && !self.can(editor, Permission::Invite) {
+            return Err("You can't add members to this circle".to_string());
+        }
+
+        if details != self.details {
+            self.details = details;
+            self.edited = Stamp::after(&self.edited);
+        }
+        for id in leaving {
+            self.remove_member(&id);
+        }
+        for member in joining {
+            self.add_member(member, Role::Member);
+        }
+        Ok(())
+    }
+
+    pub fn add_member(&mut self, member: CircleMember, role: Role) {
+        match self.members.get_mut(&member.id) {
+            Some(membership) if membership.active => {}
+            Some(membership) => {
+                membership.member = member;
+                membership.role = role;
+                membership.active = true;
+                membership.stamp = Stamp::after(&membership.stamp);
+            }
+            None => {
+                let id = member.id.clone();
+                self.members.insert(id, Membership { member, role, active: true, stamp: Stamp::now() });
+            }
+        }
+    }
+
+    pub fn remove_member(&mut self, id: &str) {
+        if let Some(membership) = self.members.get_mut(id).filter(|m| m.active) {
+            membership.active = false;
+            membership.stamp = Stamp::after(&membership.stamp);
+        }
+    }
+
+    pub fn set_role(&mut self, id: &str, role: Role) {
+        if let Some(membership) = self.members.get_mut(id).filter(|m| m.active && m.role != role) {
+            membership.role = role;
+            membership.stamp = Stamp::after(&membership.stamp);
+        }
+    }
+
+    pub fn create_invite(&mut self, created_by: &str, ttl_millis: u64, max_uses: Option<u32>) -> Result<Invite, String> {
+        if self.invite_keys.is_empty() {
+            let key = InviteKey { secret: to_hex(&random_bytes::<32>()?), stamp: Stamp::now() };
+            self.invite_keys.insert(new_id(), key);
+        }
+        let now = now_millis();
+        let invite = Invite {
+            id: new_id(),
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at: now + ttl_millis,
+            max_uses,
+            used_by: BTreeSet::new(),
+            revoked: false,
+        };
+        self.invites.insert(invite.id.clone(), invite.clone());
+        Ok(invite)
+    }
+
+    // The key new invites are signed with: the most recently made one
+    pub fn signing_key(&self) -> Option<(&String, &InviteKey)> {
+        self.invite_keys.iter().max_by(|a, b| a.1.stamp.cmp(&b.1.stamp))
+    }
+
+    // Invites that can still be used, newest first
+    pub fn outstanding_invites(&self, now: u64) -> Vec<&Invite> {
+        let mut invites: Vec<&Invite> =
+            self.invites.values().filter(|i| i.status(now) == InviteStatus::Active).collect();
+        invites.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        invites
+    }
+
+    pub fn delete(&mut self, user_id: &str) {
+        if self.deleted.is_none() {
+            self.deleted = Some(Tombstone { deleted_by: user_id.to_string(), stamp: Stamp::now() });
+        }
+    }
+}
+
+impl Identified for Circle {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Circle {
+    fn merge(&mut self, other: Self) {
+        if other.edited > self.edited {
+            self.details = other.details;
+            self.edited = other.edited;
+        }
+        for (id, theirs) in other.members {
+            match self.members.get_mut(&id) {
+                Some(ours) if theirs.stamp > ours.stamp => *ours = theirs,
+                Some(_) => {}
+                None => {
+                    self.members.insert(id, theirs);
+                }
+            }
+        }
+        self.created_at = self.created_at.min(other.created_at);
+        for (id, theirs) in other.invites {
+            match self.invites.get_mut(&id) {
+                Some(ours) => ours.merge(theirs),
+                None => {
+                    self.invites.insert(id, theirs);
+                }
+            }
+        }
+        // Keys made on two devices are both kept, so no token goes invalid
+        for (id, theirs) in other.invite_keys {
+            match self.invite_keys.get_mut(&id) {
+                Some(ours) if theirs.stamp > ours.stamp => *ours = theirs,
+                Some(_) => {}
+                None => {
+                    self.invite_keys.insert(id, theirs);
+                }
+            }
+        }
+        // Anyone who can't invite has no use for the keys, and mustn't be
+        // able to sign tokens with them
+        if !self.can(LOCAL_USER_ID, Permission::Invite) {
+            self.invite_keys.clear();
+        }
+        // The earliest deletion wins so both sides keep the same tombstone
+        self.deleted = match (self.deleted.take(), other.deleted) {
+            (Some(a), Some(b)) => Some(if b.stamp < a.stamp { b } else { a }),
+            (a, b) => a.or(b),
+        };
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use crate::services::sync::{Identified, Merge, Stamp};
+
+// Names at least this similar are offered as possible duplicates
+const NAME_SIMILARITY: f64 = 0.85;
+// Phone numbers match on their last digits so "+880 1712-345678" and
+// "01712345678" are the same number
+const PHONE_DIGITS_COMPARED: usize = 10;
+const MIN_PHONE_DIGITS: usize = 7;
+
+// Address book details for a circle member, keyed by the member's id.
+// The whole card is last-writer-wins.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct ContactCard {
+    pub member_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub given_name: String,
+    #[serde(default)]
+    pub family_name: String,
+    #[serde(default)]
+    pub phones: Vec<String>,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub organization: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub birthday: String,
+    #[serde(default)]
+    pub note: String,
+    // Web address or data: URI
+    #[serde(default)]
+    pub photo: String,
+    // The UID of the vCard this came from, kept so re-exports match up
+    #[serde(default)]
+    pub uid: String,
+    pub stamp: Stamp,
+}
+
+// Strongest evidence first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchReason {
+    Phone,
+    Email,
+    SimilarName,
+}
+
+impl MatchReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatchReason::Phone => "Same phone number",
+            MatchReason::Email => "Same email",
+            MatchReason::SimilarName => "Similar name",
+        }
+    }
+}
+
+impl ContactCard {
+    // The strongest reason to think both cards are the same person
+    pub fn matches(&self, other: &ContactCard) -> Option<MatchReason> {
+        if self.phones.iter().any(|a| other.phones.iter().any(|b| same_phone(a, b))) {
+            return Some(MatchReason::Phone);
+        }
+        if self.emails.iter().any(|a| other.emails.iter().any(|b| a.trim().eq_ignore_ascii_case(b.trim()))) {
+            return Some(MatchReason::Email);
+        }
+        (name_similarity(&self.name, &other.name) >= NAME_SIMILARITY).then_some(MatchReason::SimilarName)
+    }
+
+    // Fold another card for the same person into this one. Our own fields
+    // win; theirs fill the gaps and add phone numbers and emails we lack.
+    pub fn absorb(&mut self, other: &ContactCard) {
+        for phone in other.phones.iter() {
+            if !self.phones.iter().any(|p| same_phone(p, phone)) {
+                self.phones.push(phone.clone());
+            }
+        }
+        for email in other.emails.iter() {
+            if !self.emails.iter().any(|e| e.eq_ignore_ascii_case(email)) {
+                self.emails.push(email.clone());
+            }
+        }
+        let fill = |ours: &mut String, theirs: &String| {
+            if ours.is_empty() {
+                ours.clone_from(theirs);
+            }
+        };
+        fill(&mut self.given_name, &other.given_name);
+        fill(&mut self.family_name, &other.family_name);
+        fill(&mut self.organization, &other.organization);
+        fill(&mut self.title, &other.title);
+        fill(&mut self.birthday, &other.birthday);
+        fill(&mut self.note, &other.note);
+        fill(&mut self.photo, &other.photo);
+        fill(&mut self.uid, &other.uid);
+        self.stamp = Stamp::after(&self.stamp);
+    }
+}
+
+impl Identified for ContactCard {
+    fn id(&self) -> &str {
+        &self.member_id
+    }
+}
+
+impl Merge for ContactCard {
+    fn merge(&mut self, other: Self) {
+        if other.stamp > self.stamp {
+            *self = other;
+        }
+    }
+}
+
+fn phone_digits(phone: &str) -> String {
+    phone.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+fn same_phone(a: &str, b: &str) -> bool {
+    let (a, b) = (phone_digits(a), phone_digits(b));
+    let len = a.len().min(b.len()).min(PHONE_DIGITS_COMPARED);
+    len >= MIN_PHONE_DIGITS && a[a.len() - len..] == b[b.len() - len..]
+}
+
+// Lowercase words in alphabetical order, so "Doe, Jane" and "jane doe" agree
+fn name_key(name: &str) -> Vec<char> {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.sort();
+    words.join(" ").chars().collect()
+}
+
+// 1.0 for the same name, falling towards 0.0 with each edit needed
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (name_key(a), name_key(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    // Levenshtein distance, one row at a time
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / a.len().max(b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(name: &str, phones: &[&str], emails: &[&str]) -> ContactCard {
+        ContactCard {
+            name: name.to_string(),
+            phones: phones.iter().map(|p| p.to_string()).collect(),
+            emails: emails.iter().map(|e| e.to_string()).collect(),
+            ..ContactCard::default()
+        }
+    }
+
+    #[test]
+    fn phone_numbers_match_on_their_last_digits() {
+        assert!(same_phone("+880 1712-345678", "01712345678"));
+        assert!(same_phone("(017) 1234-5678", "+8801712345678"));
+        assert!(!same_phone("01712345678", "01712345679"));
+        // Too short to say
+        assert!(!same_phone("12345", "12345"));
+    }
+
+    #[test]
+    fn names_match_whatever_the_order_and_case() {
+        assert_eq!(name_similarity("Doe, Jane", "jane doe"), 1.0);
+        assert!(name_similarity("Rahim Uddin", "Rahim Udin") >= NAME_SIMILARITY);
+        assert!(name_similarity("Rahim Uddin", "Karim Uddin") < NAME_SIMILARITY);
+        assert_eq!(name_similarity("", "Rahim"), 0.0);
+    }
+
+    #[test]
+    fn the_strongest_reason_is_given() {
+        let rahim = card("Rahim Uddin", &["+880 1712-345678"], &["Rahim@Example.com"]);
+        assert_eq!(rahim.matches(&card("R. U.", &["01712345678"], &["rahim@example.com"])), Some(MatchReason::Phone));
+        assert_eq!(rahim.matches(&card("R. U.", &["01900000000"], &[" rahim@example.com "])), Some(MatchReason::Email));
+        assert_eq!(rahim.matches(&card("Uddin Rahim", &[], &[])), Some(MatchReason::SimilarName));
+        assert_eq!(rahim.matches(&card("Karim Mia", &["01900000000"], &["karim@example.com"])), None);
+    }
+
+    #[test]
+    fn absorbing_fills_gaps_and_adds_what_is_new() {
+        let mut ours = card("Rahim Uddin", &["+880 1712-345678"], &["rahim@example.com"]);
+        ours.title = "Engineer".to_string();
+        let mut theirs = card("Rahim", &["01712345678", "01900000000"], &["RAHIM@example.com", "r@work.com"]);
+        theirs.title = "Manager".to_string();
+        theirs.birthday = "1990-04-21".to_string();
+        let before = ours.stamp.clone();
+        ours.absorb(&theirs);
+        assert_eq!(ours.name, "Rahim Uddin");
+        assert_eq!(ours.phones, ["+880 1712-345678", "01900000000"]);
+        assert_eq!(ours.emails, ["rahim@example.com", "r@work.com"]);
+        assert_eq!(ours.title, "Engineer");
+        assert_eq!(ours.birthday, "1990-04-21");
+        assert!(ours.stamp > before);
+    }
+}
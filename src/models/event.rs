@@ -0,0 +1,527 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::CircleMember;
+use crate::models::message::Tombstone;
+use crate::models::post::earliest;
+use crate::services::sync::{Identified, Merge, Stamp};
+use crate::utils::format::{civil_from_days, days_from_civil, days_in_month};
+use crate::utils::new_id;
+
+pub const MAX_TITLE_LEN: usize = 200;
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+// Repetitions looked at before giving up on a rule, about 13 years of a
+// daily event
+const MAX_REPETITIONS: i64 = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Weekday::Mon => "Mon",
+            Weekday::Tue => "Tue",
+            Weekday::Wed => "Wed",
+            Weekday::Thu => "Thu",
+            Weekday::Fri => "Fri",
+            Weekday::Sat => "Sat",
+            Weekday::Sun => "Sun",
+        }
+    }
+
+    // Two-letter iCalendar code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Weekday::Mon => "MO",
+            Weekday::Tue => "TU",
+            Weekday::Wed => "WE",
+            Weekday::Thu => "TH",
+            Weekday::Fri => "FR",
+            Weekday::Sat => "SA",
+            Weekday::Sun => "SU",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Weekday::ALL.into_iter().find(|d| d.code().eq_ignore_ascii_case(code))
+    }
+
+    // Days after Monday
+    pub fn index(&self) -> i64 {
+        *self as i64
+    }
+
+    // Weekday of a day counted from the Unix epoch, which was a Thursday
+    pub fn of_day(days: i64) -> Self {
+        Weekday::ALL[(days + 3).rem_euclid(7) as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub const ALL: [Frequency; 4] = [Frequency::Daily, Frequency::Weekly, Frequency::Monthly, Frequency::Yearly];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "Daily",
+            Frequency::Weekly => "Weekly",
+            Frequency::Monthly => "Monthly",
+            Frequency::Yearly => "Yearly",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Frequency::ALL.into_iter().find(|f| f.code().eq_ignore_ascii_case(code))
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        }
+    }
+}
+
+// How an event repeats: the part of RRULE we understand. BYDAY only applies
+// to weekly rules; monthly and yearly rules repeat on the start's day of the
+// month and skip months that don't have it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    #[serde(default)]
+    pub by_day: Vec<Weekday>,
+    // Number of occurrences, the first included
+    #[serde(default)]
+    pub count: Option<u32>,
+    // Last moment an occurrence may start
+    #[serde(default)]
+    pub until: Option<u64>,
+}
+
+impl Recurrence {
+    // "Every 2 weeks on Mon, Thu, 10 times"
+    pub fn summary(&self) -> String {
+        let unit = self.frequency.unit();
+        let mut text = match self.interval {
+            0 | 1 => format!("Every {}", unit),
+            n => format!("Every {} {}s", n, unit),
+        };
+        if self.frequency == Frequency::Weekly && !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(Weekday::label).collect();
+            text.push_str(&format!(" on {}", days.join(", ")));
+        }
+        if let Some(count) = self.count {
+            text.push_str(&format!(", {} times", count));
+        }
+        text
+    }
+
+    // Start of every occurrence before `before`, from the first one on.
+    // Calendar arithmetic is done on local wall-clock days, `offset` being
+    // local time minus UTC.
+    fn starts(&self, start: u64, offset: i64, before: u64) -> Vec<u64> {
+        let local = start as i64 + offset;
+        let first_day = local.div_euclid(DAY_MILLIS);
+        let time_of_day = local.rem_euclid(DAY_MILLIS);
+        let interval = i64::from(self.interval.max(1));
+        let (year, month, day) = civil_from_days(first_day);
+        let mut weekdays = match self.by_day.is_empty() {
+            true => vec![Weekday::of_day(first_day)],
+            false => self.by_day.clone(),
+        };
+        weekdays.sort();
+        weekdays.dedup();
+
+        let mut starts = Vec::new();
+        for step in 0..MAX_REPETITIONS {
+            let days: Vec<i64> = match self.frequency {
+                Frequency::Daily => vec![first_day + step * interval],
+                Frequency::Weekly => {
+                    let monday = first_day - Weekday::of_day(first_day).index() + step * 7 * interval;
+                    weekdays.iter().map(|d| monday + d.index()).filter(|&d| d >= first_day).collect()
+                }
+                Frequency::Monthly | Frequency::Yearly => {
+                    let months = step * interval * if self.frequency == Frequency::Yearly { 12 } else { 1 };
+                    let index = year * 12 + i64::from(month) - 1 + months;
+                    let (y, m) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+                    match day <= days_in_month(y, m) {
+                        true => vec![days_from_civil(y, m, day)],
+                        false => vec![],
+                    }
+                }
+            };
+            for day in days {
+                let at = (day * DAY_MILLIS + time_of_day - offset).max(0) as u64;
+                let finished = at >= before
+                    || self.until.is_some_and(|until| at > until)
+                    || self.count.is_some_and(|count| starts.len() >= count as usize);
+                if finished {
+                    return starts;
+                }
+                starts.push(at);
+            }
+        }
+        starts
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rsvp {
+    Going,
+    Maybe,
+    Declined,
+}
+
+impl Rsvp {
+    pub const ALL: [Rsvp; 3] = [Rsvp::Going, Rsvp::Maybe, Rsvp::Declined];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rsvp::Going => "Going",
+            Rsvp::Maybe => "Maybe",
+            Rsvp::Declined => "Can't go",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RsvpMark {
+    pub member: CircleMember,
+    pub answer: Rsvp,
+    pub stamp: Stamp,
+}
+
+// An event in a circle's calendar. Times are UTC milliseconds; all-day events
+// run from local midnight to local midnight. The details are last-writer-wins
+// and each member's answer merges on its own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CircleEvent {
+    pub id: String,
+    pub circle_id: String,
+    // iCalendar UID, kept so the same event re-imported updates in place
+    pub uid: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub location: String,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    #[serde(default)]
+    pub all_day: bool,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    pub organizer: CircleMember,
+    // user id -> answer
+    #[serde(default)]
+    pub rsvps: BTreeMap<String, RsvpMark>,
+    pub stamp: Stamp,
+    #[serde(default)]
+    pub deleted: Option<Tombstone>,
+}
+
+impl CircleEvent {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.ends_at.saturating_sub(self.starts_at)
+    }
+
+    // Starts of the occurrences that overlap [from, to)
+    pub fn occurrences(&self, from: u64, to: u64, offset: i64) -> Vec<u64> {
+        let duration = self.duration();
+        let starts = match &self.recurrence {
+            Some(rule) => rule.starts(self.starts_at, offset, to),
+            None if self.starts_at < to => vec![self.starts_at],
+            None => vec![],
+        };
+        starts.into_iter().filter(|&start| start + duration.max(1) > from).collect()
+    }
+
+    pub fn rsvp_of(&self, user_id: &str) -> Option<Rsvp> {
+        self.rsvps.get(user_id).map(|mark| mark.answer)
+    }
+
+    pub fn set_rsvp(&mut self, member: CircleMember, answer: Rsvp) {
+        let stamp = match self.rsvps.get(&member.id) {
+            Some(previous) => Stamp::after(&previous.stamp),
+            None => Stamp::now(),
+        };
+        self.rsvps.insert(member.id.clone(), RsvpMark { member, answer, stamp });
+    }
+
+    pub fn answered(&self, answer: Rsvp) -> Vec<CircleMember> {
+        self.rsvps.values().filter(|m| m.answer == answer).map(|m| m.member.clone()).collect()
+    }
+
+    pub fn delete(&mut self, user_id: &str) {
+        if self.deleted.is_none() {
+            self.deleted = Some(Tombstone {
+                deleted_by: user_id.to_string(),
+                stamp: Stamp::now(),
+            });
+        }
+    }
+}
+
+// One time an event happens
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occurrence {
+    pub event: CircleEvent,
+    pub starts_at: u64,
+    pub ends_at: u64,
+}
+
+impl Identified for CircleEvent {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for CircleEvent {
+    fn merge(&mut self, other: Self) {
+        let mut rsvps = std::mem::take(&mut self.rsvps);
+        for (user, mark) in other.rsvps.clone() {
+            match rsvps.get(&user) {
+                Some(existing) if existing.stamp >= mark.stamp => {}
+                _ => {
+                    rsvps.insert(user, mark);
+                }
+            }
+        }
+        let deleted = earliest(self.deleted.take(), other.deleted.clone());
+        if other.stamp > self.stamp {
+            *self = other;
+        }
+        self.rsvps = rsvps;
+        self.deleted = deleted;
+    }
+}
+
+// The event form. Dates and times are what the user typed, in local time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventDraft {
+    pub title: String,
+    pub description: String,
+    pub location: String,
+    // YYYY-MM-DD
+    pub date: String,
+    // HH:MM
+    pub start_time: String,
+    pub end_time: String,
+    pub all_day: bool,
+    pub repeat: Option<Frequency>,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub count: Option<u32>,
+    // YYYY-MM-DD, empty to repeat for ever
+    pub until: String,
+}
+
+impl EventDraft {
+    pub fn new(date: String) -> Self {
+        Self {
+            title: String::new(),
+            description: String::new(),
+            location: String::new(),
+            date,
+            start_time: "18:00".to_string(),
+            end_time: "19:00".to_string(),
+            all_day: false,
+            repeat: None,
+            interval: 1,
+            by_day: Vec::new(),
+            count: None,
+            until: String::new(),
+        }
+    }
+
+    pub fn from_event(event: &CircleEvent, offset: i64) -> Self {
+        let (date, start_time) = local_date_time(event.starts_at, offset);
+        let (_, end_time) = local_date_time(event.ends_at, offset);
+        let rule = event.recurrence.clone();
+        Self {
+            title: event.title.clone(),
+            description: event.description.clone(),
+            location: event.location.clone(),
+            date,
+            start_time,
+            end_time,
+            all_day: event.all_day,
+            repeat: rule.as_ref().map(|r| r.frequency),
+            interval: rule.as_ref().map_or(1, |r| r.interval.max(1)),
+            by_day: rule.as_ref().map(|r| r.by_day.clone()).unwrap_or_default(),
+            count: rule.as_ref().and_then(|r| r.count),
+            until: rule.and_then(|r| r.until).map(|u| local_date_time(u, offset).0).unwrap_or_default(),
+        }
+    }
+
+    pub fn toggle_day(&mut self, day: Weekday) {
+        match self.by_day.iter().position(|d| *d == day) {
+            Some(index) => {
+                self.by_day.remove(index);
+            }
+            None => {
+                self.by_day.push(day);
+                self.by_day.sort();
+            }
+        }
+    }
+
+    // Start and end in UTC milliseconds
+    pub fn times(&self, offset: i64) -> Result<(u64, u64), String> {
+        let day = parse_date(&self.date).ok_or("Choose a date")?;
+        if self.all_day {
+            let start = day * DAY_MILLIS - offset;
+            return Ok((start.max(0) as u64, (start + DAY_MILLIS).max(0) as u64));
+        }
+        let start = parse_time(&self.start_time).ok_or("Choose a start time")?;
+        let end = parse_time(&self.end_time).ok_or("Choose an end time")?;
+        if end <= start {
+            return Err("The event has to end after it starts".to_string());
+        }
+        let midnight = day * DAY_MILLIS - offset;
+        Ok(((midnight + start).max(0) as u64, (midnight + end).max(0) as u64))
+    }
+
+    pub fn recurrence(&self, offset: i64) -> Result<Option<Recurrence>, String> {
+        let Some(frequency) = self.repeat else {
+            return Ok(None);
+        };
+        let until = match self.until.trim() {
+            "" => None,
+            // The whole of the last day counts
+            date => Some((parse_date(date).ok_or("The repeat end date isn't a date")? + 1) * DAY_MILLIS - offset - 1),
+        };
+        if self.count == Some(0) {
+            return Err("Repeat at least once".to_string());
+        }
+        let by_day = match frequency {
+            Frequency::Weekly => self.by_day.clone(),
+            _ => Vec::new(),
+        };
+        Ok(Some(Recurrence {
+            frequency,
+            interval: self.interval.max(1),
+            by_day,
+            count: self.count,
+            until: until.map(|u| u.max(0) as u64),
+        }))
+    }
+
+    pub fn validate(&self, offset: i64) -> Result<(), String> {
+        let title = self.title.trim();
+        if title.is_empty() {
+            return Err("Give the event a title".to_string());
+        }
+        if title.chars().count() > MAX_TITLE_LEN {
+            return Err(format!("Titles can be at most {} characters", MAX_TITLE_LEN));
+        }
+        let (start, _) = self.times(offset)?;
+        if let Some(until) = self.recurrence(offset)?.and_then(|r| r.until)
+            && until < start
+        {
+            return Err("The repeat end date is before the event".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn to_event(&self, circle_id: &str, organizer: CircleMember, offset: i64) -> Result<CircleEvent, String> {
+        self.validate(offset)?;
+        let (starts_at, ends_at) = self.times(offset)?;
+        let id = new_id();
+        Ok(CircleEvent {
+            uid: format!("{}@jeebon", id),
+            id,
+            circle_id: circle_id.to_string(),
+            title: self.title.trim().to_string(),
+            description: self.description.trim().to_string(),
+            location: self.location.trim().to_string(),
+            starts_at,
+            ends_at,
+            all_day: self.all_day,
+            recurrence: self.recurrence(offset)?,
+            organizer,
+            rsvps: BTreeMap::new(),
+            stamp: Stamp::now(),
+            deleted: None,
+        })
+    }
+
+    // Write the draft over an existing event, keeping who it belongs to
+    pub fn apply_to(&self, event: &mut CircleEvent, offset: i64) -> Result<(), String> {
+        let mut updated = self.to_event(&event.circle_id, event.organizer.clone(), offset)?;
+        updated.id = event.id.clone();
+        updated.uid = event.uid.clone();
+        updated.rsvps = std::mem::take(&mut event.rsvps);
+        updated.stamp = Stamp::after(&event.stamp);
+        *event = updated;
+        Ok(())
+    }
+}
+
+// ("2025-03-12", "18:30") in local time
+pub fn local_date_time(millis: u64, offset: i64) -> (String, String) {
+    let local = millis as i64 + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY_MILLIS));
+    let minutes = local.rem_euclid(DAY_MILLIS) / 60_000;
+    (format!("{:04}-{:02}-{:02}", year, month, day), format!("{:02}:{:02}", minutes / 60, minutes % 60))
+}
+
+// "2025-03-12" as days since the epoch
+pub fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let valid = (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day);
+    valid.then(|| days_from_civil(year, month, day))
+}
+
+// "18:30" as milliseconds after midnight
+pub fn parse_time(text: &str) -> Option<i64> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.get(..2).unwrap_or(minutes).parse().ok()?);
+    ((0..24).contains(&hours) && (0..60).contains(&minutes)).then_some((hours * 60 + minutes) * 60_000)
+}
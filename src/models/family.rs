@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::fuzzy_date::FuzzyDate;
+use crate::services::sync::{Identified, Merge, Stamp};
+use crate::utils::new_id;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gender {
+    Male,
+    Female,
+    #[default]
+    Unknown,
+}
+
+impl Gender {
+    pub const ALL: [Gender; 3] = [Gender::Male, Gender::Female, Gender::Unknown];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Gender::Male => "Male",
+            Gender::Female => "Female",
+            Gender::Unknown => "Unknown",
+        }
+    }
+}
+
+// Someone in the family tree. Dates are kept as written, since genealogy
+// dates are often partial ("1970", "abt 1890").
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Person {
+    pub id: String,
+    pub given_name: String,
+    pub surname: String,
+    // Other spellings of the full name
+    #[serde(default)]
+    pub alternate_names: Vec<String>,
+    #[serde(default)]
+    pub gender: Gender,
+    #[serde(default)]
+    pub birth_date: String,
+    #[serde(default)]
+    pub birth_place: String,
+    #[serde(default)]
+    pub death_date: String,
+    #[serde(default)]
+    pub death_place: String,
+    #[serde(default)]
+    pub occupation: String,
+    // Web address or data: URI
+    #[serde(default)]
+    pub photo: String,
+    #[serde(default)]
+    pub notes: String,
+    // The circle member this person is, if they use the app
+    #[serde(default)]
+    pub member_id: String,
+    #[serde(default)]
+    pub deleted: bool,
+    pub stamp: Stamp,
+}
+
+impl Person {
+    // The birth and death dates, when they can be read
+    pub fn born(&self) -> Option<FuzzyDate> {
+        FuzzyDate::parse(&self.birth_date).ok()
+    }
+
+    pub fn died(&self) -> Option<FuzzyDate> {
+        FuzzyDate::parse(&self.death_date).ok()
+    }
+
+    // The Gregorian year of birth, roughly for vague dates
+    pub fn birth_year(&self) -> Option<i64> {
+        self.born().map(|born| born.year())
+    }
+
+    // Without a death, anyone born in the last `years` years or with no
+    // known birth is taken to be alive
+    pub fn presumed_living(&self, this_year: i64, years: i64) -> bool {
+        self.death_date.trim().is_empty() && self.birth_year().is_none_or(|born| this_year - born < years)
+    }
+
+    pub fn full_name(&self) -> String {
+        let name = [self.given_name.as_str(), self.surname.as_str()]
+            .into_iter()
+            .filter(|n| !n.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.is_empty() { "Unnamed".to_string() } else { name }
+    }
+
+    // The photo, or a circle with initials tinted by gender
+    pub fn avatar(&self) -> String {
+        if self.photo.is_empty() { self.monogram() } else { self.photo.clone() }
+    }
+
+    // Initials on a circle coloured by gender
+    pub fn monogram(&self) -> String {
+        let initials: String = self
+            .full_name()
+            .split_whitespace()
+            .filter_map(|word| word.chars().next())
+            .flat_map(char::to_uppercase)
+            .take(3)
+            .collect();
+        let color = match self.gender {
+            Gender::Male => "%23d4e6f9",
+            Gender::Female => "%23f9d4d4",
+            Gender::Unknown => "%2334A853",
+        };
+        format!(
+            "data:image/svg+xml;utf8,<svg xmlns='http://www.w3.org/2000/svg' width='60' height='60' viewBox='0 0 60 60'><circle cx='30' cy='30' r='30' fill='{}'/><text x='30' y='35' font-family='Arial' font-size='20' fill='white' text-anchor='middle'>{}</text></svg>",
+            color, initials
+        )
+    }
+}
+
+impl Identified for Person {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Person {
+    fn merge(&mut self, other: Self) {
+        if other.stamp > self.stamp {
+            *self = other;
+        }
+    }
+}
+
+// How two people are related. Every kind except Spouse points from the
+// parent to the child.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelationKind {
+    Parent,
+    Spouse,
+    Adoptive,
+    Step,
+}
+
+impl RelationKind {
+    pub const ALL: [RelationKind; 4] = [RelationKind::Parent, RelationKind::Spouse, RelationKind::Adoptive, RelationKind::Step];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RelationKind::Parent => "Birth",
+            RelationKind::Spouse => "Spouse",
+            RelationKind::Adoptive => "Adoptive",
+            RelationKind::Step => "Step",
+        }
+    }
+
+    pub fn is_parental(&self) -> bool {
+        *self != RelationKind::Spouse
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub id: String,
+    pub kind: RelationKind,
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub removed: bool,
+    pub stamp: Stamp,
+}
+
+impl Relationship {
+    pub fn new(kind: RelationKind, from: &str, to: &str) -> Self {
+        Self {
+            id: new_id(),
+            kind,
+            from: from.to_string(),
+            to: to.to_string(),
+            removed: false,
+            stamp: Stamp::now(),
+        }
+    }
+
+    pub fn involves(&self, id: &str) -> bool {
+        self.from == id || self.to == id
+    }
+}
+
+impl Identified for Relationship {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Relationship {
+    fn merge(&mut self, other: Self) {
+        if other.stamp > self.stamp {
+            *self = other;
+        }
+    }
+}
+
+// What to hold back when the tree leaves this device
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TreePrivacy {
+    pub hide_living: bool,
+    // People born less than this many years ago count as living
+    pub living_years: i64,
+}
+
+impl Default for TreePrivacy {
+    fn default() -> Self {
+        Self { hide_living: true, living_years: 100 }
+    }
+}
+
+// Which part of the tree to share
+#[derive(Clone, Debug, PartialEq)]
+pub enum Branch {
+    Everyone,
+    Descendants(String),
+    Ancestors(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FamilyTree {
+    pub persons: Vec<Person>,
+    pub relationships: Vec<Relationship>,
+}
+
+impl FamilyTree {
+    // Everyone not deleted
+    pub fn people(&self) -> impl Iterator<Item = &Person> {
+        self.persons.iter().filter(|p| !p.deleted)
+    }
+
+    pub fn person(&self, id: &str) -> Option<&Person> {
+        self.people().find(|p| p.id == id)
+    }
+
+    pub fn person_for_member(&self, member_id: &str) -> Option<&Person> {
+        self.people().find(|p| !p.member_id.is_empty() && p.member_id == member_id)
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = &Relationship> {
+        self.relationships.iter().filter(|r| !r.removed)
+    }
+
+    // The links to and from someone, for editing them
+    pub fn links_of(&self, id: &str) -> Vec<&Relationship> {
+        self.links().filter(|r| r.involves(id)).collect()
+    }
+
+    // Parents with how they are parents, biological first
+    pub fn parents_of(&self, id: &str) -> Vec<(&Person, RelationKind)> {
+        let mut parents: Vec<(&Person, RelationKind)> = self
+            .links()
+            .filter(|r| r.kind.is_parental() && r.to == id)
+            .filter_map(|r| self.person(&r.from).map(|p| (p, r.kind)))
+            .collect();
+        parents.sort_by_key(|(_, kind)| RelationKind::ALL.iter().position(|k| k == kind));
+        parents
+    }
+
+    pub fn children_of(&self, id: &str) -> Vec<(&Person, RelationKind)> {
+        self.links()
+            .filter(|r| r.kind.is_parental() && r.from == id)
+            .filter_map(|r| self.person(&r.to).map(|p| (p, r.kind)))
+            .collect()
+    }
+
+    pub fn spouses_of(&self, id: &str) -> Vec<&Person> {
+        self.links()
+            .filter(|r| r.kind == RelationKind::Spouse && r.involves(id))
+            .filter_map(|r| self.person(if r.from == id { &r.to } else { &r.from }))
+            .collect()
+    }
+
+    // Everyone up the tree from `id`, by any kind of parenthood
+    pub fn ancestors_of(&self, id: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(next) = stack.pop() {
+            for (parent, _) in self.parents_of(&next) {
+                if found.insert(parent.id.clone()) {
+                    stack.push(parent.id.clone());
+                }
+            }
+        }
+        found
+    }
+
+    pub fn descendants_of(&self, id: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(next) = stack.pop() {
+            for (child, _) in self.children_of(&next) {
+                if found.insert(child.id.clone()) {
+                    stack.push(child.id.clone());
+                }
+            }
+        }
+        found
+    }
+
+    // Link two people. Parental links point from parent to child and may not
+    // make anyone their own ancestor.
+    pub fn relate(&mut self, kind: RelationKind, from: &str, to: &str) -> Result<(), String> {
+        if from == to {
+            return Err("Someone can't be related to themselves".to_string());
+        }
+        if self.person(from).is_none() || self.person(to).is_none() {
+            return Err("That person is no longer in the tree".to_string());
+        }
+        let exists = self.links().any(|r| {
+            let same_pair = (r.from == from && r.to == to) || (kind == RelationKind::Spouse && r.from == to && r.to == from);
+            same_pair && r.kind.is_parental() == kind.is_parental()
+        });
+        if exists {
+            return Err("They are already linked".to_string());
+        }
+        if kind.is_parental() && self.ancestors_of(from).contains(to) {
+            return Err("That would make someone their own ancestor".to_string());
+        }
+        self.relationships.push(Relationship::new(kind, from, to));
+        Ok(())
+    }
+
+    // The people in a branch. Descendants come with their spouses so the
+    // other parent of each child is there too.
+    pub fn branch(&self, branch: &Branch) -> BTreeSet<String> {
+        match branch {
+            Branch::Everyone => self.people().map(|p| p.id.clone()).collect(),
+            Branch::Ancestors(id) => {
+                let mut ids = self.ancestors_of(id);
+                ids.insert(id.clone());
+                ids
+            }
+            Branch::Descendants(id) => {
+                let mut ids = self.descendants_of(id);
+                ids.insert(id.clone());
+                let spouses: Vec<String> = ids.iter().flat_map(|i| self.spouses_of(i)).map(|s| s.id.clone()).collect();
+                ids.extend(spouses);
+                ids
+            }
+        }
+    }
+
+    // Just `ids` and the links between them
+    pub fn only(&self, ids: &BTreeSet<String>) -> FamilyTree {
+        FamilyTree {
+            persons: self.people().filter(|p| ids.contains(&p.id)).cloned().collect(),
+            relationships: self.links().filter(|r| ids.contains(&r.from) && ids.contains(&r.to)).cloned().collect(),
+        }
+    }
+
+    pub fn index(&self) -> TreeIndex<'_> {
+        let persons: BTreeMap<&str, &Person> = self.people().map(|p| (p.id.as_str(), p)).collect();
+        let mut index = TreeIndex { persons, ..Default::default() };
+        for r in self.links() {
+            let (from, to) = (index.persons.get(r.from.as_str()).copied(), index.persons.get(r.to.as_str()).copied());
+            if r.kind.is_parental() {
+                if let Some(from) = from {
+                    index.parents.entry(r.to.as_str()).or_default().push((from, r.kind));
+                }
+                if let Some(to) = to {
+                    index.children.entry(r.from.as_str()).or_default().push((to, r.kind));
+                }
+            } else {
+                if let Some(to) = to {
+                    index.spouses.entry(r.from.as_str()).or_default().push(to);
+                }
+                if let Some(from) = from {
+                    index.spouses.entry(r.to.as_str()).or_default().push(from);
+                }
+            }
+        }
+        for parents in index.parents.values_mut() {
+            parents.sort_by_key(|(_, kind)| RelationKind::ALL.iter().position(|k| k == kind));
+        }
+        index
+    }
+}
+
+// The tree's people and links by id, for work that looks them up many times
+// over, such as laying out the chart or naming everyone's relationship to
+// us. It borrows the tree, so it can't outlive a change.
+#[derive(Default)]
+pub struct TreeIndex<'a> {
+    persons: BTreeMap<&'a str, &'a Person>,
+    parents: BTreeMap<&'a str, Vec<(&'a Person, RelationKind)>>,
+    children: BTreeMap<&'a str, Vec<(&'a Person, RelationKind)>>,
+    spouses: BTreeMap<&'a str, Vec<&'a Person>>,
+}
+
+// The same answers as the FamilyTree lookups of the same names
+impl<'a> TreeIndex<'a> {
+    pub fn person(&self, id: &str) -> Option<&'a Person> {
+        self.persons.get(id).copied()
+    }
+
+    pub fn parents_of(&self, id: &str) -> Vec<(&'a Person, RelationKind)> {
+        self.parents.get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn children_of(&self, id: &str) -> Vec<(&'a Person, RelationKind)> {
+        self.children.get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn spouses_of(&self, id: &str) -> Vec<&'a Person> {
+        self.spouses.get(id).cloned().unwrap_or_default()
+    }
+}
+
+// Which new relative a quick action adds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relative {
+    Parent(RelationKind),
+    Child(RelationKind),
+    Spouse,
+}
+
+impl Relative {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Relative::Parent(_) => "Parent",
+            Relative::Child(_) => "Child",
+            Relative::Spouse => "Spouse",
+        }
+    }
+
+    // (kind, from, to) for linking `other` to `person` this way
+    pub fn link(&self, person: &str, other: &str) -> (RelationKind, String, String) {
+        match self {
+            Relative::Parent(kind) => (*kind, other.to_string(), person.to_string()),
+            Relative::Child(kind) => (*kind, person.to_string(), other.to_string()),
+            Relative::Spouse => (RelationKind::Spouse, person.to_string(), other.to_string()),
+        }
+    }
+}
+
+// The person editor's form
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PersonDraft {
+    pub given_name: String,
+    pub surname: String,
+    // Comma separated
+    pub alternate_names: String,
+    pub gender: Gender,
+    pub birth_date: String,
+    pub birth_place: String,
+    pub death_date: String,
+    pub death_place: String,
+    pub occupation: String,
+    pub photo: String,
+    pub notes: String,
+}
+
+impl PersonDraft {
+    pub fn from_person(person: &Person) -> Self {
+        Self {
+            given_name: person.given_name.clone(),
+            surname: person.surname.clone(),
+            alternate_names: person.alternate_names.join(", "),
+            gender: person.gender,
+            birth_date: person.birth_date.clone(),
+            birth_place: person.birth_place.clone(),
+            death_date: person.death_date.clone(),
+            death_place: person.death_place.clone(),
+            occupation: person.occupation.clone(),
+            photo: person.photo.clone(),
+            notes: person.notes.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.given_name.trim().is_empty() && self.surname.trim().is_empty() {
+            return Err("Give them a name".to_string());
+        }
+        if self.death_date.trim().is_empty() && !self.death_place.trim().is_empty() {
+            return Err("Add a date of death to go with the place".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn apply_to(&self, person: &mut Person) {
+        person.given_name = self.given_name.trim().to_string();
+        person.surname = self.surname.trim().to_string();
+        person.alternate_names = self.alternate_names.split(',').map(str::trim).filter(|n| !n.is_empty()).map(str::to_string).collect();
+        person.gender = self.gender;
+        person.birth_date = self.birth_date.trim().to_string();
+        person.birth_place = self.birth_place.trim().to_string();
+        person.death_date = self.death_date.trim().to_string();
+        person.death_place = self.death_place.trim().to_string();
+        person.occupation = self.occupation.trim().to_string();
+        person.photo = self.photo.trim().to_string();
+        person.notes = self.notes.trim().to_string();
+        person.stamp = Stamp::after(&person.stamp);
+    }
+
+    pub fn to_person(&self) -> Person {
+        let mut person = Person { id: new_id(), ..Person::default() };
+        self.apply_to(&mut person);
+        person
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_index_answers_like_the_tree() {
+        let mut tree = FamilyTree::default();
+        for (id, gender) in [("dad", Gender::Male), ("mum", Gender::Female), ("step", Gender::Male), ("kid", Gender::Unknown)] {
+            tree.persons.push(Person { id: id.to_string(), gender, ..Person::default() });
+        }
+        for (kind, from, to) in [
+            (RelationKind::Step, "step", "kid"),
+            (RelationKind::Spouse, "mum", "dad"),
+            (RelationKind::Adoptive, "mum", "kid"),
+            (RelationKind::Parent, "dad", "kid"),
+        ] {
+            tree.relate(kind, from, to).unwrap();
+        }
+        tree.persons[2].deleted = true;
+        let index = tree.index();
+        let ids = |people: Vec<(&Person, RelationKind)>| people.into_iter().map(|(p, k)| (p.id.clone(), k)).collect::<Vec<_>>();
+        for id in ["dad", "mum", "step", "kid", "nobody"] {
+            assert_eq!(index.person(id), tree.person(id), "{}", id);
+            assert_eq!(ids(index.parents_of(id)), ids(tree.parents_of(id)), "{}", id);
+            assert_eq!(ids(index.children_of(id)), ids(tree.children_of(id)), "{}", id);
+            assert_eq!(index.spouses_of(id), tree.spouses_of(id), "{}", id);
+        }
+        assert_eq!(ids(index.parents_of("kid")), vec![("dad".to_string(), RelationKind::Parent), ("mum".to_string(), RelationKind::Adoptive)]);
+    }
+}
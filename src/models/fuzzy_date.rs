@@ -0,0 +1,721 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::models::person_match::{normalize, skeleton};
+use crate::utils::format::{civil_from_days, days_from_civil};
+use crate::utils::now_millis;
+
+// Dates as family history knows them: often only a month or a year, or
+// "about", "before", "after" or "between" one, sometimes written with the
+// old and new style years together ("11 Feb 1750/51"), and sometimes in the
+// Julian or Bengali calendar. The tree keeps dates as typed; this reads
+// them, in English or Bengali, so they can be sorted, shown alike, turned
+// into Gregorian dates and used to work out ages. Days are counted from the
+// Unix epoch, as in the rest of the app.
+//
+// Bengali dates follow Bangladesh's calendar: the 2019 month lengths from
+// 1426 on and the 1987 ones before. Older almanac dates, and West Bengal's
+// calendar, which follows the sun's actual path, can be a day or two out.
+
+// How far "about" a date reaches on either side, in years
+const ABOUT_YEARS: i64 = 2;
+// The Bengali year begins on 14 April, 593 years after the Gregorian one
+const BENGALI_YEAR_OFFSET: i64 = 593;
+// First Bengali year with the 2019 month lengths
+const BENGALI_REVISION_YEAR: i64 = 1426;
+
+const GREGORIAN_MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+const ENGLISH_MONTHS: [&str; 12] =
+    ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"];
+// Gregorian months as written in Bengali, with a Latin spelling where
+// transliteration can't get there on its own
+const BENGALI_GREGORIAN_MONTHS: [&[&str]; 12] = [
+    &["জানু\u{09DF}ারি"],
+    &["ফেব্রু\u{09DF}ারি"],
+    &["মার্চ"],
+    &["এপ্রিল"],
+    &["মে"],
+    &["জুন"],
+    &["জুলাই"],
+    &["আগস্ট"],
+    &["সেপ্টেম্বর"],
+    &["অক্টোবর"],
+    &["নভেম্বর"],
+    &["ডিসেম্বর", "disembor"],
+];
+const BENGALI_MONTHS: [&str; 12] =
+    ["Boishakh", "Joishtho", "Asharh", "Srabon", "Bhadro", "Ashwin", "Kartik", "Ogrohayon", "Poush", "Magh", "Falgun", "Choitro"];
+// Bengali months in Bengali script and the other usual Latin spellings
+const BENGALI_MONTH_SPELLINGS: [&[&str]; 12] = [
+    &["বৈশাখ", "baishakh", "boishak"],
+    &["জ্যৈষ্ঠ", "jaishtha", "joishto", "jyoishtho"],
+    &["আষা\u{09DD}", "asadh", "asar", "ashar", "asharh"],
+    &["শ্রাবণ", "shraban", "shrabon", "sraban"],
+    &["ভাদ্র", "bhadra"],
+    &["আশ্বিন", "ashin", "aswin"],
+    &["কার্তিক", "kartick"],
+    &["অগ্রহা\u{09DF}ণ", "agrahayan", "agrahayon", "ograhayon"],
+    &["পৌষ", "paush", "pous"],
+    &["মাঘ"],
+    &["ফাল্গুন", "phalgun", "falgoon"],
+    &["চৈত্র", "chaitra", "chaitro", "choitra"],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Calendar {
+    Gregorian,
+    Julian,
+    Bengali,
+}
+
+impl Calendar {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Calendar::Gregorian => "Gregorian",
+            Calendar::Julian => "Julian",
+            Calendar::Bengali => "Bengali",
+        }
+    }
+
+    fn month_name(&self, month: u32) -> &'static str {
+        match self {
+            Calendar::Bengali => BENGALI_MONTHS[month as usize - 1],
+            _ => GREGORIAN_MONTHS[month as usize - 1],
+        }
+    }
+
+    fn is_leap(&self, year: i64) -> bool {
+        match self {
+            Calendar::Gregorian => year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0),
+            Calendar::Julian => year.rem_euclid(4) == 0,
+            // Falgun takes the extra day when the Gregorian February it ends in does
+            Calendar::Bengali => Calendar::Gregorian.is_leap(year + BENGALI_YEAR_OFFSET + 1),
+        }
+    }
+
+    fn month_length(&self, year: i64, month: u32) -> u32 {
+        match (self, month) {
+            // The 1987 rule: five months of 31 days, then 30, Falgun 31 in
+            // leap years
+            (Calendar::Bengali, 1..=5) if year < BENGALI_REVISION_YEAR => 31,
+            (Calendar::Bengali, 11) if year < BENGALI_REVISION_YEAR => if self.is_leap(year) { 31 } else { 30 },
+            (Calendar::Bengali, _) if year < BENGALI_REVISION_YEAR => 30,
+            // The 2019 revision: six months of 31 days, then 30, Falgun 29
+            (Calendar::Bengali, 1..=6) => 31,
+            (Calendar::Bengali, 11) => if self.is_leap(year) { 30 } else { 29 },
+            (Calendar::Bengali, _) => 30,
+            (_, 2) => if self.is_leap(year) { 29 } else { 28 },
+            (_, 4 | 6 | 9 | 11) => 30,
+            _ => 31,
+        }
+    }
+
+    // Days since the Unix epoch
+    fn days(&self, year: i64, month: u32, day: u32) -> i64 {
+        match self {
+            Calendar::Gregorian => days_from_civil(year, month, day),
+            Calendar::Julian => {
+                let shift = (14 - month as i64) / 12;
+                let (y, m) = (year + 4800 - shift, month as i64 + 12 * shift - 3);
+                let julian_day = day as i64 + (153 * m + 2) / 5 + 365 * y + y.div_euclid(4) - 32_083;
+                julian_day - 2_440_588
+            }
+            Calendar::Bengali => {
+                let new_year = days_from_civil(year + BENGALI_YEAR_OFFSET, 4, 14);
+                new_year + (1..month).map(|m| self.month_length(year, m) as i64).sum::<i64>() + day as i64 - 1
+            }
+        }
+    }
+}
+
+// A day, month or year in one calendar. A dual-dated year is kept as the
+// later, new style year.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CalendarDate {
+    pub calendar: Calendar,
+    pub year: i64,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    pub dual: bool,
+}
+
+impl CalendarDate {
+    fn on_day(days: i64) -> Self {
+        let (year, month, day) = civil_from_days(days);
+        CalendarDate { calendar: Calendar::Gregorian, year, month: Some(month), day: Some(day), dual: false }
+    }
+
+    // The first and last days it could be
+    pub fn first_day(&self) -> i64 {
+        self.calendar.days(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1))
+    }
+
+    pub fn last_day(&self) -> i64 {
+        let month = self.month.unwrap_or(12);
+        let day = self.day.unwrap_or_else(|| self.calendar.month_length(self.year, month));
+        self.calendar.days(self.year, month, day)
+    }
+
+    // The same day in the Gregorian calendar, or the Gregorian month or year
+    // holding the middle of a longer stretch
+    fn gregorian(&self) -> CalendarDate {
+        if self.calendar == Calendar::Gregorian {
+            return self.clone();
+        }
+        let mut out = CalendarDate::on_day((self.first_day() + self.last_day()) / 2);
+        if self.day.is_none() {
+            out.day = None;
+        }
+        if self.month.is_none() {
+            out.month = None;
+        }
+        out
+    }
+
+    fn year_text(&self) -> String {
+        if self.year < 1 {
+            format!("{} BCE", 1 - self.year)
+        } else if self.dual {
+            format!("{}/{:02}", self.year - 1, self.year % 100)
+        } else {
+            self.year.to_string()
+        }
+    }
+
+    fn to_gedcom(&self) -> String {
+        let calendar = if self.calendar == Calendar::Julian { "JULIAN " } else { "" };
+        let year = if self.year < 1 { format!("{} BCE", 1 - self.year) } else { self.year.to_string() };
+        let month = self.month.map(|m| format!("{} ", GREGORIAN_MONTHS[m as usize - 1].to_uppercase())).unwrap_or_default();
+        let day = self.day.map(|d| format!("{} ", d)).unwrap_or_default();
+        format!("{}{}{}{}", calendar, day, month, year)
+    }
+}
+
+impl fmt::Display for CalendarDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(day) = self.day {
+            write!(f, "{} ", day)?;
+        }
+        if let Some(month) = self.month {
+            write!(f, "{} ", self.calendar.month_name(month))?;
+        }
+        write!(f, "{}", self.year_text())?;
+        match self.calendar {
+            Calendar::Gregorian => Ok(()),
+            Calendar::Julian => write!(f, " (Julian)"),
+            Calendar::Bengali => write!(f, " BS"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FuzzyDate {
+    Exact(CalendarDate),
+    About(CalendarDate),
+    Before(CalendarDate),
+    After(CalendarDate),
+    Between(CalendarDate, CalendarDate),
+}
+
+// How old someone was, as closely as the dates allow; either end is open
+// when a date is only known as before or after
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Age {
+    pub least: Option<i64>,
+    pub most: Option<i64>,
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.least, self.most) {
+            (Some(least), Some(most)) if least == most => write!(f, "{}", least),
+            (Some(least), Some(most)) => write!(f, "{}–{}", least, most),
+            (Some(least), None) => write!(f, "at least {}", least),
+            (None, Some(most)) => write!(f, "at most {}", most),
+            (None, None) => write!(f, "unknown"),
+        }
+    }
+}
+
+// The same day `years` on
+fn years_later(days: i64, years: i64) -> i64 {
+    let (year, month, day) = civil_from_days(days);
+    days_from_civil(year + years, month, day)
+}
+
+// Whole years from one day to a later one, negative when it's earlier
+fn years_between(from: i64, to: i64) -> i64 {
+    if to < from {
+        return -years_between(to, from);
+    }
+    let ((y1, m1, d1), (y2, m2, d2)) = (civil_from_days(from), civil_from_days(to));
+    y2 - y1 - if (m2, d2) < (m1, d1) { 1 } else { 0 }
+}
+
+impl FuzzyDate {
+    pub fn today() -> Self {
+        FuzzyDate::Exact(CalendarDate::on_day((now_millis() / 86_400_000) as i64))
+    }
+
+    // The calendar it was written in
+    pub fn calendar(&self) -> Calendar {
+        self.parts().1.calendar
+    }
+
+    // Which kind of date, then its one or two calendar dates
+    fn parts(&self) -> (u8, &CalendarDate, Option<&CalendarDate>) {
+        match self {
+            FuzzyDate::Exact(d) => (0, d, None),
+            FuzzyDate::About(d) => (1, d, None),
+            FuzzyDate::Before(d) => (2, d, None),
+            FuzzyDate::After(d) => (3, d, None),
+            FuzzyDate::Between(a, b) => (4, a, Some(b)),
+        }
+    }
+
+    // The first and last days it could be, when there is a limit
+    pub fn earliest(&self) -> Option<i64> {
+        match self {
+            FuzzyDate::Exact(d) => Some(d.first_day()),
+            FuzzyDate::About(d) => Some(years_later(d.first_day(), -ABOUT_YEARS)),
+            FuzzyDate::Before(_) => None,
+            FuzzyDate::After(d) => Some(d.last_day() + 1),
+            FuzzyDate::Between(a, _) => Some(a.first_day()),
+        }
+    }
+
+    pub fn latest(&self) -> Option<i64> {
+        match self {
+            FuzzyDate::Exact(d) => Some(d.last_day()),
+            FuzzyDate::About(d) => Some(years_later(d.last_day(), ABOUT_YEARS)),
+            FuzzyDate::Before(d) => Some(d.first_day() - 1),
+            FuzzyDate::After(_) => None,
+            FuzzyDate::Between(_, b) => Some(b.last_day()),
+        }
+    }
+
+    // Where it sorts: at its start, with "before" just ahead of the date
+    // and "after" just behind its end
+    fn sort_key(&self) -> (i64, i8) {
+        match self {
+            FuzzyDate::Before(d) => (d.first_day(), -1),
+            FuzzyDate::After(d) => (d.last_day(), 1),
+            other => (other.parts().1.first_day(), 0),
+        }
+    }
+
+    // The Gregorian year it sorts in
+    pub fn year(&self) -> i64 {
+        civil_from_days(self.sort_key().0).0
+    }
+
+    // Age on `at` of someone born on this date
+    pub fn age_at(&self, at: &FuzzyDate) -> Age {
+        Age {
+            least: self.latest().zip(at.earliest()).map(|(born, then)| years_between(born, then)),
+            most: self.earliest().zip(at.latest()).map(|(born, then)| years_between(born, then)),
+        }
+    }
+
+    // The same date in the Gregorian calendar. A whole month or year in
+    // another calendar becomes the range of days it covers.
+    pub fn gregorian(&self) -> FuzzyDate {
+        let exact_day = |d: &CalendarDate, last: bool| CalendarDate::on_day(if last { d.last_day() } else { d.first_day() });
+        match self {
+            FuzzyDate::Exact(d) if d.calendar != Calendar::Gregorian && d.day.is_none() => {
+                FuzzyDate::Between(exact_day(d, false), exact_day(d, true))
+            }
+            FuzzyDate::Exact(d) => FuzzyDate::Exact(d.gregorian()),
+            FuzzyDate::About(d) => FuzzyDate::About(d.gregorian()),
+            FuzzyDate::Before(d) if d.calendar != Calendar::Gregorian => FuzzyDate::Before(exact_day(d, false)),
+            FuzzyDate::Before(d) => FuzzyDate::Before(d.clone()),
+            FuzzyDate::After(d) if d.calendar != Calendar::Gregorian => FuzzyDate::After(exact_day(d, true)),
+            FuzzyDate::After(d) => FuzzyDate::After(d.clone()),
+            FuzzyDate::Between(a, b) => {
+                let convert = |d: &CalendarDate, last: bool| if d.calendar == Calendar::Gregorian { d.clone() } else { exact_day(d, last) };
+                FuzzyDate::Between(convert(a, false), convert(b, true))
+            }
+        }
+    }
+
+    // A GEDCOM 7 date. GEDCOM has no Bengali calendar, so those dates go
+    // out as Gregorian ones.
+    pub fn to_gedcom(&self) -> String {
+        let date = if self.calendar() == Calendar::Bengali { self.gregorian() } else { self.clone() };
+        match &date {
+            FuzzyDate::Exact(d) => d.to_gedcom(),
+            FuzzyDate::About(d) => format!("ABT {}", d.to_gedcom()),
+            FuzzyDate::Before(d) => format!("BEF {}", d.to_gedcom()),
+            FuzzyDate::After(d) => format!("AFT {}", d.to_gedcom()),
+            FuzzyDate::Between(a, b) => format!("BET {} AND {}", a.to_gedcom(), b.to_gedcom()),
+        }
+    }
+
+    // Reads a date typed in any of the usual ways: "12 March 1970",
+    // "1970-03-12", "12/03/1970", "abt 1970", "bef. Jun 1802", "between
+    // 1970 and 1975", "1970–1975", "11 Feb 1750/51", "1 Jan 1700 Julian",
+    // "১২ বৈশাখ ১৩৭৭" or "আনুমানিক ১৯৭০", and the forms GEDCOM files use
+    pub fn parse(text: &str) -> Result<FuzzyDate, String> {
+        let cleaned = clean(text);
+        if cleaned.is_empty() {
+            return Err("The date is empty".to_string());
+        }
+        // Words that say which calendar, or which era, apply to the whole date
+        let mut calendar = None;
+        let mut bce = false;
+        let mut marked = |token: &str| -> Result<bool, String> {
+            let said = match token {
+                "@#dgregorian@" | "gregorian" | "ns" | "n.s" | "ad" | "a.d" | "ce" | "খ্রিস্টাব্দ" | "খ্রিষ্টাব্দ" | "ইং" => {
+                    Calendar::Gregorian
+                }
+                "@#djulian@" | "julian" | "os" | "o.s" => Calendar::Julian,
+                "bs" | "b.s" | "bangla" | "bengali" | "বঙ্গাব্দ" | "বাংলা" | "সন" => Calendar::Bengali,
+                "bc" | "b.c" | "bce" | "b.c.e" => {
+                    bce = true;
+                    return Ok(true);
+                }
+                // Words that add nothing: "১৯৭০ সালের আগে", "… এর মধ্যে"
+                "এর" | "মধ্যে" | "সাল" | "সালে" | "সালের" | "in" | "of" | "the" => return Ok(true),
+                _ => return Ok(false),
+            };
+            if calendar.is_some_and(|c| c != said) {
+                return Err("The date names two calendars".to_string());
+            }
+            calendar = Some(said);
+            Ok(true)
+        };
+        let mut tokens = Vec::new();
+        for token in cleaned.split_whitespace() {
+            if !marked(token)? {
+                tokens.push(token);
+            }
+        }
+        let read = |tokens: &[&str]| calendar_date(tokens, calendar, bce);
+
+        // A qualifier in front, or a Bengali one behind
+        let first = tokens.first().copied().unwrap_or_default();
+        let last = tokens.last().copied().unwrap_or_default();
+        let qualifier = match (first, last) {
+            ("abt" | "about" | "c" | "ca" | "circa" | "approx" | "approximately" | "around" | "est" | "estimated" | "cal"
+            | "calculated" | "~" | "আনুমানিক" | "প্রা\u{09DF}", _) => Some(("about", true)),
+            ("bef" | "before" | "by" | "<" | "to" | "until", _) => Some(("before", true)),
+            ("aft" | "after" | "since" | ">", _) => Some(("after", true)),
+            ("bet" | "btw" | "between" | "from", _) => Some(("between", true)),
+            ("int", _) => Some(("exact", true)),
+            (_, "নাগাদ") => Some(("about", false)),
+            (_, "আগে") => Some(("before", false)),
+            (_, "পরে") => Some(("after", false)),
+            _ => None,
+        };
+        let qualifier = match qualifier {
+            Some((kind, front)) => {
+                if front { tokens.remove(0) } else { tokens.pop().unwrap_or_default() };
+                kind
+            }
+            None => "exact",
+        };
+        // Two dates either side of a joining word make a range
+        let joint = tokens.iter().position(|t| ["and", "to", "&", "-", "until", "এবং", "ও", "থেকে"].contains(t));
+        if let Some(split) = joint {
+            if !["between", "exact"].contains(&qualifier) {
+                return Err("A range can't also be before, after or about a date".to_string());
+            }
+            let (from, to) = (read(&tokens[..split])?, read(&tokens[split + 1..])?);
+            if to.last_day() < from.first_day() {
+                return Err("The range ends before it starts".to_string());
+            }
+            return Ok(FuzzyDate::Between(from, to));
+        }
+        let date = read(&tokens)?;
+        Ok(match qualifier {
+            "about" => FuzzyDate::About(date),
+            "before" => FuzzyDate::Before(date),
+            // "from 1970" on its own
+            "after" | "between" => FuzzyDate::After(date),
+            _ => FuzzyDate::Exact(date),
+        })
+    }
+}
+
+impl fmt::Display for FuzzyDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuzzyDate::Exact(d) => write!(f, "{}", d),
+            FuzzyDate::About(d) => write!(f, "c. {}", d),
+            FuzzyDate::Before(d) => write!(f, "bef. {}", d),
+            FuzzyDate::After(d) => write!(f, "aft. {}", d),
+            FuzzyDate::Between(a, b) => write!(f, "bet. {} and {}", a, b),
+        }
+    }
+}
+
+// A date as read, or as typed when it can't be
+pub fn tidy(text: &str) -> String {
+    FuzzyDate::parse(text).map(|date| date.to_string()).unwrap_or_else(|_| text.trim().to_string())
+}
+
+// Just the years, for where there is little room: "c. 1970", "1970–1975"
+pub fn tidy_years(text: &str) -> String {
+    let year = |d: &CalendarDate| CalendarDate { month: None, day: None, ..d.clone() };
+    match FuzzyDate::parse(text) {
+        Ok(FuzzyDate::Between(a, b)) => format!("{}–{}", year(&a), year(&b)),
+        Ok(FuzzyDate::Exact(d)) => year(&d).to_string(),
+        Ok(FuzzyDate::About(d)) => FuzzyDate::About(year(&d)).to_string(),
+        Ok(FuzzyDate::Before(d)) => FuzzyDate::Before(year(&d)).to_string(),
+        Ok(FuzzyDate::After(d)) => FuzzyDate::After(year(&d)).to_string(),
+        Err(_) => text.trim().to_string(),
+    }
+}
+
+impl PartialOrd for FuzzyDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// By when they fall; dates that fall alike are kept apart by kind and then
+// by how they were written, so the order is total
+impl Ord for FuzzyDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key()
+            .cmp(&other.sort_key())
+            .then_with(|| self.latest().unwrap_or(i64::MAX).cmp(&other.latest().unwrap_or(i64::MAX)))
+            .then_with(|| self.parts().cmp(&other.parts()))
+    }
+}
+
+// Lower case, Western digits, composed Bengali letters, and the marks that
+// stand for words split off as words of their own
+fn clean(text: &str) -> String {
+    let text = text
+        .trim()
+        .to_lowercase()
+        .replace("ড\u{09BC}", "\u{09DC}")
+        .replace("ঢ\u{09BC}", "\u{09DD}")
+        .replace("য\u{09BC}", "\u{09DF}");
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '০'..='৯' => out.push(char::from(b'0' + (c as u32 - '০' as u32) as u8)),
+            '–' | '—' | '~' | '<' | '>' | '&' => {
+                out.push(' ');
+                out.push(if c == '—' { '–' } else { c });
+                out.push(' ');
+            }
+            ',' | '(' | ')' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+    // "1970-1975" and "১৯৭০–১৯৭৫" are ranges, but "1970-03-12" isn't
+    out.split_whitespace()
+        .map(|token| {
+            let token = token.strip_suffix('.').unwrap_or(token);
+            let token = if token == "–" { "-" } else { token };
+            match token.split_once('-') {
+                Some((a, b)) if a.len() >= 3 && b.len() >= 3 && [a, b].iter().all(|p| p.chars().all(|c| c.is_ascii_digit())) => {
+                    format!("{} - {}", a, b)
+                }
+                _ => token.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A month by name, with the calendar it belongs to
+fn month_by_name(token: &str) -> Option<(u32, Option<Calendar>)> {
+    if token.len() >= 3
+        && token.chars().all(|c| c.is_ascii_alphabetic())
+        && let Some(i) = ENGLISH_MONTHS.iter().position(|m| m.starts_with(token) || (token == "sept" && *m == "september"))
+    {
+        return Some((i as u32 + 1, None));
+    }
+    let find = |matches: &dyn Fn(&str) -> bool| {
+        let bengali = (0..12).find(|&i| BENGALI_MONTH_SPELLINGS[i].iter().chain([&BENGALI_MONTHS[i]]).any(|s| matches(s)));
+        if let Some(i) = bengali {
+            return Some((i as u32 + 1, Some(Calendar::Bengali)));
+        }
+        (0..12)
+            .find(|&i| BENGALI_GREGORIAN_MONTHS[i].iter().chain([&ENGLISH_MONTHS[i]]).any(|s| matches(s)))
+            .map(|i| (i as u32 + 1, None))
+    };
+    // Latin words only as spelt in the lists: read by sound, "ma" or "me"
+    // would pass for May
+    if !token.chars().any(|c| ('\u{0980}'..='\u{09FF}').contains(&c)) {
+        return find(&|spelling| spelling.eq_ignore_ascii_case(token));
+    }
+    // Bengali script is also read by sound, as vowel signs and conjuncts
+    // are written many ways
+    let normalized = normalize(token);
+    if let Some(found) = find(&|spelling| !spelling.is_ascii() && normalize(spelling) == normalized) {
+        return Some(found);
+    }
+    let sound = skeleton(&normalized);
+    if sound.chars().count() < 2 {
+        return None;
+    }
+    find(&|spelling| skeleton(&normalize(spelling)) == sound)
+}
+
+// A day of the month, allowing "12th" and "১২ই"
+fn day_number(token: &str) -> Option<u32> {
+    let digits: String = token.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let rest = &token[digits.len()..];
+    if digits.is_empty() || digits.len() > 2 || !["", "st", "nd", "rd", "th", "ই", "শে", "লা", "রা", "ঠা"].contains(&rest) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+// A year, with whether it was dual dated: "1750/51" or "1750/1751"
+fn year_number(token: &str) -> Option<(i64, bool)> {
+    let (first, second) = match token.split_once('/') {
+        Some((first, second)) => (first, Some(second)),
+        None => (token, None),
+    };
+    if first.is_empty() || first.len() > 4 || !first.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let year: i64 = first.parse().ok()?;
+    match second {
+        None => Some((year, false)),
+        Some(s) if s.len() == 2 && s.parse::<i64>().ok()? == (year + 1) % 100 => Some((year + 1, true)),
+        Some(s) if s.len() == 4 && s.parse::<i64>().ok()? == year + 1 => Some((year + 1, true)),
+        Some(_) => None,
+    }
+}
+
+// "1970-03-12", "1970-03", "1750/51-02-11", "12/03/1970" or "12.03.1970",
+// as the year, month and day
+fn numeric(token: &str) -> Option<(&str, &str, Option<&str>)> {
+    let dashed: Vec<&str> = token.split('-').collect();
+    match dashed.as_slice() {
+        [year, month] if year.len() >= 3 => return Some((year, month, None)),
+        [year, month, day] if year.len() >= 3 => return Some((year, month, Some(day))),
+        _ => {}
+    }
+    match token.split(['-', '/', '.']).collect::<Vec<_>>().as_slice() {
+        [day, month, year] if year.len() >= 3 => Some((year, month, Some(day))),
+        _ => None,
+    }
+}
+
+// One date with no qualifier
+fn calendar_date(tokens: &[&str], calendar: Option<Calendar>, bce: bool) -> Result<CalendarDate, String> {
+    let unreadable = || format!("\"{}\" isn't a date that can be read", tokens.join(" "));
+    if let [token] = tokens
+        && let Some((year, month, day)) = numeric(token)
+    {
+        let (year, dual) = year_number(year).ok_or_else(unreadable)?;
+        let month: u32 = month.parse().map_err(|_| unreadable())?;
+        let day = match day {
+            Some(day) => Some(day_number(day).ok_or_else(unreadable)?),
+            None => None,
+        };
+        return build(calendar.unwrap_or(Calendar::Gregorian), year, Some(month), day, dual, bce);
+    }
+    let mut month = None;
+    let mut named_calendar = None;
+    let mut numbers: Vec<&str> = Vec::new();
+    for token in tokens {
+        if let Some((m, named)) = month_by_name(token) {
+            if month.is_some() {
+                return Err("The date names two months".to_string());
+            }
+            month = Some(m);
+            named_calendar = named;
+        } else if token.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+            numbers.push(token);
+        } else {
+            return Err(format!("\"{}\" isn't part of a date", token));
+        }
+    }
+    let calendar = match (calendar, named_calendar) {
+        (Some(Calendar::Julian), Some(Calendar::Bengali)) => return Err("A Bengali month can't be in the Julian calendar".to_string()),
+        (_, Some(named)) => named,
+        (Some(said), None) => said,
+        (None, None) => Calendar::Gregorian,
+    };
+    // The year is the number too big to be a day, or else the last one
+    let year_at = numbers.iter().position(|n| year_number(n).is_some_and(|(y, dual)| dual || y > 31)).unwrap_or(numbers.len().max(1) - 1);
+    let (year, dual) = numbers
+        .get(year_at)
+        .and_then(|n| year_number(n))
+        // "12 Mar" is a day, not the year 12
+        .filter(|(year, dual)| *dual || month.is_none() || numbers.len() > 1 || *year > 31)
+        .ok_or_else(|| "A date needs a year".to_string())?;
+    let others: Vec<&str> = numbers.iter().enumerate().filter(|(i, _)| *i != year_at).map(|(_, n)| *n).collect();
+    let (month, day) = match (month, others.as_slice()) {
+        (month, []) => (month, None),
+        (Some(month), [day]) => (Some(month), Some(day_number(day).ok_or_else(unreadable)?)),
+        // "12 03 1970"
+        (None, [day, month]) => (Some(month.parse().map_err(|_| unreadable())?), Some(day_number(day).ok_or_else(unreadable)?)),
+        _ => return Err(unreadable()),
+    };
+    if day.is_some() && month.is_none() {
+        return Err(unreadable());
+    }
+    build(calendar, year, month, day, dual, bce)
+}
+
+fn build(calendar: Calendar, year: i64, month: Option<u32>, day: Option<u32>, dual: bool, bce: bool) -> Result<CalendarDate, String> {
+    if year == 0 {
+        return Err("There is no year 0".to_string());
+    }
+    if dual && (calendar == Calendar::Bengali || bce) {
+        return Err("Only Christian-era years are written dual dated".to_string());
+    }
+    // 1 BCE is year 0 counted astronomically, which the day sums expect
+    let year = if bce { 1 - year } else { year };
+    if let Some(month) = month
+        && !(1..=12).contains(&month)
+    {
+        return Err(format!("There is no month {}", month));
+    }
+    if let (Some(month), Some(day)) = (month, day)
+        && !(1..=calendar.month_length(year, month)).contains(&day)
+    {
+        return Err(format!("{} has no day {}", calendar.month_name(month), day));
+    }
+    Ok(CalendarDate { calendar, year, month, day, dual })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gregorian(text: &str) -> String {
+        FuzzyDate::parse(text).map(|date| date.gregorian().to_string()).unwrap_or_else(|e| e)
+    }
+
+    #[test]
+    fn short_latin_words_are_not_months() {
+        assert!(FuzzyDate::parse("ma 1970").is_err());
+        assert!(FuzzyDate::parse("1970 me").is_err());
+        assert_eq!(FuzzyDate::parse("may 1970").map(|d| d.to_string()), Ok("May 1970".to_string()));
+        assert_eq!(FuzzyDate::parse("মে ১৯৭০").map(|d| d.to_string()), Ok("May 1970".to_string()));
+    }
+
+    #[test]
+    fn bengali_months_are_read_by_spelling_and_by_sound() {
+        for text in ["Boishakh 1380", "baishakh 1380", "বৈশাখ ১৩৮০", "বোইশাখ ১৩৮০"] {
+            assert_eq!(FuzzyDate::parse(text).map(|d| d.calendar()), Ok(Calendar::Bengali), "{}", text);
+        }
+        assert!(FuzzyDate::parse("boi 1380").is_err());
+    }
+
+    #[test]
+    fn bengali_month_lengths_follow_the_revision_in_force() {
+        assert_eq!(gregorian("1 Boishakh 1426"), gregorian("14 Apr 2019"));
+        // Under the 1987 rule Ashwin is the first month of 30 days
+        assert_eq!(gregorian("1 Kartik 1425"), gregorian("16 Oct 2018"));
+        assert_eq!(gregorian("1 Kartik 1426"), gregorian("17 Oct 2019"));
+        assert!(FuzzyDate::parse("31 Ashwin 1425").is_err());
+        assert!(FuzzyDate::parse("31 Ashwin 1426").is_ok());
+        // Falgun 1426 ends in the leap February of 2020
+        assert!(FuzzyDate::parse("30 Falgun 1426").is_ok());
+        assert!(FuzzyDate::parse("30 Falgun 1427").is_err());
+        assert!(FuzzyDate::parse("31 Falgun 1422").is_ok());
+        assert_eq!(gregorian("1 Boishakh 1425"), gregorian("14 Apr 2018"));
+    }
+}
@@ -0,0 +1,553 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::family::{FamilyTree, Gender, RelationKind, TreeIndex};
+
+// Naming how two people in the family tree are related, in English or in
+// Bengali, which has separate words for the father's and the mother's side.
+
+// Generations searched up from each person
+const MAX_GENERATIONS: usize = 30;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Bengali,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Bengali];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Bengali => "বাংলা",
+        }
+    }
+}
+
+// What the second person is to the first
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kinship {
+    Same,
+    Spouse(Gender),
+    // Related through a common ancestor. `up` holds the genders from the
+    // first person's parent to that ancestor, `down` from the ancestor's
+    // child to the second person, so a parent is up [M], a child down [F]
+    // and a sibling up [_] down [M].
+    Blood { up: Vec<Gender>, down: Vec<Gender>, half: bool, adoptive: bool },
+    Step(Box<Kinship>),
+    // Married to one of our relatives
+    SpouseOf { relative: Box<Kinship>, gender: Gender },
+    // A relative of our spouse
+    OfSpouse { spouse: Gender, relative: Box<Kinship> },
+    Unrelated,
+}
+
+fn gendered(gender: Gender, male: &str, female: &str, neutral: &str) -> String {
+    match gender {
+        Gender::Male => male,
+        Gender::Female => female,
+        Gender::Unknown => neutral,
+    }
+    .to_string()
+}
+
+fn ordinal(n: usize) -> String {
+    const WORDS: [&str; 10] = ["first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth"];
+    match WORDS.get(n.wrapping_sub(1)) {
+        Some(word) => word.to_string(),
+        None => format!("{}th", n),
+    }
+}
+
+// "great-great-", then "4th great-"
+fn greats(n: usize) -> String {
+    match n {
+        0..=3 => "great-".repeat(n),
+        _ => format!("{}th great-", n),
+    }
+}
+
+fn english_blood(up: &[Gender], down: &[Gender], half: bool, adoptive: bool) -> String {
+    let (da, db) = (up.len(), down.len());
+    let gender = down.last().or(up.last()).copied().unwrap_or_default();
+    let g = |male: &str, female: &str, neutral: &str| gendered(gender, male, female, neutral);
+    let half = if half { "half-" } else { "" };
+    match (da, db) {
+        (0, 0) => "self".to_string(),
+        (1, 0) if adoptive => format!("adoptive {}", g("father", "mother", "parent")),
+        (1, 0) => g("father", "mother", "parent"),
+        (n, 0) => format!("{}{}", greats(n - 2), g("grandfather", "grandmother", "grandparent")),
+        (0, 1) if adoptive => format!("adopted {}", g("son", "daughter", "child")),
+        (0, 1) => g("son", "daughter", "child"),
+        (0, n) => format!("{}{}", greats(n - 2), g("grandson", "granddaughter", "grandchild")),
+        (1, 1) => format!("{}{}", half, g("brother", "sister", "sibling")),
+        (2, 1) => format!("{}{}", half, g("uncle", "aunt", "aunt or uncle")),
+        (n, 1) => format!("{}{}grand{}", half, greats(n - 3), g("uncle", "aunt", "aunt or uncle")),
+        (1, 2) => format!("{}{}", half, g("nephew", "niece", "nephew or niece")),
+        (1, n) => format!("{}{}grand{}", half, greats(n - 3), g("nephew", "niece", "nephew or niece")),
+        (a, b) => {
+            let removed = match a.abs_diff(b) {
+                0 => String::new(),
+                1 => " once removed".to_string(),
+                2 => " twice removed".to_string(),
+                n => format!(" {} times removed", n),
+            };
+            format!("{}{} cousin{}", half, ordinal(a.min(b) - 1), removed)
+        }
+    }
+}
+
+// Bengali adds "র", "য়ের" or "ের" for "of", depending on how the word ends
+fn possessive(word: &str) -> String {
+    const VOWEL_SIGNS: [char; 8] = ['া', 'ি', 'ী', 'ু', 'ূ', 'ে', 'ৈ', 'ো'];
+    const VOWELS: [char; 7] = ['অ', 'আ', 'ই', 'ঈ', 'উ', 'এ', 'ও'];
+    match word.chars().last() {
+        _ if word == "মা" => "মায়ের".to_string(),
+        Some(c) if VOWELS.contains(&c) => format!("{}য়ের", word),
+        Some(c) if VOWEL_SIGNS.contains(&c) => format!("{}র", word),
+        _ => format!("{}ের", word),
+    }
+}
+
+fn bengali_blood(up: &[Gender], down: &[Gender], half: bool, adoptive: bool) -> String {
+    let (da, db) = (up.len(), down.len());
+    let gender = down.last().or(up.last()).copied().unwrap_or_default();
+    let g = |male: &str, female: &str| gendered(gender, male, female, &format!("{}/{}", male, female));
+    // The father's or the mother's side
+    let paternal = up.first() == Some(&Gender::Male);
+    let maternal = up.first() == Some(&Gender::Female);
+    match (da, db) {
+        (0, 0) => "নিজে".to_string(),
+        (1, 0) if adoptive => format!("পালক {}", g("বাবা", "মা")),
+        (1, 0) => g("বাবা", "মা"),
+        (2, 0) if maternal => g("নানা", "নানি"),
+        (2, 0) => g("দাদা", "দাদি"),
+        (3, 0) if maternal => g("পরনানা", "পরনানি"),
+        (3, 0) => g("পরদাদা", "পরদাদি"),
+        (0, 1) if adoptive => format!("পালিত {}", g("ছেলে", "মেয়ে")),
+        (0, 1) => g("ছেলে", "মেয়ে"),
+        (0, 2) => g("নাতি", "নাতনি"),
+        (0, 3) => g("পুতি", "পুতনি"),
+        (1, 1) if half => format!("সৎ {}", g("ভাই", "বোন")),
+        (1, 1) => g("ভাই", "বোন"),
+        (2, 1) if paternal => g("চাচা", "ফুফু"),
+        (2, 1) if maternal => g("মামা", "খালা"),
+        (1, 2) => match down[0] {
+            Gender::Female => g("ভাগ্নে", "ভাগ্নি"),
+            _ => g("ভাতিজা", "ভাতিজি"),
+        },
+        (2, 2) => {
+            let line = match (paternal, maternal, down[0]) {
+                (true, _, Gender::Male) => "চাচাতো",
+                (true, _, Gender::Female) => "ফুফাতো",
+                (_, true, Gender::Male) => "মামাতো",
+                (_, true, Gender::Female) => "খালাতো",
+                _ => "চাচাতো/মামাতো",
+            };
+            format!("{} {}", line, g("ভাই", "বোন"))
+        }
+        (a, b) if a == b => format!("দূর সম্পর্কের {}", g("ভাই", "বোন")),
+        // Anyone further up is named through our parent: "বাবার চাচা"
+        (a, b) if a > b => {
+            let parent = bengali_blood(&up[..1], &[], false, false);
+            format!("{} {}", possessive(&parent), bengali_blood(&up[1..], down, half, false))
+        }
+        // and anyone further down through their parent: "ভাতিজার ছেলে"
+        _ => {
+            let parent = bengali_blood(up, &down[..db - 1], half, false);
+            format!("{} {}", possessive(&parent), g("ছেলে", "মেয়ে"))
+        }
+    }
+}
+
+impl Kinship {
+    fn is_blood(&self, da: usize, db: usize) -> bool {
+        matches!(self, Kinship::Blood { up, down, .. } if up.len() == da && down.len() == db)
+    }
+
+    // The gender of whoever this names
+    fn gender(&self) -> Gender {
+        match self {
+            Kinship::Spouse(gender) | Kinship::SpouseOf { gender, .. } => *gender,
+            Kinship::Blood { up, down, .. } => down.last().or(up.last()).copied().unwrap_or_default(),
+            Kinship::Step(inner) | Kinship::OfSpouse { relative: inner, .. } => inner.gender(),
+            Kinship::Same | Kinship::Unrelated => Gender::Unknown,
+        }
+    }
+
+    // The same relation but to someone of another gender, for "aunt by marriage"
+    fn with_gender(&self, gender: Gender) -> Kinship {
+        match self {
+            Kinship::Blood { up, down, half, adoptive } => {
+                let (mut up, mut down) = (up.clone(), down.clone());
+                match down.last_mut() {
+                    Some(last) => *last = gender,
+                    None => up.iter_mut().last().into_iter().for_each(|last| *last = gender),
+                }
+                Kinship::Blood { up, down, half: *half, adoptive: *adoptive }
+            }
+            other => other.clone(),
+        }
+    }
+
+    pub fn name(&self, language: Language) -> String {
+        match language {
+            Language::English => self.english(),
+            Language::Bengali => self.bengali(),
+        }
+    }
+
+    // The name starting with a capital, for labels
+    pub fn title(&self, language: Language) -> String {
+        let name = self.name(language);
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => name,
+        }
+    }
+
+    fn english(&self) -> String {
+        match self {
+            Kinship::Same => "you".to_string(),
+            Kinship::Spouse(gender) => gendered(*gender, "husband", "wife", "spouse"),
+            Kinship::Blood { up, down, half, adoptive } => english_blood(up, down, *half, *adoptive),
+            Kinship::Step(inner) => format!("step{}", inner.english()),
+            Kinship::SpouseOf { relative, gender } => {
+                let g = |male: &str, female: &str, neutral: &str| gendered(*gender, male, female, neutral);
+                match relative.as_ref() {
+                    r if r.is_blood(1, 1) => g("brother-in-law", "sister-in-law", "sibling-in-law"),
+                    Kinship::Blood { up, .. } if up.is_empty() => format!("{}-in-law", relative.with_gender(*gender).english()),
+                    Kinship::Blood { down, .. } if down.len() == 1 => format!("{} by marriage", relative.with_gender(*gender).english()),
+                    _ => format!("{}'s {}", relative.english(), g("husband", "wife", "spouse")),
+                }
+            }
+            Kinship::OfSpouse { spouse, relative } => match relative.as_ref() {
+                r if r.is_blood(1, 1) => gendered(r.gender(), "brother-in-law", "sister-in-law", "sibling-in-law"),
+                Kinship::Blood { down, .. } if down.is_empty() => format!("{}-in-law", relative.english()),
+                _ => format!("{}'s {}", gendered(*spouse, "husband", "wife", "spouse"), relative.english()),
+            },
+            Kinship::Unrelated => String::new(),
+        }
+    }
+
+    fn bengali(&self) -> String {
+        match self {
+            Kinship::Same => "নিজে".to_string(),
+            Kinship::Spouse(gender) => gendered(*gender, "স্বামী", "স্ত্রী", "স্বামী/স্ত্রী"),
+            Kinship::Blood { up, down, half, adoptive } => bengali_blood(up, down, *half, *adoptive),
+            Kinship::Step(inner) => format!("সৎ {}", inner.bengali()),
+            Kinship::SpouseOf { relative, gender } => {
+                let relative_name = relative.bengali();
+                // The wives and husbands of close relatives have their own words
+                let special = match relative_name.as_str() {
+                    "ভাই" => "ভাবি",
+                    "বোন" => "দুলাভাই",
+                    "ছেলে" => "বউমা",
+                    "মেয়ে" => "জামাই",
+                    "চাচা" => "চাচি",
+                    "মামা" => "মামি",
+                    "ফুফু" => "ফুফা",
+                    "খালা" => "খালু",
+                    _ => "",
+                };
+                match special {
+                    "" => format!("{} {}", possessive(&relative_name), gendered(*gender, "স্বামী", "স্ত্রী", "স্বামী/স্ত্রী")),
+                    word => word.to_string(),
+                }
+            }
+            Kinship::OfSpouse { spouse, relative } => {
+                let g = |male: &str, female: &str| gendered(relative.gender(), male, female, &format!("{}/{}", male, female));
+                match relative.as_ref() {
+                    r if r.is_blood(1, 0) => g("শ্বশুর", "শাশুড়ি"),
+                    r if r.is_blood(1, 1) && *spouse == Gender::Female => g("শ্যালক", "শ্যালিকা"),
+                    r if r.is_blood(1, 1) && *spouse == Gender::Male => g("দেবর", "ননদ"),
+                    _ => {
+                        let spouse_name = gendered(*spouse, "স্বামী", "স্ত্রী", "স্বামী/স্ত্রী");
+                        format!("{} {}", possessive(&spouse_name), relative.bengali())
+                    }
+                }
+            }
+            Kinship::Unrelated => String::new(),
+        }
+    }
+}
+
+impl FamilyTree {
+    // What `b` is to `a`, for a one-off question; TreeIndex::kinship is
+    // quicker for many
+    pub fn kinship(&self, a: &str, b: &str) -> Kinship {
+        self.index().kinship(a, b)
+    }
+}
+
+impl TreeIndex<'_> {
+    fn gender_of(&self, id: &str) -> Gender {
+        self.person(id).map(|p| p.gender).unwrap_or_default()
+    }
+
+    // Parents by birth or adoption, which both make blood relatives
+    fn legal_parents(&self, id: &str) -> Vec<(String, RelationKind)> {
+        self.parents_of(id)
+            .into_iter()
+            .filter(|(_, kind)| *kind != RelationKind::Step)
+            .map(|(p, kind)| (p.id.clone(), kind))
+            .collect()
+    }
+
+    // Every ancestor with the shortest path to it, starting at the parent
+    fn ancestor_paths(&self, id: &str) -> BTreeMap<String, Vec<String>> {
+        let mut paths = BTreeMap::from([(id.to_string(), Vec::new())]);
+        let mut queue = VecDeque::from([id.to_string()]);
+        while let Some(next) = queue.pop_front() {
+            let path = paths[&next].clone();
+            if path.len() >= MAX_GENERATIONS {
+                continue;
+            }
+            for (parent, _) in self.legal_parents(&next) {
+                if !paths.contains_key(&parent) {
+                    let mut longer = path.clone();
+                    longer.push(parent.clone());
+                    paths.insert(parent.clone(), longer);
+                    queue.push_back(parent);
+                }
+            }
+        }
+        paths
+    }
+
+    fn blood(&self, a: &str, b: &str) -> Option<Kinship> {
+        let ours = self.ancestor_paths(a);
+        let theirs = self.ancestor_paths(b);
+        let (common, up_path, down_path) = ours
+            .iter()
+            .filter_map(|(id, up)| theirs.get(id).map(|down| (id, up, down)))
+            .min_by_key(|(_, up, down)| (up.len() + down.len(), up.len()))?;
+        let (da, db) = (up_path.len(), down_path.len());
+        let up: Vec<Gender> = up_path.iter().map(|id| self.gender_of(id)).collect();
+        // From the common ancestor's child down to b
+        let below: Vec<&str> = std::iter::once(b).chain(down_path.iter().map(String::as_str)).take(db).collect();
+        let down: Vec<Gender> = below.iter().rev().map(|id| self.gender_of(id)).collect();
+
+        // Half relations share one of two parents. Both sides need two
+        // known parents, or a sibling whose other parent is missing from
+        // the tree would be called a half-sibling.
+        let half = da > 0 && db > 0 && {
+            let ours_below = if da > 1 { up_path[da - 2].as_str() } else { a };
+            let theirs_below = below.last().copied().unwrap_or(b);
+            let parents_a: Vec<String> = self.legal_parents(ours_below).into_iter().map(|(p, _)| p).collect();
+            let parents_b: Vec<String> = self.legal_parents(theirs_below).into_iter().map(|(p, _)| p).collect();
+            let shared = parents_a.iter().filter(|p| parents_b.contains(p)).count();
+            shared == 1 && parents_a.len() > 1 && parents_b.len() > 1
+        };
+        let adoptive = match (da, db) {
+            (1, 0) => self.legal_parents(a).iter().any(|(p, kind)| p == common && *kind == RelationKind::Adoptive),
+            (0, 1) => self.legal_parents(b).iter().any(|(p, kind)| p == a && *kind == RelationKind::Adoptive),
+            _ => false,
+        };
+        Some(Kinship::Blood { up, down, half, adoptive })
+    }
+
+    fn step_parents(&self, id: &str) -> Vec<String> {
+        let legal: Vec<String> = self.legal_parents(id).into_iter().map(|(p, _)| p).collect();
+        let mut found: Vec<String> = self
+            .parents_of(id)
+            .into_iter()
+            .filter(|(_, kind)| *kind == RelationKind::Step)
+            .map(|(p, _)| p.id.clone())
+            .collect();
+        // A parent's spouse who isn't a parent too
+        for parent in &legal {
+            for spouse in self.spouses_of(parent) {
+                if !legal.contains(&spouse.id) && !found.contains(&spouse.id) {
+                    found.push(spouse.id.clone());
+                }
+            }
+        }
+        found
+    }
+
+    // What `b` is to `a`
+    pub fn kinship(&self, a: &str, b: &str) -> Kinship {
+        if a == b {
+            return Kinship::Same;
+        }
+        let gender = self.gender_of(b);
+        if self.spouses_of(a).iter().any(|s| s.id == b) {
+            return Kinship::Spouse(gender);
+        }
+        if let Some(blood) = self.blood(a, b) {
+            return blood;
+        }
+        if self.step_parents(a).contains(&b.to_string()) {
+            return Kinship::Step(Box::new(Kinship::Blood { up: vec![gender], down: Vec::new(), half: false, adoptive: false }));
+        }
+        if self.step_parents(b).contains(&a.to_string()) {
+            return Kinship::Step(Box::new(Kinship::Blood { up: Vec::new(), down: vec![gender], half: false, adoptive: false }));
+        }
+        let our_parents: Vec<String> = self.legal_parents(a).into_iter().map(|(p, _)| p).chain(self.step_parents(a)).collect();
+        let their_parents: Vec<String> = self.legal_parents(b).into_iter().map(|(p, _)| p).chain(self.step_parents(b)).collect();
+        if our_parents.iter().any(|p| their_parents.contains(p)) {
+            return Kinship::Step(Box::new(Kinship::Blood { up: vec![Gender::Unknown], down: vec![gender], half: false, adoptive: false }));
+        }
+        for spouse in self.spouses_of(b) {
+            if let Some(relative) = self.blood(a, &spouse.id) {
+                return Kinship::SpouseOf { relative: Box::new(relative), gender };
+            }
+        }
+        for spouse in self.spouses_of(a) {
+            if let Some(relative) = self.blood(&spouse.id, b) {
+                return Kinship::OfSpouse { spouse: spouse.gender, relative: Box::new(relative) };
+            }
+        }
+        Kinship::Unrelated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::family::{Person, Relationship};
+
+    // Three generations on both sides of "me", with in-laws, a stepmother,
+    // a half-brother and a brother whose mother isn't recorded
+    fn tree() -> FamilyTree {
+        let people = [
+            ("great-grandfather", Gender::Male),
+            ("grandfather", Gender::Male),
+            ("grandmother", Gender::Female),
+            ("grandfather's brother", Gender::Male),
+            ("mother's father", Gender::Male),
+            ("father", Gender::Male),
+            ("mother", Gender::Female),
+            ("stepmother", Gender::Female),
+            ("uncle", Gender::Male),
+            ("uncle's wife", Gender::Female),
+            ("aunt", Gender::Female),
+            ("mother's brother", Gender::Male),
+            ("me", Gender::Male),
+            ("wife", Gender::Female),
+            ("wife's father", Gender::Male),
+            ("sister", Gender::Female),
+            ("sister's husband", Gender::Male),
+            ("brother", Gender::Male),
+            ("half-brother", Gender::Male),
+            ("cousin", Gender::Male),
+            ("cousin's son", Gender::Male),
+            ("mother's niece", Gender::Female),
+            ("son", Gender::Male),
+            ("adopted son", Gender::Male),
+            ("grandson", Gender::Male),
+            ("niece", Gender::Female),
+            ("nephew", Gender::Male),
+        ];
+        let persons = people.iter().map(|(id, gender)| Person { id: id.to_string(), gender: *gender, ..Person::default() }).collect();
+        let parent = |from: &str, to: &str| Relationship::new(RelationKind::Parent, from, to);
+        let spouse = |from: &str, to: &str| Relationship::new(RelationKind::Spouse, from, to);
+        let relationships = vec![
+            parent("great-grandfather", "grandfather"),
+            parent("great-grandfather", "grandfather's brother"),
+            spouse("grandfather", "grandmother"),
+            parent("grandfather", "father"),
+            parent("grandmother", "father"),
+            parent("grandfather", "uncle"),
+            parent("grandmother", "uncle"),
+            parent("grandfather", "aunt"),
+            parent("grandmother", "aunt"),
+            spouse("uncle", "uncle's wife"),
+            parent("uncle", "cousin"),
+            parent("cousin", "cousin's son"),
+            parent("mother's father", "mother"),
+            parent("mother's father", "mother's brother"),
+            parent("mother's brother", "mother's niece"),
+            spouse("father", "mother"),
+            spouse("father", "stepmother"),
+            parent("father", "me"),
+            parent("mother", "me"),
+            parent("father", "sister"),
+            parent("mother", "sister"),
+            parent("father", "brother"),
+            parent("father", "half-brother"),
+            parent("stepmother", "half-brother"),
+            spouse("sister", "sister's husband"),
+            parent("sister", "niece"),
+            parent("brother", "nephew"),
+            spouse("me", "wife"),
+            parent("wife's father", "wife"),
+            parent("me", "son"),
+            Relationship::new(RelationKind::Adoptive, "me", "adopted son"),
+            parent("son", "grandson"),
+        ];
+        FamilyTree { persons, relationships }
+    }
+
+    #[test]
+    fn relatives_are_named_in_both_languages() {
+        let tree = tree();
+        let index = tree.index();
+        let table = [
+            ("me", "you", "নিজে"),
+            ("father", "father", "বাবা"),
+            ("grandfather", "grandfather", "দাদা"),
+            ("grandmother", "grandmother", "দাদি"),
+            ("mother's father", "grandfather", "নানা"),
+            ("great-grandfather", "great-grandfather", "পরদাদা"),
+            ("grandfather's brother", "granduncle", "বাবার চাচা"),
+            ("uncle", "uncle", "চাচা"),
+            ("aunt", "aunt", "ফুফু"),
+            ("mother's brother", "uncle", "মামা"),
+            ("sister", "sister", "বোন"),
+            ("brother", "brother", "ভাই"),
+            ("half-brother", "half-brother", "সৎ ভাই"),
+            ("cousin", "first cousin", "চাচাতো ভাই"),
+            ("mother's niece", "first cousin", "মামাতো বোন"),
+            ("cousin's son", "first cousin once removed", "চাচাতো ভাইয়ের ছেলে"),
+            ("niece", "niece", "ভাগ্নি"),
+            ("nephew", "nephew", "ভাতিজা"),
+            ("son", "son", "ছেলে"),
+            ("adopted son", "adopted son", "পালিত ছেলে"),
+            ("grandson", "grandson", "নাতি"),
+            ("wife", "wife", "স্ত্রী"),
+            ("stepmother", "stepmother", "সৎ মা"),
+            ("wife's father", "father-in-law", "শ্বশুর"),
+            ("uncle's wife", "aunt by marriage", "চাচি"),
+            ("sister's husband", "brother-in-law", "দুলাভাই"),
+        ];
+        for (other, english, bengali) in table {
+            let kinship = index.kinship("me", other);
+            assert_eq!(kinship.name(Language::English), english, "{}", other);
+            assert_eq!(kinship.name(Language::Bengali), bengali, "{}", other);
+        }
+    }
+
+    #[test]
+    fn the_way_back_is_named_too() {
+        let tree = tree();
+        assert_eq!(tree.kinship("son", "me").name(Language::English), "father");
+        assert_eq!(tree.kinship("adopted son", "me").name(Language::English), "adoptive father");
+        assert_eq!(tree.kinship("niece", "me").name(Language::Bengali), "মামা");
+        assert_eq!(tree.kinship("wife", "father").name(Language::English), "father-in-law");
+        assert_eq!(tree.kinship("me", "wife's father").title(Language::English), "Father-in-law");
+    }
+
+    #[test]
+    fn a_sibling_with_one_known_parent_is_a_full_sibling() {
+        let tree = tree();
+        assert!(matches!(tree.kinship("me", "brother"), Kinship::Blood { half: false, .. }));
+        assert!(matches!(tree.kinship("brother", "me"), Kinship::Blood { half: false, .. }));
+        assert!(matches!(tree.kinship("me", "half-brother"), Kinship::Blood { half: true, .. }));
+        assert!(matches!(tree.kinship("brother", "half-brother"), Kinship::Blood { half: false, .. }));
+    }
+
+    #[test]
+    fn strangers_are_unrelated() {
+        let tree = tree();
+        assert_eq!(tree.kinship("me", "mother's father"), Kinship::Blood { up: vec![Gender::Female, Gender::Male], down: Vec::new(), half: false, adoptive: false });
+        assert_eq!(tree.kinship("wife's father", "uncle"), Kinship::Unrelated);
+        assert_eq!(Kinship::Unrelated.name(Language::English), "");
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::sync::{Identified, Merge, Stamp};
+use crate::utils::{new_id, now_millis};
+
+// Emoji offered in the quick reaction bar
+pub const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🙏"];
+
+const EXCERPT_LEN: usize = 80;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub name: String,
+    pub avatar: String,
+    // Messages sent after this moment count as unread
+    pub last_read_at: u64,
+    // Everyone else in a group conversation; empty for a 1:1 chat, whose id
+    // is the other person's
+    #[serde(default)]
+    pub members: BTreeSet<String>,
+}
+
+impl Conversation {
+    pub fn is_group(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    // The people messages in this conversation go to
+    pub fn recipients(&self) -> BTreeSet<String> {
+        if self.is_group() {
+            self.members.clone()
+        } else {
+            BTreeSet::from([self.id.clone()])
+        }
+    }
+}
+
+impl Identified for Conversation {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Conversation {
+    fn merge(&mut self, other: Self) {
+        self.last_read_at = self.last_read_at.max(other.last_read_at);
+        self.members.extend(other.members);
+    }
+}
+
+// Snapshot of the message being replied to, so the quote still renders when
+// the original has scrolled out of the loaded history
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplyQuote {
+    pub message_id: String,
+    pub sender_name: String,
+    pub excerpt: String,
+}
+
+impl ReplyQuote {
+    pub fn of(message: &Message) -> Self {
+        let text = message.text();
+        let excerpt = if text.chars().count() > EXCERPT_LEN {
+            format!("{}…", text.chars().take(EXCERPT_LEN).collect::<String>())
+        } else {
+            text.to_string()
+        };
+        Self {
+            message_id: message.id.clone(),
+            sender_name: message.sender_name.clone(),
+            excerpt,
+        }
+    }
+}
+
+// Whether one user currently has one emoji on a message. Removing a reaction
+// keeps the entry with `active: false` so the removal wins over older adds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReactionMark {
+    pub active: bool,
+    pub stamp: Stamp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub body: String,
+    pub stamp: Stamp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub deleted_by: String,
+    pub stamp: Stamp,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: usize,
+    pub mine: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub conversation_id: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    // Body as originally sent; edits live in `revisions`
+    pub body: String,
+    pub sent_at: u64,
+    #[serde(default)]
+    pub reply_to: Option<ReplyQuote>,
+    // emoji -> user id -> mark
+    #[serde(default)]
+    pub reactions: BTreeMap<String, BTreeMap<String, ReactionMark>>,
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+    #[serde(default)]
+    pub deleted: Option<Tombstone>,
+}
+
+impl Message {
+    pub fn new(conversation_id: &str, sender_id: &str, sender_name: &str, body: &str) -> Self {
+        Self {
+            id: new_id(),
+            conversation_id: conversation_id.to_string(),
+            sender_id: sender_id.to_string(),
+            sender_name: sender_name.to_string(),
+            body: body.to_string(),
+            sent_at: now_millis(),
+            reply_to: None,
+            reactions: BTreeMap::new(),
+            revisions: Vec::new(),
+            deleted: None,
+        }
+    }
+
+    pub fn replying_to(mut self, original: &Message) -> Self {
+        self.reply_to = Some(ReplyQuote::of(original));
+        self
+    }
+
+    // Current text, taking the latest edit into account
+    pub fn text(&self) -> &str {
+        self.revisions.last().map_or(&self.body, |r| &r.body)
+    }
+
+    pub fn is_edited(&self) -> bool {
+        !self.revisions.is_empty()
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    // Every version of the text, oldest first, with the time it was written
+    pub fn history(&self) -> Vec<(u64, &str)> {
+        std::iter::once((self.sent_at, self.body.as_str()))
+            .chain(self.revisions.iter().map(|r| (r.stamp.millis, r.body.as_str())))
+            .collect()
+    }
+
+    pub fn edit(&mut self, body: &str) {
+        if self.is_deleted() || body == self.text() {
+            return;
+        }
+        let stamp = match self.revisions.last() {
+            Some(last) => Stamp::after(&last.stamp),
+            None => Stamp::now(),
+        };
+        self.revisions.push(Revision { body: body.to_string(), stamp });
+    }
+
+    // Add the reaction if `user_id` has not reacted with `emoji`, remove it otherwise
+    pub fn toggle_reaction(&mut self, emoji: &str, user_id: &str) {
+        if self.is_deleted() {
+            return;
+        }
+        let marks = self.reactions.entry(emoji.to_string()).or_default();
+        let stamp = match marks.get(user_id) {
+            Some(mark) => Stamp::after(&mark.stamp),
+            None => Stamp::now(),
+        };
+        let active = !marks.get(user_id).is_some_and(|m| m.active);
+        marks.insert(user_id.to_string(), ReactionMark { active, stamp });
+    }
+
+    pub fn reaction_counts(&self, user_id: &str) -> Vec<ReactionCount> {
+        self.reactions
+            .iter()
+            .filter_map(|(emoji, marks)| {
+                let count = marks.values().filter(|m| m.active).count();
+                (count > 0).then(|| ReactionCount {
+                    emoji: emoji.clone(),
+                    count,
+                    mine: marks.get(user_id).is_some_and(|m| m.active),
+                })
+            })
+            .collect()
+    }
+
+    // Delete for everyone: the message stays in the history as a tombstone
+    // but its content is dropped
+    pub fn delete_for_everyone(&mut self, user_id: &str) {
+        if self.deleted.is_none() {
+            self.deleted = Some(Tombstone {
+                deleted_by: user_id.to_string(),
+                stamp: Stamp::now(),
+            });
+        }
+        self.scrub();
+    }
+
+    fn scrub(&mut self) {
+        self.body.clear();
+        self.revisions.clear();
+        self.reactions.clear();
+        self.reply_to = None;
+    }
+}
+
+impl Identified for Message {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Message {
+    fn merge(&mut self, other: Self) {
+        // Edits from both sides are kept, ordered by stamp
+        for revision in other.revisions {
+            if !self.revisions.iter().any(|r| r.stamp == revision.stamp) {
+                self.revisions.push(revision);
+            }
+        }
+        self.revisions.sort_by(|a, b| a.stamp.cmp(&b.stamp));
+
+        // Each user's reaction is last-writer-wins
+        for (emoji, marks) in other.reactions {
+            let local = self.reactions.entry(emoji).or_default();
+            for (user, mark) in marks {
+                match local.get(&user) {
+                    Some(existing) if existing.stamp >= mark.stamp => {}
+                    _ => {
+                        local.insert(user, mark);
+                    }
+                }
+            }
+        }
+
+        // A deletion on either side wins over everything else
+        self.deleted = match (self.deleted.take(), other.deleted) {
+            (Some(a), Some(b)) => Some(if b.stamp < a.stamp { b } else { a }),
+            (a, b) => a.or(b),
+        };
+        if self.deleted.is_some() {
+            self.scrub();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::sync::merge_by_id;
+
+    fn stamp(millis: u64, device: &str) -> Stamp {
+        Stamp { millis, device: device.to_string() }
+    }
+
+    fn original() -> Message {
+        let mut message = Message::new("chat", "ana", "Ana", "see you at 6");
+        message.id = "m1".to_string();
+        message.sent_at = 1;
+        message
+    }
+
+    fn edited(mut message: Message, body: &str, at: Stamp) -> Message {
+        message.revisions.push(Revision { body: body.to_string(), stamp: at });
+        message
+    }
+
+    fn reacted(mut message: Message, emoji: &str, user: &str, active: bool, at: Stamp) -> Message {
+        let mark = ReactionMark { active, stamp: at };
+        message.reactions.entry(emoji.to_string()).or_default().insert(user.to_string(), mark);
+        message
+    }
+
+    // Merge the two copies both ways round and check they agree
+    fn merged(a: &Message, b: &Message) -> Message {
+        let mut ab = a.clone();
+        ab.merge(b.clone());
+        let mut ba = b.clone();
+        ba.merge(a.clone());
+        assert_eq!(ab, ba);
+        ab
+    }
+
+    #[test]
+    fn delete_wins_over_a_concurrent_edit() {
+        let phone = edited(original(), "see you at 7", stamp(10, "phone"));
+        let mut laptop = original();
+        laptop.deleted = Some(Tombstone { deleted_by: "ana".to_string(), stamp: stamp(9, "laptop") });
+
+        let result = merged(&phone, &laptop);
+        assert!(result.is_deleted());
+        assert!(result.body.is_empty());
+        assert!(result.revisions.is_empty());
+        assert_eq!(result.text(), "");
+    }
+
+    #[test]
+    fn concurrent_edits_are_both_kept_in_stamp_order() {
+        let phone = edited(original(), "see you at 7", stamp(10, "phone"));
+        let laptop = edited(original(), "see you at 8", stamp(10, "laptop"));
+
+        let result = merged(&phone, &laptop);
+        let history: Vec<&str> = result.history().into_iter().map(|(_, body)| body).collect();
+        // Same millisecond: the device id breaks the tie
+        assert_eq!(history, ["see you at 6", "see you at 8", "see you at 7"]);
+        assert_eq!(result.text(), "see you at 7");
+    }
+
+    #[test]
+    fn revisions_arriving_out_of_order_settle_by_stamp() {
+        let first = edited(original(), "at 7", stamp(10, "phone"));
+        let second = edited(first.clone(), "at 8", stamp(20, "phone"));
+        let third = edited(second.clone(), "at 9", stamp(30, "laptop"));
+
+        let mut result = original();
+        for copy in [third.clone(), first.clone(), second.clone(), third] {
+            result.merge(copy);
+        }
+        assert_eq!(result.revisions.iter().map(|r| r.body.as_str()).collect::<Vec<_>>(), ["at 7", "at 8", "at 9"]);
+        assert_eq!(result.text(), "at 9");
+        assert_eq!(merged(&first, &second), second);
+    }
+
+    #[test]
+    fn the_latest_reaction_toggle_wins() {
+        // Ben adds a reaction on his phone, then takes it back on his laptop
+        let phone = reacted(original(), "👍", "ben", true, stamp(5, "phone"));
+        let laptop = reacted(phone.clone(), "👍", "ben", false, stamp(7, "laptop"));
+        let result = merged(&phone, &laptop);
+        assert!(result.reaction_counts("ben").is_empty());
+
+        // Re-adding afterwards brings it back
+        let again = reacted(laptop.clone(), "👍", "ben", true, stamp(9, "phone"));
+        let result = merged(&again, &laptop);
+        assert_eq!(result.reaction_counts("ben")[0].count, 1);
+        assert!(result.reaction_counts("ben")[0].mine);
+    }
+
+    #[test]
+    fn different_people_reacting_at_once_are_counted_together() {
+        let bens = reacted(original(), "❤️", "ben", true, stamp(5, "ben"));
+        let cals = reacted(original(), "❤️", "cal", true, stamp(5, "cal"));
+        let result = merged(&bens, &cals);
+        assert_eq!(result.reaction_counts("ana"), vec![ReactionCount { emoji: "❤️".to_string(), count: 2, mine: false }]);
+    }
+
+    #[test]
+    fn reactions_do_not_survive_a_delete() {
+        let reaction = reacted(original(), "😂", "ben", true, stamp(20, "ben"));
+        let mut deleted = original();
+        deleted.delete_for_everyone("ana");
+        let result = merged(&reaction, &deleted);
+        assert!(result.reactions.is_empty());
+    }
+
+    #[test]
+    fn thread_merge_gives_the_same_result_either_way() {
+        let shared = original();
+        let other = Message::new("chat", "ben", "Ben", "ok");
+        let ours = vec![edited(shared.clone(), "see you at 7", stamp(10, "phone"))];
+        let theirs = vec![reacted(shared, "👍", "ben", true, stamp(11, "laptop")), other];
+
+        let mut a = ours.clone();
+        assert!(merge_by_id(&mut a, theirs.clone()));
+        let mut b = theirs.clone();
+        assert!(merge_by_id(&mut b, ours));
+        b.sort_by(|x, y| x.id.cmp(&y.id));
+        a.sort_by(|x, y| x.id.cmp(&y.id));
+        assert_eq!(a, b);
+        // Nothing new the second time
+        assert!(!merge_by_id(&mut a, theirs));
+    }
+}
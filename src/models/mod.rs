@@ -0,0 +1,18 @@
+// Data models shared by repositories and components
+pub mod call;
+pub mod circle;
+pub mod contact;
+pub mod event;
+pub mod family;
+pub mod fuzzy_date;
+pub mod kinship;
+pub mod message;
+pub mod person_match;
+pub mod poll;
+pub mod post;
+pub mod tree_health;
+
+// Identity of the signed-in user until accounts are wired up
+pub const LOCAL_USER_ID: &str = "me";
+pub const LOCAL_USER_NAME: &str = "Jane Doe";
+pub const LOCAL_USER_AVATAR: &str = "https://i.pravatar.cc/150?img=5";
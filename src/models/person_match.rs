@@ -0,0 +1,556 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::models::family::{FamilyTree, Gender, Person, PersonDraft};
+use crate::models::fuzzy_date::FuzzyDate;
+
+// Finding people who are in the tree twice, as happens when the same
+// ancestor comes in from two files. Names are compared after folding
+// accents and transliterating Bengali script, and by a consonant skeleton so
+// that "Mohammad", "Muhammad" and "মোহাম্মদ" sound the same.
+
+// Pairs scoring below this aren't shown
+const MIN_SCORE: i64 = 60;
+// Names less alike than this rule a pair out
+const MIN_NAME_LIKENESS: f64 = 0.7;
+// Birth or death years further apart than this rule a pair out
+const MAX_YEAR_GAP: i64 = 10;
+
+// Two people who may be the same, best match first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub first: String,
+    pub second: String,
+    pub score: i64,
+    pub reasons: Vec<String>,
+}
+
+// Bengali letters in Latin script, roughly as names are usually spelt
+fn bengali(c: char) -> Option<&'static str> {
+    Some(match c {
+        'অ' | 'আ' | 'া' => "a",
+        'ই' | 'ঈ' | 'ি' | 'ী' => "i",
+        'উ' | 'ঊ' | 'ু' | 'ূ' => "u",
+        'ঋ' | 'ৃ' => "ri",
+        'এ' | 'ে' => "e",
+        'ঐ' | 'ৈ' => "oi",
+        'ও' | 'ো' => "o",
+        'ঔ' | 'ৌ' => "ou",
+        'ক' => "k",
+        'খ' => "kh",
+        'গ' => "g",
+        'ঘ' => "gh",
+        'ঙ' | 'ং' => "ng",
+        'চ' => "ch",
+        'ছ' => "chh",
+        'জ' | 'য' => "j",
+        'ঝ' => "jh",
+        'ঞ' | 'ণ' | 'ন' => "n",
+        'ট' | 'ত' | 'ৎ' => "t",
+        'ঠ' | 'থ' => "th",
+        'ড' | 'দ' => "d",
+        'ঢ' | 'ধ' => "dh",
+        'প' => "p",
+        'ফ' => "ph",
+        'ব' => "b",
+        'ভ' => "bh",
+        'ম' => "m",
+        'র' | '\u{09DC}' => "r",
+        '\u{09DD}' => "rh",
+        'ল' => "l",
+        'শ' | 'ষ' => "sh",
+        'স' => "s",
+        'হ' | 'ঃ' => "h",
+        '\u{09DF}' => "y",
+        _ => return None,
+    })
+}
+
+fn is_bengali_consonant(c: char) -> bool {
+    ('ক'..='হ').contains(&c) || ['\u{09DC}', '\u{09DD}', '\u{09DF}', 'ৎ'].contains(&c)
+}
+
+// Accented Latin letters without their accents
+fn fold(c: char) -> char {
+    match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' | 'ā' => 'a',
+        'ç' | 'č' | 'ć' => 'c',
+        'é' | 'è' | 'ê' | 'ë' | 'ē' => 'e',
+        'í' | 'ì' | 'î' | 'ï' | 'ī' => 'i',
+        'ñ' | 'ń' => 'n',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' | 'ō' => 'o',
+        'ś' | 'š' | 'ş' => 's',
+        'ú' | 'ù' | 'û' | 'ü' | 'ū' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ž' | 'ź' | 'ż' => 'z',
+        other => other,
+    }
+}
+
+// Lower-case Latin letters and single spaces
+pub fn normalize(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        // The nukta turns ড and ঢ into ড় and ঢ়
+        let c = match (c, chars.get(i + 1)) {
+            ('ড', Some('\u{09BC}')) => '\u{09DC}',
+            ('ঢ', Some('\u{09BC}')) => '\u{09DD}',
+            ('য', Some('\u{09BC}')) => '\u{09DF}',
+            _ => c,
+        };
+        if let Some(latin) = bengali(c) {
+            out.push_str(latin);
+            // A consonant followed by another carries an "a"; before a vowel
+            // sign or virama it doesn't, and at the end of a word it's silent
+            let next = chars.get(i + 1).filter(|n| **n != '\u{09BC}').or(chars.get(i + 2));
+            if is_bengali_consonant(c) && next.is_some_and(|n| is_bengali_consonant(*n)) {
+                out.push('a');
+            }
+            continue;
+        }
+        for lower in c.to_lowercase().map(fold) {
+            if lower.is_ascii_alphabetic() {
+                out.push(lower);
+            } else if !out.is_empty() && !out.ends_with(' ') && (lower.is_whitespace() || lower == '-' || lower == '.') {
+                out.push(' ');
+            }
+        }
+    }
+    out.trim_end().to_string()
+}
+
+// How a normalized word sounds: digraphs folded, letters that stand in for
+// each other in transliteration merged, inner vowels and doubled letters
+// dropped
+pub fn skeleton(word: &str) -> String {
+    let word = word
+        .replace("ph", "f")
+        .replace("kh", "k")
+        .replace("gh", "g")
+        .replace("th", "t")
+        .replace("dh", "d")
+        .replace("bh", "b")
+        .replace("sh", "s")
+        .replace("ch", "x")
+        .replace("ck", "k")
+        .replace("qu", "k");
+    let mut out = String::new();
+    for (i, c) in word.chars().enumerate() {
+        let c = match c {
+            'q' | 'c' => 'k',
+            'z' => 'j',
+            'v' => 'b',
+            'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'w' | 'h' if i == 0 => 'a',
+            'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'w' | 'h' => continue,
+            other => other,
+        };
+        if !out.ends_with(c) {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// How a normalized name sounds, word by word
+fn sounds(name: &str) -> Vec<String> {
+    name.split(' ').map(skeleton).collect()
+}
+
+// How alike two normalized names are, from 0 to 1, given how each sounds
+fn likeness((a, a_sounds): (&str, &[String]), (b, b_sounds): (&str, &[String])) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    if a_sounds == b_sounds {
+        return 0.9;
+    }
+    let longest = a.chars().count().max(b.chars().count());
+    0.8 * (1.0 - edit_distance(a, b) as f64 / longest as f64)
+}
+
+// Everything someone is called, normalized
+fn names(person: &Person) -> Vec<String> {
+    std::iter::once(format!("{} {}", person.given_name, person.surname))
+        .chain(person.alternate_names.iter().cloned())
+        .map(|n| normalize(&n))
+        .filter(|n| !n.is_empty())
+        .collect()
+}
+
+fn year(date: &str) -> Option<i64> {
+    FuzzyDate::parse(date).ok().map(|date| date.year())
+}
+
+// What comparing someone needs, worked out once per person rather than once
+// per pair
+struct Profile<'a> {
+    person: &'a Person,
+    names: Vec<(String, Vec<String>)>,
+    born: Option<i64>,
+    died: Option<i64>,
+    birth_place: String,
+    death_place: String,
+    // Parents, spouses and children, as indexes into the profiles
+    relatives: BTreeSet<usize>,
+}
+
+impl<'a> Profile<'a> {
+    fn new(person: &'a Person) -> Self {
+        Self {
+            person,
+            names: names(person)
+                .into_iter()
+                .map(|name| {
+                    let sounds = sounds(&name);
+                    (name, sounds)
+                })
+                .collect(),
+            born: year(&person.birth_date),
+            died: year(&person.death_date),
+            birth_place: normalize(&person.birth_place),
+            death_place: normalize(&person.death_place),
+            relatives: BTreeSet::new(),
+        }
+    }
+
+    // How alike the closest pair of names is
+    fn likeness(&self, other: &Profile) -> f64 {
+        self.names
+            .iter()
+            .flat_map(|(a, a_sounds)| other.names.iter().map(move |(b, b_sounds)| likeness((a, a_sounds), (b, b_sounds))))
+            .fold(0.0, f64::max)
+    }
+}
+
+// Everyone in the tree with their relatives filled in
+fn profiles(tree: &FamilyTree) -> Vec<Profile<'_>> {
+    let mut profiles: Vec<Profile> = tree.people().map(Profile::new).collect();
+    let index: BTreeMap<&str, usize> = profiles.iter().enumerate().map(|(i, p)| (p.person.id.as_str(), i)).collect();
+    for link in tree.links() {
+        if let (Some(&from), Some(&to)) = (index.get(link.from.as_str()), index.get(link.to.as_str())) {
+            profiles[from].relatives.insert(to);
+            profiles[to].relatives.insert(from);
+        }
+    }
+    profiles
+}
+
+// How likely profiles `a` and `b` are to be the same person, with why, or
+// None when something rules it out
+fn compare(profiles: &[Profile], a: usize, b: usize) -> Option<(i64, Vec<String>)> {
+    let (pa, pb) = (&profiles[a], &profiles[b]);
+    if pa.person.gender != pb.person.gender && pa.person.gender != Gender::Unknown && pb.person.gender != Gender::Unknown {
+        return None;
+    }
+    let likeness = pa.likeness(pb);
+    if likeness < MIN_NAME_LIKENESS {
+        return None;
+    }
+    let mut reasons = vec![if likeness >= 1.0 {
+        "Same name".to_string()
+    } else if likeness >= 0.9 {
+        "Names sound alike".to_string()
+    } else {
+        "Similar names".to_string()
+    }];
+    let mut score = (likeness * 50.0).round() as i64;
+
+    for (what, x, y, exact, close) in [("born", pa.born, pb.born, 20, 10), ("died", pa.died, pb.died, 15, 8)] {
+        let (Some(x), Some(y)) = (x, y) else {
+            continue;
+        };
+        match (x - y).abs() {
+            0 => {
+                score += exact;
+                reasons.push(format!("Both {} in {}", what, x));
+            }
+            1..=2 => {
+                score += close;
+                reasons.push(format!("Both {} around {}", what, x.min(y)));
+            }
+            gap if gap > MAX_YEAR_GAP => return None,
+            _ => score -= exact * 2,
+        }
+    }
+    let places = [
+        ("born", &pa.birth_place, &pb.birth_place, &pa.person.birth_place, 10),
+        ("died", &pa.death_place, &pb.death_place, &pa.person.death_place, 5),
+    ];
+    for (what, x, y, written, points) in places {
+        if !x.is_empty() && x == y {
+            score += points;
+            reasons.push(format!("Both {} in {}", what, written.trim()));
+        }
+    }
+
+    // Relatives in common, whether the same record or a look-alike
+    let shared = pa
+        .relatives
+        .iter()
+        .filter(|&&mine| {
+            pb.relatives.iter().any(|&theirs| mine == theirs || profiles[mine].likeness(&profiles[theirs]) >= 0.9)
+        })
+        .count() as i64;
+    if shared > 0 {
+        score += (shared * 10).min(30);
+        reasons.push(if shared == 1 { "A relative in common".to_string() } else { format!("{} relatives in common", shared) });
+    }
+    Some((score, reasons))
+}
+
+// Everyone who may be in the tree twice, leaving out pairs already marked
+// as different people and people linked to each other
+pub fn duplicates(tree: &FamilyTree, distinct: &[(String, String)]) -> Vec<Candidate> {
+    let profiles = profiles(tree);
+    let distinct: BTreeSet<(&str, &str)> =
+        distinct.iter().flat_map(|(x, y)| [(x.as_str(), y.as_str()), (y.as_str(), x.as_str())]).collect();
+    // Only compare people whose names start with the same sound
+    let mut buckets: BTreeMap<char, Vec<usize>> = BTreeMap::new();
+    for (i, profile) in profiles.iter().enumerate() {
+        let starts: BTreeSet<char> = profile.names.iter().filter_map(|(name, _)| skeleton(name).chars().next()).collect();
+        for start in starts {
+            buckets.entry(start).or_default().push(i);
+        }
+    }
+    let mut pairs = BTreeSet::new();
+    for bucket in buckets.values() {
+        for (n, &a) in bucket.iter().enumerate() {
+            for &b in &bucket[n + 1..] {
+                pairs.insert((a, b));
+            }
+        }
+    }
+    let mut found = Vec::new();
+    for (a, b) in pairs {
+        let (first, second) = (&profiles[a].person.id, &profiles[b].person.id);
+        if profiles[a].relatives.contains(&b) || distinct.contains(&(first.as_str(), second.as_str())) {
+            continue;
+        }
+        if let Some((score, reasons)) = compare(&profiles, a, b).filter(|(score, _)| *score >= MIN_SCORE) {
+            found.push(Candidate { first: first.clone(), second: second.clone(), score, reasons });
+        }
+    }
+    found.sort_by_key(|c| std::cmp::Reverse(c.score));
+    found
+}
+
+// A property the merge screen picks from one side or the other
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PersonField {
+    GivenName,
+    Surname,
+    Gender,
+    BirthDate,
+    BirthPlace,
+    DeathDate,
+    DeathPlace,
+    Occupation,
+    Photo,
+    Notes,
+}
+
+impl PersonField {
+    pub const ALL: [PersonField; 10] = [
+        PersonField::GivenName,
+        PersonField::Surname,
+        PersonField::Gender,
+        PersonField::BirthDate,
+        PersonField::BirthPlace,
+        PersonField::DeathDate,
+        PersonField::DeathPlace,
+        PersonField::Occupation,
+        PersonField::Photo,
+        PersonField::Notes,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PersonField::GivenName => "Given name",
+            PersonField::Surname => "Surname",
+            PersonField::Gender => "Gender",
+            PersonField::BirthDate => "Born",
+            PersonField::BirthPlace => "Birth place",
+            PersonField::DeathDate => "Died",
+            PersonField::DeathPlace => "Death place",
+            PersonField::Occupation => "Occupation",
+            PersonField::Photo => "Photo",
+            PersonField::Notes => "Notes",
+        }
+    }
+
+    pub fn value(&self, draft: &PersonDraft) -> String {
+        match self {
+            PersonField::GivenName => draft.given_name.clone(),
+            PersonField::Surname => draft.surname.clone(),
+            PersonField::Gender => draft.gender.label().to_string(),
+            PersonField::BirthDate => draft.birth_date.clone(),
+            PersonField::BirthPlace => draft.birth_place.clone(),
+            PersonField::DeathDate => draft.death_date.clone(),
+            PersonField::DeathPlace => draft.death_place.clone(),
+            PersonField::Occupation => draft.occupation.clone(),
+            PersonField::Photo => draft.photo.clone(),
+            PersonField::Notes => draft.notes.clone(),
+        }
+    }
+
+    // Take this property from `from`
+    pub fn copy(&self, from: &PersonDraft, to: &mut PersonDraft) {
+        match self {
+            PersonField::GivenName => to.given_name = from.given_name.clone(),
+            PersonField::Surname => to.surname = from.surname.clone(),
+            PersonField::Gender => to.gender = from.gender,
+            PersonField::BirthDate => to.birth_date = from.birth_date.clone(),
+            PersonField::BirthPlace => to.birth_place = from.birth_place.clone(),
+            PersonField::DeathDate => to.death_date = from.death_date.clone(),
+            PersonField::DeathPlace => to.death_place = from.death_place.clone(),
+            PersonField::Occupation => to.occupation = from.occupation.clone(),
+            PersonField::Photo => to.photo = from.photo.clone(),
+            PersonField::Notes => to.notes = from.notes.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::family::{RelationKind, Relationship};
+
+    fn person(id: &str, name: &str, born: &str) -> Person {
+        let (given_name, surname) = name.split_once(' ').unwrap_or((name, ""));
+        Person { id: id.to_string(), given_name: given_name.to_string(), surname: surname.to_string(), birth_date: born.to_string(), ..Person::default() }
+    }
+
+    fn alike(a: &str, b: &str) -> f64 {
+        let (a, b) = (normalize(a), normalize(b));
+        likeness((&a, &sounds(&a)), (&b, &sounds(&b)))
+    }
+
+    // The score for the first two people in the tree
+    fn score(tree: &FamilyTree) -> Option<(i64, Vec<String>)> {
+        compare(&profiles(tree), 0, 1)
+    }
+
+    #[test]
+    fn bengali_is_written_in_latin_letters() {
+        assert_eq!(normalize("মোহাম্মদ"), "mohammad");
+        assert_eq!(normalize("মোহাম্মদ আলী"), normalize("Mohammad Ali"));
+        // A consonant before another carries the inherent "a"
+        assert_eq!(normalize("করিম"), "karim");
+        assert_eq!(normalize("কমল"), "kamal");
+        // ড় written as ড with a nukta or as one letter reads the same
+        assert_eq!(normalize("বড়ুয়া"), "baruya");
+        assert_eq!(normalize("ব\u{09DC}ু\u{09DF}া"), "baruya");
+        assert_eq!(normalize("আষাঢ়"), "asharh");
+    }
+
+    #[test]
+    fn accents_case_and_punctuation_are_folded() {
+        assert_eq!(normalize("José Ñúñez"), "jose nunez");
+        assert_eq!(normalize("  Abdul-Karim   M. "), "abdul karim m");
+        assert_eq!(normalize("O'Brien"), "obrien");
+    }
+
+    #[test]
+    fn spellings_that_sound_alike_share_a_skeleton() {
+        assert_eq!(skeleton("mohammad"), skeleton("muhammad"));
+        assert_eq!(skeleton("chowdhury"), skeleton("choudhuri"));
+        assert_eq!(skeleton("sheikh"), skeleton("shaikh"));
+        assert_eq!(skeleton("farooq"), skeleton("faruk"));
+        assert_ne!(skeleton("karim"), skeleton("rahim"));
+    }
+
+    #[test]
+    fn names_are_scored_by_how_alike_they_are() {
+        assert_eq!(alike("Mohammad Ali", "mohammad ali"), 1.0);
+        assert_eq!(alike("মোহাম্মদ আলী", "Muhammad Ali"), 0.9);
+        assert_eq!(alike("রহমান", "Rahman"), 0.9);
+        assert_eq!(alike("Fatema Begum", "Fatima Begam"), 0.9);
+        let close = alike("Abdul Karim", "Abdul Karin");
+        assert!((0.7..0.9).contains(&close), "{}", close);
+        assert!(alike("Karim", "Rahim") < MIN_NAME_LIKENESS);
+        assert_eq!(alike("", "Karim"), 0.0);
+    }
+
+    #[test]
+    fn matching_years_and_places_add_up() {
+        let mut a = person("a", "Mohammad Ali", "1920");
+        let mut b = person("b", "Muhammad Ali", "1920");
+        a.birth_place = "Sylhet".to_string();
+        b.birth_place = "sylhet".to_string();
+        let tree = FamilyTree { persons: vec![a, b], relationships: Vec::new() };
+        let (points, reasons) = score(&tree).unwrap();
+        assert_eq!(points, 45 + 20 + 10);
+        assert_eq!(reasons, ["Names sound alike", "Both born in 1920", "Both born in Sylhet"]);
+    }
+
+    #[test]
+    fn years_far_apart_rule_a_pair_out() {
+        let tree = |second: &str| FamilyTree { persons: vec![person("a", "Karim Uddin", "1900"), person("b", "Karim Uddin", second)], relationships: Vec::new() };
+        assert_eq!(score(&tree("1902")).unwrap().0, 50 + 10);
+        // A few years apart counts against the pair, more rules it out
+        assert_eq!(score(&tree("1905")).unwrap().0, 50 - 40);
+        assert_eq!(score(&tree("1911")), None);
+    }
+
+    #[test]
+    fn different_genders_or_names_rule_a_pair_out() {
+        let mut a = person("a", "Karim Uddin", "");
+        let mut b = person("b", "Karim Uddin", "");
+        a.gender = Gender::Male;
+        b.gender = Gender::Female;
+        assert_eq!(score(&FamilyTree { persons: vec![a.clone(), b.clone()], relationships: Vec::new() }), None);
+        b.gender = Gender::Unknown;
+        assert!(score(&FamilyTree { persons: vec![a, b], relationships: Vec::new() }).is_some());
+        let strangers = FamilyTree { persons: vec![person("a", "Karim Uddin", "1900"), person("b", "Rahim Mia", "1900")], relationships: Vec::new() };
+        assert_eq!(score(&strangers), None);
+    }
+
+    #[test]
+    fn look_alike_relatives_count_as_shared() {
+        let persons = vec![
+            person("a", "Rahim Uddin", "1950"),
+            person("b", "Rahim Uddin", "1950"),
+            person("father a", "Karim Uddin", ""),
+            person("father b", "করিম উদ্দিন", ""),
+        ];
+        let relationships =
+            vec![Relationship::new(RelationKind::Parent, "father a", "a"), Relationship::new(RelationKind::Parent, "father b", "b")];
+        let (points, reasons) = score(&FamilyTree { persons, relationships }).unwrap();
+        assert_eq!(points, 50 + 20 + 10);
+        assert_eq!(reasons.last().unwrap(), "A relative in common");
+    }
+
+    #[test]
+    fn duplicates_leave_out_linked_and_distinct_pairs() {
+        let persons = vec![
+            person("a", "Mohammad Ali", "1920"),
+            person("b", "Muhammad Ali", "1920"),
+            person("c", "Mohammad Ali", "1921"),
+            person("d", "Mohammad Ali", "1920"),
+        ];
+        let relationships = vec![Relationship::new(RelationKind::Parent, "a", "d")];
+        let tree = FamilyTree { persons, relationships };
+        let found = duplicates(&tree, &[("c".to_string(), "b".to_string())]);
+        let pairs: Vec<(&str, &str)> = found.iter().map(|c| (c.first.as_str(), c.second.as_str())).collect();
+        assert_eq!(pairs, [("a", "b"), ("b", "d"), ("a", "c"), ("c", "d")]);
+        assert!(found.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::circle::CircleMember;
+use crate::services::sync::{Merge, Stamp};
+use crate::utils::{new_id, to_hex};
+
+pub const MIN_POLL_OPTIONS: usize = 2;
+pub const MAX_POLL_OPTIONS: usize = 10;
+pub const MAX_OPTION_LEN: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PollOption {
+    pub id: String,
+    pub text: String,
+}
+
+// One person's current vote. An empty choice withdraws it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ballot {
+    pub choices: BTreeSet<String>,
+    // Left out of anonymous polls
+    #[serde(default)]
+    pub voter: Option<CircleMember>,
+    pub stamp: Stamp,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollResult {
+    pub option: PollOption,
+    pub votes: usize,
+    // Empty for anonymous polls
+    pub voters: Vec<CircleMember>,
+}
+
+// A poll attached to a post; the post's body is the question. Each voter's
+// ballot is last-writer-wins, and of two deadlines the earlier one holds so
+// closing a poll early sticks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Poll {
+    pub options: Vec<PollOption>,
+    pub multiple: bool,
+    pub anonymous: bool,
+    #[serde(default)]
+    pub closes_at: Option<u64>,
+    // voter key -> ballot
+    #[serde(default)]
+    pub ballots: BTreeMap<String, Ballot>,
+}
+
+impl Poll {
+    pub fn is_open(&self, now: u64) -> bool {
+        self.closes_at.is_none_or(|closes_at| now < closes_at)
+    }
+
+    // Ballots are keyed by user id, or for anonymous polls by a hash of it so
+    // names never reach the stored or synced data. Someone trying every
+    // member's id could still match them up.
+    pub fn voter_key(&self, post_id: &str, user_id: &str) -> String {
+        match self.anonymous {
+            true => to_hex(&Sha256::digest(format!("{}:{}", post_id, user_id).as_bytes())),
+            false => user_id.to_string(),
+        }
+    }
+
+    pub fn choices_of(&self, post_id: &str, user_id: &str) -> BTreeSet<String> {
+        self.ballots.get(&self.voter_key(post_id, user_id)).map(|b| b.choices.clone()).unwrap_or_default()
+    }
+
+    // Pick or unpick an option. Single-choice polls swap the vote instead.
+    // Returns the voter key and the new ballot, for sending to others.
+    pub fn toggle(&mut self, post_id: &str, voter: CircleMember, option_id: &str, now: u64) -> Result<(String, Ballot), String> {
+        if !self.is_open(now) {
+            return Err("This poll has closed".to_string());
+        }
+        if !self.options.iter().any(|o| o.id == option_id) {
+            return Err("That option is no longer in the poll".to_string());
+        }
+        let key = self.voter_key(post_id, &voter.id);
+        let mut choices = self.choices_of(post_id, &voter.id);
+        if !choices.remove(option_id) {
+            if !self.multiple {
+                choices.clear();
+            }
+            choices.insert(option_id.to_string());
+        }
+        let stamp = match self.ballots.get(&key) {
+            Some(previous) => Stamp::after(&previous.stamp),
+            None => Stamp::now(),
+        };
+        let ballot = Ballot { choices, voter: (!self.anonymous).then_some(voter), stamp };
+        self.ballots.insert(key.clone(), ballot.clone());
+        Ok((key, ballot))
+    }
+
+    // A ballot that arrived from `sender`. It has to be filed under the
+    // sender's own key and reach us while the poll is open; the stamp on it
+    // is the sender's to set, so only our own clock counts.
+    pub fn receive(&mut self, post_id: &str, sender: &str, key: String, ballot: Ballot, now: u64) -> Result<(), String> {
+        if !self.is_open(now) {
+            return Err("This poll has closed".to_string());
+        }
+        if key != self.voter_key(post_id, sender) {
+            return Err("Ballot filed under someone else's key".to_string());
+        }
+        self.check(&key, &ballot)?;
+        self.accept(key, ballot);
+        Ok(())
+    }
+
+    // What any ballot must be, however it reached us: naming the voter its
+    // key belongs to (or no one, when anonymous) and picking only options in
+    // the poll
+    fn check(&self, key: &str, ballot: &Ballot) -> Result<(), String> {
+        let voter_ok = match (&ballot.voter, self.anonymous) {
+            (None, true) => key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()),
+            (Some(voter), false) => voter.id == key,
+            _ => false,
+        };
+        if !voter_ok {
+            return Err("Ballot names someone other than the sender".to_string());
+        }
+        if !ballot.choices.iter().all(|choice| self.options.iter().any(|o| &o.id == choice)) {
+            return Err("Ballot picks an option that isn't in the poll".to_string());
+        }
+        if !self.multiple && ballot.choices.len() > 1 {
+            return Err("Ballot picks more than one option".to_string());
+        }
+        Ok(())
+    }
+
+    // Last writer wins per voter
+    fn accept(&mut self, key: String, ballot: Ballot) {
+        match self.ballots.get(&key) {
+            Some(existing) if existing.stamp >= ballot.stamp => {}
+            _ => {
+                self.ballots.insert(key, ballot);
+            }
+        }
+    }
+
+    pub fn close(&mut self, now: u64) {
+        if self.is_open(now) {
+            self.closes_at = Some(now);
+        }
+    }
+
+    // People with at least one choice
+    pub fn voter_count(&self) -> usize {
+        self.ballots.values().filter(|b| !b.choices.is_empty()).count()
+    }
+
+    pub fn results(&self) -> Vec<PollResult> {
+        self.options
+            .iter()
+            .map(|option| {
+                let ballots: Vec<&Ballot> = self.ballots.values().filter(|b| b.choices.contains(&option.id)).collect();
+                PollResult {
+                    option: option.clone(),
+                    votes: ballots.len(),
+                    voters: ballots.iter().filter_map(|b| b.voter.clone()).collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Merge for Poll {
+    fn merge(&mut self, other: Self) {
+        for (key, ballot) in other.ballots {
+            if self.check(&key, &ballot).is_ok() {
+                self.accept(key, ballot);
+            }
+        }
+        self.closes_at = match (self.closes_at, other.closes_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+// The poll part of the composer
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollDraft {
+    pub options: Vec<String>,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<u64>,
+}
+
+impl Default for PollDraft {
+    fn default() -> Self {
+        Self { options: vec![String::new(); MIN_POLL_OPTIONS], multiple: false, anonymous: false, closes_at: None }
+    }
+}
+
+impl PollDraft {
+    fn filled(&self) -> Vec<&str> {
+        self.options.iter().map(|o| o.trim()).filter(|o| !o.is_empty()).collect()
+    }
+
+    pub fn validate(&self, now: u64) -> Result<(), String> {
+        let options = self.filled();
+        if options.len() < MIN_POLL_OPTIONS {
+            return Err(format!("A poll needs at least {} options", MIN_POLL_OPTIONS));
+        }
+        if options.len() > MAX_POLL_OPTIONS {
+            return Err(format!("A poll can have at most {} options", MAX_POLL_OPTIONS));
+        }
+        if options.iter().any(|o| o.chars().count() > MAX_OPTION_LEN) {
+            return Err(format!("Options can be at most {} characters", MAX_OPTION_LEN));
+        }
+        let unique: BTreeSet<String> = options.iter().map(|o| o.to_lowercase()).collect();
+        if unique.len() != options.len() {
+            return Err("Each option has to be different".to_string());
+        }
+        if self.closes_at.is_some_and(|closes_at| closes_at <= now) {
+            return Err("The deadline has to be in the future".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Poll {
+        Poll {
+            options: self.filled().into_iter().map(|text| PollOption { id: new_id(), text: text.to_string() }).collect(),
+            multiple: self.multiple,
+            anonymous: self.anonymous,
+            closes_at: self.closes_at,
+            ballots: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(id: &str) -> CircleMember {
+        CircleMember { id: id.to_string(), name: id.to_string(), avatar: String::new() }
+    }
+
+    fn poll(multiple: bool, anonymous: bool) -> Poll {
+        Poll {
+            options: ["a", "b"].iter().map(|id| PollOption { id: id.to_string(), text: id.to_string() }).collect(),
+            multiple,
+            anonymous,
+            closes_at: Some(100),
+            ballots: BTreeMap::new(),
+        }
+    }
+
+    fn ballot(choices: &[&str], voter: Option<&str>, millis: u64) -> Ballot {
+        Ballot {
+            choices: choices.iter().map(|c| c.to_string()).collect(),
+            voter: voter.map(member),
+            stamp: Stamp { millis, device: "d".to_string() },
+        }
+    }
+
+    #[test]
+    fn ballots_are_bound_to_their_sender() {
+        let mut open = poll(false, false);
+        assert!(open.receive("p", "ana", "ana".to_string(), ballot(&["a"], Some("ana"), 10), 20).is_ok());
+        assert!(open.receive("p", "ana", "bo".to_string(), ballot(&["a"], Some("bo"), 10), 20).is_err());
+        assert!(open.receive("p", "ana", "ana".to_string(), ballot(&["b"], Some("bo"), 11), 20).is_err());
+        assert_eq!(open.choices_of("p", "ana"), BTreeSet::from(["a".to_string()]));
+        assert!(open.choices_of("p", "bo").is_empty());
+
+        let mut anonymous = poll(false, true);
+        let key = anonymous.voter_key("p", "ana");
+        assert!(anonymous.receive("p", "ana", key.clone(), ballot(&["a"], Some("ana"), 10), 20).is_err());
+        assert!(anonymous.receive("p", "bo", key.clone(), ballot(&["a"], None, 10), 20).is_err());
+        assert!(anonymous.receive("p", "ana", key, ballot(&["a"], None, 10), 20).is_ok());
+        assert_eq!(anonymous.voter_count(), 1);
+    }
+
+    #[test]
+    fn ballots_pick_only_what_the_poll_allows() {
+        let mut single = poll(false, false);
+        assert!(single.receive("p", "ana", "ana".to_string(), ballot(&["z"], Some("ana"), 10), 20).is_err());
+        assert!(single.receive("p", "ana", "ana".to_string(), ballot(&["a", "b"], Some("ana"), 10), 20).is_err());
+        assert_eq!(single.voter_count(), 0);
+
+        let mut multiple = poll(true, false);
+        assert!(multiple.receive("p", "ana", "ana".to_string(), ballot(&["a", "b"], Some("ana"), 10), 20).is_ok());
+        assert_eq!(multiple.results().iter().map(|r| r.votes).collect::<Vec<_>>(), vec![1, 1]);
+    }
+
+    #[test]
+    fn ballots_after_closing_are_ignored() {
+        let mut closed = poll(false, false);
+        // Stamped early but only arriving now
+        assert!(closed.receive("p", "ana", "ana".to_string(), ballot(&["a"], Some("ana"), 10), 100).is_err());
+        assert_eq!(closed.voter_count(), 0);
+        // The sender's clock being ahead doesn't matter while the poll is open
+        assert!(closed.receive("p", "ana", "ana".to_string(), ballot(&["a"], Some("ana"), 150), 20).is_ok());
+        assert_eq!(closed.voter_count(), 1);
+    }
+
+    #[test]
+    fn merged_ballots_are_checked_too() {
+        let mut ours = poll(false, false);
+        let mut theirs = poll(false, false);
+        theirs.ballots.insert("ana".to_string(), ballot(&["a"], Some("ana"), 10));
+        theirs.ballots.insert("bo".to_string(), ballot(&["a"], Some("ana"), 10));
+        theirs.ballots.insert("cy".to_string(), ballot(&["z"], Some("cy"), 10));
+        theirs.ballots.insert("di".to_string(), ballot(&["a", "b"], Some("di"), 10));
+        ours.merge(theirs);
+        assert_eq!(ours.ballots.keys().collect::<Vec<_>>(), ["ana"]);
+
+        let mut anonymous = poll(true, true);
+        let mut other = poll(true, true);
+        let key = other.voter_key("p", "ana");
+        other.ballots.insert(key.clone(), ballot(&["a", "b"], None, 10));
+        other.ballots.insert("bo".to_string(), ballot(&["a"], None, 10));
+        other.ballots.insert(other.voter_key("p", "cy"), ballot(&["a"], Some("cy"), 10));
+        anonymous.merge(other);
+        assert_eq!(anonymous.ballots.keys().collect::<Vec<_>>(), [&key]);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::CircleMember;
+use crate::models::message::{ReactionMark, Tombstone};
+use crate::models::poll::{Poll, PollDraft};
+use crate::services::sync::{Identified, Merge, Stamp};
+use crate::utils::{new_id, now_millis};
+
+pub const MAX_POST_LEN: usize = 5000;
+pub const MAX_COMMENT_LEN: usize = 1000;
+pub const MAX_IMAGES: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: String,
+    pub author: CircleMember,
+    pub body: String,
+    pub posted_at: u64,
+    #[serde(default)]
+    pub deleted: Option<Tombstone>,
+}
+
+impl Comment {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+}
+
+impl Merge for Comment {
+    fn merge(&mut self, other: Self) {
+        self.deleted = earliest(self.deleted.take(), other.deleted);
+        if self.deleted.is_some() {
+            self.body.clear();
+        }
+    }
+}
+
+// A post is shared with one or more circles at once; it shows up in the
+// feed of each of them and is the same post everywhere
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Post {
+    pub id: String,
+    pub author: CircleMember,
+    pub body: String,
+    #[serde(default)]
+    pub images: Vec<String>,
+    // Addresses found in the body, shown as link cards
+    #[serde(default)]
+    pub links: Vec<String>,
+    pub audience: BTreeSet<String>,
+    pub posted_at: u64,
+    // user id -> like
+    #[serde(default)]
+    pub likes: BTreeMap<String, ReactionMark>,
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+    // The body is the poll's question
+    #[serde(default)]
+    pub poll: Option<Poll>,
+    #[serde(default)]
+    pub deleted: Option<Tombstone>,
+}
+
+// What the composer produces
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PostDraft {
+    pub body: String,
+    pub images: Vec<String>,
+    pub audience: BTreeSet<String>,
+    pub poll: Option<PollDraft>,
+}
+
+impl PostDraft {
+    pub fn to(circle_id: &str) -> Self {
+        Self { audience: BTreeSet::from([circle_id.to_string()]), ..Self::default() }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.body.trim().is_empty() && self.images.is_empty() {
+            return Err("Write something or add a picture".to_string());
+        }
+        if self.body.chars().count() > MAX_POST_LEN {
+            return Err(format!("Posts can be at most {} characters", MAX_POST_LEN));
+        }
+        if self.images.len() > MAX_IMAGES {
+            return Err(format!("A post can have at most {} pictures", MAX_IMAGES));
+        }
+        if self.images.iter().any(|url| !is_web_url(url)) {
+            return Err("Pictures need a web address starting with http:// or https://".to_string());
+        }
+        if self.audience.is_empty() {
+            return Err("Choose at least one circle to share with".to_string());
+        }
+        if let Some(poll) = &self.poll {
+            if self.body.trim().is_empty() {
+                return Err("Write the question you're asking".to_string());
+            }
+            poll.validate(now_millis())?;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_circle(&mut self, circle_id: &str) {
+        if !self.audience.remove(circle_id) {
+            self.audience.insert(circle_id.to_string());
+        }
+    }
+}
+
+impl Post {
+    pub fn create(draft: PostDraft, author: CircleMember) -> Self {
+        let body = draft.body.trim().to_string();
+        Self {
+            id: new_id(),
+            author,
+            links: find_links(&body),
+            body,
+            images: draft.images,
+            audience: draft.audience,
+            posted_at: now_millis(),
+            likes: BTreeMap::new(),
+            comments: Vec::new(),
+            poll: draft.poll.map(|poll| poll.build()),
+            deleted: None,
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+
+    pub fn like_count(&self) -> usize {
+        self.likes.values().filter(|l| l.active).count()
+    }
+
+    pub fn liked_by(&self, user_id: &str) -> bool {
+        self.likes.get(user_id).is_some_and(|l| l.active)
+    }
+
+    pub fn toggle_like(&mut self, user_id: &str) {
+        if self.is_deleted() {
+            return;
+        }
+        let stamp = match self.likes.get(user_id) {
+            Some(like) => Stamp::after(&like.stamp),
+            None => Stamp::now(),
+        };
+        let active = !self.liked_by(user_id);
+        self.likes.insert(user_id.to_string(), ReactionMark { active, stamp });
+    }
+
+    // Comments still standing, oldest first
+    pub fn visible_comments(&self) -> Vec<&Comment> {
+        let mut comments: Vec<&Comment> = self.comments.iter().filter(|c| !c.is_deleted()).collect();
+        comments.sort_by_key(|c| c.posted_at);
+        comments
+    }
+
+    pub fn add_comment(&mut self, author: CircleMember, body: &str) -> Result<(), String> {
+        let body = body.trim();
+        if self.is_deleted() {
+            return Err("This post was deleted".to_string());
+        }
+        if body.is_empty() {
+            return Err("Comments can't be empty".to_string());
+        }
+        if body.chars().count() > MAX_COMMENT_LEN {
+            return Err(format!("Comments can be at most {} characters", MAX_COMMENT_LEN));
+        }
+        self.comments.push(Comment {
+            id: new_id(),
+            author,
+            body: body.to_string(),
+            posted_at: now_millis(),
+            deleted: None,
+        });
+        Ok(())
+    }
+
+    // Comments can be deleted by whoever wrote them or by the post's author
+    pub fn delete_comment(&mut self, comment_id: &str, user_id: &str) -> Result<(), String> {
+        let post_author = self.author.id.clone();
+        let comment = self
+            .comments
+            .iter_mut()
+            .find(|c| c.id == comment_id && !c.is_deleted())
+            .ok_or_else(|| "This comment no longer exists".to_string())?;
+        if comment.author.id != user_id && post_author != user_id {
+            return Err("You can only delete your own comments".to_string());
+        }
+        comment.deleted = Some(Tombstone { deleted_by: user_id.to_string(), stamp: Stamp::now() });
+        comment.body.clear();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, user_id: &str) {
+        if self.deleted.is_none() {
+            self.deleted = Some(Tombstone { deleted_by: user_id.to_string(), stamp: Stamp::now() });
+        }
+        self.scrub();
+    }
+
+    fn scrub(&mut self) {
+        self.body.clear();
+        self.images.clear();
+        self.links.clear();
+        self.likes.clear();
+        self.comments.clear();
+        self.poll = None;
+    }
+}
+
+impl Identified for Post {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Merge for Post {
+    fn merge(&mut self, other: Self) {
+        self.audience.extend(other.audience);
+
+        // Each user's like is last-writer-wins
+        for (user, like) in other.likes {
+            match self.likes.get(&user) {
+                Some(existing) if existing.stamp >= like.stamp => {}
+                _ => {
+                    self.likes.insert(user, like);
+                }
+            }
+        }
+
+        for comment in other.comments {
+            match self.comments.iter_mut().find(|c| c.id == comment.id) {
+                Some(existing) => existing.merge(comment),
+                None => self.comments.push(comment),
+            }
+        }
+
+        if let (Some(poll), Some(theirs)) = (self.poll.as_mut(), other.poll) {
+            poll.merge(theirs);
+        }
+
+        self.deleted = earliest(self.deleted.take(), other.deleted);
+        if self.deleted.is_some() {
+            self.scrub();
+        }
+    }
+}
+
+// How far we have read a circle's feed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedCursor {
+    pub circle_id: String,
+    pub last_read_at: u64,
+}
+
+impl Identified for FeedCursor {
+    fn id(&self) -> &str {
+        &self.circle_id
+    }
+}
+
+impl Merge for FeedCursor {
+    fn merge(&mut self, other: Self) {
+        self.last_read_at = self.last_read_at.max(other.last_read_at);
+    }
+}
+
+// The earlier of two deletions, so both sides settle on the same one
+pub fn earliest(a: Option<Tombstone>, b: Option<Tombstone>) -> Option<Tombstone> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.stamp < a.stamp { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+pub fn is_web_url(text: &str) -> bool {
+    (text.starts_with("https://") || text.starts_with("http://")) && text.len() > "https://".len()
+}
+
+// Web addresses in the text, in order and without repeats
+pub fn find_links(text: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+        if is_web_url(word) && !links.iter().any(|l| l == word) {
+            links.push(word.to_string());
+        }
+    }
+    links
+}
@@ -0,0 +1,281 @@
+use std::collections::BTreeSet;
+
+use crate::models::family::{FamilyTree, Gender, Person, RelationKind};
+
+// Checks that the family tree makes sense: nobody their own ancestor, no
+// children born before their parents or after them, no impossible ages,
+// and no links that are doubled up or point at nobody. Vague dates count as
+// wrong only if they would be wrong wherever in their range they fell.
+
+const MAX_AGE: i64 = 120;
+const MIN_PARENT_AGE: i64 = 12;
+const MAX_MOTHER_AGE: i64 = 60;
+const MAX_FATHER_AGE: i64 = 90;
+// How long after his death a father's child can still be born
+const FATHER_GRACE_DAYS: i64 = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Problem {
+    Cycle,
+    BornBeforeParent,
+    BornAfterParentDied,
+    ImplausibleAge,
+    DuplicateLink,
+    Orphaned,
+}
+
+impl Problem {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Problem::Cycle => "Own ancestor",
+            Problem::BornBeforeParent => "Born before a parent",
+            Problem::BornAfterParentDied => "Born after a parent died",
+            Problem::ImplausibleAge => "Unlikely age",
+            Problem::DuplicateLink => "Linked twice",
+            Problem::Orphaned => "Missing relative",
+        }
+    }
+}
+
+// One thing wrong, with the people it is about, first the one to look at
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Issue {
+    pub problem: Problem,
+    pub people: Vec<String>,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn involves(&self, ids: &BTreeSet<String>) -> bool {
+        self.people.iter().any(|id| ids.contains(id))
+    }
+}
+
+// Everything wrong in the tree, including links whose people are all gone
+pub fn check_all(tree: &FamilyTree) -> Vec<Issue> {
+    let everyone: BTreeSet<String> =
+        tree.persons.iter().map(|p| p.id.clone()).chain(tree.links().flat_map(|r| [r.from.clone(), r.to.clone()])).collect();
+    check(tree, &everyone)
+}
+
+// Everything wrong that involves any of `ids`, for rechecking after an edit
+pub fn check(tree: &FamilyTree, ids: &BTreeSet<String>) -> Vec<Issue> {
+    let mut found: BTreeSet<Issue> = BTreeSet::new();
+    for person in ids.iter().filter_map(|id| tree.person(id)) {
+        ages(person, &mut found);
+        cycle(tree, person, &mut found);
+        for (parent, kind) in tree.parents_of(&person.id) {
+            parentage(person, parent, kind, &mut found);
+        }
+        for (child, kind) in tree.children_of(&person.id) {
+            parentage(child, person, kind, &mut found);
+        }
+    }
+    links(tree, ids, &mut found);
+    found.into_iter().collect()
+}
+
+fn ages(person: &Person, found: &mut BTreeSet<Issue>) {
+    let (Some(born), Some(died)) = (person.born(), person.died()) else {
+        return;
+    };
+    let age = born.age_at(&died);
+    let message = if age.most.is_some_and(|most| most < 0) {
+        format!("{} died {}, before being born {}", person.full_name(), died, born)
+    } else if age.least.is_some_and(|least| least > MAX_AGE) {
+        format!("{} would have lived to {}", person.full_name(), age)
+    } else {
+        return;
+    };
+    found.insert(Issue { problem: Problem::ImplausibleAge, people: vec![person.id.clone()], message });
+}
+
+// Everyone on a loop back to `person`, reported once for the whole loop
+fn cycle(tree: &FamilyTree, person: &Person, found: &mut BTreeSet<Issue>) {
+    let ancestors = tree.ancestors_of(&person.id);
+    if !ancestors.contains(&person.id) {
+        return;
+    }
+    let on_loop: Vec<String> = ancestors.intersection(&tree.descendants_of(&person.id)).cloned().collect();
+    let names: Vec<String> = on_loop.iter().filter_map(|id| tree.person(id)).map(|p| p.full_name()).collect();
+    found.insert(Issue {
+        problem: Problem::Cycle,
+        people: on_loop,
+        message: format!("{} are each other's ancestors", names.join(", ")),
+    });
+}
+
+fn parentage(child: &Person, parent: &Person, kind: RelationKind, found: &mut BTreeSet<Issue>) {
+    let Some(born) = child.born() else {
+        return;
+    };
+    let people = vec![child.id.clone(), parent.id.clone()];
+    if let Some(parent_born) = parent.born() {
+        let age = parent_born.age_at(&born);
+        // Step-parents can be any age
+        if kind != RelationKind::Step && age.most.is_some_and(|most| most < 0) {
+            found.insert(Issue {
+                problem: Problem::BornBeforeParent,
+                people: people.clone(),
+                message: format!("{} was born {}, before their parent {} was born {}", child.full_name(), born, parent.full_name(), parent_born),
+            });
+        }
+        let oldest = if parent.gender == Gender::Female { MAX_MOTHER_AGE } else { MAX_FATHER_AGE };
+        let too_young = age.most.is_some_and(|most| (0..MIN_PARENT_AGE).contains(&most));
+        let too_old = age.least.is_some_and(|least| least > oldest);
+        if kind == RelationKind::Parent && (too_young || too_old) {
+            found.insert(Issue {
+                problem: Problem::ImplausibleAge,
+                people: people.clone(),
+                message: format!("{} was {} when {} was born", parent.full_name(), age, child.full_name()),
+            });
+        }
+    }
+    // A father may die before the birth, but not long before
+    if kind == RelationKind::Parent
+        && let Some(died) = parent.died()
+        && let (Some(earliest), Some(last)) = (born.earliest(), died.latest())
+    {
+        let grace = if parent.gender == Gender::Female { 0 } else { FATHER_GRACE_DAYS };
+        if earliest > last + grace {
+            found.insert(Issue {
+                problem: Problem::BornAfterParentDied,
+                people,
+                message: format!("{} was born {}, after their parent {} died {}", child.full_name(), born, parent.full_name(), died),
+            });
+        }
+    }
+}
+
+// Doubled-up links between the same two people, and links to people who
+// aren't in the tree
+fn links(tree: &FamilyTree, ids: &BTreeSet<String>, found: &mut BTreeSet<Issue>) {
+    let mut seen: BTreeSet<(String, String, bool)> = BTreeSet::new();
+    for r in tree.links().filter(|r| ids.contains(&r.from) || ids.contains(&r.to)) {
+        let (from, to) = (tree.person(&r.from), tree.person(&r.to));
+        let (Some(from), Some(to)) = (from, to) else {
+            // With nobody at either end the issue is filed under both ids,
+            // so that a recheck of either can clear it
+            found.insert(match from.or(to) {
+                Some(there) => Issue {
+                    problem: Problem::Orphaned,
+                    people: vec![there.id.clone()],
+                    message: format!("{} is linked to someone who isn't in the tree", there.full_name()),
+                },
+                None => Issue {
+                    problem: Problem::Orphaned,
+                    people: vec![r.from.clone(), r.to.clone()],
+                    message: format!("A link points at two people who aren't in the tree ({})", r.id),
+                },
+            });
+            continue;
+        };
+        let pair = if r.from < r.to { (r.from.clone(), r.to.clone()) } else { (r.to.clone(), r.from.clone()) };
+        let parental = r.kind.is_parental();
+        if !seen.insert((pair.0, pair.1, parental)) {
+            let how = if parental { "as parent and child" } else { "as spouses" };
+            found.insert(Issue {
+                problem: Problem::DuplicateLink,
+                people: vec![from.id.clone(), to.id.clone()],
+                message: format!("{} and {} are linked more than once {}", from.full_name(), to.full_name(), how),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::family::Relationship;
+
+    fn person(id: &str, gender: Gender, born: &str, died: &str) -> Person {
+        Person {
+            id: id.to_string(),
+            given_name: id.to_string(),
+            gender,
+            birth_date: born.to_string(),
+            death_date: died.to_string(),
+            ..Person::default()
+        }
+    }
+
+    fn tree(persons: Vec<Person>, links: &[(RelationKind, &str, &str)]) -> FamilyTree {
+        let relationships = links.iter().map(|(kind, from, to)| Relationship::new(*kind, from, to)).collect();
+        FamilyTree { persons, relationships }
+    }
+
+    fn problems(tree: &FamilyTree) -> Vec<(Problem, Vec<String>)> {
+        check_all(tree).into_iter().map(|issue| (issue.problem, issue.people)).collect()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn a_loop_of_ancestors_is_reported_once() {
+        let persons = vec![person("a", Gender::Male, "", ""), person("b", Gender::Male, "", ""), person("c", Gender::Male, "", "")];
+        let tree = tree(persons, &[(RelationKind::Parent, "a", "b"), (RelationKind::Parent, "b", "c"), (RelationKind::Adoptive, "c", "a")]);
+        assert_eq!(problems(&tree), [(Problem::Cycle, ids(&["a", "b", "c"]))]);
+    }
+
+    #[test]
+    fn children_born_before_a_parent_are_caught() {
+        let persons = vec![person("father", Gender::Male, "1950", ""), person("child", Gender::Female, "1940", "")];
+        assert_eq!(problems(&tree(persons.clone(), &[(RelationKind::Parent, "father", "child")]))[0], (Problem::BornBeforeParent, ids(&["child", "father"])));
+        // Step-parents can be any age, and vague dates that may be right pass
+        assert!(problems(&tree(persons, &[(RelationKind::Step, "father", "child")])).is_empty());
+        let vague = vec![person("father", Gender::Male, "1950", ""), person("child", Gender::Female, "abt 1950", "")];
+        assert_eq!(problems(&tree(vague, &[(RelationKind::Adoptive, "father", "child")])), []);
+    }
+
+    #[test]
+    fn a_father_may_die_shortly_before_the_birth() {
+        let links = [(RelationKind::Parent, "parent", "child")];
+        let case = |gender: Gender, born: &str| {
+            problems(&tree(vec![person("parent", gender, "1920", "1950-01-01"), person("child", Gender::Male, born, "")], &links))
+        };
+        assert!(case(Gender::Male, "1950-06-01").is_empty());
+        assert_eq!(case(Gender::Male, "1951-06-01"), [(Problem::BornAfterParentDied, ids(&["child", "parent"]))]);
+        assert_eq!(case(Gender::Female, "1950-06-01"), [(Problem::BornAfterParentDied, ids(&["child", "parent"]))]);
+    }
+
+    #[test]
+    fn unlikely_ages_are_caught() {
+        let old = person("old", Gender::Male, "1800", "1930");
+        let backwards = person("backwards", Gender::Male, "1900", "1890");
+        let fine = person("fine", Gender::Male, "1800", "1900");
+        assert_eq!(
+            problems(&tree(vec![old, backwards, fine], &[])),
+            [(Problem::ImplausibleAge, ids(&["backwards"])), (Problem::ImplausibleAge, ids(&["old"]))]
+        );
+        let young = vec![person("mother", Gender::Female, "1900", ""), person("child", Gender::Male, "1908", "")];
+        assert_eq!(problems(&tree(young, &[(RelationKind::Parent, "mother", "child")])), [(Problem::ImplausibleAge, ids(&["child", "mother"]))]);
+        let older = vec![person("mother", Gender::Female, "1900", ""), person("child", Gender::Male, "1965", "")];
+        assert_eq!(problems(&tree(older.clone(), &[(RelationKind::Parent, "mother", "child")])).len(), 1);
+        assert!(problems(&tree(older, &[(RelationKind::Adoptive, "mother", "child")])).is_empty());
+    }
+
+    #[test]
+    fn the_same_pair_linked_twice_is_caught() {
+        let persons = vec![person("a", Gender::Male, "", ""), person("b", Gender::Female, "", "")];
+        let doubled = tree(persons.clone(), &[(RelationKind::Parent, "a", "b"), (RelationKind::Adoptive, "a", "b")]);
+        assert_eq!(problems(&doubled), [(Problem::DuplicateLink, ids(&["a", "b"]))]);
+        let spouses = tree(persons.clone(), &[(RelationKind::Spouse, "a", "b"), (RelationKind::Spouse, "b", "a")]);
+        assert_eq!(problems(&spouses), [(Problem::DuplicateLink, ids(&["b", "a"]))]);
+        assert!(problems(&tree(persons, &[(RelationKind::Parent, "a", "b"), (RelationKind::Spouse, "a", "b")])).is_empty());
+    }
+
+    #[test]
+    fn links_to_nobody_are_caught() {
+        let mut gone = person("gone", Gender::Male, "", "");
+        gone.deleted = true;
+        let links = [(RelationKind::Parent, "a", "gone"), (RelationKind::Spouse, "ghost", "gone")];
+        let tree = tree(vec![person("a", Gender::Male, "", ""), gone], &links);
+        assert_eq!(problems(&tree), [(Problem::Orphaned, ids(&["a"])), (Problem::Orphaned, ids(&["ghost", "gone"]))]);
+        // Checking around either end finds the link with nobody at it
+        let around = check(&tree, &BTreeSet::from(["ghost".to_string()]));
+        assert_eq!(around.len(), 1);
+        assert!(around[0].involves(&BTreeSet::from(["gone".to_string()])));
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+
+use crate::models::call::{Call, CallDirection, CallKind, CallOutcome, CallParticipant};
+use crate::models::LOCAL_USER_ID;
+use crate::utils::new_id;
+
+use super::room::{RoomEvent, RoomRequest, RoomServer, RoomUpdate, Roster};
+
+// How long each demo participant talks before the next one takes over
+const DEMO_TURN_MILLIS: u64 = 4_000;
+
+// How room requests reach the forwarding server and its updates come back
+pub trait RoomTransport {
+    fn send(&mut self, request: &RoomRequest, now: u64);
+    fn poll(&mut self, now: u64) -> Vec<RoomUpdate>;
+}
+
+// Runs the room server in-process with a few simulated participants, so
+// group calls work without a backend
+pub struct LocalRoom {
+    server: RoomServer,
+    inbox: VecDeque<RoomUpdate>,
+    members: Vec<CallParticipant>,
+    room_id: Option<String>,
+    next_turn: Option<u64>,
+    turn: usize,
+}
+
+impl LocalRoom {
+    pub fn new(members: Vec<CallParticipant>) -> Self {
+        Self { server: RoomServer::default(), inbox: VecDeque::new(), members, room_id: None, next_turn: None, turn: 0 }
+    }
+
+    fn deliver(&mut self, from: &str, request: RoomRequest, now: u64) {
+        let updates = self.server.handle(from, request, now);
+        self.inbox.extend(updates.into_iter().filter(|(to, _)| to == LOCAL_USER_ID).map(|(_, update)| update));
+    }
+
+    // Members join once we are in, then take turns talking; the second one
+    // raises a hand along the way
+    fn simulate(&mut self, now: u64) {
+        let (Some(room_id), Some(next_turn)) = (self.room_id.clone(), self.next_turn) else {
+            return;
+        };
+        if now < next_turn || self.members.is_empty() {
+            return;
+        }
+        let members = self.members.clone();
+        for (index, member) in members.iter().enumerate() {
+            let level = if index == self.turn % members.len() { 60 } else { 0 };
+            self.deliver(&member.id, RoomRequest::AudioLevel { room_id: room_id.clone(), level }, now);
+        }
+        if self.turn == 1 && members.len() > 1 {
+            self.deliver(&members[1].id, RoomRequest::RaiseHand { room_id: room_id.clone(), raised: true }, now);
+        }
+        self.turn += 1;
+        self.next_turn = Some(now + DEMO_TURN_MILLIS);
+    }
+}
+
+impl RoomTransport for LocalRoom {
+    fn send(&mut self, request: &RoomRequest, now: u64) {
+        let join = match request {
+            RoomRequest::Join { room_id, title, kind, participant } => Some((room_id.clone(), title.clone(), *kind, participant.id.clone())),
+            _ => None,
+        };
+        // The room is the circle's, so its members and we may join
+        if let Some((room_id, _, _, local)) = join.as_ref().filter(|_| self.room_id.is_none()) {
+            let members = self.members.iter().map(|m| m.id.clone()).chain([local.clone()]);
+            self.server.set_members(room_id, members);
+        }
+        self.deliver(LOCAL_USER_ID, request.clone(), now);
+        if let Some((room_id, title, kind, _)) = join.filter(|_| self.room_id.is_none()) {
+            self.room_id = Some(room_id.clone());
+            for member in self.members.clone() {
+                let request = RoomRequest::Join { room_id: room_id.clone(), title: title.clone(), kind, participant: member.clone() };
+                self.deliver(&member.id, request, now);
+            }
+            self.next_turn = Some(now + DEMO_TURN_MILLIS);
+        }
+    }
+
+    fn poll(&mut self, now: u64) -> Vec<RoomUpdate> {
+        self.simulate(now);
+        self.inbox.drain(..).collect()
+    }
+}
+
+// What the group call screen shows
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupCall {
+    pub room_id: String,
+    pub title: String,
+    pub kind: CallKind,
+    // Empty until the server welcomes us
+    pub roster: Option<Roster>,
+    // Something the server told us, such as being muted by the host
+    pub notice: Option<String>,
+    pub started_at: u64,
+    pub ended: bool,
+}
+
+// Our side of a group call: sends requests, applies the server's updates
+// and keeps track of who took part for the call history
+pub struct GroupSession {
+    local: CallParticipant,
+    transport: Option<Box<dyn RoomTransport>>,
+    online: bool,
+    call: Option<GroupCall>,
+    call_id: String,
+    seen: Vec<CallParticipant>,
+}
+
+impl GroupSession {
+    pub fn new(local: CallParticipant, transport: Option<Box<dyn RoomTransport>>) -> Self {
+        Self {
+            local,
+            online: transport.is_some(),
+            transport,
+            call: None,
+            call_id: String::new(),
+            seen: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> Option<&GroupCall> {
+        self.call.as_ref()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.call.as_ref().is_some_and(|c| !c.ended)
+    }
+
+    // `members` stand in for the room when there is no server to join
+    pub fn join(&mut self, room_id: &str, title: &str, kind: CallKind, members: Vec<CallParticipant>, now: u64) {
+        if self.is_active() {
+            return;
+        }
+        if !self.online {
+            self.transport = Some(Box::new(LocalRoom::new(members)));
+        }
+        self.call = Some(GroupCall {
+            room_id: room_id.to_string(),
+            title: title.to_string(),
+            kind,
+            roster: None,
+            notice: None,
+            started_at: now,
+            ended: false,
+        });
+        self.call_id = new_id();
+        self.seen.clear();
+        self.send(
+            RoomRequest::Join {
+                room_id: room_id.to_string(),
+                title: title.to_string(),
+                kind,
+                participant: self.local.clone(),
+            },
+            now,
+        );
+    }
+
+    pub fn leave(&mut self, now: u64) -> Option<Call> {
+        let room_id = self.call.as_ref().filter(|c| !c.ended)?.room_id.clone();
+        self.send(RoomRequest::Leave { room_id }, now);
+        self.finish(None, now)
+    }
+
+    pub fn set_muted(&mut self, muted: bool, now: u64) {
+        self.request(now, |room_id| RoomRequest::SetMuted { room_id, muted });
+    }
+
+    pub fn set_video(&mut self, enabled: bool, now: u64) {
+        self.request(now, |room_id| RoomRequest::SetVideo { room_id, enabled });
+    }
+
+    pub fn raise_hand(&mut self, raised: bool, now: u64) {
+        self.request(now, |room_id| RoomRequest::RaiseHand { room_id, raised });
+    }
+
+    pub fn mute_participant(&mut self, target: &str, now: u64) {
+        let target = target.to_string();
+        self.request(now, |room_id| RoomRequest::MuteParticipant { room_id, target });
+    }
+
+    pub fn remove_participant(&mut self, target: &str, now: u64) {
+        let target = target.to_string();
+        self.request(now, |room_id| RoomRequest::RemoveParticipant { room_id, target });
+    }
+
+    pub fn reinvite(&mut self, target: &str, now: u64) {
+        let target = target.to_string();
+        self.request(now, |room_id| RoomRequest::Reinvite { room_id, target });
+    }
+
+    pub fn dismiss(&mut self) {
+        if self.call.as_ref().is_some_and(|c| c.ended) {
+            self.call = None;
+            if !self.online {
+                self.transport = None;
+            }
+        }
+    }
+
+    // Apply updates from the server. Returns the call for the history if
+    // we were removed from it.
+    pub fn pump(&mut self, now: u64) -> Option<Call> {
+        let updates = self.transport.as_mut().map(|t| t.poll(now)).unwrap_or_default();
+        let mut finished = None;
+        for update in updates {
+            let Some(call) = self.call.as_mut().filter(|c| !c.ended && c.room_id == update.room_id) else {
+                continue;
+            };
+            match &update.event {
+                RoomEvent::Welcome { roster } => call.roster = Some(roster.clone()),
+                RoomEvent::MutedByHost { .. } => call.notice = Some("The host muted you".to_string()),
+                RoomEvent::Denied { reason } => call.notice = Some(reason.clone()),
+                RoomEvent::Removed { .. } => {
+                    finished = self.finish(Some("You were removed from the call".to_string()), now);
+                    continue;
+                }
+                event => {
+                    if let Some(roster) = call.roster.as_mut() {
+                        roster.apply(event);
+                    }
+                }
+            }
+            self.note_participants();
+        }
+        finished
+    }
+
+    fn note_participants(&mut self) {
+        let Some(roster) = self.call.as_ref().and_then(|c| c.roster.as_ref()) else {
+            return;
+        };
+        for entry in &roster.entries {
+            if entry.participant.id != self.local.id && !self.seen.iter().any(|p| p.id == entry.participant.id) {
+                self.seen.push(entry.participant.clone());
+            }
+        }
+    }
+
+    fn request(&mut self, now: u64, request: impl FnOnce(String) -> RoomRequest) {
+        if let Some(room_id) = self.call.as_ref().filter(|c| !c.ended).map(|c| c.room_id.clone()) {
+            self.send(request(room_id), now);
+        }
+    }
+
+    fn send(&mut self, request: RoomRequest, now: u64) {
+        if let Some(transport) = self.transport.as_mut() {
+            transport.send(&request, now);
+        }
+    }
+
+    fn finish(&mut self, notice: Option<String>, now: u64) -> Option<Call> {
+        let call = self.call.as_mut()?;
+        call.ended = true;
+        call.notice = notice;
+        let connected = call.roster.is_some();
+        Some(Call {
+            id: self.call_id.clone(),
+            kind: call.kind,
+            direction: CallDirection::Outgoing,
+            participants: self.seen.clone(),
+            started_at: call.started_at,
+            duration_secs: now.saturating_sub(call.started_at) / 1000,
+            outcome: if connected { CallOutcome::Completed } else { CallOutcome::Missed },
+            seen: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    // Several sessions sharing one in-process server
+    #[derive(Clone, Default)]
+    struct SharedServer {
+        server: Rc<RefCell<RoomServer>>,
+        mailboxes: Rc<RefCell<Vec<(String, RoomUpdate)>>>,
+    }
+
+    struct ClientTransport {
+        id: String,
+        shared: SharedServer,
+    }
+
+    impl RoomTransport for ClientTransport {
+        fn send(&mut self, request: &RoomRequest, now: u64) {
+            let updates = self.shared.server.borrow_mut().handle(&self.id, request.clone(), now);
+            self.shared.mailboxes.borrow_mut().extend(updates);
+        }
+
+        fn poll(&mut self, _now: u64) -> Vec<RoomUpdate> {
+            let mut mailboxes = self.shared.mailboxes.borrow_mut();
+            let (mine, others): (Vec<_>, Vec<_>) = mailboxes.drain(..).partition(|(to, _)| *to == self.id);
+            *mailboxes = others;
+            mine.into_iter().map(|(_, update)| update).collect()
+        }
+    }
+
+    fn participant(id: &str) -> CallParticipant {
+        CallParticipant { id: id.to_string(), name: id.to_string(), avatar: String::new() }
+    }
+
+    // A server for a circle of ana, ben and cal
+    fn circle() -> SharedServer {
+        let shared = SharedServer::default();
+        shared.server.borrow_mut().set_members("room", ["ana", "ben", "cal"].map(str::to_string));
+        shared
+    }
+
+    fn session(id: &str, shared: &SharedServer) -> GroupSession {
+        let transport = ClientTransport { id: id.to_string(), shared: shared.clone() };
+        GroupSession::new(participant(id), Some(Box::new(transport)))
+    }
+
+    fn pump_all(sessions: &mut [&mut GroupSession], now: u64) -> Vec<Call> {
+        sessions.iter_mut().filter_map(|s| s.pump(now)).collect()
+    }
+
+    fn roster_ids(session: &GroupSession) -> Vec<String> {
+        let roster = session.current().and_then(|c| c.roster.as_ref()).unwrap();
+        roster.entries.iter().map(|e| e.participant.id.clone()).collect()
+    }
+
+    #[test]
+    fn sessions_join_mute_and_leave() {
+        let shared = circle();
+        let (mut ana, mut ben, mut cal) = (session("ana", &shared), session("ben", &shared), session("cal", &shared));
+        for (index, s) in [&mut ana, &mut ben, &mut cal].into_iter().enumerate() {
+            s.join("room", "Family", CallKind::Audio, Vec::new(), index as u64 * 1000);
+        }
+        pump_all(&mut [&mut ana, &mut ben, &mut cal], 3000);
+        for s in [&ana, &ben, &cal] {
+            assert_eq!(roster_ids(s), ["ana", "ben", "cal"]);
+        }
+
+        ben.set_muted(true, 4000);
+        ana.mute_participant("cal", 4000);
+        pump_all(&mut [&mut ana, &mut ben, &mut cal], 5000);
+        for s in [&ana, &ben, &cal] {
+            let roster = s.current().unwrap().roster.as_ref().unwrap();
+            assert!(!roster.entry("ana").unwrap().muted);
+            assert!(roster.entry("ben").unwrap().muted);
+            assert!(roster.entry("cal").unwrap().muted);
+        }
+        assert_eq!(cal.current().unwrap().notice.as_deref(), Some("The host muted you"));
+        assert_eq!(ben.current().unwrap().notice, None);
+
+        let call = ben.leave(65_000).unwrap();
+        assert!(ben.current().unwrap().ended);
+        assert_eq!(call.outcome, CallOutcome::Completed);
+        assert_eq!(call.duration_secs, 64);
+        let mut met: Vec<&str> = call.participants.iter().map(|p| p.id.as_str()).collect();
+        met.sort();
+        assert_eq!(met, ["ana", "cal"]);
+
+        pump_all(&mut [&mut ana, &mut cal], 66_000);
+        assert_eq!(roster_ids(&ana), ["ana", "cal"]);
+        assert_eq!(roster_ids(&cal), ["ana", "cal"]);
+        // Requests after leaving go nowhere
+        ben.set_muted(false, 67_000);
+        assert!(shared.mailboxes.borrow().is_empty());
+    }
+
+    #[test]
+    fn removed_by_the_host() {
+        let shared = circle();
+        let (mut ana, mut ben) = (session("ana", &shared), session("ben", &shared));
+        ana.join("room", "Family", CallKind::Video, Vec::new(), 0);
+        ben.join("room", "Family", CallKind::Video, Vec::new(), 0);
+        pump_all(&mut [&mut ana, &mut ben], 10);
+
+        ben.remove_participant("ana", 20);
+        pump_all(&mut [&mut ana, &mut ben], 30);
+        assert_eq!(ben.current().unwrap().notice.as_deref(), Some("Only the host can remove people"));
+
+        ana.remove_participant("ben", 40);
+        let finished = pump_all(&mut [&mut ana, &mut ben], 50);
+        assert_eq!(finished.len(), 1);
+        assert!(ben.current().unwrap().ended);
+        assert_eq!(ben.current().unwrap().notice.as_deref(), Some("You were removed from the call"));
+        assert_eq!(roster_ids(&ana), ["ana"]);
+    }
+
+    #[test]
+    fn offline_room_simulates_members() {
+        let mut local = GroupSession::new(participant(LOCAL_USER_ID), None);
+        let members = vec![participant("ana"), participant("ben")];
+        local.join("room", "Family", CallKind::Audio, members, 0);
+        local.pump(0);
+        assert_eq!(roster_ids(&local), [LOCAL_USER_ID, "ana", "ben"]);
+        assert!(local.current().unwrap().roster.as_ref().unwrap().is_host(LOCAL_USER_ID));
+
+        // Members take turns talking once the first turn comes round
+        local.pump(DEMO_TURN_MILLIS);
+        let roster = local.current().unwrap().roster.clone().unwrap();
+        assert_eq!(roster.active_speaker.as_deref(), Some("ana"));
+        local.pump(2 * DEMO_TURN_MILLIS);
+        let roster = local.current().unwrap().roster.clone().unwrap();
+        assert_eq!(roster.active_speaker.as_deref(), Some("ben"));
+        assert_eq!(roster.raised_hands().len(), 1);
+
+        let call = local.leave(3 * DEMO_TURN_MILLIS).unwrap();
+        assert_eq!(call.participants.len(), 2);
+        local.dismiss();
+        assert!(local.current().is_none());
+    }
+}
@@ -0,0 +1,194 @@
+// Calling: the signaling state machine, how its messages travel and the
+// session that ties them to the UI
+use dioxus::prelude::*;
+
+pub mod group;
+pub mod room;
+pub mod session;
+pub mod signaling;
+pub mod transport;
+
+use crate::models::call::{Call, CallKind, CallParticipant};
+use crate::models::{LOCAL_USER_AVATAR, LOCAL_USER_ID, LOCAL_USER_NAME};
+use crate::services::repositories::{use_call_repo, CallRepository};
+use crate::utils::{now_millis, sleep};
+use group::{GroupCall, GroupSession, RoomTransport};
+use session::CallSession;
+use signaling::ActiveCall;
+use transport::Transport;
+
+// How often received signaling is processed and timeouts are checked
+const PUMP_INTERVAL_MILLIS: u32 = 250;
+
+// Shared handle for placing and answering calls. The session itself is not
+// reactive; only the visible call state is, and it only changes when the
+// call does.
+#[derive(Clone, Copy)]
+pub struct CallController {
+    session: CopyValue<CallSession>,
+    active: Signal<Option<ActiveCall>>,
+    group: CopyValue<GroupSession>,
+    group_call: Signal<Option<GroupCall>>,
+    history: Signal<CallRepository>,
+}
+
+impl CallController {
+    pub fn active(&self) -> Option<ActiveCall> {
+        self.active.read().clone()
+    }
+
+    pub fn group_call(&self) -> Option<GroupCall> {
+        self.group_call.read().clone()
+    }
+
+    // Only one call at a time, 1:1 or group
+    fn is_busy(&self) -> bool {
+        self.session.read().current().is_some_and(|c| !c.is_over()) || self.group.read().is_active()
+    }
+
+    pub fn dial(&mut self, peer: CallParticipant, kind: CallKind) {
+        if self.is_busy() {
+            return;
+        }
+        let finished = self.session.write().dial(peer, kind, now_millis());
+        self.settle(finished);
+    }
+
+    pub fn accept(&mut self) {
+        let finished = self.session.write().accept(now_millis());
+        self.settle(finished);
+    }
+
+    pub fn decline(&mut self) {
+        let finished = self.session.write().decline(now_millis());
+        self.settle(finished);
+    }
+
+    pub fn hang_up(&mut self) {
+        let finished = self.session.write().hang_up(now_millis());
+        self.settle(finished);
+    }
+
+    pub fn dismiss(&mut self) {
+        self.session.write().dismiss();
+        self.settle(Vec::new());
+    }
+
+    // Join a circle's group call; `members` are who to expect when offline
+    pub fn join_room(&mut self, room_id: &str, title: &str, kind: CallKind, members: Vec<CallParticipant>) {
+        if self.is_busy() {
+            return;
+        }
+        self.group.write().join(room_id, title, kind, members, now_millis());
+        self.settle(Vec::new());
+    }
+
+    pub fn leave_room(&mut self) {
+        let finished = self.group.write().leave(now_millis());
+        self.settle(finished.into_iter().collect());
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.group.write().set_muted(muted, now_millis());
+    }
+
+    pub fn set_video(&mut self, enabled: bool) {
+        self.group.write().set_video(enabled, now_millis());
+    }
+
+    pub fn raise_hand(&mut self, raised: bool) {
+        self.group.write().raise_hand(raised, now_millis());
+    }
+
+    pub fn mute_participant(&mut self, id: &str) {
+        self.group.write().mute_participant(id, now_millis());
+    }
+
+    pub fn remove_participant(&mut self, id: &str) {
+        self.group.write().remove_participant(id, now_millis());
+    }
+
+    pub fn reinvite(&mut self, id: &str) {
+        self.group.write().reinvite(id, now_millis());
+    }
+
+    pub fn dismiss_room(&mut self) {
+        self.group.write().dismiss();
+        self.settle(Vec::new());
+    }
+
+    fn pump(&mut self) {
+        let now = now_millis();
+        let mut finished = self.session.write().pump(now);
+        finished.extend(self.group.write().pump(now));
+        self.settle(finished);
+    }
+
+    // Record finished calls and publish the call state if it moved
+    fn settle(&mut self, finished: Vec<Call>) {
+        for call in finished {
+            self.history.write().record(call);
+        }
+        let current = self.session.read().current().cloned();
+        if *self.active.peek() != current {
+            self.active.set(current);
+        }
+        let group_call = self.group.read().current().cloned();
+        if *self.group_call.peek() != group_call {
+            self.group_call.set(group_call);
+        }
+    }
+}
+
+pub fn local_participant() -> CallParticipant {
+    CallParticipant {
+        id: LOCAL_USER_ID.to_string(),
+        name: LOCAL_USER_NAME.to_string(),
+        avatar: LOCAL_USER_AVATAR.to_string(),
+    }
+}
+
+// Signaling goes over the realtime socket when one is configured
+fn call_transport() -> Option<Box<dyn Transport>> {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        crate::services::realtime::shared(LOCAL_USER_ID)
+            .map(|socket| Box::new(transport::SocketTransport(socket)) as Box<dyn Transport>)
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    None
+}
+
+fn room_transport() -> Option<Box<dyn RoomTransport>> {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        crate::services::realtime::shared(LOCAL_USER_ID)
+            .map(|socket| Box::new(transport::SocketTransport(socket)) as Box<dyn RoomTransport>)
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    None
+}
+
+// Start the call session for the whole app; needs the repositories first
+pub fn use_calls_provider() {
+    let history = use_call_repo();
+    let mut controller = use_context_provider(|| CallController {
+        session: CopyValue::new(CallSession::new(call_transport())),
+        active: Signal::new(None),
+        group: CopyValue::new(GroupSession::new(local_participant(), room_transport())),
+        group_call: Signal::new(None),
+        history,
+    });
+    use_future(move || async move {
+        loop {
+            sleep(PUMP_INTERVAL_MILLIS).await;
+            controller.pump();
+        }
+    });
+}
+
+pub fn use_calls() -> CallController {
+    use_context()
+}
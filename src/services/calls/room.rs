@@ -0,0 +1,606 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::call::{CallKind, CallParticipant};
+
+// Multi-party calls. A forwarding server (SFU) owns each room: clients send
+// it requests and keep their roster in step with the updates it broadcasts.
+// `RoomServer` is that server's room logic, used directly when offline.
+// Only members of the room's circle may join, and someone the host removed
+// stays out until the host invites them back.
+
+// Audio level (0-100) from which someone counts as speaking
+pub const SPEAKING_LEVEL: u8 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoomRole {
+    Host,
+    Member,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RosterEntry {
+    pub participant: CallParticipant,
+    pub role: RoomRole,
+    pub muted: bool,
+    pub video: bool,
+    // When the hand went up, so hands are taken in order
+    pub hand_raised_at: Option<u64>,
+    pub joined_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Roster {
+    pub room_id: String,
+    pub title: String,
+    pub kind: CallKind,
+    // In joining order
+    pub entries: Vec<RosterEntry>,
+    pub active_speaker: Option<String>,
+    // Removed by the host and kept out until invited back
+    #[serde(default)]
+    pub removed: Vec<CallParticipant>,
+}
+
+impl Roster {
+    pub fn entry(&self, id: &str) -> Option<&RosterEntry> {
+        self.entries.iter().find(|e| e.participant.id == id)
+    }
+
+    pub fn is_host(&self, id: &str) -> bool {
+        self.entry(id).is_some_and(|e| e.role == RoomRole::Host)
+    }
+
+    pub fn is_removed(&self, id: &str) -> bool {
+        self.removed.iter().any(|p| p.id == id)
+    }
+
+    pub fn raised_hands(&self) -> Vec<&RosterEntry> {
+        let mut raised: Vec<&RosterEntry> = self.entries.iter().filter(|e| e.hand_raised_at.is_some()).collect();
+        raised.sort_by_key(|e| e.hand_raised_at);
+        raised
+    }
+
+    fn upsert(&mut self, entry: RosterEntry) {
+        match self.entries.iter_mut().find(|e| e.participant.id == entry.participant.id) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    fn remove(&mut self, id: &str) -> Option<RosterEntry> {
+        let index = self.entries.iter().position(|e| e.participant.id == id)?;
+        if self.active_speaker.as_deref() == Some(id) {
+            self.active_speaker = None;
+        }
+        Some(self.entries.remove(index))
+    }
+
+    // Bring a client's copy up to date with a broadcast event
+    pub fn apply(&mut self, event: &RoomEvent) {
+        match event {
+            RoomEvent::Joined { entry } | RoomEvent::Updated { entry } => self.upsert(entry.clone()),
+            RoomEvent::Left { id } => {
+                self.remove(id);
+            }
+            RoomEvent::ActiveSpeaker { id } => self.active_speaker = id.clone(),
+            RoomEvent::Barred { participant } => {
+                if !self.is_removed(&participant.id) {
+                    self.removed.push(participant.clone());
+                }
+            }
+            RoomEvent::Reinvited { id } => self.removed.retain(|p| &p.id != id),
+            RoomEvent::Welcome { roster } => *self = roster.clone(),
+            RoomEvent::MutedByHost { .. } | RoomEvent::Removed { .. } | RoomEvent::Denied { .. } => {}
+        }
+    }
+}
+
+// Sent by clients to the server
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomRequest {
+    Join { room_id: String, title: String, kind: CallKind, participant: CallParticipant },
+    Leave { room_id: String },
+    SetMuted { room_id: String, muted: bool },
+    SetVideo { room_id: String, enabled: bool },
+    RaiseHand { room_id: String, raised: bool },
+    // Reported by the media layer a few times a second
+    AudioLevel { room_id: String, level: u8 },
+    // Host only
+    MuteParticipant { room_id: String, target: String },
+    RemoveParticipant { room_id: String, target: String },
+    // Let someone who was removed join again
+    Reinvite { room_id: String, target: String },
+}
+
+impl RoomRequest {
+    pub fn room_id(&self) -> &str {
+        match self {
+            RoomRequest::Join { room_id, .. }
+            | RoomRequest::Leave { room_id }
+            | RoomRequest::SetMuted { room_id, .. }
+            | RoomRequest::SetVideo { room_id, .. }
+            | RoomRequest::RaiseHand { room_id, .. }
+            | RoomRequest::AudioLevel { room_id, .. }
+            | RoomRequest::MuteParticipant { room_id, .. }
+            | RoomRequest::RemoveParticipant { room_id, .. }
+            | RoomRequest::Reinvite { room_id, .. } => room_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomEvent {
+    // The whole room, sent to someone who just joined
+    Welcome { roster: Roster },
+    Joined { entry: RosterEntry },
+    Updated { entry: RosterEntry },
+    Left { id: String },
+    ActiveSpeaker { id: Option<String> },
+    Barred { participant: CallParticipant },
+    Reinvited { id: String },
+    // Only sent to the person affected
+    MutedByHost { by: String },
+    Removed { by: String },
+    Denied { reason: String },
+}
+
+// Sent by the server to clients
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomUpdate {
+    pub room_id: String,
+    pub event: RoomEvent,
+}
+
+type Outbox = Vec<(String, RoomUpdate)>;
+
+#[derive(Default)]
+pub struct RoomServer {
+    rooms: HashMap<String, Roster>,
+    // Latest audio level per room and participant
+    levels: HashMap<String, HashMap<String, u8>>,
+    // Who may join each room: the members of its circle
+    members: HashMap<String, BTreeSet<String>>,
+}
+
+impl RoomServer {
+    // Who belongs to the circle behind `room_id`, kept up to date by whoever
+    // runs the server
+    pub fn set_members(&mut self, room_id: &str, members: impl IntoIterator<Item = String>) {
+        self.members.insert(room_id.to_string(), members.into_iter().collect());
+    }
+
+    fn is_member(&self, room_id: &str, id: &str) -> bool {
+        self.members.get(room_id).is_some_and(|members| members.contains(id))
+    }
+
+    // Apply a request from `from` and return the updates to deliver, each
+    // with its recipient
+    pub fn handle(&mut self, from: &str, request: RoomRequest, now: u64) -> Vec<(String, RoomUpdate)> {
+        let room_id = request.room_id().to_string();
+        let mut out = Outbox::new();
+
+        if let RoomRequest::Join { title, kind, participant, .. } = request {
+            if participant.id != from {
+                deny(&mut out, from, &room_id, "Cannot join as someone else");
+            } else if !self.is_member(&room_id, from) {
+                deny(&mut out, from, &room_id, "Only members of this circle can join");
+            } else if self.rooms.get(&room_id).is_some_and(|r| r.is_removed(from)) {
+                deny(&mut out, from, &room_id, "The host removed you from this call");
+            } else {
+                self.join(&room_id, title, kind, participant, now, &mut out);
+            }
+            return out;
+        }
+
+        let Some(roster) = self.rooms.get_mut(&room_id) else {
+            deny(&mut out, from, &room_id, "No such call");
+            return out;
+        };
+        let Some(sender) = roster.entry(from).cloned() else {
+            deny(&mut out, from, &room_id, "Not in this call");
+            return out;
+        };
+        let is_host = sender.role == RoomRole::Host;
+
+        match request {
+            RoomRequest::Join { .. } => {}
+            RoomRequest::Leave { .. } => {
+                self.leave(&room_id, from, &mut out);
+                return out;
+            }
+            RoomRequest::SetMuted { muted, .. } => {
+                update(roster, from, |e| e.muted = muted, &mut out);
+            }
+            RoomRequest::SetVideo { enabled, .. } => {
+                update(roster, from, |e| e.video = enabled, &mut out);
+            }
+            RoomRequest::RaiseHand { raised, .. } => {
+                let at = if raised { Some(sender.hand_raised_at.unwrap_or(now)) } else { None };
+                update(roster, from, |e| e.hand_raised_at = at, &mut out);
+            }
+            RoomRequest::AudioLevel { level, .. } => {
+                self.levels.entry(room_id.clone()).or_default().insert(from.to_string(), level);
+            }
+            RoomRequest::MuteParticipant { target, .. } => {
+                if !is_host {
+                    deny(&mut out, from, &room_id, "Only the host can mute others");
+                } else if roster.entry(&target).is_some_and(|e| !e.muted) {
+                    update(roster, &target, |e| e.muted = true, &mut out);
+                    out.push((target, RoomUpdate { room_id: room_id.clone(), event: RoomEvent::MutedByHost { by: from.to_string() } }));
+                }
+            }
+            RoomRequest::RemoveParticipant { target, .. } => {
+                if !is_host {
+                    deny(&mut out, from, &room_id, "Only the host can remove people");
+                } else if target != from && let Some(gone) = roster.entry(&target).map(|e| e.participant.clone()) {
+                    out.push((target.clone(), RoomUpdate { room_id: room_id.clone(), event: RoomEvent::Removed { by: from.to_string() } }));
+                    self.leave(&room_id, &target, &mut out);
+                    if let Some(roster) = self.rooms.get_mut(&room_id) {
+                        broadcast(roster, None, RoomEvent::Barred { participant: gone.clone() }, &mut out);
+                        roster.removed.push(gone);
+                    }
+                    return out;
+                }
+            }
+            RoomRequest::Reinvite { target, .. } => {
+                if !is_host {
+                    deny(&mut out, from, &room_id, "Only the host can invite people back");
+                } else if roster.is_removed(&target) {
+                    roster.removed.retain(|p| p.id != target);
+                    broadcast(roster, None, RoomEvent::Reinvited { id: target.clone() }, &mut out);
+                    out.push((target.clone(), RoomUpdate { room_id: room_id.clone(), event: RoomEvent::Reinvited { id: target } }));
+                }
+            }
+        }
+
+        self.pick_speaker(&room_id, &mut out);
+        out
+    }
+
+    fn join(&mut self, room_id: &str, title: String, kind: CallKind, participant: CallParticipant, now: u64, out: &mut Outbox) {
+        let roster = self.rooms.entry(room_id.to_string()).or_insert_with(|| Roster {
+            room_id: room_id.to_string(),
+            title,
+            kind,
+            entries: Vec::new(),
+            active_speaker: None,
+            removed: Vec::new(),
+        });
+        let id = participant.id.clone();
+        if roster.entry(&id).is_none() {
+            let entry = RosterEntry {
+                participant,
+                // Whoever opens the room runs it
+                role: if roster.entries.is_empty() { RoomRole::Host } else { RoomRole::Member },
+                muted: false,
+                video: kind == CallKind::Video,
+                hand_raised_at: None,
+                joined_at: now,
+            };
+            broadcast(roster, Some(&id), RoomEvent::Joined { entry: entry.clone() }, out);
+            roster.entries.push(entry);
+        }
+        out.push((id, RoomUpdate { room_id: room_id.to_string(), event: RoomEvent::Welcome { roster: roster.clone() } }));
+    }
+
+    fn leave(&mut self, room_id: &str, id: &str, out: &mut Outbox) {
+        let Some(roster) = self.rooms.get_mut(room_id) else {
+            return;
+        };
+        let Some(gone) = roster.remove(id) else {
+            return;
+        };
+        if let Some(levels) = self.levels.get_mut(room_id) {
+            levels.remove(id);
+        }
+        if roster.entries.is_empty() {
+            self.rooms.remove(room_id);
+            self.levels.remove(room_id);
+            return;
+        }
+        broadcast(roster, None, RoomEvent::Left { id: id.to_string() }, out);
+        // Hand the room to whoever has been there longest
+        if gone.role == RoomRole::Host {
+            let next = roster.entries.iter().min_by_key(|e| e.joined_at).map(|e| e.participant.id.clone());
+            if let Some(next) = next {
+                update(roster, &next, |e| e.role = RoomRole::Host, out);
+            }
+        }
+        self.pick_speaker(room_id, out);
+    }
+
+    // The loudest unmuted speaker becomes active; the last one stays active
+    // through silence until someone else speaks
+    fn pick_speaker(&mut self, room_id: &str, out: &mut Outbox) {
+        let Some(roster) = self.rooms.get_mut(room_id) else {
+            return;
+        };
+        let levels = self.levels.get(room_id);
+        let loudest = roster
+            .entries
+            .iter()
+            .filter(|e| !e.muted)
+            .filter_map(|e| {
+                let level = levels.and_then(|l| l.get(&e.participant.id)).copied().unwrap_or(0);
+                (level >= SPEAKING_LEVEL).then_some((level, &e.participant.id))
+            })
+            .max_by_key(|(level, _)| *level)
+            .map(|(_, id)| id.clone());
+        let current_ok = roster
+            .active_speaker
+            .as_deref()
+            .is_some_and(|id| roster.entry(id).is_some_and(|e| !e.muted));
+        let next = match loudest {
+            Some(id) => Some(id),
+            None if current_ok => roster.active_speaker.clone(),
+            None => None,
+        };
+        if next != roster.active_speaker {
+            roster.active_speaker = next.clone();
+            broadcast(roster, None, RoomEvent::ActiveSpeaker { id: next }, out);
+        }
+    }
+}
+
+fn deny(out: &mut Outbox, to: &str, room_id: &str, reason: &str) {
+    out.push((to.to_string(), RoomUpdate { room_id: room_id.to_string(), event: RoomEvent::Denied { reason: reason.to_string() } }));
+}
+
+fn broadcast(roster: &Roster, except: Option<&str>, event: RoomEvent, out: &mut Outbox) {
+    for entry in roster.entries.iter().filter(|e| Some(e.participant.id.as_str()) != except) {
+        out.push((entry.participant.id.clone(), RoomUpdate { room_id: roster.room_id.clone(), event: event.clone() }));
+    }
+}
+
+fn update(roster: &mut Roster, id: &str, change: impl FnOnce(&mut RosterEntry), out: &mut Outbox) {
+    let Some(entry) = roster.entries.iter_mut().find(|e| e.participant.id == id) else {
+        return;
+    };
+    change(entry);
+    let entry = entry.clone();
+    broadcast(roster, None, RoomEvent::Updated { entry }, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOM: &str = "family";
+
+    fn participant(id: &str) -> CallParticipant {
+        CallParticipant { id: id.to_string(), name: id.to_string(), avatar: String::new() }
+    }
+
+    // A server plus each client's copy of the roster, built only from the
+    // updates the server sent them
+    #[derive(Default)]
+    struct Room {
+        server: RoomServer,
+        clients: HashMap<String, Roster>,
+        inbox: Vec<(String, RoomEvent)>,
+    }
+
+    impl Room {
+        // A room for the circle of ana, ben and cal
+        fn circle() -> Self {
+            let mut room = Room::default();
+            room.server.set_members(ROOM, ["ana", "ben", "cal"].map(str::to_string));
+            room
+        }
+
+        fn send(&mut self, from: &str, request: RoomRequest, now: u64) {
+            for (to, update) in self.server.handle(from, request, now) {
+                assert_eq!(update.room_id, ROOM);
+                if let RoomEvent::Welcome { roster } = &update.event {
+                    self.clients.insert(to.clone(), roster.clone());
+                } else if let Some(roster) = self.clients.get_mut(&to) {
+                    roster.apply(&update.event);
+                }
+                self.inbox.push((to, update.event));
+            }
+        }
+
+        fn join(&mut self, id: &str, now: u64) {
+            let request = RoomRequest::Join {
+                room_id: ROOM.to_string(),
+                title: "Family".to_string(),
+                kind: CallKind::Video,
+                participant: participant(id),
+            };
+            self.send(id, request, now);
+        }
+
+        fn server_roster(&self) -> Option<&Roster> {
+            self.server.rooms.get(ROOM)
+        }
+
+        // Every client still in the room sees what the server sees
+        fn assert_in_step(&self) {
+            let roster = self.server_roster().unwrap();
+            for entry in &roster.entries {
+                assert_eq!(self.clients.get(&entry.participant.id), Some(roster));
+            }
+        }
+
+        fn denied(&self, id: &str) -> Vec<String> {
+            self.inbox
+                .iter()
+                .filter_map(|(to, event)| match event {
+                    RoomEvent::Denied { reason } if to == id => Some(reason.clone()),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    fn ids(roster: &Roster) -> Vec<&str> {
+        roster.entries.iter().map(|e| e.participant.id.as_str()).collect()
+    }
+
+    #[test]
+    fn joining_and_leaving() {
+        let mut room = Room::circle();
+        room.join("ana", 0);
+        room.join("ben", 10);
+        room.join("cal", 20);
+        // Joining twice changes nothing
+        room.join("ben", 30);
+        room.assert_in_step();
+        let roster = room.server_roster().unwrap();
+        assert_eq!(ids(roster), ["ana", "ben", "cal"]);
+        assert!(roster.is_host("ana"));
+        assert!(!roster.is_host("ben"));
+
+        room.send("ben", RoomRequest::Leave { room_id: ROOM.to_string() }, 40);
+        room.assert_in_step();
+        assert_eq!(ids(room.server_roster().unwrap()), ["ana", "cal"]);
+
+        // The host leaving hands the room to the longest-standing member
+        room.send("ana", RoomRequest::Leave { room_id: ROOM.to_string() }, 50);
+        room.assert_in_step();
+        assert!(room.server_roster().unwrap().is_host("cal"));
+
+        room.send("cal", RoomRequest::Leave { room_id: ROOM.to_string() }, 60);
+        assert!(room.server_roster().is_none());
+    }
+
+    #[test]
+    fn muting_and_hands() {
+        let mut room = Room::circle();
+        for (index, id) in ["ana", "ben", "cal"].into_iter().enumerate() {
+            room.join(id, index as u64);
+        }
+        room.send("ben", RoomRequest::SetMuted { room_id: ROOM.to_string(), muted: true }, 10);
+        room.send("cal", RoomRequest::RaiseHand { room_id: ROOM.to_string(), raised: true }, 20);
+        room.send("ben", RoomRequest::RaiseHand { room_id: ROOM.to_string(), raised: true }, 30);
+        // Raising again keeps the place in the queue
+        room.send("cal", RoomRequest::RaiseHand { room_id: ROOM.to_string(), raised: true }, 40);
+        room.assert_in_step();
+
+        let roster = room.server_roster().unwrap();
+        assert!(roster.entry("ben").unwrap().muted);
+        assert!(!roster.entry("cal").unwrap().muted);
+        let hands: Vec<&str> = roster.raised_hands().iter().map(|e| e.participant.id.as_str()).collect();
+        assert_eq!(hands, ["cal", "ben"]);
+
+        // Only the host may mute someone else, and only they are told
+        room.send("ben", RoomRequest::MuteParticipant { room_id: ROOM.to_string(), target: "cal".to_string() }, 50);
+        assert_eq!(room.denied("ben"), ["Only the host can mute others"]);
+        assert!(!room.server_roster().unwrap().entry("cal").unwrap().muted);
+        room.send("ana", RoomRequest::MuteParticipant { room_id: ROOM.to_string(), target: "cal".to_string() }, 60);
+        room.assert_in_step();
+        assert!(room.server_roster().unwrap().entry("cal").unwrap().muted);
+        let told: Vec<&str> = room
+            .inbox
+            .iter()
+            .filter(|(_, event)| matches!(event, RoomEvent::MutedByHost { .. }))
+            .map(|(to, _)| to.as_str())
+            .collect();
+        assert_eq!(told, ["cal"]);
+    }
+
+    #[test]
+    fn removing_participants() {
+        let mut room = Room::circle();
+        room.join("ana", 0);
+        room.join("ben", 1);
+        room.join("cal", 2);
+        room.send("ben", RoomRequest::RemoveParticipant { room_id: ROOM.to_string(), target: "cal".to_string() }, 10);
+        assert_eq!(room.denied("ben"), ["Only the host can remove people"]);
+
+        room.send("ana", RoomRequest::RemoveParticipant { room_id: ROOM.to_string(), target: "cal".to_string() }, 20);
+        room.assert_in_step();
+        assert_eq!(ids(room.server_roster().unwrap()), ["ana", "ben"]);
+        assert!(room.inbox.iter().any(|(to, event)| to == "cal" && matches!(event, RoomEvent::Removed { .. })));
+
+        // Someone who is not in the room cannot act in it
+        room.send("cal", RoomRequest::SetMuted { room_id: ROOM.to_string(), muted: true }, 30);
+        assert_eq!(room.denied("cal"), ["Not in this call"]);
+    }
+
+    #[test]
+    fn only_circle_members_join() {
+        let mut room = Room::circle();
+        room.join("ana", 0);
+        room.join("dan", 10);
+        assert_eq!(room.denied("dan"), ["Only members of this circle can join"]);
+        assert_eq!(ids(room.server_roster().unwrap()), ["ana"]);
+
+        // A room nobody has said the members of takes no one
+        let mut unknown = Room::default();
+        unknown.join("ana", 0);
+        assert_eq!(unknown.denied("ana"), ["Only members of this circle can join"]);
+        assert!(unknown.server_roster().is_none());
+    }
+
+    #[test]
+    fn removed_people_stay_out_until_invited_back() {
+        let mut room = Room::circle();
+        room.join("ana", 0);
+        room.join("ben", 1);
+        room.join("cal", 2);
+        room.send("ana", RoomRequest::RemoveParticipant { room_id: ROOM.to_string(), target: "cal".to_string() }, 10);
+        room.assert_in_step();
+        assert!(room.server_roster().unwrap().is_removed("cal"));
+
+        room.join("cal", 20);
+        assert_eq!(room.denied("cal"), ["The host removed you from this call"]);
+        assert_eq!(ids(room.server_roster().unwrap()), ["ana", "ben"]);
+
+        let reinvite = |target: &str| RoomRequest::Reinvite { room_id: ROOM.to_string(), target: target.to_string() };
+        room.send("ben", reinvite("cal"), 30);
+        assert_eq!(room.denied("ben"), ["Only the host can invite people back"]);
+        room.send("ana", reinvite("cal"), 40);
+        room.assert_in_step();
+        assert!(room.inbox.iter().any(|(to, event)| to == "cal" && matches!(event, RoomEvent::Reinvited { .. })));
+        room.join("cal", 50);
+        room.assert_in_step();
+        assert_eq!(ids(room.server_roster().unwrap()), ["ana", "ben", "cal"]);
+        assert!(room.server_roster().unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn impostors_and_unknown_rooms_are_denied() {
+        let mut room = Room::circle();
+        let request = RoomRequest::Join {
+            room_id: ROOM.to_string(),
+            title: "Family".to_string(),
+            kind: CallKind::Audio,
+            participant: participant("ana"),
+        };
+        room.send("mallory", request, 0);
+        assert_eq!(room.denied("mallory"), ["Cannot join as someone else"]);
+        assert!(room.server_roster().is_none());
+
+        room.send("ana", RoomRequest::Leave { room_id: ROOM.to_string() }, 10);
+        assert_eq!(room.denied("ana"), ["No such call"]);
+    }
+
+    #[test]
+    fn active_speaker_follows_the_loudest_unmuted_voice() {
+        let mut room = Room::circle();
+        room.join("ana", 0);
+        room.join("ben", 1);
+        let level = |level| RoomRequest::AudioLevel { room_id: ROOM.to_string(), level };
+
+        room.send("ben", level(SPEAKING_LEVEL - 1), 10);
+        assert_eq!(room.server_roster().unwrap().active_speaker, None);
+        room.send("ben", level(70), 20);
+        room.send("ana", level(40), 30);
+        room.assert_in_step();
+        assert_eq!(room.server_roster().unwrap().active_speaker.as_deref(), Some("ben"));
+
+        // Silence keeps the last speaker; muting hands over to the next voice
+        room.send("ben", level(0), 40);
+        assert_eq!(room.server_roster().unwrap().active_speaker.as_deref(), Some("ana"));
+        room.send("ana", level(0), 50);
+        assert_eq!(room.server_roster().unwrap().active_speaker.as_deref(), Some("ana"));
+        room.send("ana", RoomRequest::SetMuted { room_id: ROOM.to_string(), muted: true }, 60);
+        room.assert_in_step();
+        assert_eq!(room.server_roster().unwrap().active_speaker, None);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use crate::models::call::{Call, CallKind, CallParticipant};
+use crate::models::LOCAL_USER_ID;
+
+use super::signaling::{Action, ActiveCall, CallMachine, CallPhase};
+use super::local_participant;
+use super::transport::{LoopbackTransport, Transport};
+
+// How long the demo contact lets the phone ring before picking up
+const DEMO_ANSWER_DELAY_MILLIS: u64 = 3_000;
+
+// Drives the signaling machine: runs its actions against a transport and a
+// stand-in media layer. Without a realtime connection every call goes to a
+// simulated contact over a loopback transport so the flow can be tried out.
+pub struct CallSession {
+    machine: CallMachine,
+    transport: Option<Box<dyn Transport>>,
+    // Set when there is no realtime connection
+    demo_peer: Option<DemoPeer>,
+    online: bool,
+}
+
+// The far end of an offline call, answering automatically
+struct DemoPeer {
+    machine: CallMachine,
+    transport: LoopbackTransport,
+    answer_at: Option<u64>,
+}
+
+impl DemoPeer {
+    fn step(&mut self, now: u64) {
+        let mut actions = Vec::new();
+        for (from, message) in self.transport.poll() {
+            actions.extend(self.machine.receive(&from, message, now));
+        }
+        if let Some((CallPhase::RingingIn, kind)) = self.machine.current().map(|c| (c.phase, c.kind)) {
+            match self.answer_at {
+                None => self.answer_at = Some(now + DEMO_ANSWER_DELAY_MILLIS),
+                Some(at) if now >= at => actions.extend(self.machine.accept(stub_sdp(kind, "answer"), now)),
+                Some(_) => {}
+            }
+        }
+        actions.extend(self.machine.tick(now));
+
+        let mut queue = VecDeque::from(actions);
+        while let Some(action) = queue.pop_front() {
+            match action {
+                Action::Send { to, message } => self.transport.send(&to, &message),
+                Action::SetRemoteDescription(_) => {
+                    queue.extend(self.machine.local_candidate(stub_candidate(2)));
+                    self.machine.media_connected(now);
+                }
+                Action::AddIceCandidate(_) | Action::Finished(_) => {}
+            }
+        }
+    }
+}
+
+impl CallSession {
+    pub fn new(transport: Option<Box<dyn Transport>>) -> Self {
+        Self {
+            machine: CallMachine::new(local_participant()),
+            online: transport.is_some(),
+            transport,
+            demo_peer: None,
+        }
+    }
+
+    pub fn current(&self) -> Option<&ActiveCall> {
+        self.machine.current()
+    }
+
+    pub fn dial(&mut self, peer: CallParticipant, kind: CallKind, now: u64) -> Vec<Call> {
+        if self.current().is_some_and(|c| !c.is_over()) {
+            return Vec::new();
+        }
+        if !self.online {
+            let (local, remote) = LoopbackTransport::pair(LOCAL_USER_ID, &peer.id);
+            self.transport = Some(Box::new(local));
+            self.demo_peer = Some(DemoPeer {
+                machine: CallMachine::new(peer.clone()),
+                transport: remote,
+                answer_at: None,
+            });
+        }
+        let actions = self.machine.dial(peer, kind, stub_sdp(kind, "offer"), now);
+        self.run(actions, now)
+    }
+
+    pub fn accept(&mut self, now: u64) -> Vec<Call> {
+        let Some(kind) = self.current().map(|c| c.kind) else {
+            return Vec::new();
+        };
+        let actions = self.machine.accept(stub_sdp(kind, "answer"), now);
+        self.run(actions, now)
+    }
+
+    pub fn decline(&mut self, now: u64) -> Vec<Call> {
+        let actions = self.machine.decline(now);
+        self.run(actions, now)
+    }
+
+    pub fn hang_up(&mut self, now: u64) -> Vec<Call> {
+        let actions = self.machine.hang_up(now);
+        let finished = self.run(actions, now);
+        // Let the demo contact see the hang-up before it goes away
+        if let Some(peer) = self.demo_peer.as_mut() {
+            peer.step(now);
+        }
+        finished
+    }
+
+    pub fn dismiss(&mut self) {
+        self.machine.dismiss();
+        if !self.online && self.current().is_none() {
+            self.transport = None;
+            self.demo_peer = None;
+        }
+    }
+
+    // Deliver pending messages and enforce timeouts. Returns calls that
+    // finished and belong in the history.
+    pub fn pump(&mut self, now: u64) -> Vec<Call> {
+        if let Some(peer) = self.demo_peer.as_mut() {
+            peer.step(now);
+        }
+        let received = self.transport.as_mut().map(|t| t.poll()).unwrap_or_default();
+        let mut actions = Vec::new();
+        for (from, message) in received {
+            actions.extend(self.machine.receive(&from, message, now));
+        }
+        actions.extend(self.machine.tick(now));
+        self.run(actions, now)
+    }
+
+    fn run(&mut self, actions: Vec<Action>, now: u64) -> Vec<Call> {
+        let mut finished = Vec::new();
+        let mut queue = VecDeque::from(actions);
+        while let Some(action) = queue.pop_front() {
+            match action {
+                Action::Send { to, message } => {
+                    if let Some(transport) = self.transport.as_mut() {
+                        transport.send(&to, &message);
+                    }
+                }
+                // Media is simulated: once both descriptions are known the
+                // call counts as connected
+                Action::SetRemoteDescription(_) => {
+                    queue.extend(self.machine.local_candidate(stub_candidate(1)));
+                    self.machine.media_connected(now);
+                }
+                Action::AddIceCandidate(candidate) => log::debug!("Remote ICE candidate: {}", candidate),
+                Action::Finished(call) => finished.push(call),
+            }
+        }
+        finished
+    }
+}
+
+// Minimal session description standing in for the browser's until media
+// capture is wired up
+fn stub_sdp(kind: CallKind, role: &str) -> String {
+    let mut sdp = format!("v=0\r\no=jeebon-{} 0 0 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\n", role);
+    if kind == CallKind::Video {
+        sdp.push_str("m=video 9 UDP/TLS/RTP/SAVPF 96\r\n");
+    }
+    sdp
+}
+
+fn stub_candidate(foundation: u32) -> String {
+    format!("candidate:{} 1 udp 2122260223 127.0.0.1 9 typ host", foundation)
+}
@@ -0,0 +1,568 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::call::{Call, CallDirection, CallKind, CallOutcome, CallParticipant};
+use crate::utils::new_id;
+
+// Client-side call signaling as a pure state machine. It never touches the
+// network or media devices: callers feed it user actions, received messages
+// and clock ticks, and carry out the `Action`s it returns.
+
+// How long a call may ring before it is given up
+pub const RING_TIMEOUT_MILLIS: u64 = 45_000;
+// How long media may take to connect once the call is answered
+pub const CONNECT_TIMEOUT_MILLIS: u64 = 20_000;
+
+// Messages exchanged with the other party over the realtime socket
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignalMessage {
+    Offer { call_id: String, kind: CallKind, caller: CallParticipant, sdp: String },
+    Answer { call_id: String, sdp: String },
+    IceCandidate { call_id: String, candidate: String },
+    Ringing { call_id: String },
+    Decline { call_id: String },
+    Busy { call_id: String },
+    HangUp { call_id: String },
+}
+
+impl SignalMessage {
+    pub fn call_id(&self) -> &str {
+        match self {
+            SignalMessage::Offer { call_id, .. }
+            | SignalMessage::Answer { call_id, .. }
+            | SignalMessage::IceCandidate { call_id, .. }
+            | SignalMessage::Ringing { call_id }
+            | SignalMessage::Decline { call_id }
+            | SignalMessage::Busy { call_id }
+            | SignalMessage::HangUp { call_id } => call_id,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    HungUp,
+    RemoteHungUp,
+    Declined,
+    RemoteDeclined,
+    Busy,
+    NoAnswer,
+    Missed,
+    ConnectionFailed,
+}
+
+impl EndReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EndReason::HungUp | EndReason::RemoteHungUp => "Call ended",
+            EndReason::Declined | EndReason::RemoteDeclined => "Call declined",
+            EndReason::Busy => "Line busy",
+            EndReason::NoAnswer => "No answer",
+            EndReason::Missed => "Missed call",
+            EndReason::ConnectionFailed => "Connection failed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallPhase {
+    // Offer sent, waiting for the other device to ring
+    Dialing,
+    // The other device is ringing
+    RingingOut,
+    // Someone is calling us
+    RingingIn,
+    // Answered, waiting for media to connect
+    Connecting,
+    Connected,
+    Ended(EndReason),
+}
+
+// Side effects for the caller to perform, in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Send { to: String, message: SignalMessage },
+    SetRemoteDescription(String),
+    AddIceCandidate(String),
+    // The call is over and should go into the call history
+    Finished(Call),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveCall {
+    pub call_id: String,
+    pub peer: CallParticipant,
+    pub kind: CallKind,
+    pub direction: CallDirection,
+    pub phase: CallPhase,
+    pub started_at: u64,
+    pub connected_at: Option<u64>,
+    deadline: Option<u64>,
+    remote_offer: Option<String>,
+    // Candidates that arrived before the remote description
+    pending_candidates: Vec<String>,
+    has_remote_description: bool,
+}
+
+impl ActiveCall {
+    pub fn is_over(&self) -> bool {
+        matches!(self.phase, CallPhase::Ended(_))
+    }
+}
+
+pub struct CallMachine {
+    local: CallParticipant,
+    current: Option<ActiveCall>,
+}
+
+impl CallMachine {
+    pub fn new(local: CallParticipant) -> Self {
+        Self { local, current: None }
+    }
+
+    pub fn current(&self) -> Option<&ActiveCall> {
+        self.current.as_ref()
+    }
+
+    fn is_busy(&self) -> bool {
+        self.current.as_ref().is_some_and(|c| !c.is_over())
+    }
+
+    // Forget a finished call so its end screen goes away
+    pub fn dismiss(&mut self) {
+        if !self.is_busy() {
+            self.current = None;
+        }
+    }
+
+    pub fn dial(&mut self, peer: CallParticipant, kind: CallKind, offer_sdp: String, now: u64) -> Vec<Action> {
+        if self.is_busy() {
+            return Vec::new();
+        }
+        let call_id = new_id();
+        let offer = SignalMessage::Offer {
+            call_id: call_id.clone(),
+            kind,
+            caller: self.local.clone(),
+            sdp: offer_sdp,
+        };
+        let to = peer.id.clone();
+        self.current = Some(ActiveCall {
+            call_id,
+            peer,
+            kind,
+            direction: CallDirection::Outgoing,
+            phase: CallPhase::Dialing,
+            started_at: now,
+            connected_at: None,
+            deadline: Some(now + RING_TIMEOUT_MILLIS),
+            remote_offer: None,
+            pending_candidates: Vec::new(),
+            has_remote_description: false,
+        });
+        vec![Action::Send { to, message: offer }]
+    }
+
+    pub fn accept(&mut self, answer_sdp: String, now: u64) -> Vec<Action> {
+        let Some(call) = self.current.as_mut().filter(|c| c.phase == CallPhase::RingingIn) else {
+            return Vec::new();
+        };
+        call.phase = CallPhase::Connecting;
+        call.deadline = Some(now + CONNECT_TIMEOUT_MILLIS);
+        call.has_remote_description = true;
+        let mut actions = Vec::new();
+        if let Some(offer) = call.remote_offer.take() {
+            actions.push(Action::SetRemoteDescription(offer));
+        }
+        actions.extend(call.pending_candidates.drain(..).map(Action::AddIceCandidate));
+        actions.push(Action::Send {
+            to: call.peer.id.clone(),
+            message: SignalMessage::Answer { call_id: call.call_id.clone(), sdp: answer_sdp },
+        });
+        actions
+    }
+
+    pub fn decline(&mut self, now: u64) -> Vec<Action> {
+        if self.current.as_ref().is_some_and(|c| c.phase == CallPhase::RingingIn) {
+            self.end(EndReason::Declined, now, |call_id| SignalMessage::Decline { call_id })
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn hang_up(&mut self, now: u64) -> Vec<Action> {
+        match self.current.as_ref().map(|c| c.phase) {
+            Some(CallPhase::RingingIn) => self.decline(now),
+            Some(CallPhase::Ended(_)) | None => Vec::new(),
+            Some(_) => self.end(EndReason::HungUp, now, |call_id| SignalMessage::HangUp { call_id }),
+        }
+    }
+
+    // A candidate gathered by our media layer
+    pub fn local_candidate(&mut self, candidate: String) -> Vec<Action> {
+        match self.current.as_ref().filter(|c| !c.is_over()) {
+            Some(call) => vec![Action::Send {
+                to: call.peer.id.clone(),
+                message: SignalMessage::IceCandidate { call_id: call.call_id.clone(), candidate },
+            }],
+            None => Vec::new(),
+        }
+    }
+
+    // Reported by the media layer once audio/video flows
+    pub fn media_connected(&mut self, now: u64) {
+        if let Some(call) = self.current.as_mut().filter(|c| c.phase == CallPhase::Connecting) {
+            call.phase = CallPhase::Connected;
+            call.connected_at = Some(now);
+            call.deadline = None;
+        }
+    }
+
+    pub fn receive(&mut self, from: &str, message: SignalMessage, now: u64) -> Vec<Action> {
+        if let SignalMessage::Offer { call_id, kind, caller, sdp } = message {
+            return self.receive_offer(from, call_id, kind, caller, sdp, now);
+        }
+
+        // Everything else must belong to the current call and come from its peer
+        let Some(call) = self
+            .current
+            .as_mut()
+            .filter(|c| !c.is_over() && c.call_id == message.call_id() && c.peer.id == from)
+        else {
+            return Vec::new();
+        };
+
+        match (call.phase, message) {
+            (CallPhase::Dialing, SignalMessage::Ringing { .. }) => {
+                call.phase = CallPhase::RingingOut;
+                Vec::new()
+            }
+            (CallPhase::Dialing | CallPhase::RingingOut, SignalMessage::Answer { sdp, .. }) => {
+                call.phase = CallPhase::Connecting;
+                call.deadline = Some(now + CONNECT_TIMEOUT_MILLIS);
+                call.has_remote_description = true;
+                let mut actions = vec![Action::SetRemoteDescription(sdp)];
+                actions.extend(call.pending_candidates.drain(..).map(Action::AddIceCandidate));
+                actions
+            }
+            (_, SignalMessage::IceCandidate { candidate, .. }) => {
+                if call.has_remote_description {
+                    vec![Action::AddIceCandidate(candidate)]
+                } else {
+                    call.pending_candidates.push(candidate);
+                    Vec::new()
+                }
+            }
+            (CallPhase::Dialing | CallPhase::RingingOut, SignalMessage::Decline { .. }) => {
+                self.finish(EndReason::RemoteDeclined, now)
+            }
+            (CallPhase::Dialing | CallPhase::RingingOut, SignalMessage::Busy { .. }) => {
+                self.finish(EndReason::Busy, now)
+            }
+            // The caller gave up before we answered
+            (CallPhase::RingingIn, SignalMessage::HangUp { .. }) => self.finish(EndReason::Missed, now),
+            (_, SignalMessage::HangUp { .. }) => self.finish(EndReason::RemoteHungUp, now),
+            _ => Vec::new(),
+        }
+    }
+
+    fn receive_offer(
+        &mut self,
+        from: &str,
+        call_id: String,
+        kind: CallKind,
+        caller: CallParticipant,
+        sdp: String,
+        now: u64,
+    ) -> Vec<Action> {
+        if caller.id != from {
+            return Vec::new();
+        }
+        if self.is_busy() {
+            // Tell the caller we are busy and keep a record of the missed call
+            return vec![
+                Action::Send { to: from.to_string(), message: SignalMessage::Busy { call_id: call_id.clone() } },
+                Action::Finished(Call {
+                    id: call_id,
+                    kind,
+                    direction: CallDirection::Incoming,
+                    participants: vec![caller],
+                    started_at: now,
+                    duration_secs: 0,
+                    outcome: CallOutcome::Missed,
+                    seen: false,
+                }),
+            ];
+        }
+        self.current = Some(ActiveCall {
+            call_id: call_id.clone(),
+            peer: caller,
+            kind,
+            direction: CallDirection::Incoming,
+            phase: CallPhase::RingingIn,
+            started_at: now,
+            connected_at: None,
+            deadline: Some(now + RING_TIMEOUT_MILLIS),
+            remote_offer: Some(sdp),
+            pending_candidates: Vec::new(),
+            has_remote_description: false,
+        });
+        vec![Action::Send { to: from.to_string(), message: SignalMessage::Ringing { call_id } }]
+    }
+
+    // Enforce ringing and connection timeouts
+    pub fn tick(&mut self, now: u64) -> Vec<Action> {
+        let Some(call) = self.current.as_ref().filter(|c| c.deadline.is_some_and(|d| now >= d)) else {
+            return Vec::new();
+        };
+        match call.phase {
+            CallPhase::Dialing | CallPhase::RingingOut => {
+                self.end(EndReason::NoAnswer, now, |call_id| SignalMessage::HangUp { call_id })
+            }
+            // The caller times out on their side too, so nothing needs sending
+            CallPhase::RingingIn => self.finish(EndReason::Missed, now),
+            CallPhase::Connecting => {
+                self.end(EndReason::ConnectionFailed, now, |call_id| SignalMessage::HangUp { call_id })
+            }
+            CallPhase::Connected | CallPhase::Ended(_) => Vec::new(),
+        }
+    }
+
+    // End the call locally and tell the peer
+    fn end(&mut self, reason: EndReason, now: u64, message: impl FnOnce(String) -> SignalMessage) -> Vec<Action> {
+        let Some(call) = self.current.as_ref() else {
+            return Vec::new();
+        };
+        let send = Action::Send { to: call.peer.id.clone(), message: message(call.call_id.clone()) };
+        let mut actions = vec![send];
+        actions.extend(self.finish(reason, now));
+        actions
+    }
+
+    fn finish(&mut self, reason: EndReason, now: u64) -> Vec<Action> {
+        let Some(call) = self.current.as_mut() else {
+            return Vec::new();
+        };
+        call.phase = CallPhase::Ended(reason);
+        call.deadline = None;
+        let outcome = match (call.connected_at, reason) {
+            (Some(_), _) => CallOutcome::Completed,
+            (None, EndReason::Declined | EndReason::RemoteDeclined) => CallOutcome::Declined,
+            (None, _) => CallOutcome::Missed,
+        };
+        vec![Action::Finished(Call {
+            id: call.call_id.clone(),
+            kind: call.kind,
+            direction: call.direction,
+            participants: vec![call.peer.clone()],
+            started_at: call.started_at,
+            duration_secs: call.connected_at.map_or(0, |at| now.saturating_sub(at) / 1000),
+            outcome,
+            seen: false,
+        })]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::calls::transport::{LoopbackTransport, Transport};
+
+    // One end of a call: the machine, its transport and what it was told to do
+    struct Party {
+        machine: CallMachine,
+        transport: LoopbackTransport,
+        remote_descriptions: Vec<String>,
+        candidates: Vec<String>,
+        finished: Vec<Call>,
+    }
+
+    impl Party {
+        fn run(&mut self, actions: Vec<Action>) {
+            for action in actions {
+                match action {
+                    Action::Send { to, message } => self.transport.send(&to, &message),
+                    Action::SetRemoteDescription(sdp) => self.remote_descriptions.push(sdp),
+                    Action::AddIceCandidate(candidate) => self.candidates.push(candidate),
+                    Action::Finished(call) => self.finished.push(call),
+                }
+            }
+        }
+
+        fn deliver(&mut self, now: u64) {
+            for (from, message) in self.transport.poll() {
+                let actions = self.machine.receive(&from, message, now);
+                self.run(actions);
+            }
+        }
+
+        fn phase(&self) -> Option<CallPhase> {
+            self.machine.current().map(|c| c.phase)
+        }
+    }
+
+    fn participant(id: &str) -> CallParticipant {
+        CallParticipant { id: id.to_string(), name: id.to_string(), avatar: String::new() }
+    }
+
+    fn parties() -> (Party, Party) {
+        let (a, b) = LoopbackTransport::pair("alice", "bob");
+        let party = |id: &str, transport| Party {
+            machine: CallMachine::new(participant(id)),
+            transport,
+            remote_descriptions: Vec::new(),
+            candidates: Vec::new(),
+            finished: Vec::new(),
+        };
+        (party("alice", a), party("bob", b))
+    }
+
+    // Alice calls Bob and Bob's phone starts ringing
+    fn ringing(alice: &mut Party, bob: &mut Party) {
+        let actions = alice.machine.dial(participant("bob"), CallKind::Video, "offer".to_string(), 0);
+        alice.run(actions);
+        bob.deliver(10);
+        alice.deliver(20);
+        assert_eq!(alice.phase(), Some(CallPhase::RingingOut));
+        assert_eq!(bob.phase(), Some(CallPhase::RingingIn));
+    }
+
+    #[test]
+    fn offer_answer_ice_and_hang_up() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+
+        // Bob's candidate races ahead of his answer on the wire
+        let early = alice.machine.local_candidate("alice-candidate".to_string());
+        alice.run(early);
+        bob.deliver(30);
+        assert!(bob.candidates.is_empty());
+
+        let actions = bob.machine.accept("answer".to_string(), 1_000);
+        bob.run(actions);
+        assert_eq!(bob.remote_descriptions, ["offer"]);
+        assert_eq!(bob.candidates, ["alice-candidate"]);
+        assert_eq!(bob.phase(), Some(CallPhase::Connecting));
+
+        let late = bob.machine.local_candidate("bob-candidate".to_string());
+        bob.run(late);
+        alice.deliver(1_010);
+        assert_eq!(alice.remote_descriptions, ["answer"]);
+        assert_eq!(alice.candidates, ["bob-candidate"]);
+        assert_eq!(alice.phase(), Some(CallPhase::Connecting));
+
+        alice.machine.media_connected(1_100);
+        bob.machine.media_connected(1_100);
+        assert_eq!(alice.phase(), Some(CallPhase::Connected));
+
+        let actions = alice.machine.hang_up(61_100);
+        alice.run(actions);
+        bob.deliver(61_110);
+        assert_eq!(alice.phase(), Some(CallPhase::Ended(EndReason::HungUp)));
+        assert_eq!(bob.phase(), Some(CallPhase::Ended(EndReason::RemoteHungUp)));
+        for party in [&alice, &bob] {
+            let [call] = party.finished.as_slice() else { panic!("expected one finished call") };
+            assert_eq!(call.outcome, CallOutcome::Completed);
+            assert_eq!(call.duration_secs, 60);
+        }
+        assert_eq!(alice.finished[0].direction, CallDirection::Outgoing);
+        assert_eq!(bob.finished[0].direction, CallDirection::Incoming);
+    }
+
+    #[test]
+    fn declined_call() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+        let actions = bob.machine.decline(500);
+        bob.run(actions);
+        alice.deliver(510);
+        assert_eq!(bob.phase(), Some(CallPhase::Ended(EndReason::Declined)));
+        assert_eq!(alice.phase(), Some(CallPhase::Ended(EndReason::RemoteDeclined)));
+        assert_eq!(alice.finished[0].outcome, CallOutcome::Declined);
+        assert_eq!(bob.finished[0].outcome, CallOutcome::Declined);
+    }
+
+    #[test]
+    fn caller_gives_up_before_the_answer() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+        let actions = alice.machine.hang_up(500);
+        alice.run(actions);
+        bob.deliver(510);
+        assert_eq!(bob.phase(), Some(CallPhase::Ended(EndReason::Missed)));
+        assert_eq!(bob.finished[0].outcome, CallOutcome::Missed);
+        // Accepting afterwards does nothing
+        assert!(bob.machine.accept("answer".to_string(), 600).is_empty());
+    }
+
+    #[test]
+    fn ringing_and_connecting_time_out() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+        assert!(alice.machine.tick(RING_TIMEOUT_MILLIS - 1).is_empty());
+        let actions = alice.machine.tick(RING_TIMEOUT_MILLIS);
+        alice.run(actions);
+        bob.deliver(RING_TIMEOUT_MILLIS + 10);
+        assert_eq!(alice.phase(), Some(CallPhase::Ended(EndReason::NoAnswer)));
+        assert_eq!(bob.phase(), Some(CallPhase::Ended(EndReason::Missed)));
+
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+        let actions = bob.machine.accept("answer".to_string(), 100);
+        bob.run(actions);
+        alice.deliver(110);
+        let actions = bob.machine.tick(100 + CONNECT_TIMEOUT_MILLIS);
+        bob.run(actions);
+        alice.deliver(200 + CONNECT_TIMEOUT_MILLIS);
+        assert_eq!(bob.phase(), Some(CallPhase::Ended(EndReason::ConnectionFailed)));
+        assert_eq!(alice.phase(), Some(CallPhase::Ended(EndReason::RemoteHungUp)));
+        assert_eq!(alice.finished[0].outcome, CallOutcome::Missed);
+    }
+
+    #[test]
+    fn second_caller_gets_busy() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+
+        let mut carol = CallMachine::new(participant("carol"));
+        let dialed = carol.dial(participant("bob"), CallKind::Audio, "offer".to_string(), 0);
+        let [Action::Send { message: offer, .. }] = dialed.as_slice() else {
+            panic!("dialing sends the offer")
+        };
+        let answered = bob.machine.receive("carol", offer.clone(), 50);
+        let [Action::Send { to, message: busy }, Action::Finished(missed)] = answered.as_slice() else {
+            panic!("a busy line answers and records the missed call")
+        };
+        assert_eq!(to, "carol");
+        assert_eq!(missed.outcome, CallOutcome::Missed);
+        carol.receive("bob", busy.clone(), 60);
+        assert_eq!(carol.current().map(|c| c.phase), Some(CallPhase::Ended(EndReason::Busy)));
+        // Bob's call with Alice is untouched
+        assert_eq!(bob.phase(), Some(CallPhase::RingingIn));
+    }
+
+    #[test]
+    fn messages_from_strangers_are_ignored() {
+        let (mut alice, mut bob) = parties();
+        ringing(&mut alice, &mut bob);
+        let call_id = alice.machine.current().unwrap().call_id.clone();
+
+        // A hang-up for the right call from the wrong sender
+        assert!(bob.machine.receive("mallory", SignalMessage::HangUp { call_id }, 100).is_empty());
+        // An answer for a different call from the right sender
+        let other = SignalMessage::Answer { call_id: "other".to_string(), sdp: "x".to_string() };
+        assert!(alice.machine.receive("bob", other, 100).is_empty());
+        // An offer whose caller does not match the sender
+        let (mut eve, _) = parties();
+        let offer = SignalMessage::Offer {
+            call_id: "fake".to_string(),
+            kind: CallKind::Audio,
+            caller: participant("alice"),
+            sdp: "x".to_string(),
+        };
+        assert!(eve.machine.receive("mallory", offer, 100).is_empty());
+        assert_eq!(eve.phase(), None);
+        assert_eq!(bob.phase(), Some(CallPhase::RingingIn));
+        assert_eq!(alice.phase(), Some(CallPhase::RingingOut));
+    }
+}
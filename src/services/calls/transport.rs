@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::signaling::SignalMessage;
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+use super::group::RoomTransport;
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+use super::room::{RoomRequest, RoomUpdate};
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+use crate::services::realtime::RealtimeSocket;
+
+// How signaling messages reach the other party
+pub trait Transport {
+    fn send(&mut self, to: &str, message: &SignalMessage);
+    // Messages received since the last poll, with the sender's id
+    fn poll(&mut self) -> Vec<(String, SignalMessage)>;
+}
+
+// (from, to, JSON payload)
+type Wire = Rc<RefCell<VecDeque<(String, String, String)>>>;
+
+// In-memory transport connecting two parties directly. Messages still go
+// through JSON so both ends see exactly what the socket would carry.
+pub struct LoopbackTransport {
+    local_id: String,
+    wire: Wire,
+}
+
+impl LoopbackTransport {
+    pub fn pair(a: &str, b: &str) -> (Self, Self) {
+        let wire = Wire::default();
+        (
+            Self { local_id: a.to_string(), wire: wire.clone() },
+            Self { local_id: b.to_string(), wire },
+        )
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, to: &str, message: &SignalMessage) {
+        match serde_json::to_string(message) {
+            Ok(payload) => self.wire.borrow_mut().push_back((self.local_id.clone(), to.to_string(), payload)),
+            Err(e) => log::error!("Failed to encode signaling message: {}", e),
+        }
+    }
+
+    fn poll(&mut self) -> Vec<(String, SignalMessage)> {
+        let mut wire = self.wire.borrow_mut();
+        let (mine, others): (VecDeque<_>, VecDeque<_>) = wire.drain(..).partition(|(_, to, _)| *to == self.local_id);
+        *wire = others;
+        mine.into_iter()
+            .filter_map(|(from, _, payload)| serde_json::from_str(&payload).ok().map(|m| (from, m)))
+            .collect()
+    }
+}
+
+// Signaling over the shared realtime socket. 1:1 messages go straight to
+// the peer, group call requests to the forwarding server.
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+pub struct SocketTransport(pub Rc<RealtimeSocket>);
+
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+impl Transport for SocketTransport {
+    fn send(&mut self, to: &str, message: &SignalMessage) {
+        self.0.send("call", to, message);
+    }
+
+    fn poll(&mut self) -> Vec<(String, SignalMessage)> {
+        self.0
+            .take("call")
+            .into_iter()
+            .filter_map(|(from, payload)| serde_json::from_value(payload).ok().map(|m| (from, m)))
+            .collect()
+    }
+}
+
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+impl RoomTransport for SocketTransport {
+    fn send(&mut self, request: &RoomRequest, _now: u64) {
+        self.0.send("room", "sfu", request);
+    }
+
+    fn poll(&mut self, _now: u64) -> Vec<RoomUpdate> {
+        self.0
+            .take("room")
+            .into_iter()
+            .filter_map(|(_, payload)| serde_json::from_value(payload).ok())
+            .collect()
+    }
+}
@@ -0,0 +1,388 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use dioxus::prelude::*;
+use serde::Deserialize;
+
+use crate::models::family::{FamilyTree, Gender, RelationKind};
+use crate::services::tree_layout::{self, CARD_HEIGHT, CARD_WIDTH, Layout};
+
+// Drawing the family tree for keeping or printing: a standalone SVG, a PNG
+// rasterised from it by the browser, or a PDF of A4 landscape pages with the
+// chart tiled across as many as it needs. Every page and picture carries a
+// title block and a legend. Only photos already stored in the tree (data
+// URLs) are embedded; anyone else gets their initials, so the files work
+// offline.
+
+// A4 landscape, in points
+pub const PAGE_WIDTH: f64 = 842.0;
+pub const PAGE_HEIGHT: f64 = 595.0;
+const PAGE_MARGIN: f64 = 24.0;
+const TITLE_HEIGHT: f64 = 56.0;
+const LEGEND_HEIGHT: f64 = 36.0;
+// Points per chart unit when tiling, so names stay readable on paper
+const TILE_SCALE: f64 = 0.5;
+// Pixels per point when rasterising, and the largest side a canvas can take
+pub const PIXEL_SCALE: f64 = 2.0;
+const MAX_PIXELS: f64 = 8000.0;
+const FONT: &str = "Arial, Helvetica, sans-serif";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// The cards and lines, in chart units, with `title` giving how each person
+// is related to us
+pub fn chart_body(tree: &FamilyTree, layout: &Layout, title: impl Fn(&str) -> String) -> String {
+    let mut out = String::from(
+        "<defs><clipPath id=\"avatarClip\" clipPathUnits=\"objectBoundingBox\"><circle cx=\"0.5\" cy=\"0.5\" r=\"0.5\"/></clipPath></defs>",
+    );
+    for line in &layout.lines {
+        let points: Vec<String> = line.points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        out.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" stroke-dasharray=\"{}\"/>",
+            points.join(" "),
+            tree_layout::line_stroke(line.kind),
+            tree_layout::line_dash(line.kind)
+        ));
+    }
+    for card in &layout.cards {
+        let Some(person) = tree.person(&card.id) else {
+            continue;
+        };
+        let [name, dates, relation] = tree_layout::card_text(person, &title(&card.id));
+        let photo = if person.photo.starts_with("data:") { person.photo.clone() } else { person.monogram() };
+        out.push_str(&format!(
+            "<g transform=\"translate({}, {})\" font-family=\"{}\">\
+             <rect width=\"{}\" height=\"{}\" rx=\"10\" fill=\"{}\" stroke=\"#adb5bd\"/>\
+             <image href=\"{}\" x=\"10\" y=\"10\" width=\"60\" height=\"60\" clip-path=\"url(#avatarClip)\" preserveAspectRatio=\"xMidYMid slice\"/>\
+             <text x=\"80\" y=\"26\" font-size=\"14\" font-weight=\"bold\" fill=\"#212529\">{}</text>\
+             <text x=\"80\" y=\"46\" font-size=\"12\" fill=\"#495057\">{}</text>\
+             <text x=\"80\" y=\"64\" font-size=\"12\" fill=\"#6c757d\">{}</text></g>",
+            card.x,
+            card.y,
+            FONT,
+            CARD_WIDTH,
+            CARD_HEIGHT,
+            tree_layout::card_fill(person.gender),
+            escape(&photo),
+            escape(&name),
+            escape(&dates),
+            escape(&relation)
+        ));
+    }
+    out
+}
+
+// The title and a line under it, across `width` from the top left
+fn title_block(title: &str, subtitle: &str, width: f64) -> String {
+    format!(
+        "<g font-family=\"{}\"><text x=\"0\" y=\"24\" font-size=\"22\" font-weight=\"bold\" fill=\"#212529\">{}</text>\
+         <text x=\"0\" y=\"44\" font-size=\"12\" fill=\"#6c757d\">{}</text>\
+         <line x1=\"0\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#dee2e6\"/></g>",
+        FONT,
+        escape(title),
+        escape(subtitle),
+        TITLE_HEIGHT - 4.0,
+        width,
+        TITLE_HEIGHT - 4.0
+    )
+}
+
+// Swatches for the card colours and samples of each kind of line
+fn legend() -> String {
+    let mut out = format!("<g font-family=\"{}\" font-size=\"11\" fill=\"#495057\">", FONT);
+    let mut x = 0.0;
+    for (gender, label) in [(Gender::Male, "Male"), (Gender::Female, "Female"), (Gender::Unknown, "Unknown")] {
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"10\" width=\"22\" height=\"14\" rx=\"3\" fill=\"{}\" stroke=\"#adb5bd\"/><text x=\"{}\" y=\"21\">{}</text>",
+            x,
+            tree_layout::card_fill(gender),
+            x + 28.0,
+            label
+        ));
+        x += 90.0;
+    }
+    for (kind, label) in [
+        (RelationKind::Parent, "Parent"),
+        (RelationKind::Adoptive, "Adoptive"),
+        (RelationKind::Step, "Step"),
+        (RelationKind::Spouse, "Spouse"),
+    ] {
+        out.push_str(&format!(
+            "<line x1=\"{}\" y1=\"17\" x2=\"{}\" y2=\"17\" stroke=\"{}\" stroke-width=\"2\" stroke-dasharray=\"{}\"/><text x=\"{}\" y=\"21\">{}</text>",
+            x,
+            x + 30.0,
+            tree_layout::line_stroke(kind),
+            tree_layout::line_dash(kind),
+            x + 36.0,
+            label
+        ));
+        x += 100.0;
+    }
+    out.push_str("</g>");
+    out
+}
+
+fn document(width: f64, height: f64, content: &str) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\"><rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>{}</svg>",
+        width, height, width, height, content
+    )
+}
+
+// The whole chart on one sheet, returned with its size
+pub fn svg(body: &str, layout: &Layout, title: &str, subtitle: &str) -> (String, f64, f64) {
+    let width = layout.width.max(760.0) + 2.0 * PAGE_MARGIN;
+    let height = layout.height + TITLE_HEIGHT + LEGEND_HEIGHT + 2.0 * PAGE_MARGIN;
+    let content = format!(
+        "<g transform=\"translate({m}, {m})\">{}</g><g transform=\"translate({m}, {})\">{}</g><g transform=\"translate({m}, {})\">{}</g>",
+        title_block(title, subtitle, width - 2.0 * PAGE_MARGIN),
+        PAGE_MARGIN + TITLE_HEIGHT,
+        body,
+        height - PAGE_MARGIN - LEGEND_HEIGHT,
+        legend(),
+        m = PAGE_MARGIN
+    );
+    (document(width, height, &content), width, height)
+}
+
+// The chart shrunk onto one page, or tiled across pages at a readable size,
+// going across then down
+pub fn pages(body: &str, layout: &Layout, title: &str, subtitle: &str, fit: bool) -> Vec<String> {
+    let area_width = PAGE_WIDTH - 2.0 * PAGE_MARGIN;
+    let area_height = PAGE_HEIGHT - 2.0 * PAGE_MARGIN - TITLE_HEIGHT - LEGEND_HEIGHT;
+    let scale = if fit { (area_width / layout.width).min(area_height / layout.height).min(1.0) } else { TILE_SCALE };
+    // How much of the chart fits on one page
+    let (tile_width, tile_height) = (area_width / scale, area_height / scale);
+    let across = (layout.width / tile_width).ceil().max(1.0) as usize;
+    let down = (layout.height / tile_height).ceil().max(1.0) as usize;
+    let count = across * down;
+    let mut out = Vec::new();
+    for row in 0..down {
+        for column in 0..across {
+            let number = out.len() + 1;
+            let heading = if count == 1 {
+                subtitle.to_string()
+            } else {
+                format!("{} · Page {} of {} · row {}, column {}", subtitle, number, count, row + 1, column + 1)
+            };
+            let content = format!(
+                "<g transform=\"translate({m}, {m})\">{}</g>\
+                 <svg x=\"{m}\" y=\"{}\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">{}</svg>\
+                 <rect x=\"{m}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#dee2e6\"/>\
+                 <g transform=\"translate({m}, {})\">{}</g>",
+                title_block(title, &heading, area_width),
+                PAGE_MARGIN + TITLE_HEIGHT,
+                area_width,
+                area_height,
+                column as f64 * tile_width,
+                row as f64 * tile_height,
+                tile_width,
+                tile_height,
+                body,
+                PAGE_MARGIN + TITLE_HEIGHT,
+                area_width,
+                area_height,
+                PAGE_HEIGHT - PAGE_MARGIN - LEGEND_HEIGHT,
+                legend(),
+                m = PAGE_MARGIN
+            );
+            out.push(document(PAGE_WIDTH, PAGE_HEIGHT, &content));
+        }
+    }
+    out
+}
+
+// How big to rasterise a picture, keeping within what a canvas can hold
+pub fn pixels(width: f64, height: f64) -> (u32, u32) {
+    let scale = PIXEL_SCALE.min(MAX_PIXELS / width.max(height));
+    ((width * scale).round() as u32, (height * scale).round() as u32)
+}
+
+// A PDF with one JPEG, `width` by `height` pixels, filling each A4 page
+pub fn pdf(jpegs: &[Vec<u8>], width: u32, height: u32, title: &str) -> Vec<u8> {
+    let mut out: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::new();
+    let mut object = |out: &mut Vec<u8>, head: String, stream: Option<&[u8]>| {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{}\n", offsets.len(), head).bytes());
+        if let Some(bytes) = stream {
+            out.extend(b"stream\n");
+            out.extend(bytes);
+            out.extend(b"\nendstream\n");
+        }
+        out.extend(b"endobj\n");
+    };
+    // Catalog, page list and document info come first; each page then takes
+    // three objects: the page, what it draws, and its image
+    let kids: Vec<String> = (0..jpegs.len()).map(|i| format!("{} 0 R", 4 + 3 * i)).collect();
+    object(&mut out, "<< /Type /Catalog /Pages 2 0 R >>".to_string(), None);
+    object(&mut out, format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), jpegs.len()), None);
+    let utf16: String = title.encode_utf16().map(|unit| format!("{:04X}", unit)).collect();
+    object(&mut out, format!("<< /Title <FEFF{}> /Producer (Jeebon) >>", utf16), None);
+    for (i, jpeg) in jpegs.iter().enumerate() {
+        let page = 4 + 3 * i;
+        object(
+            &mut out,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page + 2,
+                page + 1
+            ),
+            None,
+        );
+        let draw = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", PAGE_WIDTH, PAGE_HEIGHT);
+        object(&mut out, format!("<< /Length {} >>", draw.len()), Some(draw.as_bytes()));
+        object(
+            &mut out,
+            format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>",
+                width,
+                height,
+                jpeg.len()
+            ),
+            Some(jpeg),
+        );
+    }
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).bytes());
+    for offset in &offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    out.extend(format!("trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n", offsets.len() + 1, xref).bytes());
+    out
+}
+
+pub fn data_url(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, STANDARD.encode(bytes))
+}
+
+// The bytes in a base64 data URL
+pub fn decode(url: &str) -> Result<Vec<u8>, String> {
+    let (_, data) = url.split_once(";base64,").ok_or_else(|| "the picture came back empty".to_string())?;
+    STANDARD.decode(data).map_err(|e| e.to_string())
+}
+
+const RASTERIZE_JS: &str = r#"
+    const [svg, width, height, mime] = await dioxus.recv();
+    try {
+        const url = URL.createObjectURL(new Blob([svg], { type: 'image/svg+xml' }));
+        const image = new Image();
+        await new Promise((done, fail) => {
+            image.onload = done;
+            image.onerror = () => fail(new Error('the chart could not be drawn'));
+            image.src = url;
+        });
+        const canvas = document.createElement('canvas');
+        canvas.width = width;
+        canvas.height = height;
+        const context = canvas.getContext('2d');
+        context.fillStyle = '#ffffff';
+        context.fillRect(0, 0, width, height);
+        context.drawImage(image, 0, 0, width, height);
+        URL.revokeObjectURL(url);
+        dioxus.send({ url: canvas.toDataURL(mime, 0.92) });
+    } catch (e) {
+        dioxus.send({ error: String(e.message || e) });
+    }
+"#;
+
+#[derive(Deserialize)]
+struct Drawn {
+    url: Option<String>,
+    error: Option<String>,
+}
+
+// Has the browser draw `svg` onto a canvas of `width` by `height` pixels and
+// returns it as a data URL of type `mime`
+pub async fn rasterize(svg: &str, width: u32, height: u32, mime: &str) -> Result<String, String> {
+    let mut eval = document::eval(RASTERIZE_JS);
+    eval.send((svg, width, height, mime)).map_err(|e| e.to_string())?;
+    let drawn: Drawn = eval.recv().await.map_err(|e| e.to_string())?;
+    match (drawn.url, drawn.error) {
+        (Some(url), _) if url.starts_with("data:") && url.len() > "data:,".len() => Ok(url),
+        (_, Some(error)) => Err(error),
+        _ => Err("the chart is too big to draw".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(width: f64, height: f64) -> Layout {
+        Layout { width, height, ..Layout::default() }
+    }
+
+    fn count(width: f64, height: f64, fit: bool) -> usize {
+        pages("", &layout(width, height), "Tree", "Everyone", fit).len()
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    // The number written at `at`
+    fn number_at(bytes: &[u8], at: usize) -> usize {
+        let digits: String = bytes[at..].iter().map(|&b| b as char).take_while(char::is_ascii_digit).collect();
+        digits.parse().unwrap()
+    }
+
+    #[test]
+    fn every_xref_entry_points_at_its_object() {
+        // Stand-in images with line breaks and bytes that aren't text
+        let jpegs = vec![vec![0xFF, 0xD8, b'\n', 0x00, 0xFF, 0xD9], vec![0xFF, 0xD8, b'e', b'n', b'd', 0xFF, 0xD9], Vec::new()];
+        let bytes = pdf(&jpegs, 1684, 1190, "আমাদের পরিবার");
+        assert!(bytes.starts_with(b"%PDF-1.4\n"));
+        assert!(bytes.ends_with(b"%%EOF\n"));
+        let startxref = find(&bytes, b"startxref\n").unwrap() + "startxref\n".len();
+        let xref = number_at(&bytes, startxref);
+        assert!(bytes[xref..].starts_with(b"xref\n0 "));
+        let size = number_at(&bytes, xref + "xref\n0 ".len());
+        assert_eq!(size, 4 + 3 * jpegs.len());
+        let table = &bytes[xref..];
+        let first = find(table, b"0000000000 65535 f \n").unwrap() + 20;
+        for object in 1..size {
+            let entry = &table[first + (object - 1) * 20..first + object * 20];
+            assert!(entry.ends_with(b" 00000 n \n"));
+            let offset = number_at(entry, 0);
+            assert!(bytes[offset..].starts_with(format!("{} 0 obj\n", object).as_bytes()), "object {}", object);
+        }
+        assert!(find(&bytes, format!("/Size {} ", size).as_bytes()).is_some());
+        assert!(find(&bytes, b"/Count 3 ").is_some());
+    }
+
+    #[test]
+    fn the_chart_is_tiled_across_then_down() {
+        // A page holds 1588 by 910 chart units at the tiling scale
+        assert_eq!(count(1588.0, 910.0, false), 1);
+        assert_eq!(count(1589.0, 910.0, false), 2);
+        assert_eq!(count(1588.0, 911.0, false), 2);
+        assert_eq!(count(3200.0, 2000.0, false), 9);
+        let tiles = pages("", &layout(3200.0, 900.0), "Tree", "Everyone", false);
+        assert_eq!(tiles.len(), 3);
+        assert!(tiles[1].contains("Everyone · Page 2 of 3 · row 1, column 2"));
+        assert!(!pages("", &layout(800.0, 600.0), "Tree", "Everyone", false)[0].contains("Page"));
+    }
+
+    #[test]
+    fn fitting_takes_one_page_whatever_the_size() {
+        assert_eq!(count(10_000.0, 8_000.0, true), 1);
+        assert_eq!(count(100.0, 100.0, true), 1);
+        for (width, height) in [(0.0, 0.0), (0.0, 400.0), (400.0, 0.0)] {
+            for fit in [true, false] {
+                let drawn = pages("", &layout(width, height), "Tree", "Everyone", fit);
+                assert_eq!(drawn.len(), 1);
+                assert!(!drawn[0].contains("NaN") && !drawn[0].contains("inf"));
+            }
+        }
+    }
+
+    #[test]
+    fn pictures_stay_within_what_a_canvas_holds() {
+        assert_eq!(pixels(842.0, 595.0), (1684, 1190));
+        assert_eq!(pixels(16_000.0, 4_000.0), (8000, 2000));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::ratchet::{RatchetMessage, Session};
+use super::safety_number::safety_number;
+use super::sender_keys::{OwnSenderKey, ReceivedSenderKey, SenderKeyDistribution, SenderKeyMessage};
+use super::x3dh::{self, InitialMessage, OneTimePreKey, PreKeyBundle, SignedPreKey};
+use super::{hkdf, open, random, seal, CryptoError, IdentityKeyPair, PublicIdentity};
+
+// Everything secret this device holds for end-to-end encryption, and the
+// passphrase-based sealing used to keep it encrypted at rest.
+
+pub const PBKDF2_ROUNDS: u32 = 100_000;
+const ONE_TIME_PREKEY_BATCH: u32 = 20;
+
+// A 1:1 message on the wire. The X3DH header rides along until the peer has
+// answered, so they can set up the session from any of our first messages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirectEnvelope {
+    pub initial: Option<InitialMessage>,
+    pub message: RatchetMessage,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyMaterial {
+    identity: IdentityKeyPair,
+    signed_prekey: SignedPreKey,
+    one_time_prekeys: Vec<OneTimePreKey>,
+    next_prekey_id: u32,
+    sessions: BTreeMap<String, Session>,
+    pending_initial: BTreeMap<String, InitialMessage>,
+    // Identity keys seen for each contact, trusted on first use
+    peer_identities: BTreeMap<String, PublicIdentity>,
+    verified: BTreeSet<String>,
+    own_sender_keys: BTreeMap<String, OwnSenderKey>,
+    // Keyed by "group_id/sender_id"
+    received_sender_keys: BTreeMap<String, ReceivedSenderKey>,
+    // Members holding our current sender key, by group
+    #[serde(default)]
+    distributed: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl KeyMaterial {
+    pub fn generate() -> Result<Self, CryptoError> {
+        Self::with_identity(IdentityKeyPair::generate()?)
+    }
+
+    pub fn with_identity(identity: IdentityKeyPair) -> Result<Self, CryptoError> {
+        let signed_prekey = SignedPreKey::generate(&identity, 1)?;
+        let mut material = Self {
+            identity,
+            signed_prekey,
+            one_time_prekeys: Vec::new(),
+            next_prekey_id: 1,
+            sessions: BTreeMap::new(),
+            pending_initial: BTreeMap::new(),
+            peer_identities: BTreeMap::new(),
+            verified: BTreeSet::new(),
+            own_sender_keys: BTreeMap::new(),
+            received_sender_keys: BTreeMap::new(),
+            distributed: BTreeMap::new(),
+        };
+        material.replenish_prekeys()?;
+        Ok(material)
+    }
+
+    pub fn public_identity(&self) -> PublicIdentity {
+        self.identity.public()
+    }
+
+    fn replenish_prekeys(&mut self) -> Result<(), CryptoError> {
+        while (self.one_time_prekeys.len() as u32) < ONE_TIME_PREKEY_BATCH {
+            self.one_time_prekeys.push(OneTimePreKey::generate(self.next_prekey_id)?);
+            self.next_prekey_id += 1;
+        }
+        Ok(())
+    }
+
+    // Bundle to publish; the server hands each one-time prekey out once
+    pub fn bundle(&self) -> PreKeyBundle {
+        PreKeyBundle {
+            identity: self.identity.public(),
+            signed_prekey_id: self.signed_prekey.id,
+            signed_prekey: self.signed_prekey.public,
+            signed_prekey_signature: self.signed_prekey.signature.clone(),
+            one_time_prekey: self.one_time_prekeys.first().map(|k| (k.id, k.public)),
+        }
+    }
+
+    pub fn start_session(&mut self, peer_id: &str, bundle: &PreKeyBundle) -> Result<(), CryptoError> {
+        let (agreement, initial) = x3dh::initiate(&self.identity, bundle)?;
+        self.remember_identity(peer_id, &bundle.identity);
+        let session = Session::initiator(agreement, bundle.signed_prekey)?;
+        self.sessions.insert(peer_id.to_string(), session);
+        self.pending_initial.insert(peer_id.to_string(), initial);
+        Ok(())
+    }
+
+    pub fn has_session(&self, peer_id: &str) -> bool {
+        self.sessions.contains_key(peer_id)
+    }
+
+    pub fn encrypt_direct(&mut self, peer_id: &str, plaintext: &[u8]) -> Result<DirectEnvelope, CryptoError> {
+        let session = self.sessions.get_mut(peer_id).ok_or(CryptoError::NoSession)?;
+        Ok(DirectEnvelope {
+            initial: self.pending_initial.get(peer_id).cloned(),
+            message: session.encrypt(plaintext)?,
+        })
+    }
+
+    // Nothing is committed until the message decrypts, so a forged header
+    // cannot replace the session, burn a one-time prekey or swap the identity
+    pub fn decrypt_direct(&mut self, peer_id: &str, envelope: &DirectEnvelope) -> Result<Vec<u8>, CryptoError> {
+        let initial = envelope.initial.as_ref().filter(|initial| {
+            !self.pending_initial.contains_key(peer_id)
+                && (!self.sessions.contains_key(peer_id) || self.is_new_initial(initial))
+        });
+        let (mut session, used_prekey) = match initial {
+            Some(initial) => self.respond(initial)?,
+            None => (self.sessions.get(peer_id).cloned().ok_or(CryptoError::NoSession)?, None),
+        };
+        let plaintext = session.decrypt(&envelope.message)?;
+
+        // One-time prekeys are consumed by the first session that uses them
+        if let Some(id) = used_prekey {
+            self.one_time_prekeys.retain(|k| k.id != id);
+            self.replenish_prekeys()?;
+        }
+        if let Some(initial) = initial {
+            self.remember_identity(peer_id, &initial.identity);
+        }
+        self.sessions.insert(peer_id.to_string(), session);
+        // They have our session now, so stop attaching the X3DH header
+        self.pending_initial.remove(peer_id);
+        Ok(plaintext)
+    }
+
+    // A header with a one-time prekey we still hold means the peer started over
+    fn is_new_initial(&self, initial: &InitialMessage) -> bool {
+        initial
+            .one_time_prekey_id
+            .is_some_and(|id| self.one_time_prekeys.iter().any(|k| k.id == id))
+    }
+
+    // The session an X3DH header would set up, and the one-time prekey it uses
+    fn respond(&self, initial: &InitialMessage) -> Result<(Session, Option<u32>), CryptoError> {
+        let one_time = match initial.one_time_prekey_id {
+            Some(id) => Some(
+                self.one_time_prekeys
+                    .iter()
+                    .find(|k| k.id == id)
+                    .ok_or(CryptoError::MissingPreKey)?,
+            ),
+            None => None,
+        };
+        let agreement = x3dh::respond(&self.identity, &self.signed_prekey, one_time, initial)?;
+        let session = Session::responder(agreement, self.signed_prekey.secret, self.signed_prekey.public);
+        Ok((session, one_time.map(|k| k.id)))
+    }
+
+    // A changed identity key invalidates any earlier verification
+    fn remember_identity(&mut self, peer_id: &str, identity: &PublicIdentity) {
+        if self.peer_identities.get(peer_id) != Some(identity) {
+            self.verified.remove(peer_id);
+            self.peer_identities.insert(peer_id.to_string(), identity.clone());
+        }
+    }
+
+    pub fn safety_number_with(&self, our_id: &str, peer_id: &str) -> Option<String> {
+        let theirs = self.peer_identities.get(peer_id)?;
+        Some(safety_number(our_id, &self.identity.public(), peer_id, theirs))
+    }
+
+    pub fn is_verified(&self, peer_id: &str) -> bool {
+        self.verified.contains(peer_id)
+    }
+
+    pub fn set_verified(&mut self, peer_id: &str, verified: bool) {
+        if verified && self.peer_identities.contains_key(peer_id) {
+            self.verified.insert(peer_id.to_string());
+        } else {
+            self.verified.remove(peer_id);
+        }
+    }
+
+    // Our sender key state for a group, to be sent to every member
+    pub fn group_distribution(&mut self, group_id: &str) -> Result<SenderKeyDistribution, CryptoError> {
+        Ok(self.own_sender_key(group_id)?.distribution())
+    }
+
+    pub fn encrypt_group(&mut self, group_id: &str, plaintext: &[u8]) -> Result<SenderKeyMessage, CryptoError> {
+        Ok(self.own_sender_key(group_id)?.encrypt(plaintext))
+    }
+
+    fn own_sender_key(&mut self, group_id: &str) -> Result<&mut OwnSenderKey, CryptoError> {
+        if !self.own_sender_keys.contains_key(group_id) {
+            self.own_sender_keys.insert(group_id.to_string(), OwnSenderKey::generate(group_id)?);
+        }
+        Ok(self.own_sender_keys.get_mut(group_id).expect("inserted above"))
+    }
+
+    // Members of `group_id` who still need our sender key. If anyone holding
+    // it has left, the key is replaced so they cannot read what follows.
+    pub fn sender_key_recipients(&mut self, group_id: &str, members: &BTreeSet<String>) -> Vec<String> {
+        let holders = self.distributed.entry(group_id.to_string()).or_default();
+        if !holders.is_subset(members) {
+            holders.clear();
+            self.own_sender_keys.remove(group_id);
+        }
+        members.difference(holders).cloned().collect()
+    }
+
+    pub fn mark_distributed(&mut self, group_id: &str, member_id: &str) {
+        self.distributed
+            .entry(group_id.to_string())
+            .or_default()
+            .insert(member_id.to_string());
+    }
+
+    pub fn accept_distribution(&mut self, sender_id: &str, distribution: SenderKeyDistribution) {
+        let key = format!("{}/{}", distribution.group_id, sender_id);
+        self.received_sender_keys.insert(key, distribution.into());
+    }
+
+    pub fn decrypt_group(&mut self, sender_id: &str, message: &SenderKeyMessage) -> Result<Vec<u8>, CryptoError> {
+        let key = format!("{}/{}", message.group_id, sender_id);
+        self.received_sender_keys
+            .get_mut(&key)
+            .ok_or(CryptoError::NoSession)?
+            .decrypt(message)
+    }
+}
+
+// Key material encrypted under a passphrase
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SealedKeys {
+    pub salt: [u8; 16],
+    pub rounds: u32,
+    pub nonce: [u8; 16],
+    pub ciphertext: Vec<u8>,
+}
+
+pub fn derive_wrapping_key(passphrase: &str, salt: &[u8; 16], rounds: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, rounds)
+}
+
+impl SealedKeys {
+    // Each save uses a fresh nonce, so the wrapping key can be reused
+    pub fn seal(material: &KeyMaterial, wrapping_key: &[u8; 32], salt: [u8; 16], rounds: u32) -> Result<Self, CryptoError> {
+        let nonce = random::<16>()?;
+        let key: [u8; 32] = hkdf(&nonce, wrapping_key, b"JeebonKeyStore");
+        let plaintext = serde_json::to_vec(material).expect("key material serialises");
+        Ok(Self { salt, rounds, nonce, ciphertext: seal(&key, &plaintext, &salt) })
+    }
+
+    pub fn open(&self, wrapping_key: &[u8; 32]) -> Result<KeyMaterial, CryptoError> {
+        let key: [u8; 32] = hkdf(&self.nonce, wrapping_key, b"JeebonKeyStore");
+        let plaintext = open(&key, &self.ciphertext, &self.salt).map_err(|_| CryptoError::WrongPassphrase)?;
+        serde_json::from_slice(&plaintext).map_err(|e| CryptoError::Corrupted(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direct_session_round_trip() {
+        let mut alice = KeyMaterial::generate().unwrap();
+        let mut bob = KeyMaterial::generate().unwrap();
+        alice.start_session("bob", &bob.bundle()).unwrap();
+        assert_eq!(alice.encrypt_direct("carol", b"unused").err(), Some(CryptoError::NoSession));
+
+        let m1 = alice.encrypt_direct("bob", b"hello bob").unwrap();
+        let m2 = alice.encrypt_direct("bob", b"are you there?").unwrap();
+        assert_eq!(bob.decrypt_direct("alice", &m2).unwrap(), b"are you there?");
+        assert_eq!(bob.decrypt_direct("alice", &m1).unwrap(), b"hello bob");
+
+        let reply = bob.encrypt_direct("alice", b"hi alice").unwrap();
+        assert!(reply.initial.is_none());
+        assert_eq!(alice.decrypt_direct("bob", &reply).unwrap(), b"hi alice");
+        let next = alice.encrypt_direct("bob", b"great").unwrap();
+        assert!(next.initial.is_none());
+        assert_eq!(bob.decrypt_direct("alice", &next).unwrap(), b"great");
+
+        let ours = alice.safety_number_with("alice", "bob");
+        assert!(ours.is_some());
+        assert_eq!(ours, bob.safety_number_with("bob", "alice"));
+    }
+
+    #[test]
+    fn forged_first_message_changes_nothing() {
+        let mut alice = KeyMaterial::generate().unwrap();
+        let mut bob = KeyMaterial::generate().unwrap();
+        alice.start_session("bob", &bob.bundle()).unwrap();
+        let mut forged = alice.encrypt_direct("bob", b"hello").unwrap();
+        forged.message.ciphertext[0] ^= 1;
+
+        let prekeys: Vec<u32> = bob.one_time_prekeys.iter().map(|k| k.id).collect();
+        assert!(bob.decrypt_direct("alice", &forged).is_err());
+        assert!(!bob.sessions.contains_key("alice"));
+        assert!(!bob.peer_identities.contains_key("alice"));
+        assert_eq!(bob.one_time_prekeys.iter().map(|k| k.id).collect::<Vec<_>>(), prekeys);
+
+        // The genuine message still works afterwards
+        let genuine = alice.encrypt_direct("bob", b"hello again").unwrap();
+        assert_eq!(bob.decrypt_direct("alice", &genuine).unwrap(), b"hello again");
+        assert!(bob.one_time_prekeys.iter().all(|k| Some(k.id) != genuine.initial.as_ref().unwrap().one_time_prekey_id));
+    }
+
+    #[test]
+    fn forged_restart_keeps_the_existing_session() {
+        let mut alice = KeyMaterial::generate().unwrap();
+        let mut bob = KeyMaterial::generate().unwrap();
+        alice.start_session("bob", &bob.bundle()).unwrap();
+        let first = alice.encrypt_direct("bob", b"hi").unwrap();
+        bob.decrypt_direct("alice", &first).unwrap();
+        bob.set_verified("alice", true);
+
+        // Mallory claims to be Alice starting over with a fresh prekey
+        let mut mallory = KeyMaterial::generate().unwrap();
+        mallory.start_session("bob", &bob.bundle()).unwrap();
+        let mut restart = mallory.encrypt_direct("bob", b"it's me").unwrap();
+        restart.message.ciphertext[0] ^= 1;
+        assert!(bob.decrypt_direct("alice", &restart).is_err());
+        assert!(bob.is_verified("alice"));
+
+        let reply = bob.encrypt_direct("alice", b"still you?").unwrap();
+        assert_eq!(alice.decrypt_direct("bob", &reply).unwrap(), b"still you?");
+    }
+
+    #[test]
+    fn group_messages_round_trip() {
+        let mut alice = KeyMaterial::generate().unwrap();
+        let mut bob = KeyMaterial::generate().unwrap();
+        bob.accept_distribution("alice", alice.group_distribution("family").unwrap());
+        let message = alice.encrypt_group("family", b"dinner at 7").unwrap();
+        assert_eq!(bob.decrypt_group("alice", &message).unwrap(), b"dinner at 7");
+        assert_eq!(bob.decrypt_group("carol", &message), Err(CryptoError::NoSession));
+    }
+
+    #[test]
+    fn sealed_storage_needs_the_passphrase() {
+        let material = KeyMaterial::with_identity(IdentityKeyPair::from_secrets([7; 32], [9; 32])).unwrap();
+        let salt = [3; 16];
+        // A low round count keeps the test fast; production sealing uses PBKDF2_ROUNDS
+        let key = derive_wrapping_key("correct horse", &salt, 1000);
+        let sealed = SealedKeys::seal(&material, &key, salt, 1000).unwrap();
+
+        let opened = sealed.open(&key).unwrap();
+        assert_eq!(opened.public_identity(), material.public_identity());
+        let wrong = derive_wrapping_key("wrong horse", &salt, 1000);
+        assert!(matches!(sealed.open(&wrong), Err(CryptoError::WrongPassphrase)));
+    }
+}
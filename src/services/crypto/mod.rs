@@ -0,0 +1,230 @@
+// End-to-end encryption.
+// X3DH establishes a shared secret with a contact from their published
+// prekeys, a Double Ratchet session then encrypts every 1:1 message with a
+// fresh key, and groups use sender keys distributed over those 1:1 sessions.
+use std::fmt;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::utils::random_bytes;
+
+pub mod keystore;
+pub mod ratchet;
+pub mod safety_number;
+pub mod sender_keys;
+pub mod x3dh;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CryptoError {
+    BadSignature,
+    DecryptionFailed,
+    MissingPreKey,
+    NoSession,
+    TooManySkippedMessages,
+    WrongPassphrase,
+    Corrupted(String),
+    NoRandomness(String),
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::BadSignature => write!(f, "signature verification failed"),
+            CryptoError::DecryptionFailed => write!(f, "message could not be decrypted"),
+            CryptoError::MissingPreKey => write!(f, "referenced prekey is not available"),
+            CryptoError::NoSession => write!(f, "no session with this contact"),
+            CryptoError::TooManySkippedMessages => write!(f, "too many skipped messages"),
+            CryptoError::WrongPassphrase => write!(f, "wrong passphrase"),
+            CryptoError::Corrupted(what) => write!(f, "corrupted key material: {}", what),
+            CryptoError::NoRandomness(why) => write!(f, "no random source for new keys: {}", why),
+        }
+    }
+}
+
+// Long-term identity: an X25519 key for key agreement and an Ed25519 key for
+// signing prekeys and group messages
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IdentityKeyPair {
+    dh_secret: [u8; 32],
+    signing_secret: [u8; 32],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicIdentity {
+    pub dh: [u8; 32],
+    pub signing: [u8; 32],
+}
+
+impl IdentityKeyPair {
+    pub fn generate() -> Result<Self, CryptoError> {
+        Ok(Self::from_secrets(random()?, random()?))
+    }
+
+    pub fn from_secrets(dh_secret: [u8; 32], signing_secret: [u8; 32]) -> Self {
+        Self { dh_secret, signing_secret }
+    }
+
+    pub fn public(&self) -> PublicIdentity {
+        PublicIdentity {
+            dh: x25519_public(&self.dh_secret),
+            signing: SigningKey::from_bytes(&self.signing_secret).verifying_key().to_bytes(),
+        }
+    }
+
+    pub fn dh(&self, their_public: &[u8; 32]) -> [u8; 32] {
+        x25519(&self.dh_secret, their_public)
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        sign(&self.signing_secret, message)
+    }
+}
+
+impl PublicIdentity {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.dh.as_slice(), self.signing.as_slice()].concat()
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), CryptoError> {
+        verify(&self.signing, message, signature)
+    }
+}
+
+// Fresh secret bytes for a key
+pub fn random<const N: usize>() -> Result<[u8; N], CryptoError> {
+    random_bytes().map_err(CryptoError::NoRandomness)
+}
+
+// A fresh X25519 key pair as (secret, public)
+pub fn x25519_keypair() -> Result<([u8; 32], [u8; 32]), CryptoError> {
+    let secret = random::<32>()?;
+    Ok((secret, x25519_public(&secret)))
+}
+
+pub fn x25519_public(secret: &[u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(*secret)).to_bytes()
+}
+
+pub fn x25519(secret: &[u8; 32], their_public: &[u8; 32]) -> [u8; 32] {
+    StaticSecret::from(*secret)
+        .diffie_hellman(&PublicKey::from(*their_public))
+        .to_bytes()
+}
+
+pub fn sign(signing_secret: &[u8; 32], message: &[u8]) -> Vec<u8> {
+    SigningKey::from_bytes(signing_secret).sign(message).to_bytes().to_vec()
+}
+
+pub fn verify(signing_public: &[u8; 32], message: &[u8], signature: &[u8]) -> Result<(), CryptoError> {
+    let key = VerifyingKey::from_bytes(signing_public).map_err(|_| CryptoError::BadSignature)?;
+    let signature = Signature::from_slice(signature).map_err(|_| CryptoError::BadSignature)?;
+    key.verify(message, &signature).map_err(|_| CryptoError::BadSignature)
+}
+
+pub fn hkdf<const N: usize>(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; N] {
+    let mut out = [0u8; N];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut out)
+        .expect("HKDF output length is within bounds");
+    out
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+// Encrypt with a single-use message key. The AEAD key and nonce are both
+// derived from it, so a message key must never be used twice.
+pub fn seal(message_key: &[u8; 32], plaintext: &[u8], associated_data: &[u8]) -> Vec<u8> {
+    let (cipher, nonce) = message_cipher(message_key);
+    cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad: associated_data })
+        .expect("ChaCha20-Poly1305 encryption cannot fail for in-memory buffers")
+}
+
+pub fn open(message_key: &[u8; 32], ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let (cipher, nonce) = message_cipher(message_key);
+    cipher
+        .decrypt(&nonce, Payload { msg: ciphertext, aad: associated_data })
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+fn message_cipher(message_key: &[u8; 32]) -> (ChaCha20Poly1305, Nonce) {
+    let okm: [u8; 44] = hkdf(&[0u8; 32], message_key, b"JeebonMessageKeys");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..32]));
+    (cipher, *Nonce::from_slice(&okm[32..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(input: &str) -> Vec<u8> {
+        (0..input.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&input[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn hex32(input: &str) -> [u8; 32] {
+        hex(input).try_into().unwrap()
+    }
+
+    // RFC 7748 section 6.1
+    #[test]
+    fn x25519_vectors() {
+        let alice = hex32("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = hex32("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = hex32("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_public = hex32("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = hex32("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+        assert_eq!(x25519_public(&alice), alice_public);
+        assert_eq!(x25519_public(&bob), bob_public);
+        assert_eq!(x25519(&alice, &bob_public), shared);
+        assert_eq!(x25519(&bob, &alice_public), shared);
+    }
+
+    // RFC 5869 test case 1
+    #[test]
+    fn hkdf_vectors() {
+        let okm: [u8; 42] = hkdf(&hex("000102030405060708090a0b0c"), &[0x0b; 22], &hex("f0f1f2f3f4f5f6f7f8f9"));
+        let expected = hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865");
+        assert_eq!(okm.as_slice(), expected.as_slice());
+    }
+
+    // RFC 4231 test case 2
+    #[test]
+    fn hmac_vectors() {
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let expected = hex32("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert_eq!(mac, expected);
+    }
+
+    #[test]
+    fn sealed_messages_reject_tampering() {
+        let key = [5; 32];
+        let mut sealed = seal(&key, b"hello", b"ad");
+        assert_eq!(open(&key, &sealed, b"ad").unwrap(), b"hello");
+        assert_eq!(open(&key, &sealed, b"other ad"), Err(CryptoError::DecryptionFailed));
+        sealed[0] ^= 1;
+        assert_eq!(open(&key, &sealed, b"ad"), Err(CryptoError::DecryptionFailed));
+    }
+
+    #[test]
+    fn signatures_bind_the_message() {
+        let identity = IdentityKeyPair::from_secrets([1; 32], [2; 32]);
+        let signature = identity.sign(b"prekey");
+        assert_eq!(identity.public().verify(b"prekey", &signature), Ok(()));
+        assert_eq!(identity.public().verify(b"other", &signature), Err(CryptoError::BadSignature));
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use super::x3dh::Agreement;
+use super::{hkdf, hmac_sha256, open, seal, x25519, x25519_keypair, CryptoError};
+
+// Double Ratchet session, following the Signal specification. Every message
+// gets its own key; a new Diffie-Hellman ratchet step happens whenever the
+// direction of the conversation changes.
+
+const ROOT_INFO: &[u8] = b"JeebonRatchet";
+// Upper bound on message keys kept for out-of-order delivery
+const MAX_SKIP: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub dh: [u8; 32],
+    pub previous_chain_length: u32,
+    pub number: u32,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.dh.to_vec();
+        bytes.extend_from_slice(&self.previous_chain_length.to_be_bytes());
+        bytes.extend_from_slice(&self.number.to_be_bytes());
+        bytes
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RatchetMessage {
+    pub header: Header,
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SkippedKey {
+    dh: [u8; 32],
+    number: u32,
+    key: [u8; 32],
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    dh_secret: [u8; 32],
+    dh_public: [u8; 32],
+    remote_dh: Option<[u8; 32]>,
+    root_key: [u8; 32],
+    sending_chain: Option<[u8; 32]>,
+    receiving_chain: Option<[u8; 32]>,
+    sent: u32,
+    received: u32,
+    previous_chain_length: u32,
+    skipped: Vec<SkippedKey>,
+    associated_data: Vec<u8>,
+}
+
+fn kdf_root(root_key: &[u8; 32], dh_output: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    let okm: [u8; 64] = hkdf(root_key, dh_output, ROOT_INFO);
+    let (root, chain) = okm.split_at(32);
+    (root.try_into().unwrap(), chain.try_into().unwrap())
+}
+
+// Advance a chain key, returning (next chain key, message key)
+fn kdf_chain(chain_key: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    (hmac_sha256(chain_key, &[0x02]), hmac_sha256(chain_key, &[0x01]))
+}
+
+impl Session {
+    // The X3DH initiator sends first, ratcheting against the responder's
+    // signed prekey
+    pub fn initiator(agreement: Agreement, their_signed_prekey: [u8; 32]) -> Result<Self, CryptoError> {
+        let (dh_secret, dh_public) = x25519_keypair()?;
+        let (root_key, sending_chain) = kdf_root(&agreement.shared_secret, &x25519(&dh_secret, &their_signed_prekey));
+        Ok(Self {
+            dh_secret,
+            dh_public,
+            remote_dh: Some(their_signed_prekey),
+            root_key,
+            sending_chain: Some(sending_chain),
+            receiving_chain: None,
+            sent: 0,
+            received: 0,
+            previous_chain_length: 0,
+            skipped: Vec::new(),
+            associated_data: agreement.associated_data,
+        })
+    }
+
+    // The responder starts from its signed prekey and waits for the first message
+    pub fn responder(agreement: Agreement, signed_prekey_secret: [u8; 32], signed_prekey_public: [u8; 32]) -> Self {
+        Self {
+            dh_secret: signed_prekey_secret,
+            dh_public: signed_prekey_public,
+            remote_dh: None,
+            root_key: agreement.shared_secret,
+            sending_chain: None,
+            receiving_chain: None,
+            sent: 0,
+            received: 0,
+            previous_chain_length: 0,
+            skipped: Vec::new(),
+            associated_data: agreement.associated_data,
+        }
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<RatchetMessage, CryptoError> {
+        let chain = self.sending_chain.ok_or(CryptoError::NoSession)?;
+        let (next, message_key) = kdf_chain(&chain);
+        self.sending_chain = Some(next);
+        let header = Header {
+            dh: self.dh_public,
+            previous_chain_length: self.previous_chain_length,
+            number: self.sent,
+        };
+        self.sent += 1;
+        let ciphertext = seal(&message_key, plaintext, &self.message_ad(&header));
+        Ok(RatchetMessage { header, ciphertext })
+    }
+
+    // Decrypt a message. The session is only updated if decryption succeeds,
+    // so forged or corrupted messages cannot desynchronise it.
+    pub fn decrypt(&mut self, message: &RatchetMessage) -> Result<Vec<u8>, CryptoError> {
+        let mut next = self.clone();
+        let plaintext = next.decrypt_in_place(message)?;
+        *self = next;
+        Ok(plaintext)
+    }
+
+    fn decrypt_in_place(&mut self, message: &RatchetMessage) -> Result<Vec<u8>, CryptoError> {
+        let header = &message.header;
+        let ad = self.message_ad(header);
+
+        if let Some(index) = self
+            .skipped
+            .iter()
+            .position(|k| k.dh == header.dh && k.number == header.number)
+        {
+            let key = self.skipped.remove(index).key;
+            return open(&key, &message.ciphertext, &ad);
+        }
+
+        if self.remote_dh != Some(header.dh) {
+            self.skip_to(header.previous_chain_length)?;
+            self.dh_ratchet(header.dh)?;
+        }
+        self.skip_to(header.number)?;
+
+        let chain = self.receiving_chain.ok_or(CryptoError::NoSession)?;
+        let (next, message_key) = kdf_chain(&chain);
+        self.receiving_chain = Some(next);
+        self.received += 1;
+        open(&message_key, &message.ciphertext, &ad)
+    }
+
+    // Store keys for messages of the current receiving chain that have not arrived yet
+    fn skip_to(&mut self, until: u32) -> Result<(), CryptoError> {
+        let (Some(mut chain), Some(remote)) = (self.receiving_chain, self.remote_dh) else {
+            return Ok(());
+        };
+        if until > self.received + MAX_SKIP {
+            return Err(CryptoError::TooManySkippedMessages);
+        }
+        while self.received < until {
+            let (next, key) = kdf_chain(&chain);
+            self.skipped.push(SkippedKey { dh: remote, number: self.received, key });
+            chain = next;
+            self.received += 1;
+        }
+        self.receiving_chain = Some(chain);
+        // Drop the oldest keys once the bound is exceeded
+        let excess = self.skipped.len().saturating_sub(MAX_SKIP as usize);
+        self.skipped.drain(..excess);
+        Ok(())
+    }
+
+    fn dh_ratchet(&mut self, remote: [u8; 32]) -> Result<(), CryptoError> {
+        self.previous_chain_length = self.sent;
+        self.sent = 0;
+        self.received = 0;
+        self.remote_dh = Some(remote);
+
+        let (root_key, receiving_chain) = kdf_root(&self.root_key, &x25519(&self.dh_secret, &remote));
+        let (dh_secret, dh_public) = x25519_keypair()?;
+        let (root_key, sending_chain) = kdf_root(&root_key, &x25519(&dh_secret, &remote));
+
+        self.dh_secret = dh_secret;
+        self.dh_public = dh_public;
+        self.root_key = root_key;
+        self.receiving_chain = Some(receiving_chain);
+        self.sending_chain = Some(sending_chain);
+        Ok(())
+    }
+
+    fn message_ad(&self, header: &Header) -> Vec<u8> {
+        [self.associated_data.clone(), header.to_bytes()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (Session, Session) {
+        let (prekey_secret, prekey_public) = x25519_keypair().unwrap();
+        let agreement = || Agreement { shared_secret: [4; 32], associated_data: b"alice|bob".to_vec() };
+        (
+            Session::initiator(agreement(), prekey_public).unwrap(),
+            Session::responder(agreement(), prekey_secret, prekey_public),
+        )
+    }
+
+    #[test]
+    fn out_of_order_messages_decrypt() {
+        let (mut alice, mut bob) = pair();
+        let m1 = alice.encrypt(b"one").unwrap();
+        let m2 = alice.encrypt(b"two").unwrap();
+        let m3 = alice.encrypt(b"three").unwrap();
+        assert_eq!(bob.decrypt(&m3).unwrap(), b"three");
+        assert_eq!(bob.decrypt(&m1).unwrap(), b"one");
+        assert_eq!(bob.decrypt(&m2).unwrap(), b"two");
+    }
+
+    #[test]
+    fn replies_ratchet_forward() {
+        let (mut alice, mut bob) = pair();
+        assert!(matches!(bob.encrypt(b"too early"), Err(CryptoError::NoSession)));
+        let first = alice.encrypt(b"hi").unwrap();
+        bob.decrypt(&first).unwrap();
+
+        let reply = bob.encrypt(b"hello").unwrap();
+        assert_ne!(reply.header.dh, first.header.dh);
+        assert_eq!(alice.decrypt(&reply).unwrap(), b"hello");
+        let next = alice.encrypt(b"again").unwrap();
+        assert_ne!(next.header.dh, first.header.dh);
+        assert_eq!(next.header.previous_chain_length, 1);
+        assert_eq!(bob.decrypt(&next).unwrap(), b"again");
+    }
+
+    #[test]
+    fn replays_and_forgeries_leave_the_session_intact() {
+        let (mut alice, mut bob) = pair();
+        let message = alice.encrypt(b"once").unwrap();
+        bob.decrypt(&message).unwrap();
+        assert_eq!(bob.decrypt(&message), Err(CryptoError::DecryptionFailed));
+
+        let mut forged = alice.encrypt(b"original").unwrap();
+        forged.ciphertext[0] ^= 1;
+        assert_eq!(bob.decrypt(&forged), Err(CryptoError::DecryptionFailed));
+        // A forged ratchet key must not move the session either
+        let mut forged = alice.encrypt(b"original").unwrap();
+        forged.header.dh = [9; 32];
+        assert!(bob.decrypt(&forged).is_err());
+
+        let after = alice.encrypt(b"still fine").unwrap();
+        assert_eq!(bob.decrypt(&after).unwrap(), b"still fine");
+    }
+
+    #[test]
+    fn skipping_is_bounded() {
+        let (mut alice, mut bob) = pair();
+        bob.decrypt(&alice.encrypt(b"first").unwrap()).unwrap();
+        let mut far = alice.encrypt(b"far ahead").unwrap();
+        far.header.number = MAX_SKIP + 2;
+        assert_eq!(bob.decrypt(&far), Err(CryptoError::TooManySkippedMessages));
+    }
+}
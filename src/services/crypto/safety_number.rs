@@ -0,0 +1,81 @@
+use sha2::{Digest, Sha512};
+
+use super::PublicIdentity;
+
+// Safety numbers let two people confirm out of band that they hold each
+// other's real identity keys. Each side contributes 30 digits derived from
+// its identity key and user id; both halves are shown in the same order on
+// both devices, so the full 60 digits match when nobody is in the middle.
+
+const VERSION: [u8; 2] = [0, 0];
+const ITERATIONS: usize = 5200;
+
+// The 30-digit half belonging to one identity
+pub fn fingerprint(user_id: &str, identity: &PublicIdentity) -> String {
+    let key = identity.to_bytes();
+    let mut hash = Sha512::new()
+        .chain_update(VERSION)
+        .chain_update(&key)
+        .chain_update(user_id.as_bytes())
+        .finalize();
+    for _ in 0..ITERATIONS {
+        hash = Sha512::new().chain_update(hash).chain_update(&key).finalize();
+    }
+    hash[..30]
+        .chunks(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect()
+}
+
+// The 60-digit number both parties compare
+pub fn safety_number(
+    our_id: &str,
+    ours: &PublicIdentity,
+    their_id: &str,
+    theirs: &PublicIdentity,
+) -> String {
+    let mut halves = [fingerprint(our_id, ours), fingerprint(their_id, theirs)];
+    halves.sort();
+    halves.concat()
+}
+
+// Split into groups of five digits for display
+pub fn display_groups(number: &str) -> Vec<String> {
+    number
+        .as_bytes()
+        .chunks(5)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::crypto::IdentityKeyPair;
+
+    #[test]
+    fn both_sides_see_the_same_number() {
+        let alice = IdentityKeyPair::from_secrets([1; 32], [2; 32]).public();
+        let bob = IdentityKeyPair::from_secrets([3; 32], [4; 32]).public();
+        let ours = safety_number("alice", &alice, "bob", &bob);
+        assert_eq!(ours, safety_number("bob", &bob, "alice", &alice));
+        assert_eq!(ours.len(), 60);
+        assert!(ours.bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(display_groups(&ours).len(), 12);
+    }
+
+    #[test]
+    fn number_changes_with_the_identity_key() {
+        let alice = IdentityKeyPair::from_secrets([1; 32], [2; 32]).public();
+        let bob = IdentityKeyPair::from_secrets([3; 32], [4; 32]).public();
+        let mallory = IdentityKeyPair::from_secrets([5; 32], [6; 32]).public();
+        assert_ne!(
+            safety_number("alice", &alice, "bob", &bob),
+            safety_number("alice", &alice, "bob", &mallory)
+        );
+        assert_ne!(fingerprint("alice", &alice), fingerprint("bob", &alice));
+    }
+}
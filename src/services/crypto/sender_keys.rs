@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use super::{hmac_sha256, open, random, seal, sign, verify, CryptoError};
+
+// Sender keys for group messaging. Each member keeps one symmetric chain per
+// group and hands its current state to the other members over their 1:1
+// sessions. A group message is then encrypted once instead of once per member,
+// and signed so members cannot impersonate each other.
+
+// Bound on how far a receiver will fast-forward a chain
+const MAX_FORWARD: u32 = 2000;
+
+// Shared with each group member over an encrypted 1:1 session
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SenderKeyDistribution {
+    pub group_id: String,
+    pub iteration: u32,
+    pub chain_key: [u8; 32],
+    pub signing_public: [u8; 32],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SenderKeyMessage {
+    pub group_id: String,
+    pub iteration: u32,
+    pub ciphertext: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SenderKeyMessage {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.group_id.as_bytes().to_vec();
+        bytes.extend_from_slice(&self.iteration.to_be_bytes());
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+}
+
+fn step(chain_key: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    (hmac_sha256(chain_key, &[0x02]), hmac_sha256(chain_key, &[0x01]))
+}
+
+// Our own sending chain for one group
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OwnSenderKey {
+    group_id: String,
+    iteration: u32,
+    chain_key: [u8; 32],
+    signing_secret: [u8; 32],
+}
+
+impl OwnSenderKey {
+    pub fn generate(group_id: &str) -> Result<Self, CryptoError> {
+        Ok(Self {
+            group_id: group_id.to_string(),
+            iteration: 0,
+            chain_key: random()?,
+            signing_secret: random()?,
+        })
+    }
+
+    pub fn distribution(&self) -> SenderKeyDistribution {
+        SenderKeyDistribution {
+            group_id: self.group_id.clone(),
+            iteration: self.iteration,
+            chain_key: self.chain_key,
+            signing_public: ed25519_dalek::SigningKey::from_bytes(&self.signing_secret)
+                .verifying_key()
+                .to_bytes(),
+        }
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> SenderKeyMessage {
+        let (next, message_key) = step(&self.chain_key);
+        let mut message = SenderKeyMessage {
+            group_id: self.group_id.clone(),
+            iteration: self.iteration,
+            ciphertext: seal(&message_key, plaintext, self.group_id.as_bytes()),
+            signature: Vec::new(),
+        };
+        message.signature = sign(&self.signing_secret, &message.signed_bytes());
+        self.chain_key = next;
+        self.iteration += 1;
+        message
+    }
+}
+
+// Another member's chain, as received in their distribution message
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReceivedSenderKey {
+    distribution: SenderKeyDistribution,
+    skipped: Vec<(u32, [u8; 32])>,
+}
+
+impl From<SenderKeyDistribution> for ReceivedSenderKey {
+    fn from(distribution: SenderKeyDistribution) -> Self {
+        Self { distribution, skipped: Vec::new() }
+    }
+}
+
+impl ReceivedSenderKey {
+    pub fn decrypt(&mut self, message: &SenderKeyMessage) -> Result<Vec<u8>, CryptoError> {
+        let state = &mut self.distribution;
+        if message.group_id != state.group_id {
+            return Err(CryptoError::NoSession);
+        }
+        verify(&state.signing_public, &message.signed_bytes(), &message.signature)?;
+
+        // A late message uses a key kept when the chain was fast-forwarded
+        if message.iteration < state.iteration {
+            let index = self
+                .skipped
+                .iter()
+                .position(|(i, _)| *i == message.iteration)
+                .ok_or(CryptoError::DecryptionFailed)?;
+            let plaintext = open(&self.skipped[index].1, &message.ciphertext, state.group_id.as_bytes())?;
+            self.skipped.remove(index);
+            return Ok(plaintext);
+        }
+        if message.iteration - state.iteration > MAX_FORWARD {
+            return Err(CryptoError::TooManySkippedMessages);
+        }
+
+        let mut chain = state.chain_key;
+        let mut skipped = Vec::new();
+        for iteration in state.iteration..message.iteration {
+            let (next, key) = step(&chain);
+            skipped.push((iteration, key));
+            chain = next;
+        }
+        let (next, key) = step(&chain);
+        // Decrypt before committing so a bad message cannot advance the chain
+        let plaintext = open(&key, &message.ciphertext, state.group_id.as_bytes())?;
+        state.chain_key = next;
+        state.iteration = message.iteration + 1;
+        self.skipped.extend(skipped);
+        let excess = self.skipped.len().saturating_sub(MAX_FORWARD as usize);
+        self.skipped.drain(..excess);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_messages_use_kept_keys() {
+        let mut alice = OwnSenderKey::generate("family").unwrap();
+        let mut bob = ReceivedSenderKey::from(alice.distribution());
+        let m1 = alice.encrypt(b"dinner at 7");
+        let m2 = alice.encrypt(b"bring dessert");
+        assert_eq!(bob.decrypt(&m2).unwrap(), b"bring dessert");
+        assert_eq!(bob.decrypt(&m1).unwrap(), b"dinner at 7");
+        // Each kept key is used once
+        assert_eq!(bob.decrypt(&m1), Err(CryptoError::DecryptionFailed));
+    }
+
+    #[test]
+    fn members_joining_later_cannot_read_earlier_messages() {
+        let mut alice = OwnSenderKey::generate("family").unwrap();
+        let before = alice.encrypt(b"before");
+        let mut carol = ReceivedSenderKey::from(alice.distribution());
+        assert_eq!(carol.decrypt(&before), Err(CryptoError::DecryptionFailed));
+        assert_eq!(carol.decrypt(&alice.encrypt(b"after")).unwrap(), b"after");
+    }
+
+    #[test]
+    fn forged_messages_are_rejected() {
+        let mut alice = OwnSenderKey::generate("family").unwrap();
+        let mut bob = ReceivedSenderKey::from(alice.distribution());
+
+        let mut forged = alice.encrypt(b"signed");
+        forged.ciphertext[0] ^= 1;
+        assert_eq!(bob.decrypt(&forged), Err(CryptoError::BadSignature));
+
+        let mut other_group = alice.encrypt(b"signed");
+        other_group.group_id = "work".to_string();
+        assert_eq!(bob.decrypt(&other_group), Err(CryptoError::NoSession));
+
+        let mut far = alice.encrypt(b"far");
+        far.iteration += MAX_FORWARD + 1;
+        far.signature = Vec::new();
+        assert!(bob.decrypt(&far).is_err());
+        assert_eq!(bob.decrypt(&alice.encrypt(b"next")).unwrap(), b"next");
+    }
+}
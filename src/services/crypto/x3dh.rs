@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+use super::{hkdf, x25519, x25519_keypair, CryptoError, IdentityKeyPair, PublicIdentity};
+
+// Extended Triple Diffie-Hellman key agreement, following the Signal
+// specification with X25519 and SHA-256.
+
+const INFO: &[u8] = b"JeebonX3DH";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SignedPreKey {
+    pub id: u32,
+    pub secret: [u8; 32],
+    pub public: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+impl SignedPreKey {
+    pub fn generate(identity: &IdentityKeyPair, id: u32) -> Result<Self, CryptoError> {
+        let (secret, public) = x25519_keypair()?;
+        Ok(Self { id, secret, public, signature: identity.sign(&public) })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OneTimePreKey {
+    pub id: u32,
+    pub secret: [u8; 32],
+    pub public: [u8; 32],
+}
+
+impl OneTimePreKey {
+    pub fn generate(id: u32) -> Result<Self, CryptoError> {
+        let (secret, public) = x25519_keypair()?;
+        Ok(Self { id, secret, public })
+    }
+}
+
+// What a contact publishes so others can start a session while they are offline
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreKeyBundle {
+    pub identity: PublicIdentity,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: [u8; 32],
+    pub signed_prekey_signature: Vec<u8>,
+    pub one_time_prekey: Option<(u32, [u8; 32])>,
+}
+
+// Sent alongside the first ratchet message so the responder can derive the
+// same secret
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InitialMessage {
+    pub identity: PublicIdentity,
+    pub ephemeral: [u8; 32],
+    pub signed_prekey_id: u32,
+    pub one_time_prekey_id: Option<u32>,
+}
+
+pub struct Agreement {
+    pub shared_secret: [u8; 32],
+    // Both identities, bound into every message of the session
+    pub associated_data: Vec<u8>,
+}
+
+fn derive(dh_outputs: &[[u8; 32]]) -> [u8; 32] {
+    // 32 bytes of 0xFF precede the DH outputs to separate X25519 from XEdDSA
+    let mut ikm = vec![0xFF; 32];
+    for output in dh_outputs {
+        ikm.extend_from_slice(output);
+    }
+    hkdf(&[0u8; 32], &ikm, INFO)
+}
+
+fn associated_data(initiator: &PublicIdentity, responder: &PublicIdentity) -> Vec<u8> {
+    [initiator.to_bytes(), responder.to_bytes()].concat()
+}
+
+// Start a session with the owner of `bundle`
+pub fn initiate(ours: &IdentityKeyPair, bundle: &PreKeyBundle) -> Result<(Agreement, InitialMessage), CryptoError> {
+    bundle
+        .identity
+        .verify(&bundle.signed_prekey, &bundle.signed_prekey_signature)?;
+
+    let (ephemeral_secret, ephemeral) = x25519_keypair()?;
+    let mut outputs = vec![
+        ours.dh(&bundle.signed_prekey),
+        x25519(&ephemeral_secret, &bundle.identity.dh),
+        x25519(&ephemeral_secret, &bundle.signed_prekey),
+    ];
+    if let Some((_, one_time)) = &bundle.one_time_prekey {
+        outputs.push(x25519(&ephemeral_secret, one_time));
+    }
+
+    let agreement = Agreement {
+        shared_secret: derive(&outputs),
+        associated_data: associated_data(&ours.public(), &bundle.identity),
+    };
+    let initial = InitialMessage {
+        identity: ours.public(),
+        ephemeral,
+        signed_prekey_id: bundle.signed_prekey_id,
+        one_time_prekey_id: bundle.one_time_prekey.map(|(id, _)| id),
+    };
+    Ok((agreement, initial))
+}
+
+// Derive the initiator's secret from our prekeys
+pub fn respond(
+    ours: &IdentityKeyPair,
+    signed_prekey: &SignedPreKey,
+    one_time_prekey: Option<&OneTimePreKey>,
+    initial: &InitialMessage,
+) -> Result<Agreement, CryptoError> {
+    if initial.signed_prekey_id != signed_prekey.id
+        || initial.one_time_prekey_id != one_time_prekey.map(|k| k.id)
+    {
+        return Err(CryptoError::MissingPreKey);
+    }
+
+    let mut outputs = vec![
+        x25519(&signed_prekey.secret, &initial.identity.dh),
+        ours.dh(&initial.ephemeral),
+        x25519(&signed_prekey.secret, &initial.ephemeral),
+    ];
+    if let Some(one_time) = one_time_prekey {
+        outputs.push(x25519(&one_time.secret, &initial.ephemeral));
+    }
+
+    Ok(Agreement {
+        shared_secret: derive(&outputs),
+        associated_data: associated_data(&initial.identity, &ours.public()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(identity: &IdentityKeyPair, signed: &SignedPreKey, one_time: Option<&OneTimePreKey>) -> PreKeyBundle {
+        PreKeyBundle {
+            identity: identity.public(),
+            signed_prekey_id: signed.id,
+            signed_prekey: signed.public,
+            signed_prekey_signature: signed.signature.clone(),
+            one_time_prekey: one_time.map(|k| (k.id, k.public)),
+        }
+    }
+
+    #[test]
+    fn both_sides_derive_the_same_secret() {
+        let alice = IdentityKeyPair::generate().unwrap();
+        let bob = IdentityKeyPair::generate().unwrap();
+        let signed = SignedPreKey::generate(&bob, 1).unwrap();
+        let one_time = OneTimePreKey::generate(7).unwrap();
+
+        for one_time in [None, Some(&one_time)] {
+            let (ours, initial) = initiate(&alice, &bundle(&bob, &signed, one_time)).unwrap();
+            let theirs = respond(&bob, &signed, one_time, &initial).unwrap();
+            assert_eq!(ours.shared_secret, theirs.shared_secret);
+            assert_eq!(ours.associated_data, theirs.associated_data);
+            assert_eq!(initial.one_time_prekey_id, one_time.map(|k| k.id));
+        }
+    }
+
+    #[test]
+    fn unsigned_prekeys_are_refused() {
+        let alice = IdentityKeyPair::generate().unwrap();
+        let bob = IdentityKeyPair::generate().unwrap();
+        let mallory = IdentityKeyPair::generate().unwrap();
+        // A prekey signed by someone other than the bundle's identity
+        let signed = SignedPreKey::generate(&mallory, 1).unwrap();
+        let result = initiate(&alice, &bundle(&bob, &signed, None));
+        assert!(matches!(result, Err(CryptoError::BadSignature)));
+    }
+
+    #[test]
+    fn responder_needs_the_referenced_prekeys() {
+        let alice = IdentityKeyPair::generate().unwrap();
+        let bob = IdentityKeyPair::generate().unwrap();
+        let signed = SignedPreKey::generate(&bob, 1).unwrap();
+        let one_time = OneTimePreKey::generate(7).unwrap();
+        let (_, initial) = initiate(&alice, &bundle(&bob, &signed, Some(&one_time))).unwrap();
+
+        let result = respond(&bob, &signed, None, &initial);
+        assert!(matches!(result, Err(CryptoError::MissingPreKey)));
+        let rotated = SignedPreKey::generate(&bob, 2).unwrap();
+        let result = respond(&bob, &rotated, Some(&one_time), &initial);
+        assert!(matches!(result, Err(CryptoError::MissingPreKey)));
+    }
+}
@@ -0,0 +1,438 @@
+use crate::models::contact::ContactCard;
+use crate::services::sync::Stamp;
+use crate::utils::format::days_in_month;
+
+// Reading contact lists kept in spreadsheets: character encoding and
+// delimiter detection, RFC 4180 parsing and turning mapped rows into cards.
+
+// Lines looked at when guessing the delimiter
+const SNIFF_LINES: usize = 10;
+const MIN_PHONE_DIGITS: usize = 7;
+
+// Windows-1252 characters for bytes 0x80-0x9F; the rest match Latin-1
+const CP1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 4] = [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Windows1252];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    // Byte order marks settle it; otherwise anything that is valid UTF-8 is
+    // taken as UTF-8 and the rest as the usual Windows spreadsheet encoding
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Encoding::Utf8,
+            [0xFF, 0xFE, ..] => Encoding::Utf16Le,
+            [0xFE, 0xFF, ..] => Encoding::Utf16Be,
+            _ if std::str::from_utf8(bytes).is_ok() => Encoding::Utf8,
+            _ => Encoding::Windows1252,
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes)).into_owned(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let bytes = bytes.strip_prefix(&[0xFF, 0xFE]).or(bytes.strip_prefix(&[0xFE, 0xFF])).unwrap_or(bytes);
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Semicolon,
+    Tab,
+    Pipe,
+}
+
+impl Delimiter {
+    pub const ALL: [Delimiter; 4] = [Delimiter::Comma, Delimiter::Semicolon, Delimiter::Tab, Delimiter::Pipe];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Delimiter::Comma => "Comma",
+            Delimiter::Semicolon => "Semicolon",
+            Delimiter::Tab => "Tab",
+            Delimiter::Pipe => "Pipe",
+        }
+    }
+
+    fn char(&self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Semicolon => ';',
+            Delimiter::Tab => '\t',
+            Delimiter::Pipe => '|',
+        }
+    }
+
+    // The delimiter that splits the first lines into the same, largest
+    // number of columns
+    pub fn detect(text: &str) -> Self {
+        let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(SNIFF_LINES).collect();
+        Delimiter::ALL
+            .into_iter()
+            .map(|delimiter| {
+                let counts: Vec<usize> = lines.iter().map(|l| count_outside_quotes(l, delimiter.char())).collect();
+                let consistent = counts.windows(2).all(|w| w[0] == w[1]);
+                let columns = counts.first().copied().unwrap_or(0);
+                (delimiter, (columns > 0, consistent, columns))
+            })
+            .max_by_key(|(_, score)| *score)
+            .filter(|(_, (found, _, _))| *found)
+            .map_or(Delimiter::Comma, |(delimiter, _)| delimiter)
+    }
+}
+
+fn count_outside_quotes(line: &str, delimiter: char) -> usize {
+    let mut quoted = false;
+    line.chars()
+        .filter(|&c| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == delimiter && !quoted
+        })
+        .count()
+}
+
+// A row of fields with the line of the file it starts on, counting from 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+// Rows of fields. Quoted fields may hold delimiters, doubled quotes and
+// line breaks. Blank rows are left out.
+pub fn parse(text: &str, delimiter: Delimiter) -> Vec<Row> {
+    let separator = delimiter.char();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let (mut line, mut start) = (1, 1);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => {
+                field.push(c);
+                line += 1;
+            }
+            c if quoted => field.push(c),
+            c if c == separator => fields.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                rows.push(Row { line: start, fields: std::mem::take(&mut fields) });
+                line += 1;
+                start = line;
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        rows.push(Row { line: start, fields });
+    }
+    rows.retain(|r| r.fields.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContactField {
+    Ignore,
+    Name,
+    GivenName,
+    FamilyName,
+    Phone,
+    Email,
+    Organization,
+    Title,
+    Birthday,
+    Note,
+}
+
+impl ContactField {
+    pub const ALL: [ContactField; 10] = [
+        ContactField::Ignore,
+        ContactField::Name,
+        ContactField::GivenName,
+        ContactField::FamilyName,
+        ContactField::Phone,
+        ContactField::Email,
+        ContactField::Organization,
+        ContactField::Title,
+        ContactField::Birthday,
+        ContactField::Note,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContactField::Ignore => "Don't import",
+            ContactField::Name => "Full name",
+            ContactField::GivenName => "First name",
+            ContactField::FamilyName => "Last name",
+            ContactField::Phone => "Phone",
+            ContactField::Email => "Email",
+            ContactField::Organization => "Organisation",
+            ContactField::Title => "Job title",
+            ContactField::Birthday => "Birthday",
+            ContactField::Note => "Note",
+        }
+    }
+
+    // A first guess from the column heading, in English or Bangla
+    pub fn guess(heading: &str) -> Self {
+        let heading = heading.trim().to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|w| heading.contains(w));
+        if has(&["first", "given", "forename"]) {
+            ContactField::GivenName
+        } else if has(&["last", "family", "surname"]) {
+            ContactField::FamilyName
+        } else if has(&["e-mail", "email", "mail", "ইমেইল"]) {
+            ContactField::Email
+        } else if has(&["phone", "mobile", "tel", "cell", "number", "ফোন", "মোবাইল"]) {
+            ContactField::Phone
+        } else if has(&["birth", "bday", "dob", "জন্ম"]) {
+            ContactField::Birthday
+        } else if has(&["org", "company", "employer"]) {
+            ContactField::Organization
+        } else if has(&["title", "position", "role", "job"]) {
+            ContactField::Title
+        } else if has(&["note", "comment", "relation", "সম্পর্ক"]) {
+            ContactField::Note
+        } else if has(&["name", "নাম"]) {
+            ContactField::Name
+        } else {
+            ContactField::Ignore
+        }
+    }
+}
+
+// One data row after mapping, with what is wrong with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowCheck {
+    // The line of the file the row starts on
+    pub line: usize,
+    pub card: ContactCard,
+    pub errors: Vec<String>,
+}
+
+impl RowCheck {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+pub fn check_rows(rows: &[Row], mapping: &[ContactField], has_header: bool) -> Vec<RowCheck> {
+    let skip = usize::from(has_header);
+    rows.iter()
+        .skip(skip)
+        .map(|row| {
+            let mut card = ContactCard { stamp: Stamp::now(), ..ContactCard::default() };
+            let mut errors = Vec::new();
+            for (field, value) in mapping.iter().zip(row.fields.iter()) {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                match field {
+                    ContactField::Ignore => {}
+                    ContactField::Name => card.name = value.to_string(),
+                    ContactField::GivenName => card.given_name = value.to_string(),
+                    ContactField::FamilyName => card.family_name = value.to_string(),
+                    ContactField::Phone => {
+                        if value.chars().filter(|c| c.is_ascii_digit()).count() < MIN_PHONE_DIGITS
+                            || value.chars().any(|c| c.is_alphabetic())
+                        {
+                            errors.push(format!("\"{}\" isn't a phone number", value));
+                        } else {
+                            card.phones.push(value.to_string());
+                        }
+                    }
+                    ContactField::Email => {
+                        let valid = value
+                            .split_once('@')
+                            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !domain.ends_with('.'));
+                        if valid && !value.contains(char::is_whitespace) {
+                            card.emails.push(value.to_string());
+                        } else {
+                            errors.push(format!("\"{}\" isn't an email address", value));
+                        }
+                    }
+                    ContactField::Organization => card.organization = value.to_string(),
+                    ContactField::Title => card.title = value.to_string(),
+                    ContactField::Birthday => match normalise_date(value) {
+                        Some(date) => card.birthday = date,
+                        None => errors.push(format!("\"{}\" isn't a date (use YYYY-MM-DD or DD/MM/YYYY)", value)),
+                    },
+                    ContactField::Note => {
+                        if !card.note.is_empty() {
+                            card.note.push('\n');
+                        }
+                        card.note.push_str(value);
+                    }
+                }
+            }
+            if card.name.is_empty() {
+                card.name = [card.given_name.as_str(), card.family_name.as_str()]
+                    .into_iter()
+                    .filter(|n| !n.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+            }
+            if card.name.is_empty() {
+                errors.push("No name".to_string());
+            }
+            RowCheck { line: row.line, card, errors }
+        })
+        .collect()
+}
+
+// "1990-04-21", "21/04/1990", "21.04.1990" or "21-04-1990" as "1990-04-21"
+fn normalise_date(value: &str) -> Option<String> {
+    let parts: Vec<&str> = value.split(['-', '/', '.']).map(str::trim).collect();
+    let [a, b, c] = parts.as_slice() else {
+        return None;
+    };
+    let (year, month, day) = if a.len() == 4 { (a, b, c) } else { (c, b, a) };
+    let (year, month, day): (u32, u32, u32) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let valid = (1000..=9999).contains(&year) && (1..=12).contains(&month) && (1..=days_in_month(year as i64, month)).contains(&day);
+    valid.then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(rows: &[Row]) -> Vec<Vec<&str>> {
+        rows.iter().map(|r| r.fields.iter().map(String::as_str).collect()).collect()
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() }).collect()
+    }
+
+    #[test]
+    fn encodings_are_told_apart() {
+        let text = "নাম,ফোন\nRahim,01711\n";
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(utf16(text, false));
+        let mut be = vec![0xFE, 0xFF];
+        be.extend(utf16(text, true));
+        let mut bom = vec![0xEF, 0xBB, 0xBF];
+        bom.extend(text.bytes());
+        for (bytes, encoding) in [(le, Encoding::Utf16Le), (be, Encoding::Utf16Be), (bom, Encoding::Utf8), (text.as_bytes().to_vec(), Encoding::Utf8)] {
+            assert_eq!(Encoding::detect(&bytes), encoding);
+            assert_eq!(encoding.decode(&bytes), text);
+        }
+        // Not UTF-8, so the Windows code page, where 0x93 and 0x94 are quotes
+        let windows = b"Jos\xE9,\x93Boss\x94,\x80";
+        assert_eq!(Encoding::detect(windows), Encoding::Windows1252);
+        assert_eq!(Encoding::Windows1252.decode(windows), "José,“Boss”,€");
+    }
+
+    #[test]
+    fn the_delimiter_is_the_one_that_splits_evenly() {
+        assert_eq!(Delimiter::detect("name,phone\nRahim,017\n"), Delimiter::Comma);
+        assert_eq!(Delimiter::detect("name;phone;note\nRahim;017;\"a, b\"\n"), Delimiter::Semicolon);
+        assert_eq!(Delimiter::detect("name\tphone\nRahim, Jr\t017\n"), Delimiter::Tab);
+        assert_eq!(Delimiter::detect("name|phone\n\nRahim|017\n"), Delimiter::Pipe);
+        // Delimiters inside quotes don't count, and one column falls back to commas
+        assert_eq!(Delimiter::detect("\"a;b\",c\n\"d;e\",f"), Delimiter::Comma);
+        assert_eq!(Delimiter::detect("name\nRahim"), Delimiter::Comma);
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_quotes_and_line_breaks() {
+        let text = "name,note\r\n\"Uddin, Rahim\",\"said \"\"hi\"\"\nand left\"\r\nKarim,\n";
+        let rows = parse(text, Delimiter::Comma);
+        assert_eq!(fields(&rows), [vec!["name", "note"], vec!["Uddin, Rahim", "said \"hi\"\nand left"], vec!["Karim", ""]]);
+        assert_eq!(fields(&parse("a,b", Delimiter::Comma)), [vec!["a", "b"]]);
+        assert!(parse("", Delimiter::Comma).is_empty());
+    }
+
+    #[test]
+    fn rows_know_the_line_they_start_on() {
+        let text = "name,note\n\n,\nRahim,\"two\nlines\"\n\nKarim,x\n";
+        let rows = parse(text, Delimiter::Comma);
+        assert_eq!(rows.iter().map(|r| r.line).collect::<Vec<_>>(), [1, 4, 7]);
+        let checks = check_rows(&rows, &[ContactField::Name, ContactField::Note], true);
+        assert_eq!(checks.iter().map(|c| c.line).collect::<Vec<_>>(), [4, 7]);
+        assert_eq!(checks[0].card.note, "two\nlines");
+    }
+
+    #[test]
+    fn rows_are_checked_field_by_field() {
+        let mapping = [ContactField::GivenName, ContactField::FamilyName, ContactField::Phone, ContactField::Email, ContactField::Birthday];
+        let row = |values: [&str; 5]| Row { line: 2, fields: values.iter().map(|v| v.to_string()).collect() };
+        let rows = [
+            row(["Rahim", "Uddin", "+880 1711-000000", "rahim@example.com", "21/04/1990"]),
+            row(["", "", "12345", "rahim@example", "31/02/1990"]),
+            row(["Karim", "", "call me", "a b@example.com", "1990-04-31"]),
+        ];
+        let checks = check_rows(&rows, &mapping, false);
+        assert!(checks[0].is_valid());
+        assert_eq!(checks[0].card.name, "Rahim Uddin");
+        assert_eq!(checks[0].card.birthday, "1990-04-21");
+        assert_eq!(checks[1].errors.len(), 4);
+        assert_eq!(checks[1].errors.last().unwrap(), "No name");
+        assert_eq!(checks[2].errors.len(), 3);
+        assert_eq!(checks[2].card.name, "Karim");
+    }
+
+    #[test]
+    fn dates_must_exist() {
+        assert_eq!(normalise_date("1990-04-21"), Some("1990-04-21".to_string()));
+        assert_eq!(normalise_date("21.4.1990"), Some("1990-04-21".to_string()));
+        assert_eq!(normalise_date("29/02/2000"), Some("2000-02-29".to_string()));
+        assert_eq!(normalise_date("29/02/1900"), None);
+        assert_eq!(normalise_date("31/02/1990"), None);
+        assert_eq!(normalise_date("31-04-1990"), None);
+        assert_eq!(normalise_date("1990-13-01"), None);
+        assert_eq!(normalise_date("21/04/90"), None);
+        assert_eq!(normalise_date("April 21"), None);
+    }
+}
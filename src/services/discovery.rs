@@ -0,0 +1,371 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+// Directory of public circles. Queries go to the API when JEEBON_API_URL is
+// set at build time; otherwise a built-in directory answers them the same
+// way, cursors included.
+
+const API_URL: Option<&str> = option_env!("JEEBON_API_URL");
+pub const PAGE_SIZE: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Technology,
+    Community,
+    Education,
+    Hobbies,
+    Family,
+    Culture,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Technology,
+        Category::Community,
+        Category::Education,
+        Category::Hobbies,
+        Category::Family,
+        Category::Culture,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Technology => "Technology",
+            Category::Community => "Community",
+            Category::Education => "Education",
+            Category::Hobbies => "Hobbies",
+            Category::Family => "Family",
+            Category::Culture => "Culture",
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Category::Technology => "technology",
+            Category::Community => "community",
+            Category::Education => "education",
+            Category::Hobbies => "hobbies",
+            Category::Family => "family",
+            Category::Culture => "culture",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    // Most new members this week
+    Trending,
+    MostMembers,
+}
+
+impl SortOrder {
+    fn key(&self) -> &'static str {
+        match self {
+            SortOrder::Trending => "trending",
+            SortOrder::MostMembers => "members",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+    pub category: Category,
+    pub member_count: u32,
+    pub weekly_joins: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct DirectoryPage {
+    pub circles: Vec<DirectoryEntry>,
+    // Pass back to get the next page; None on the last one
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectoryQuery {
+    pub text: String,
+    pub category: Option<Category>,
+    pub sort: SortOrder,
+    pub cursor: Option<String>,
+}
+
+pub async fn search(query: &DirectoryQuery) -> Result<DirectoryPage, String> {
+    match API_URL {
+        Some(api) => {
+            let mut params = vec![("sort", query.sort.key().to_string()), ("limit", PAGE_SIZE.to_string())];
+            if !query.text.trim().is_empty() {
+                params.push(("q", query.text.trim().to_string()));
+            }
+            if let Some(category) = query.category {
+                params.push(("category", category.key().to_string()));
+            }
+            if let Some(cursor) = &query.cursor {
+                params.push(("cursor", cursor.clone()));
+            }
+            let response = reqwest::Client::new()
+                .get(format!("{}/circles/public", api))
+                .query(&params)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("Couldn't reach the directory: {}", e))?;
+            response.json().await.map_err(|e| format!("Unexpected directory response: {}", e))
+        }
+        None => Ok(LOCAL.with(|local| local.borrow().search(query))),
+    }
+}
+
+pub async fn join(id: &str) -> Result<(), String> {
+    membership_change(id, "join", 1).await
+}
+
+pub async fn leave(id: &str) -> Result<(), String> {
+    membership_change(id, "leave", -1).await
+}
+
+#[derive(Deserialize)]
+struct MembershipResponse {
+    member: bool,
+}
+
+// Whether the directory counts us as a member, to settle a join or leave
+// that only went through on one side
+pub async fn membership(id: &str) -> Result<bool, String> {
+    match API_URL {
+        Some(api) => {
+            let response = reqwest::Client::new()
+                .get(format!("{}/circles/{}/membership", api, id))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("Couldn't check the circle: {}", e))?;
+            let body: MembershipResponse =
+                response.json().await.map_err(|e| format!("Unexpected directory response: {}", e))?;
+            Ok(body.member)
+        }
+        None => Ok(LOCAL.with(|local| local.borrow().joined.contains(id))),
+    }
+}
+
+async fn membership_change(id: &str, action: &str, delta: i32) -> Result<(), String> {
+    match API_URL {
+        Some(api) => reqwest::Client::new()
+            .post(format!("{}/circles/{}/{}", api, id, action))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("Couldn't {} the circle: {}", action, e)),
+        None => LOCAL.with(|local| local.borrow_mut().adjust(id, delta)),
+    }
+}
+
+thread_local! {
+    static LOCAL: RefCell<LocalDirectory> = RefCell::new(LocalDirectory::seed());
+}
+
+struct LocalDirectory {
+    entries: Vec<DirectoryEntry>,
+    // Circles we belong to, as the server would see it
+    joined: BTreeSet<String>,
+}
+
+impl LocalDirectory {
+    fn search(&self, query: &DirectoryQuery) -> DirectoryPage {
+        let text = query.text.trim().to_lowercase();
+        let mut matches: Vec<&DirectoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| query.category.is_none_or(|c| e.category == c))
+            .filter(|e| {
+                text.is_empty() || e.name.to_lowercase().contains(&text) || e.description.to_lowercase().contains(&text)
+            })
+            .collect();
+        match query.sort {
+            SortOrder::Trending => matches.sort_by(|a, b| b.weekly_joins.cmp(&a.weekly_joins).then_with(|| a.name.cmp(&b.name))),
+            SortOrder::MostMembers => matches.sort_by(|a, b| b.member_count.cmp(&a.member_count).then_with(|| a.name.cmp(&b.name))),
+        }
+        // The cursor is simply the offset of the next page
+        let offset = query.cursor.as_deref().and_then(|c| c.parse().ok()).unwrap_or(0);
+        let end = (offset + PAGE_SIZE).min(matches.len());
+        DirectoryPage {
+            circles: matches.get(offset..end).unwrap_or_default().iter().map(|e| (*e).clone()).collect(),
+            next_cursor: (end < matches.len()).then(|| end.to_string()),
+        }
+    }
+
+    fn adjust(&mut self, id: &str, delta: i32) -> Result<(), String> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| "This circle is no longer listed".to_string())?;
+        let changed = if delta > 0 { self.joined.insert(id.to_string()) } else { self.joined.remove(id) };
+        if changed {
+            entry.member_count = entry.member_count.saturating_add_signed(delta);
+        }
+        Ok(())
+    }
+
+    fn seed() -> Self {
+        let entries = [
+            ("seed-circle-tech", "Tech News", "Latest in technology and development", "bi-globe", Category::Technology, 156, 12),
+            ("seed-circle-rust", "Rust Community", "Discussions about Rust programming", "bi-gear", Category::Technology, 324, 31),
+            ("dir-web-dev", "Web Developers", "Frontend, backend and everything between", "bi-globe", Category::Technology, 512, 18),
+            ("dir-open-source", "Open Source Bangladesh", "Contributing to open source together", "bi-gear", Category::Technology, 241, 27),
+            ("dir-ai-reading", "AI Reading Group", "Weekly papers on machine learning", "bi-book", Category::Technology, 98, 22),
+            ("dir-dhaka-neighbours", "Dhaka Neighbours", "Local news and help in Dhaka", "bi-house-heart", Category::Community, 1204, 45),
+            ("dir-volunteers", "Weekend Volunteers", "Organising volunteer work every weekend", "bi-person-lines-fill", Category::Community, 187, 9),
+            ("dir-flood-relief", "Flood Relief Network", "Coordinating relief during the monsoon", "bi-globe", Category::Community, 860, 64),
+            ("dir-expats", "Bangladeshis Abroad", "Staying connected far from home", "bi-globe", Category::Community, 2310, 38),
+            ("dir-hsc-maths", "HSC Maths Help", "Questions and answers for HSC students", "bi-book", Category::Education, 743, 51),
+            ("dir-english-club", "English Conversation Club", "Practise speaking with others", "bi-book", Category::Education, 392, 14),
+            ("dir-scholarships", "Scholarship Hunters", "Sharing scholarship and admission news", "bi-briefcase", Category::Education, 615, 33),
+            ("dir-photography", "Street Photography", "Share your best shots", "bi-globe", Category::Hobbies, 278, 11),
+            ("dir-gardening", "Rooftop Gardening", "Growing food on city rooftops", "bi-house-heart", Category::Hobbies, 331, 19),
+            ("dir-cricket", "Cricket Fans", "Match talk and banter", "bi-person-lines-fill", Category::Hobbies, 4021, 120),
+            ("dir-cooking", "Home Cooks", "Recipes from every district", "bi-house-heart", Category::Hobbies, 1575, 42),
+            ("dir-chess", "Chess Club", "Casual games and puzzles", "bi-gear", Category::Hobbies, 146, 5),
+            ("dir-new-parents", "New Parents", "Support for the first years", "bi-house-heart", Category::Family, 489, 26),
+            ("dir-family-history", "Family Historians", "Tracing family trees together", "bi-book", Category::Family, 212, 17),
+            ("dir-elder-care", "Caring for Parents", "Looking after elderly family members", "bi-house-heart", Category::Family, 173, 8),
+            ("dir-poetry", "Bangla Poetry", "Reading and writing Bangla poetry", "bi-book", Category::Culture, 667, 29),
+            ("dir-folk-music", "Folk Music Lovers", "Baul, bhatiali and more", "bi-globe", Category::Culture, 354, 16),
+            ("dir-film", "Film Society", "Monthly screenings and reviews", "bi-globe", Category::Culture, 289, 13),
+            ("dir-heritage", "Heritage Walks", "Exploring old Dhaka's history", "bi-house-heart", Category::Culture, 198, 21),
+        ];
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|(id, name, description, icon, category, member_count, weekly_joins)| DirectoryEntry {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    icon: icon.to_string(),
+                    category,
+                    member_count,
+                    weekly_joins,
+                })
+                .collect(),
+            joined: BTreeSet::from(["seed-circle-tech".to_string(), "seed-circle-rust".to_string()]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(text: &str, category: Option<Category>, sort: SortOrder) -> DirectoryQuery {
+        DirectoryQuery { text: text.to_string(), category, sort, cursor: None }
+    }
+
+    fn names(page: &DirectoryPage) -> Vec<&str> {
+        page.circles.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn cursors_walk_every_page_once() {
+        let directory = LocalDirectory::seed();
+        let mut q = query("", None, SortOrder::MostMembers);
+        let mut seen = Vec::new();
+        let mut pages = 0;
+        loop {
+            let page = directory.search(&q);
+            pages += 1;
+            assert!(page.circles.len() <= PAGE_SIZE);
+            seen.extend(page.circles.into_iter().map(|c| c.id));
+            match page.next_cursor {
+                Some(cursor) => q.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        assert_eq!(seen.len(), directory.entries.len());
+        assert_eq!(seen.iter().collect::<BTreeSet<_>>().len(), seen.len());
+    }
+
+    #[test]
+    fn bad_or_past_the_end_cursors_are_safe() {
+        let directory = LocalDirectory::seed();
+        let mut q = query("", None, SortOrder::Trending);
+        q.cursor = Some("nonsense".to_string());
+        assert_eq!(directory.search(&q), directory.search(&query("", None, SortOrder::Trending)));
+
+        q.cursor = Some("500".to_string());
+        assert_eq!(directory.search(&q), DirectoryPage::default());
+    }
+
+    #[test]
+    fn text_and_category_narrow_the_results() {
+        let directory = LocalDirectory::seed();
+
+        // Matches names and descriptions, ignoring case and spaces around
+        let page = directory.search(&query("  RUST ", None, SortOrder::MostMembers));
+        assert_eq!(names(&page), ["Rust Community"]);
+        let page = directory.search(&query("rooftops", None, SortOrder::MostMembers));
+        assert_eq!(names(&page), ["Rooftop Gardening"]);
+
+        let page = directory.search(&query("", Some(Category::Family), SortOrder::MostMembers));
+        assert_eq!(names(&page), ["New Parents", "Family Historians", "Caring for Parents"]);
+        assert_eq!(page.next_cursor, None);
+
+        // Both filters have to match
+        let page = directory.search(&query("parents", Some(Category::Education), SortOrder::MostMembers));
+        assert!(page.circles.is_empty());
+    }
+
+    #[test]
+    fn sort_orders_rank_by_their_own_count() {
+        let directory = LocalDirectory::seed();
+        let trending = directory.search(&query("", Some(Category::Technology), SortOrder::Trending));
+        assert_eq!(
+            names(&trending),
+            ["Rust Community", "Open Source Bangladesh", "AI Reading Group", "Web Developers", "Tech News"]
+        );
+        let biggest = directory.search(&query("", Some(Category::Technology), SortOrder::MostMembers));
+        assert_eq!(
+            names(&biggest),
+            ["Web Developers", "Rust Community", "Open Source Bangladesh", "Tech News", "AI Reading Group"]
+        );
+    }
+
+    #[test]
+    fn ties_are_broken_by_name() {
+        let mut directory = LocalDirectory::seed();
+        for entry in directory.entries.iter_mut() {
+            entry.weekly_joins = 1;
+        }
+        let page = directory.search(&query("", Some(Category::Culture), SortOrder::Trending));
+        assert_eq!(names(&page), ["Bangla Poetry", "Film Society", "Folk Music Lovers", "Heritage Walks"]);
+    }
+
+    #[test]
+    fn joining_and_leaving_count_once() {
+        let mut directory = LocalDirectory::seed();
+        let count = |d: &LocalDirectory| d.entries.iter().find(|e| e.id == "dir-chess").unwrap().member_count;
+
+        directory.adjust("dir-chess", 1).unwrap();
+        directory.adjust("dir-chess", 1).unwrap();
+        assert_eq!(count(&directory), 147);
+        assert!(directory.joined.contains("dir-chess"));
+
+        directory.adjust("dir-chess", -1).unwrap();
+        directory.adjust("dir-chess", -1).unwrap();
+        assert_eq!(count(&directory), 146);
+        assert!(!directory.joined.contains("dir-chess"));
+
+        // Already a member of the seeded circles
+        directory.adjust("seed-circle-rust", 1).unwrap();
+        assert_eq!(directory.entries.iter().find(|e| e.id == "seed-circle-rust").unwrap().member_count, 324);
+
+        assert_eq!(directory.adjust("gone", 1), Err("This circle is no longer listed".to_string()));
+    }
+}
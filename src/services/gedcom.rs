@@ -0,0 +1,1187 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::models::family::{Branch, FamilyTree, Gender, Person, RelationKind, Relationship};
+use crate::models::fuzzy_date::FuzzyDate;
+use crate::services::csv::Encoding;
+use crate::services::sync::Stamp;
+use crate::utils::format::civil_from_days;
+use crate::utils::{new_id, now_millis};
+
+// Reading family trees exported by other genealogy programs as GEDCOM 5.5.1
+// or 7.0, and writing ours as 7.0. People, families, sources, notes and
+// media are mapped onto the tree model; whatever has no place there is
+// listed in the import report.
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+// Record bookkeeping that has nothing to carry over
+const BOOKKEEPING: [&str; 7] = ["CHAN", "CREA", "RIN", "UID", "REFN", "EXID", "RESN"];
+
+// ANSEL characters for bytes 0xA1-0xC8; '\0' marks bytes with no character
+const ANSEL_SPACING: [char; 40] = [
+    'Ł', 'Ø', 'Đ', 'Þ', 'Æ', 'Œ', 'ʹ', '·', '♭', '®', '±', 'Ơ', 'Ư', 'ʼ', '\0', 'ʻ',
+    'ł', 'ø', 'đ', 'þ', 'æ', 'œ', 'ʺ', 'ı', '£', 'ð', '\0', 'ơ', 'ư', '□', '■', '°',
+    'ℓ', '℗', '©', '♯', '¿', '¡', 'ß', '€',
+];
+// Combining marks for bytes 0xE0-0xFE. ANSEL writes them before the letter
+// they belong to, Unicode after.
+const ANSEL_COMBINING: [char; 31] = [
+    '\u{309}', '\u{300}', '\u{301}', '\u{302}', '\u{303}', '\u{304}', '\u{306}', '\u{307}',
+    '\u{308}', '\u{30C}', '\u{30A}', '\u{FE20}', '\u{FE21}', '\u{315}', '\u{30B}', '\u{310}',
+    '\u{327}', '\u{328}', '\u{323}', '\u{324}', '\u{325}', '\u{333}', '\u{332}', '\u{326}',
+    '\u{31C}', '\u{32E}', '\u{FE22}', '\u{FE23}', '\0', '\0', '\u{313}',
+];
+// Letters that have a precomposed form with the common marks
+const PRECOMPOSED: [(char, &str, &str); 8] = [
+    ('\u{300}', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ('\u{301}', "AEIOUYaeiouyCNSZcnsz", "ÁÉÍÓÚÝáéíóúýĆŃŚŹćńśź"),
+    ('\u{302}', "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ('\u{303}', "ANOano", "ÃÑÕãñõ"),
+    ('\u{308}', "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    ('\u{30A}', "AaUu", "ÅåŮů"),
+    ('\u{327}', "CcSs", "ÇçŞş"),
+    ('\u{30C}', "CSZcszERNern", "ČŠŽčšžĚŘŇěřň"),
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub version: String,
+    pub encoding: String,
+    pub persons: usize,
+    pub relationships: usize,
+    // Tag path such as "INDI.EVEN" -> how often it was skipped
+    pub unsupported: BTreeMap<String, usize>,
+    // What was changed on the way in -> how often
+    pub lossy: BTreeMap<String, usize>,
+}
+
+impl ImportReport {
+    fn lossy(&mut self, what: &str) {
+        *self.lossy.entry(what.to_string()).or_default() += 1;
+    }
+
+    fn unsupported(&mut self, path: &str, tag: &str) {
+        let key = if path.is_empty() { tag.to_string() } else { format!("{}.{}", path, tag) };
+        *self.unsupported.entry(key).or_default() += 1;
+    }
+}
+
+// One line with the lines nested under it
+#[derive(Clone, Debug, Default)]
+struct Node {
+    xref: Option<String>,
+    tag: String,
+    value: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, tag: &str) -> Option<&Node> {
+        self.children.iter().find(|c| c.tag == tag)
+    }
+
+    fn text(&self, tag: &str) -> String {
+        self.child(tag).map(|c| c.value.trim().to_string()).unwrap_or_default()
+    }
+
+    // "@I1@" values point at another record
+    fn pointer(&self) -> Option<&str> {
+        let value = self.value.trim();
+        (value.len() > 2 && value.starts_with('@') && value.ends_with('@') && !value.starts_with("@#")).then_some(value)
+    }
+}
+
+fn ansel(bytes: &[u8], report: &mut ImportReport) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    let mut marks: Vec<char> = Vec::new();
+    for &byte in bytes {
+        let c = match byte {
+            0x00..=0x7F => byte as char,
+            0xA1..=0xC8 => ANSEL_SPACING[(byte - 0xA1) as usize],
+            0xCF => 'ß',
+            0xE0..=0xFE => {
+                let mark = ANSEL_COMBINING[(byte - 0xE0) as usize];
+                if mark != '\0' {
+                    marks.push(mark);
+                    continue;
+                }
+                '\0'
+            }
+            _ => '\0',
+        };
+        if c == '\0' {
+            report.lossy("Characters with no ANSEL meaning were replaced");
+            text.push(char::REPLACEMENT_CHARACTER);
+            continue;
+        }
+        let mut base = c;
+        let mut rest = Vec::new();
+        for mark in marks.drain(..) {
+            let composed = PRECOMPOSED
+                .iter()
+                .find(|(m, _, _)| *m == mark && rest.is_empty())
+                .and_then(|(_, plain, accented)| plain.chars().position(|p| p == base).and_then(|i| accented.chars().nth(i)));
+            match composed {
+                Some(composed) => base = composed,
+                None => rest.push(mark),
+            }
+        }
+        text.push(base);
+        text.extend(rest);
+    }
+    text
+}
+
+// The character set a file declares in "1 CHAR", read before decoding
+fn declared_charset(bytes: &[u8]) -> String {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]).into_owned();
+    head.lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("1 CHAR "))
+        .map(|c| c.trim().to_ascii_uppercase())
+        .unwrap_or_default()
+}
+
+// Byte order marks and zero bytes give UTF-16 away; otherwise the header says
+fn decode(bytes: &[u8], report: &mut ImportReport) -> String {
+    let utf16 = match bytes {
+        [0xFF, 0xFE, ..] | [b'0', 0, ..] => Some(Encoding::Utf16Le),
+        [0xFE, 0xFF, ..] | [0, b'0', ..] => Some(Encoding::Utf16Be),
+        _ => None,
+    };
+    if let Some(encoding) = utf16 {
+        report.encoding = encoding.label().to_string();
+        return encoding.decode(bytes);
+    }
+    let declared = declared_charset(bytes);
+    if declared == "ANSEL" {
+        report.encoding = "ANSEL".to_string();
+        return ansel(bytes, report);
+    }
+    let encoding = Encoding::detect(bytes);
+    if encoding == Encoding::Windows1252 && (declared == "UTF-8" || declared == "UNICODE") {
+        report.lossy("Bytes that weren't valid UTF-8 were read as Windows-1252");
+    }
+    report.encoding = encoding.label().to_string();
+    encoding.decode(bytes)
+}
+
+// "level [@xref@] tag [value]"
+fn parse_line(line: &str) -> Option<(usize, Node)> {
+    let line = line.trim_start_matches('\u{FEFF}').trim_start();
+    let (level, rest) = line.split_once(' ')?;
+    let level = level.parse().ok()?;
+    let (xref, rest) = match rest.strip_prefix('@') {
+        Some(after) => {
+            let (xref, rest) = after.split_once("@ ")?;
+            (Some(format!("@{}@", xref)), rest)
+        }
+        None => (None, rest),
+    };
+    let (tag, value) = rest.split_once(' ').unwrap_or((rest, ""));
+    if tag.is_empty() {
+        return None;
+    }
+    // 7.0 doubles a leading "@" in text
+    let value = value.strip_prefix("@@").map(|v| format!("@{}", v)).unwrap_or_else(|| value.to_string());
+    Some((level, Node { xref, tag: tag.to_ascii_uppercase(), value, children: Vec::new() }))
+}
+
+// Nest the lines by level, folding CONT and CONC back into their value
+fn parse_nodes(text: &str, report: &mut ImportReport) -> Vec<Node> {
+    let mut stack: Vec<(usize, Node)> = Vec::new();
+    let mut records: Vec<Node> = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let Some((level, node)) = parse_line(line) else {
+            report.lossy("Lines that couldn't be read were skipped");
+            continue;
+        };
+        while stack.last().is_some_and(|(l, _)| *l >= level) {
+            let (_, done) = stack.pop().unwrap_or_default();
+            match stack.last_mut() {
+                Some((_, parent)) => parent.children.push(done),
+                None => records.push(done),
+            }
+        }
+        if let Some((_, parent)) = stack.last_mut().filter(|_| node.tag == "CONT" || node.tag == "CONC") {
+            if node.tag == "CONT" {
+                parent.value.push('\n');
+            }
+            parent.value.push_str(&node.value);
+            continue;
+        }
+        if level > stack.last().map_or(0, |(l, _)| l + 1) {
+            report.lossy("Lines nested too deep were skipped");
+            continue;
+        }
+        stack.push((level, node));
+    }
+    while let Some((_, done)) = stack.pop() {
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(done),
+            None => records.push(done),
+        }
+    }
+    records
+}
+
+fn month_number(token: &str) -> Option<usize> {
+    MONTHS.iter().position(|m| *m == token).map(|i| i + 1)
+}
+
+// "12 MAR 1970" -> "1970-03-12", "MAR 1970" -> "1970-03", "1970" -> "1970",
+// "15 MAR 44 BCE" -> "0044-03-15 BCE"
+fn simple_date(tokens: &[&str]) -> Option<String> {
+    let (year_token, bce) = match tokens {
+        [rest @ .., era] if ["BCE", "BC", "B.C."].contains(era) => (rest.last()?, true),
+        [.., year] => (year, false),
+        [] => return None,
+    };
+    // Dual years such as "1700/01" stay as written
+    let year_digits = year_token.split('/').next()?;
+    if year_digits.is_empty() || !year_digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let era = if bce { " BCE" } else { "" };
+    let before_year = &tokens[..tokens.len() - if bce { 2 } else { 1 }];
+    // Years are padded where a month follows so "0044-03" can't be read
+    // day first
+    match before_year {
+        [] => Some(format!("{}{}", year_token, era)),
+        [month] => month_number(month).map(|m| format!("{:0>4}-{:02}{}", year_token, m, era)),
+        [day, month] => {
+            let day: u32 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+            month_number(month).map(|m| format!("{:0>4}-{:02}-{:02}{}", year_token, m, day, era))
+        }
+        _ => None,
+    }
+}
+
+// GEDCOM dates in the readable form the tree keeps: qualifiers in words,
+// calendars other than the Gregorian kept as written
+fn date(value: &str, report: &mut ImportReport) -> String {
+    let value = value.trim();
+    if value.is_empty() {
+        return String::new();
+    }
+    if let Some(phrase) = value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        return phrase.trim().to_string();
+    }
+    let upper = value.to_ascii_uppercase();
+    let upper = upper.replace("@#DGREGORIAN@", "").replace("GREGORIAN ", "");
+    if upper.contains("@#D") || ["JULIAN", "HEBREW", "FRENCH_R"].iter().any(|c| upper.split_whitespace().any(|t| t == *c)) {
+        report.lossy("Dates in other calendars were kept as written");
+        return value.to_string();
+    }
+    // "INT 1900 (about then)": the interpreted date is enough
+    let upper = upper.split('(').next().unwrap_or_default().trim().to_string();
+    let tokens: Vec<&str> = upper.split_whitespace().collect();
+    let read = |part: &[&str]| simple_date(part);
+    let readable = match tokens.as_slice() {
+        ["ABT" | "CAL" | "EST", rest @ ..] => read(rest).map(|d| format!("abt {}", d)),
+        ["BEF", rest @ ..] => read(rest).map(|d| format!("before {}", d)),
+        ["AFT", rest @ ..] => read(rest).map(|d| format!("after {}", d)),
+        ["INT", rest @ ..] => read(rest),
+        ["BET", rest @ ..] | ["FROM", rest @ ..] => {
+            let word = if tokens[0] == "BET" { "AND" } else { "TO" };
+            match rest.iter().position(|t| *t == word) {
+                Some(split) => read(&rest[..split])
+                    .zip(read(&rest[split + 1..]))
+                    .map(|(from, to)| if word == "AND" { format!("between {} and {}", from, to) } else { format!("{} to {}", from, to) }),
+                None => read(rest).map(|d| format!("from {}", d)),
+            }
+        }
+        ["TO", rest @ ..] => read(rest).map(|d| format!("to {}", d)),
+        _ => read(&tokens),
+    };
+    readable.unwrap_or_else(|| {
+        report.lossy("Dates that couldn't be read were kept as written");
+        value.to_string()
+    })
+}
+
+// Jurisdictions from smallest to largest, with empty ones dropped
+fn place(value: &str) -> String {
+    value.split(',').map(str::trim).filter(|p| !p.is_empty()).collect::<Vec<_>>().join(", ")
+}
+
+// "John /Doe/ Jr" -> ("John Jr", "Doe")
+fn split_name(value: &str) -> (String, String) {
+    let mut parts = value.splitn(3, '/');
+    let before = parts.next().unwrap_or_default().trim();
+    let surname = parts.next().unwrap_or_default().trim();
+    let after = parts.next().unwrap_or_default().trim();
+    let given = [before, after].into_iter().filter(|p| !p.is_empty()).collect::<Vec<_>>().join(" ");
+    (given, surname.to_string())
+}
+
+fn is_web_address(file: &str) -> bool {
+    ["http://", "https://", "data:"].iter().any(|p| file.to_ascii_lowercase().starts_with(p))
+}
+
+// The kind of parenthood behind a pedigree value
+fn pedigree(value: &str, report: &mut ImportReport) -> RelationKind {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "ADOPTED" => RelationKind::Adoptive,
+        "STEP" => RelationKind::Step,
+        "FOSTER" => {
+            report.lossy("Foster parents were imported as step-parents");
+            RelationKind::Step
+        }
+        "" | "BIRTH" | "NATURAL" | "BIOLOGICAL" => RelationKind::Parent,
+        _ => {
+            report.lossy("Unusual pedigrees were imported as birth parents");
+            RelationKind::Parent
+        }
+    }
+}
+
+struct Importer<'a> {
+    records: BTreeMap<&'a str, &'a Node>,
+    report: ImportReport,
+    // GEDCOM xref -> person id
+    ids: BTreeMap<String, String>,
+    // (child xref, family xref) -> how the father and mother are parents
+    pedigrees: BTreeMap<(String, String), (RelationKind, RelationKind)>,
+}
+
+impl<'a> Importer<'a> {
+    fn record(&self, pointer: &str, tag: &str) -> Option<&'a Node> {
+        self.records.get(pointer).copied().filter(|r| r.tag == tag)
+    }
+
+    // Inline note text or the shared note it points to
+    fn note(&mut self, node: &Node) -> String {
+        match node.pointer() {
+            Some(pointer) => match self.record(pointer, "NOTE").or_else(|| self.record(pointer, "SNOTE")) {
+                Some(record) => record.value.trim().to_string(),
+                None => {
+                    self.report.lossy("Notes missing from the file were skipped");
+                    String::new()
+                }
+            },
+            None => node.value.trim().to_string(),
+        }
+    }
+
+    // "Source: Title (Author), page"
+    fn citation(&mut self, node: &Node) -> String {
+        let mut text = match node.pointer() {
+            Some(pointer) => match self.record(pointer, "SOUR") {
+                Some(source) => {
+                    let title = [source.text("TITL"), source.text("ABBR"), source.value.trim().to_string()]
+                        .into_iter()
+                        .find(|t| !t.is_empty())
+                        .unwrap_or_else(|| "Untitled source".to_string());
+                    let author = source.text("AUTH");
+                    if author.is_empty() { title } else { format!("{} ({})", title, author) }
+                }
+                None => {
+                    self.report.lossy("Sources missing from the file were skipped");
+                    return String::new();
+                }
+            },
+            None => node.value.trim().to_string(),
+        };
+        let page = node.text("PAGE");
+        if !page.is_empty() {
+            text = format!("{}, {}", text, page);
+        }
+        if text.is_empty() { text } else { format!("Source: {}", text) }
+    }
+
+    // The file of an inline or shared media object
+    fn media_file(&self, node: &Node) -> String {
+        let object = node.pointer().and_then(|p| self.record(p, "OBJE")).unwrap_or(node);
+        object.text("FILE")
+    }
+
+    // Date, place, notes and sources of a birth or death
+    fn event(&mut self, node: &Node, path: &str, notes: &mut Vec<String>) -> (String, String) {
+        let path = format!("{}.{}", path, node.tag);
+        let mut when = String::new();
+        let mut place_name = String::new();
+        for child in &node.children {
+            match child.tag.as_str() {
+                "DATE" => {
+                    when = date(&child.value, &mut self.report);
+                    // Dates written another way, such as in the Bengali
+                    // calendar or dual dated, go out as plain GEDCOM dates with
+                    // the words as typed in the phrase. The phrase wins back
+                    // whenever it still comes to the same date.
+                    let phrase = child.text("PHRASE");
+                    let same = match (FuzzyDate::parse(&phrase), FuzzyDate::parse(&when)) {
+                        (Ok(written), Ok(dated)) => written.to_gedcom() == dated.to_gedcom(),
+                        _ => false,
+                    };
+                    if child.value.trim().is_empty() || same {
+                        when = phrase;
+                    }
+                }
+                "PLAC" => {
+                    place_name = place(&child.value);
+                    for detail in &child.children {
+                        self.report.unsupported(&format!("{}.PLAC", path), &detail.tag);
+                    }
+                }
+                "NOTE" | "SNOTE" => notes.push(self.note(child)),
+                "SOUR" => notes.push(self.citation(child)),
+                "TYPE" => {}
+                tag => self.report.unsupported(&path, tag),
+            }
+        }
+        (when, place_name)
+    }
+
+    fn person(&mut self, record: &Node) -> Person {
+        let mut person = Person { id: new_id(), stamp: Stamp::now(), ..Person::default() };
+        let mut notes: Vec<String> = Vec::new();
+        let mut occupations: Vec<String> = Vec::new();
+        let (mut named, mut born, mut died) = (false, false, false);
+        for child in &record.children {
+            match child.tag.as_str() {
+                // Later names are other spellings
+                "NAME" if named => {
+                    let (given, surname) = split_name(&child.value);
+                    let name = [given, surname].into_iter().filter(|n| !n.is_empty()).collect::<Vec<_>>().join(" ");
+                    if !name.is_empty() && !person.alternate_names.contains(&name) {
+                        person.alternate_names.push(name);
+                    }
+                }
+                "NAME" => {
+                    named = true;
+                    let (given, surname) = split_name(&child.value);
+                    person.given_name = Some(child.text("GIVN")).filter(|g| !g.is_empty()).unwrap_or(given);
+                    person.surname = Some(child.text("SURN")).filter(|s| !s.is_empty()).unwrap_or(surname);
+                    for part in &child.children {
+                        if !["GIVN", "SURN", "TYPE"].contains(&part.tag.as_str()) {
+                            self.report.unsupported("INDI.NAME", &part.tag);
+                        }
+                    }
+                }
+                "SEX" => {
+                    person.gender = match child.value.trim().to_ascii_uppercase().as_str() {
+                        "M" => Gender::Male,
+                        "F" => Gender::Female,
+                        "U" | "" => Gender::Unknown,
+                        _ => {
+                            self.report.lossy("Sexes other than M, F and U were imported as unknown");
+                            Gender::Unknown
+                        }
+                    }
+                }
+                "BIRT" | "DEAT" => {
+                    let first = if child.tag == "BIRT" { !born } else { !died };
+                    if !first {
+                        self.report.lossy("Only the first of several births or deaths was kept");
+                        continue;
+                    }
+                    let (when, place_name) = self.event(child, "INDI", &mut notes);
+                    if child.tag == "BIRT" {
+                        born = true;
+                        (person.birth_date, person.birth_place) = (when, place_name);
+                    } else {
+                        died = true;
+                        (person.death_date, person.death_place) = (when, place_name);
+                    }
+                }
+                "OCCU" => occupations.push(child.value.trim().to_string()),
+                "NOTE" | "SNOTE" => notes.push(self.note(child)),
+                "SOUR" => notes.push(self.citation(child)),
+                "OBJE" => {
+                    let file = self.media_file(child);
+                    if !person.photo.is_empty() {
+                        self.report.lossy("Only the first photo of each person was kept");
+                    } else if is_web_address(&file) {
+                        person.photo = file;
+                    } else {
+                        self.report.lossy("Media files stored on the other computer couldn't be brought over");
+                    }
+                }
+                "FAMC" => {
+                    if let Some(family) = child.pointer() {
+                        // 7.0 spells out pedigrees it has no word for in a phrase
+                        let pedi = match child.child("PEDI") {
+                            Some(pedi) if pedi.value.trim().eq_ignore_ascii_case("OTHER") && !pedi.text("PHRASE").is_empty() => pedi.text("PHRASE"),
+                            Some(pedi) => pedi.value.clone(),
+                            None => String::new(),
+                        };
+                        let kind = pedigree(&pedi, &mut self.report);
+                        self.record_pedigree(record, family, (kind, kind));
+                    }
+                }
+                "ADOP" => {
+                    // Which of the family's parents adopted
+                    if let Some(famc) = child.child("FAMC")
+                        && let Some(family) = famc.pointer()
+                    {
+                        let kinds = match famc.text("ADOP").to_ascii_uppercase().as_str() {
+                            "HUSB" => (RelationKind::Adoptive, RelationKind::Parent),
+                            "WIFE" => (RelationKind::Parent, RelationKind::Adoptive),
+                            _ => (RelationKind::Adoptive, RelationKind::Adoptive),
+                        };
+                        self.record_pedigree(record, family, kinds);
+                    }
+                }
+                "FAMS" => {}
+                tag if BOOKKEEPING.contains(&tag) => {}
+                tag => self.report.unsupported("INDI", tag),
+            }
+        }
+        person.occupation = occupations.into_iter().filter(|o| !o.is_empty()).collect::<Vec<_>>().join("; ");
+        person.notes = notes.into_iter().filter(|n| !n.is_empty()).collect::<Vec<_>>().join("\n\n");
+        person
+    }
+
+    fn record_pedigree(&mut self, record: &Node, family: &str, kinds: (RelationKind, RelationKind)) {
+        if let Some(xref) = &record.xref {
+            let entry = self.pedigrees.entry((xref.clone(), family.to_string())).or_insert(kinds);
+            // An adoption event is more specific than a birth pedigree
+            if kinds.0 != RelationKind::Parent {
+                entry.0 = kinds.0;
+            }
+            if kinds.1 != RelationKind::Parent {
+                entry.1 = kinds.1;
+            }
+        }
+    }
+
+    fn family(&mut self, record: &Node, tree: &mut FamilyTree) {
+        let id_of = |importer: &mut Self, node: Option<&Node>| -> Option<String> {
+            let pointer = node?.pointer()?;
+            let id = importer.ids.get(pointer).cloned();
+            if id.is_none() {
+                importer.report.lossy("Links to people missing from the file were skipped");
+            }
+            id
+        };
+        let father = id_of(self, record.child("HUSB"));
+        let mother = id_of(self, record.child("WIFE"));
+        if let (Some(father), Some(mother)) = (&father, &mother) {
+            tree.relationships.push(Relationship::new(RelationKind::Spouse, father, mother));
+        }
+        for child in &record.children {
+            match child.tag.as_str() {
+                "CHIL" => {
+                    let Some(child_id) = id_of(self, Some(child)) else {
+                        continue;
+                    };
+                    let key = (child.value.trim().to_string(), record.xref.clone().unwrap_or_default());
+                    let (mut father_kind, mut mother_kind) =
+                        self.pedigrees.get(&key).copied().unwrap_or((RelationKind::Parent, RelationKind::Parent));
+                    // Family Tree Maker marks each parent separately
+                    let frel = child.text("_FREL");
+                    if !frel.is_empty() {
+                        father_kind = pedigree(&frel, &mut self.report);
+                    }
+                    let mrel = child.text("_MREL");
+                    if !mrel.is_empty() {
+                        mother_kind = pedigree(&mrel, &mut self.report);
+                    }
+                    for (parent, kind) in [(&father, father_kind), (&mother, mother_kind)] {
+                        if let Some(parent) = parent {
+                            tree.relationships.push(Relationship::new(kind, parent, &child_id));
+                        }
+                    }
+                }
+                "HUSB" | "WIFE" => {}
+                "MARR" | "ENGA" => self.report.lossy("Marriage dates and places weren't kept"),
+                "DIV" | "ANUL" => self.report.lossy("Divorced couples were imported as spouses"),
+                "NOTE" | "SNOTE" | "SOUR" => self.report.lossy("Notes and sources on families weren't kept"),
+                tag if BOOKKEEPING.contains(&tag) => {}
+                tag => self.report.unsupported("FAM", tag),
+            }
+        }
+    }
+}
+
+// Read a GEDCOM file into a tree of its own, ready to add to ours
+pub fn parse(bytes: &[u8]) -> Result<(FamilyTree, ImportReport), String> {
+    let mut report = ImportReport::default();
+    let text = decode(bytes, &mut report);
+    let nodes = parse_nodes(&text, &mut report);
+    let Some(head) = nodes.first().filter(|n| n.tag == "HEAD") else {
+        return Err("This doesn't look like a GEDCOM file".to_string());
+    };
+    report.version = head.child("GEDC").map(|g| g.text("VERS")).unwrap_or_default();
+    if report.version.is_empty() {
+        report.version = "unknown".to_string();
+    }
+
+    let mut importer = Importer {
+        records: nodes.iter().filter_map(|n| n.xref.as_deref().map(|x| (x, n))).collect(),
+        report,
+        ids: BTreeMap::new(),
+        pedigrees: BTreeMap::new(),
+    };
+    let mut tree = FamilyTree::default();
+    for node in &nodes {
+        match node.tag.as_str() {
+            "INDI" => {
+                let person = importer.person(node);
+                if let Some(xref) = &node.xref {
+                    importer.ids.insert(xref.clone(), person.id.clone());
+                }
+                tree.persons.push(person);
+            }
+            // Used through the people and families that point at them
+            "HEAD" | "TRLR" | "FAM" | "SOUR" | "NOTE" | "SNOTE" | "OBJE" | "SUBM" | "SUBN" => {}
+            tag => importer.report.unsupported("", tag),
+        }
+    }
+    for node in nodes.iter().filter(|n| n.tag == "FAM") {
+        importer.family(node, &mut tree);
+    }
+    if tree.persons.is_empty() {
+        return Err("There are no people in this file".to_string());
+    }
+    let mut report = importer.report;
+    report.persons = tree.persons.len();
+    report.relationships = tree.relationships.len();
+    Ok((tree, report))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportOptions {
+    pub branch: Branch,
+    // Leave out everything but the sex of people who may still be alive
+    pub hide_living: bool,
+    pub living_years: i64,
+}
+
+// "1970-03-12" -> "12 MAR 1970", the reverse of `simple_date`
+fn gedcom_simple(value: &str) -> Option<String> {
+    let (value, era) = match value.strip_suffix(" BCE") {
+        Some(rest) => (rest, " BCE"),
+        None => (value, ""),
+    };
+    let mut parts = value.split('-');
+    let year = parts.next()?;
+    let year = if year.len() > 1 { year.trim_start_matches('0') } else { year };
+    let digits = year.split('/').next()?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let month = match parts.next() {
+        Some(m) => Some(*MONTHS.get(m.parse::<usize>().ok()?.checked_sub(1)?)?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(d) => Some(d.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?),
+        None => None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(match (day, month) {
+        (Some(day), Some(month)) => format!("{} {} {}{}", day, month, year, era),
+        (None, Some(month)) => format!("{} {}{}", month, year, era),
+        _ => format!("{}{}", year, era),
+    })
+}
+
+// A date as the tree keeps it -> a GEDCOM date, or None when only a phrase
+// will do
+fn gedcom_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    let qualified = |prefix: &str, keyword: &str| value.strip_prefix(prefix).and_then(gedcom_simple).map(|d| format!("{} {}", keyword, d));
+    let pair = |prefix: &str, joint: &str, first: &str, second: &str| {
+        let (from, to) = value.strip_prefix(prefix).unwrap_or(value).split_once(joint)?;
+        Some(format!("{} {} {} {}", first, gedcom_simple(from)?, second, gedcom_simple(to)?))
+    };
+    gedcom_simple(value)
+        .or_else(|| qualified("abt ", "ABT"))
+        .or_else(|| qualified("before ", "BEF"))
+        .or_else(|| qualified("after ", "AFT"))
+        .or_else(|| value.starts_with("between ").then(|| pair("between ", " and ", "BET", "AND")).flatten())
+        .or_else(|| pair("", " to ", "FROM", "TO"))
+        .or_else(|| qualified("from ", "FROM"))
+        .or_else(|| qualified("to ", "TO"))
+}
+
+fn push_text(out: &mut String, level: usize, tag: &str, value: &str) {
+    let mut lines = value.split('\n');
+    let first = lines.next().unwrap_or_default();
+    let escaped = |line: &str| if line.starts_with('@') { format!("@{}", line) } else { line.to_string() };
+    match first.is_empty() {
+        true => out.push_str(&format!("{} {}\n", level, tag)),
+        false => out.push_str(&format!("{} {} {}\n", level, tag, escaped(first))),
+    }
+    for line in lines {
+        match line.is_empty() {
+            true => out.push_str(&format!("{} CONT\n", level + 1)),
+            false => out.push_str(&format!("{} CONT {}\n", level + 1, escaped(line))),
+        }
+    }
+}
+
+fn push_event(out: &mut String, tag: &str, when: &str, place_name: &str) {
+    if when.is_empty() && place_name.is_empty() {
+        return;
+    }
+    out.push_str(&format!("1 {}\n", tag));
+    if !when.is_empty() {
+        match gedcom_date(when) {
+            Some(date) => out.push_str(&format!("2 DATE {}\n", date)),
+            // Typed some other way or in another calendar: the date it comes
+            // to, with the words as typed
+            None => {
+                match FuzzyDate::parse(when) {
+                    Ok(date) => out.push_str(&format!("2 DATE {}\n", date.to_gedcom())),
+                    Err(_) => out.push_str("2 DATE\n"),
+                }
+                push_text(out, 3, "PHRASE", when);
+            }
+        }
+    }
+    if !place_name.is_empty() {
+        push_text(out, 2, "PLAC", place_name);
+    }
+}
+
+fn pedigree_value(kind: RelationKind) -> Option<&'static str> {
+    match kind {
+        RelationKind::Adoptive => Some("Adopted"),
+        RelationKind::Step => Some("Step"),
+        _ => None,
+    }
+}
+
+// A couple, or a single parent, with their children
+#[derive(Default)]
+struct Family {
+    husband: Option<String>,
+    wife: Option<String>,
+    // child id, how the husband and the wife are parents
+    children: Vec<(String, RelationKind, RelationKind)>,
+}
+
+// Group parents into GEDCOM families: every couple with their children,
+// and single parents for children of parents who aren't a couple
+fn families(tree: &FamilyTree, included: &BTreeSet<String>) -> Vec<Family> {
+    let mut families: Vec<Family> = Vec::new();
+    let place = |families: &mut Vec<Family>, a: Option<&Person>, b: Option<&Person>| -> usize {
+        // Men go in HUSB where there's a choice
+        let (husband, wife) = match (a, b) {
+            (Some(a), b) if a.gender == Gender::Female || b.is_some_and(|b| b.gender == Gender::Male && a.gender != Gender::Male) => (b, Some(a)),
+            (a, b) => (a, b),
+        };
+        let key = (husband.map(|p| p.id.clone()), wife.map(|p| p.id.clone()));
+        match families.iter().position(|f| (f.husband.clone(), f.wife.clone()) == key) {
+            Some(i) => i,
+            None => {
+                families.push(Family { husband: key.0, wife: key.1, children: Vec::new() });
+                families.len() - 1
+            }
+        }
+    };
+    for person in tree.people().filter(|p| included.contains(&p.id)) {
+        for spouse in tree.spouses_of(&person.id).into_iter().filter(|s| included.contains(&s.id) && s.id > person.id) {
+            place(&mut families, Some(person), Some(spouse));
+        }
+    }
+    for child in tree.people().filter(|p| included.contains(&p.id)) {
+        let mut parents: Vec<(&Person, RelationKind)> =
+            tree.parents_of(&child.id).into_iter().filter(|(p, _)| included.contains(&p.id)).collect();
+        while !parents.is_empty() {
+            let (first, kind) = parents.remove(0);
+            // A FAM with both parents would read back as a marriage, so only
+            // spouses share one
+            let partner = parents
+                .iter()
+                .position(|(p, _)| tree.spouses_of(&first.id).iter().any(|s| s.id == p.id))
+                .map(|i| parents.remove(i));
+            let index = place(&mut families, Some(first), partner.map(|(p, _)| p));
+            let family = &mut families[index];
+            let kind_of = |id: &Option<String>| match id {
+                Some(id) if *id == first.id => kind,
+                Some(_) => partner.map_or(kind, |(_, k)| k),
+                None => kind,
+            };
+            let (husband_kind, wife_kind) = (kind_of(&family.husband), kind_of(&family.wife));
+            family.children.push((child.id.clone(), husband_kind, wife_kind));
+        }
+    }
+    families
+}
+
+// The tree, or a branch of it, as a GEDCOM 7.0 file
+pub fn serialize(tree: &FamilyTree, options: &ExportOptions) -> String {
+    let (this_year, month, day) = civil_from_days((now_millis() / 86_400_000) as i64);
+    let included = tree.branch(&options.branch);
+    let persons: Vec<&Person> = tree.people().filter(|p| included.contains(&p.id)).collect();
+    let xref: BTreeMap<&str, String> = persons.iter().enumerate().map(|(i, p)| (p.id.as_str(), format!("@I{}@", i + 1))).collect();
+    let families = families(tree, &included);
+    let family_xref = |i: usize| format!("@F{}@", i + 1);
+    let mut objects: Vec<String> = Vec::new();
+
+    let mut out = String::new();
+    out.push_str("0 HEAD\n1 GEDC\n2 VERS 7.0\n1 SOUR JEEBON\n2 NAME Jeebon\n");
+    out.push_str(&format!("1 DATE {} {} {}\n", day, MONTHS[month as usize - 1], this_year));
+    for person in &persons {
+        let hidden = options.hide_living && person.presumed_living(this_year, options.living_years);
+        out.push_str(&format!("0 {} INDI\n", xref[person.id.as_str()]));
+        if hidden {
+            out.push_str("1 RESN PRIVACY\n1 NAME Living //\n");
+        } else {
+            out.push_str(&format!("1 NAME {} /{}/\n", person.given_name, person.surname));
+            if !person.given_name.is_empty() {
+                out.push_str(&format!("2 GIVN {}\n", person.given_name));
+            }
+            if !person.surname.is_empty() {
+                out.push_str(&format!("2 SURN {}\n", person.surname));
+            }
+            for name in &person.alternate_names {
+                out.push_str(&format!("1 NAME {}\n2 TYPE AKA\n", name));
+            }
+        }
+        out.push_str(&format!("1 SEX {}\n", match person.gender {
+            Gender::Male => "M",
+            Gender::Female => "F",
+            Gender::Unknown => "U",
+        }));
+        if !hidden {
+            push_event(&mut out, "BIRT", &person.birth_date, &person.birth_place);
+            push_event(&mut out, "DEAT", &person.death_date, &person.death_place);
+            if !person.occupation.is_empty() {
+                push_text(&mut out, 1, "OCCU", &person.occupation);
+            }
+            if !person.notes.is_empty() {
+                push_text(&mut out, 1, "NOTE", &person.notes);
+            }
+            if !person.photo.is_empty() {
+                objects.push(person.photo.clone());
+                out.push_str(&format!("1 OBJE @O{}@\n", objects.len()));
+            }
+        }
+        for (i, family) in families.iter().enumerate() {
+            if family.husband.as_deref() == Some(&person.id) || family.wife.as_deref() == Some(&person.id) {
+                out.push_str(&format!("1 FAMS {}\n", family_xref(i)));
+            }
+            for (child, husband_kind, wife_kind) in &family.children {
+                if *child != person.id {
+                    continue;
+                }
+                out.push_str(&format!("1 FAMC {}\n", family_xref(i)));
+                // One pedigree covers both parents; otherwise the family says
+                if husband_kind == wife_kind {
+                    match husband_kind {
+                        RelationKind::Adoptive => out.push_str("2 PEDI ADOPTED\n"),
+                        RelationKind::Step => out.push_str("2 PEDI OTHER\n3 PHRASE Step\n"),
+                        _ => out.push_str("2 PEDI BIRTH\n"),
+                    }
+                }
+            }
+        }
+    }
+    for (i, family) in families.iter().enumerate() {
+        out.push_str(&format!("0 {} FAM\n", family_xref(i)));
+        if let Some(husband) = &family.husband {
+            out.push_str(&format!("1 HUSB {}\n", xref[husband.as_str()]));
+        }
+        if let Some(wife) = &family.wife {
+            out.push_str(&format!("1 WIFE {}\n", xref[wife.as_str()]));
+        }
+        for (child, husband_kind, wife_kind) in &family.children {
+            out.push_str(&format!("1 CHIL {}\n", xref[child.as_str()]));
+            if husband_kind != wife_kind {
+                for (tag, kind, parent) in [("_FREL", husband_kind, &family.husband), ("_MREL", wife_kind, &family.wife)] {
+                    if parent.is_some() {
+                        out.push_str(&format!("2 {} {}\n", tag, pedigree_value(*kind).unwrap_or("Birth")));
+                    }
+                }
+            }
+        }
+    }
+    for (i, file) in objects.iter().enumerate() {
+        out.push_str(&format!("0 @O{}@ OBJE\n1 FILE {}\n", i + 1, file));
+        let form = match file.strip_prefix("data:") {
+            Some(rest) => rest.split([';', ',']).next().unwrap_or("image/jpeg").to_string(),
+            None => "image/jpeg".to_string(),
+        };
+        out.push_str(&format!("2 FORM {}\n", form));
+    }
+    out.push_str("0 TRLR\n");
+    out
+}
+
+pub fn data_url(tree: &FamilyTree, options: &ExportOptions) -> String {
+    format!("data:text/vnd.familysearch.gedcom;charset=utf-8;base64,{}", STANDARD.encode(serialize(tree, options)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each date through an export and back in
+    fn round_trip(dates: &[&str]) -> Vec<String> {
+        let persons = dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| Person { id: i.to_string(), given_name: format!("P{}", i), birth_date: date.to_string(), ..Person::default() })
+            .collect();
+        let tree = FamilyTree { persons, relationships: Vec::new() };
+        let options = ExportOptions { branch: Branch::Everyone, hide_living: false, living_years: 100 };
+        let (read, _) = parse(serialize(&tree, &options).as_bytes()).unwrap();
+        read.persons.iter().map(|p| p.birth_date.clone()).collect()
+    }
+
+    fn person(id: &str, gender: Gender, born: &str) -> Person {
+        Person { id: id.to_string(), given_name: id.to_string(), surname: "Das".to_string(), gender, birth_date: born.to_string(), ..Person::default() }
+    }
+
+    // Two parents, a birth child with a spouse, an adopted child and a
+    // child who is a stepchild of only one parent
+    fn family() -> FamilyTree {
+        let mut rahim = person("Rahim", Gender::Male, "1920");
+        rahim.death_date = "1990".to_string();
+        let mut karima = person("Karima", Gender::Female, "1925");
+        karima.death_date = "1995".to_string();
+        karima.alternate_names = vec!["Karima Begum".to_string()];
+        karima.photo = "https://example.com/karima.jpg".to_string();
+        let persons = vec![
+            rahim,
+            karima,
+            person("Nasir", Gender::Male, "1950"),
+            person("Lipi", Gender::Female, "1952"),
+            person("Tania", Gender::Female, "1955"),
+            person("Sumon", Gender::Male, "2001"),
+        ];
+        let relationships = vec![
+            Relationship::new(RelationKind::Spouse, "Rahim", "Karima"),
+            Relationship::new(RelationKind::Parent, "Rahim", "Nasir"),
+            Relationship::new(RelationKind::Parent, "Karima", "Nasir"),
+            Relationship::new(RelationKind::Adoptive, "Rahim", "Lipi"),
+            Relationship::new(RelationKind::Adoptive, "Karima", "Lipi"),
+            Relationship::new(RelationKind::Step, "Rahim", "Tania"),
+            Relationship::new(RelationKind::Parent, "Karima", "Tania"),
+            Relationship::new(RelationKind::Parent, "Nasir", "Sumon"),
+        ];
+        FamilyTree { persons, relationships }
+    }
+
+    fn export(tree: &FamilyTree, branch: Branch, hide_living: bool) -> String {
+        serialize(tree, &ExportOptions { branch, hide_living, living_years: 100 })
+    }
+
+    // Every link as (from name, kind, to name), so ids don't matter
+    fn links(tree: &FamilyTree) -> BTreeSet<(String, String, String)> {
+        let name = |id: &str| tree.person(id).map(|p| p.given_name.clone()).unwrap_or_default();
+        tree.links().map(|l| (name(&l.from), l.kind.label().to_string(), name(&l.to))).collect()
+    }
+
+    #[test]
+    fn links_of_every_kind_come_back() {
+        let tree = family();
+        let text = export(&tree, Branch::Everyone, false);
+        assert!(text.contains("2 PEDI ADOPTED"));
+        assert!(text.contains("2 _FREL Step"));
+        assert!(text.contains("2 _MREL Birth"));
+        let (read, report) = parse(text.as_bytes()).unwrap();
+        assert_eq!(report.persons, 6);
+        assert_eq!(links(&read), links(&tree));
+    }
+
+    #[test]
+    fn names_and_photos_come_back() {
+        let (read, _) = parse(export(&family(), Branch::Everyone, false).as_bytes()).unwrap();
+        let karima = read.people().find(|p| p.given_name == "Karima").unwrap();
+        assert_eq!(karima.surname, "Das");
+        assert_eq!(karima.alternate_names, vec!["Karima Begum".to_string()]);
+        assert_eq!(karima.photo, "https://example.com/karima.jpg");
+        assert_eq!(karima.death_date, "1995");
+    }
+
+    #[test]
+    fn a_branch_takes_only_its_people() {
+        let (read, _) = parse(export(&family(), Branch::Ancestors("Sumon".to_string()), false).as_bytes()).unwrap();
+        let names: BTreeSet<String> = read.people().map(|p| p.given_name.clone()).collect();
+        let expected: BTreeSet<String> = ["Rahim", "Karima", "Nasir", "Sumon"].iter().map(|n| n.to_string()).collect();
+        assert_eq!(names, expected);
+        assert_eq!(read.links().count(), 4);
+    }
+
+    #[test]
+    fn living_people_are_left_out_when_asked() {
+        let mut tree = family();
+        tree.persons[5].photo = "https://example.com/sumon.jpg".to_string();
+        tree.persons[5].birth_place = "Sylhet".to_string();
+        let text = export(&tree, Branch::Everyone, true);
+        // Everyone born within the last hundred years is hidden
+        let hidden: Vec<&str> = text.split("\n0 ").filter(|record| record.contains("1 RESN PRIVACY")).collect();
+        assert_eq!(hidden.len(), 4);
+        for record in hidden {
+            assert!(record.contains("1 NAME Living //"));
+            assert!(!record.contains("1 BIRT") && !record.contains("1 DEAT") && !record.contains("1 OBJE"));
+        }
+        assert!(!text.contains("Sumon") && !text.contains("Sylhet") && !text.contains("sumon.jpg"));
+        // The dead are written in full, and the living keep their links
+        assert!(text.contains("1 NAME Rahim /Das/"));
+        let (read, _) = parse(text.as_bytes()).unwrap();
+        assert_eq!(read.links().count(), 8);
+    }
+
+    #[test]
+    fn dates_written_another_way_come_back_as_written() {
+        let dates = ["1970-03-12", "11 Feb 1750/51", "১২ বৈশাখ ১৩৭৭", "abt 1900", "0044-03-15 BCE", "44 BCE"];
+        assert_eq!(round_trip(&dates), dates);
+    }
+
+    #[test]
+    fn the_era_follows_the_whole_date() {
+        assert_eq!(simple_date(&["15", "MAR", "44", "BCE"]), Some("0044-03-15 BCE".to_string()));
+        assert_eq!(simple_date(&["MAR", "44", "B.C."]), Some("0044-03 BCE".to_string()));
+        assert_eq!(simple_date(&["44", "BC"]), Some("44 BCE".to_string()));
+        assert_eq!(gedcom_simple("0044-03-15 BCE"), Some("15 MAR 44 BCE".to_string()));
+        assert!(FuzzyDate::parse("0044-03-15 BCE").is_ok());
+        let mut report = ImportReport::default();
+        assert_eq!(date("15 MAR 44 BCE", &mut report), "0044-03-15 BCE");
+    }
+
+    fn file(charset: &str, body: &str) -> String {
+        format!("0 HEAD\n1 GEDC\n2 VERS 5.5.1\n1 CHAR {}\n{}0 TRLR\n", charset, body)
+    }
+
+    #[test]
+    fn ansel_marks_come_before_their_letter() {
+        let mut bytes = file("ANSEL", "0 @I1@ INDI\n1 NAME ").into_bytes();
+        bytes.truncate(bytes.len() - "0 TRLR\n".len());
+        // "Jos\u{301}e" written the ANSEL way, a letter with no precomposed
+        // form, two marks on one letter and spacing characters
+        bytes.extend(b"Jos\xE2e /\xE8Ozt\xE2g\xE2\xF2a\xA1\xCF/\n0 TRLR\n");
+        let (tree, report) = parse(&bytes).unwrap();
+        assert_eq!(report.encoding, "ANSEL");
+        assert_eq!(tree.persons[0].given_name, "José");
+        assert_eq!(tree.persons[0].surname, "Öztg\u{301}á\u{323}Łß");
+        assert!(report.lossy.is_empty());
+
+        let mut report = ImportReport::default();
+        assert_eq!(ansel(b"a\x80b\xFC", &mut report), "a\u{FFFD}b\u{FFFD}");
+        assert_eq!(report.lossy.values().sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn utf16_is_found_with_or_without_a_byte_order_mark() {
+        let text = file("UNICODE", "0 @I1@ INDI\n1 NAME রহিম /উদ্দিন/\n");
+        for big_endian in [false, true] {
+            let units: Vec<u8> = text.encode_utf16().flat_map(|u| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() }).collect();
+            let bom: &[u8] = if big_endian { &[0xFE, 0xFF] } else { &[0xFF, 0xFE] };
+            for bytes in [units.clone(), [bom, &units].concat()] {
+                let (tree, report) = parse(&bytes).unwrap();
+                assert_eq!(report.encoding, if big_endian { "UTF-16 BE" } else { "UTF-16 LE" });
+                assert_eq!(tree.persons[0].full_name(), "রহিম উদ্দিন");
+            }
+        }
+        // UTF-8 declared but not what the file holds
+        let (tree, report) = parse(b"0 HEAD\n1 CHAR UTF-8\n0 @I1@ INDI\n1 NAME Jos\xE9 //\n0 TRLR\n").unwrap();
+        assert_eq!(report.encoding, "Windows-1252");
+        assert_eq!(tree.persons[0].given_name, "José");
+        assert_eq!(report.lossy.len(), 1);
+    }
+
+    #[test]
+    fn records_are_mapped_onto_the_tree() {
+        let body = "0 @I1@ INDI\n\
+                    1 NAME Rahim /Uddin/\n\
+                    2 GIVN Abdur Rahim\n\
+                    1 NAME Rohim /Uddin/\n\
+                    1 SEX M\n\
+                    1 BIRT\n\
+                    2 DATE ABT 1890\n\
+                    2 PLAC Sylhet, , Bengal\n\
+                    2 SOUR @S1@\n\
+                    3 PAGE p. 12\n\
+                    1 DEAT\n\
+                    2 DATE BET 1950 AND 1955\n\
+                    1 OCCU Farmer\n\
+                    1 OCCU Teacher\n\
+                    1 NOTE @N1@\n\
+                    1 NOTE Came from\n\
+                    2 CONT the village\n\
+                    1 OBJE @O1@\n\
+                    1 OBJE\n\
+                    2 FILE https://example.com/second.jpg\n\
+                    1 FAMS @F1@\n\
+                    1 CHAN\n\
+                    2 DATE 1 JAN 2000\n\
+                    0 @I2@ INDI\n\
+                    1 NAME Karima //\n\
+                    1 SEX F\n\
+                    1 OBJE\n\
+                    2 FILE C:\\photos\\karima.jpg\n\
+                    0 @I3@ INDI\n\
+                    1 NAME Nasir /Uddin/\n\
+                    1 FAMC @F1@\n\
+                    0 @F1@ FAM\n\
+                    1 HUSB @I1@\n\
+                    1 WIFE @I2@\n\
+                    1 CHIL @I3@\n\
+                    1 MARR\n\
+                    2 DATE 1915\n\
+                    0 @S1@ SOUR\n\
+                    1 TITL Census\n\
+                    1 AUTH Government\n\
+                    0 @N1@ NOTE A shared note\n\
+                    0 @O1@ OBJE\n\
+                    1 FILE https://example.com/rahim.jpg\n";
+        let (tree, report) = parse(file("UTF-8", body).as_bytes()).unwrap();
+        assert_eq!((report.version.as_str(), report.persons, report.relationships), ("5.5.1", 3, 3));
+        let rahim = &tree.persons[0];
+        assert_eq!(rahim.full_name(), "Abdur Rahim Uddin");
+        assert_eq!(rahim.alternate_names, ["Rohim Uddin"]);
+        assert_eq!(rahim.gender, Gender::Male);
+        assert_eq!((rahim.birth_date.as_str(), rahim.birth_place.as_str()), ("abt 1890", "Sylhet, Bengal"));
+        assert_eq!(rahim.death_date, "between 1950 and 1955");
+        assert_eq!(rahim.occupation, "Farmer; Teacher");
+        assert_eq!(rahim.notes, "Source: Census (Government), p. 12\n\nA shared note\n\nCame from\nthe village");
+        assert_eq!(rahim.photo, "https://example.com/rahim.jpg");
+        assert!(tree.persons[1].photo.is_empty());
+        let nasir = &tree.persons[2].id;
+        let parents: Vec<(String, RelationKind)> = tree.parents_of(nasir).into_iter().map(|(p, kind)| (p.given_name.clone(), kind)).collect();
+        assert_eq!(parents, [("Abdur Rahim".to_string(), RelationKind::Parent), ("Karima".to_string(), RelationKind::Parent)]);
+        assert_eq!(tree.spouses_of(&rahim.id).len(), 1);
+        assert_eq!(
+            report.lossy.keys().collect::<Vec<_>>(),
+            [
+                "Marriage dates and places weren't kept",
+                "Media files stored on the other computer couldn't be brought over",
+                "Only the first photo of each person was kept",
+            ]
+        );
+        assert!(report.unsupported.is_empty());
+    }
+
+    #[test]
+    fn what_has_no_place_is_reported() {
+        let body = "0 @I1@ INDI\n\
+                    1 NAME Rahim /Uddin/\n\
+                    2 NPFX Dr\n\
+                    1 SEX X\n\
+                    1 EVEN\n\
+                    1 EVEN\n\
+                    1 BIRT\n\
+                    2 DATE 12 MAR 1890\n\
+                    2 AGE 0\n\
+                    2 PLAC Sylhet\n\
+                    3 MAP\n\
+                    1 BIRT\n\
+                    2 DATE 1891\n\
+                    1 DEAT\n\
+                    2 DATE @#DJULIAN@ 1 JAN 1950\n\
+                    1 NOTE @N9@\n\
+                    0 @R1@ REPO\n\
+                    1 NAME Archive\n";
+        let (tree, report) = parse(file("UTF-8", body).as_bytes()).unwrap();
+        let skipped: Vec<(&str, usize)> = report.unsupported.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(skipped, [("INDI.BIRT.AGE", 1), ("INDI.BIRT.PLAC.MAP", 1), ("INDI.EVEN", 2), ("INDI.NAME.NPFX", 1), ("REPO", 1)]);
+        assert_eq!(tree.persons[0].birth_date, "1890-03-12");
+        assert_eq!(tree.persons[0].death_date, "@#DJULIAN@ 1 JAN 1950");
+        assert_eq!(tree.persons[0].gender, Gender::Unknown);
+        assert_eq!(report.lossy.len(), 4);
+        assert!(parse(b"0 @I1@ INDI\n").is_err());
+        assert!(parse(file("UTF-8", "").as_bytes()).is_err());
+    }
+
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::models::circle::CircleMember;
+use crate::models::event::{CircleEvent, Frequency, Recurrence, Weekday};
+use crate::services::sync::Stamp;
+use crate::services::vcard::{escape, push_line, unfold, Property};
+use crate::utils::format::{civil_from_days, days_from_civil};
+use crate::utils::new_id;
+
+// iCalendar (RFC 5545) reading and writing of circle events. Only VEVENTs
+// are read. Repeat rules a Recurrence can't hold exactly, such as "second
+// Monday" or ones with EXDATE exceptions, come in as single events and are
+// counted in the result. Times with a TZID are taken as local time, as we
+// don't carry a time zone database.
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parsed {
+    pub events: Vec<CircleEvent>,
+    // Repeating events brought in as their first occurrence only
+    pub unrepeated: usize,
+}
+
+// Every event in the text, organised by `organizer` and not yet in a circle
+pub fn parse(text: &str, organizer: &CircleMember, offset: i64) -> Result<Parsed, String> {
+    let mut events = Vec::new();
+    let mut unrepeated = 0;
+    let mut current: Option<Vec<Property>> = None;
+    for line in unfold(text) {
+        let Some(property) = Property::parse(&line) else {
+            continue;
+        };
+        match (property.name.as_str(), property.text().to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => current = Some(Vec::new()),
+            ("END", "VEVENT") => {
+                if let Some((event, dropped_rule)) = current.take().and_then(|properties| event(&properties, organizer, offset)) {
+                    events.push(event);
+                    unrepeated += usize::from(dropped_rule);
+                }
+            }
+            _ => {
+                if let Some(properties) = current.as_mut() {
+                    properties.push(property);
+                }
+            }
+        }
+    }
+    if events.is_empty() {
+        return Err("No events found in this file".to_string());
+    }
+    Ok(Parsed { events, unrepeated })
+}
+
+// The event, and whether it had a repeat rule we couldn't keep
+fn event(properties: &[Property], organizer: &CircleMember, offset: i64) -> Option<(CircleEvent, bool)> {
+    let find = |name: &str| properties.iter().find(|p| p.name == name);
+    let text = |name: &str| find(name).map(|p| p.text().trim().to_string()).unwrap_or_default();
+    let (starts_at, all_day) = date_time(find("DTSTART")?, offset)?;
+    let ends_at = match (find("DTEND"), find("DURATION")) {
+        (Some(end), _) => date_time(end, offset).map(|(end, _)| end)?,
+        (None, Some(duration)) => starts_at.saturating_add(parse_duration(&duration.text())?),
+        (None, None) if all_day => starts_at + DAY_MILLIS as u64,
+        (None, None) => starts_at,
+    };
+    let recurrence = match find("RRULE") {
+        Some(_) if find("EXDATE").is_some() || find("RDATE").is_some() => None,
+        Some(rule) => parse_rrule(&rule.text(), starts_at, offset),
+        None => None,
+    };
+    let dropped_rule = find("RRULE").is_some() && recurrence.is_none();
+    let id = new_id();
+    let uid = match text("UID") {
+        uid if uid.is_empty() => format!("{}@jeebon", id),
+        uid => uid,
+    };
+    let title = match text("SUMMARY") {
+        title if title.is_empty() => "Untitled event".to_string(),
+        title => title,
+    };
+    let event = CircleEvent {
+        id,
+        circle_id: String::new(),
+        uid,
+        title,
+        description: text("DESCRIPTION"),
+        location: text("LOCATION"),
+        starts_at,
+        ends_at: ends_at.max(starts_at),
+        all_day,
+        recurrence,
+        organizer: organizer.clone(),
+        rsvps: BTreeMap::new(),
+        stamp: Stamp::now(),
+        deleted: None,
+    };
+    Some((event, dropped_rule))
+}
+
+// A DATE or DATE-TIME value as UTC milliseconds, and whether it was a date
+fn date_time(property: &Property, offset: i64) -> Option<(u64, bool)> {
+    let value = property.text();
+    let value = value.trim();
+    let date_only = property.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.len() == 8;
+    let millis = parse_stamp(value, offset)?;
+    Some((millis, date_only))
+}
+
+// "20250312", "20250312T183000" (local) or "20250312T183000Z" (UTC)
+fn parse_stamp(value: &str, offset: i64) -> Option<u64> {
+    let digits = |range: std::ops::Range<usize>| value.get(range).and_then(|d| d.parse::<i64>().ok());
+    let day = days_from_civil(digits(0..4)?, digits(4..6)? as u32, digits(6..8)? as u32);
+    let time = match value.get(8..9) {
+        Some("T") => (digits(9..11)? * 3600 + digits(11..13)? * 60 + digits(13..15).unwrap_or(0)) * 1000,
+        _ => 0,
+    };
+    let utc = value.ends_with('Z');
+    let millis = day * DAY_MILLIS + time - if utc { 0 } else { offset };
+    u64::try_from(millis).ok()
+}
+
+// "PT1H30M", "P1D" or "P2W" in milliseconds
+fn parse_duration(value: &str) -> Option<u64> {
+    let value = value.trim().trim_start_matches('+').strip_prefix('P')?;
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        let unit: u64 = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'T' => continue,
+            'W' => 7 * DAY_MILLIS as u64,
+            'D' => DAY_MILLIS as u64,
+            'H' => 3_600_000,
+            'M' => 60_000,
+            'S' => 1000,
+            _ => return None,
+        };
+        total = number.parse::<u64>().ok()?.checked_mul(unit).and_then(|part| total.checked_add(part))?;
+        number.clear();
+    }
+    Some(total)
+}
+
+// "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10". None for rules we can't
+// follow exactly: hourly repeats, BYDAY outside weekly rules or with an
+// ordinal ("2MO"), BYSETPOS, and BYMONTHDAY or BYMONTH other than the
+// start's own day and month.
+fn parse_rrule(value: &str, starts_at: u64, offset: i64) -> Option<Recurrence> {
+    let parts: BTreeMap<String, String> = value
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_uppercase(), value.trim().to_string()))
+        .collect();
+    let frequency = Frequency::from_code(parts.get("FREQ")?)?;
+    let (_, start_month, start_day) = civil_from_days((starts_at as i64 + offset).div_euclid(DAY_MILLIS));
+    for (key, value) in &parts {
+        let follows = match key.as_str() {
+            "FREQ" | "INTERVAL" | "COUNT" | "UNTIL" | "WKST" => true,
+            "BYDAY" => frequency == Frequency::Weekly,
+            "BYMONTHDAY" => frequency != Frequency::Weekly && frequency != Frequency::Daily && value.parse() == Ok(start_day),
+            "BYMONTH" => frequency == Frequency::Yearly && value.parse() == Ok(start_month),
+            _ => false,
+        };
+        if !follows {
+            return None;
+        }
+    }
+    let by_day = match parts.get("BYDAY") {
+        Some(days) => days.split(',').map(|day| Weekday::from_code(day.trim())).collect::<Option<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    Some(Recurrence {
+        frequency,
+        interval: parts.get("INTERVAL").and_then(|i| i.parse().ok()).unwrap_or(1),
+        by_day,
+        count: parts.get("COUNT").and_then(|c| c.parse().ok()),
+        until: parts.get("UNTIL").and_then(|until| match until.len() {
+            // A date means the whole of that day
+            8 => parse_stamp(until, offset).map(|start| start + DAY_MILLIS as u64 - 1),
+            _ => parse_stamp(until, offset),
+        }),
+    })
+}
+
+fn rrule(rule: &Recurrence) -> String {
+    let mut parts = vec![format!("FREQ={}", rule.frequency.code())];
+    if rule.interval > 1 {
+        parts.push(format!("INTERVAL={}", rule.interval));
+    }
+    if rule.frequency == Frequency::Weekly && !rule.by_day.is_empty() {
+        parts.push(format!("BYDAY={}", rule.by_day.iter().map(Weekday::code).collect::<Vec<_>>().join(",")));
+    }
+    if let Some(count) = rule.count {
+        parts.push(format!("COUNT={}", count));
+    }
+    if let Some(until) = rule.until {
+        parts.push(format!("UNTIL={}", utc_stamp(until)));
+    }
+    parts.join(";")
+}
+
+fn utc_stamp(millis: u64) -> String {
+    let millis = millis as i64;
+    let (year, month, day) = civil_from_days(millis.div_euclid(DAY_MILLIS));
+    let seconds = millis.rem_euclid(DAY_MILLIS) / 1000;
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
+
+fn local_date(millis: u64, offset: i64) -> String {
+    let (year, month, day) = civil_from_days((millis as i64 + offset).div_euclid(DAY_MILLIS));
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+pub fn serialize(events: &[CircleEvent], offset: i64) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//Jeebon//Circles//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", escape(&event.uid)));
+        push_line(&mut out, &format!("DTSTAMP:{}", utc_stamp(event.stamp.millis)));
+        if event.all_day {
+            push_line(&mut out, &format!("DTSTART;VALUE=DATE:{}", local_date(event.starts_at, offset)));
+            push_line(&mut out, &format!("DTEND;VALUE=DATE:{}", local_date(event.ends_at, offset)));
+        } else {
+            push_line(&mut out, &format!("DTSTART:{}", utc_stamp(event.starts_at)));
+            push_line(&mut out, &format!("DTEND:{}", utc_stamp(event.ends_at)));
+        }
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&event.title)));
+        for (name, value) in [("DESCRIPTION", &event.description), ("LOCATION", &event.location)] {
+            if !value.is_empty() {
+                push_line(&mut out, &format!("{}:{}", name, escape(value)));
+            }
+        }
+        if let Some(rule) = &event.recurrence {
+            push_line(&mut out, &format!("RRULE:{}", rrule(rule)));
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+// A data: URI for downloading the events as an .ics file
+pub fn data_url(events: &[CircleEvent], offset: i64) -> String {
+    format!("data:text/calendar;charset=utf-8;base64,{}", STANDARD.encode(serialize(events, offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(body: &str) -> Parsed {
+        let text = format!("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART:20250310T090000Z\r\n{}END:VEVENT\r\nEND:VCALENDAR\r\n", body);
+        parse(&text, &CircleMember::local(), 0).unwrap()
+    }
+
+    #[test]
+    fn rules_we_follow_are_kept() {
+        let parsed = calendar("RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10\r\n");
+        let rule = parsed.events[0].recurrence.clone().unwrap();
+        assert_eq!((rule.interval, rule.by_day, rule.count), (2, vec![Weekday::Mon, Weekday::Thu], Some(10)));
+        assert_eq!(parsed.unrepeated, 0);
+
+        // The start is on the 10th of March
+        let parsed = calendar("RRULE:FREQ=YEARLY;BYMONTH=3;BYMONTHDAY=10\r\n");
+        assert!(parsed.events[0].recurrence.is_some());
+        assert_eq!(parsed.unrepeated, 0);
+    }
+
+    #[test]
+    fn rules_we_cant_follow_become_single_events() {
+        for rule in [
+            "RRULE:FREQ=MONTHLY;BYDAY=2MO\r\n",
+            "RRULE:FREQ=MONTHLY;BYDAY=MO;BYSETPOS=-1\r\n",
+            "RRULE:FREQ=MONTHLY;BYMONTHDAY=15\r\n",
+            "RRULE:FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR\r\n",
+            "RRULE:FREQ=HOURLY\r\n",
+            "RRULE:FREQ=WEEKLY\r\nEXDATE:20250317T090000Z\r\n",
+        ] {
+            let parsed = calendar(rule);
+            assert_eq!(parsed.events[0].recurrence, None, "{}", rule);
+            assert_eq!(parsed.unrepeated, 1, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn durations_that_overflow_are_rejected() {
+        assert_eq!(parse_duration("PT1H30M"), Some(90 * 60_000));
+        assert_eq!(parse_duration("P2W"), Some(14 * DAY_MILLIS as u64));
+        assert_eq!(parse_duration("P99999999999999999W"), None);
+        assert_eq!(parse_duration("P18446744073709551615D"), None);
+    }
+}
@@ -0,0 +1,180 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::CircleDetails;
+use crate::services::crypto::hmac_sha256;
+
+// Circle invite tokens. A token carries the circle's id and details, so the
+// person invited can see what they are joining, and an HMAC under one of the
+// circle's invite keys, which only its moderators and above (and the server)
+// hold. Anyone else can't tell a genuine token from a forged one, so invitees
+// have the server redeem it. Expiry and usage limits are checked against the
+// invite record itself.
+
+const API_URL: Option<&str> = option_env!("JEEBON_API_URL");
+// Scheme the mobile apps register for opening invites
+const DEEP_LINK_SCHEME: &str = "jeebon";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InviteToken {
+    #[serde(rename = "c")]
+    pub circle_id: String,
+    #[serde(rename = "i")]
+    pub invite_id: String,
+    // Which of the circle's invite keys signed it
+    #[serde(rename = "k")]
+    pub key_id: String,
+    #[serde(rename = "e")]
+    pub expires_at: u64,
+    #[serde(rename = "d")]
+    pub details: CircleDetails,
+    // Name of whoever created the invite
+    #[serde(rename = "b")]
+    pub invited_by: String,
+}
+
+// "<payload>.<signature>", both base64url
+pub fn issue(secret: &str, token: &InviteToken) -> String {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(token).unwrap_or_default());
+    let signature = URL_SAFE_NO_PAD.encode(hmac_sha256(secret.as_bytes(), payload.as_bytes()));
+    format!("{}.{}", payload, signature)
+}
+
+// Read a token without checking its signature, to find the circle
+pub fn decode(token: &str) -> Result<InviteToken, String> {
+    let (payload, _) = token.split_once('.').ok_or_else(|| "This invite link is incomplete".to_string())?;
+    URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| "This invite link is damaged".to_string())
+}
+
+pub fn verify(secret: &str, token: &str) -> Result<InviteToken, String> {
+    let decoded = decode(token)?;
+    let (payload, signature) = token.split_once('.').unwrap_or_default();
+    let expected = hmac_sha256(secret.as_bytes(), payload.as_bytes());
+    let valid = URL_SAFE_NO_PAD
+        .decode(signature)
+        .is_ok_and(|given| given.len() == expected.len() && given.iter().zip(expected).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0);
+    if secret.is_empty() || !valid {
+        return Err("This invite link isn't genuine".to_string());
+    }
+    Ok(decoded)
+}
+
+#[derive(Serialize)]
+struct RedeemRequest<'a> {
+    token: &'a str,
+}
+
+// Have the server check the token against the circle and count the use
+pub async fn redeem(token: &str) -> Result<(), String> {
+    let Some(api) = API_URL else {
+        return Err("This invite can only be checked online".to_string());
+    };
+    reqwest::Client::new()
+        .post(format!("{}/invites/redeem", api))
+        .json(&RedeemRequest { token })
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("The server didn't accept this invite: {}", e))
+}
+
+pub fn deep_link(token: &str) -> String {
+    format!("{}://invite/{}", DEEP_LINK_SCHEME, token)
+}
+
+// Link that opens the accept-invite page in a browser, or the app when
+// there is no web address to use
+pub fn share_url(token: &str) -> String {
+    match app_origin() {
+        Some(origin) => format!("{}/invite/{}", origin, token),
+        None => deep_link(token),
+    }
+}
+
+fn app_origin() -> Option<String> {
+    if let Some(origin) = option_env!("JEEBON_APP_URL") {
+        return Some(origin.trim_end_matches('/').to_string());
+    }
+
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    if let Some(origin) = web_sys::window().and_then(|w| w.location().origin().ok()) {
+        return Some(origin);
+    }
+
+    None
+}
+
+// The text as a QR code in SVG, one unit per module with a quiet zone
+pub fn qr_svg(text: &str) -> Option<String> {
+    let code = match QrCode::new(text.as_bytes()) {
+        Ok(code) => code,
+        Err(e) => {
+            log::error!("Failed to build QR code: {}", e);
+            return None;
+        }
+    };
+    let width = code.width();
+    let quiet = 4;
+    let size = width + 2 * quiet;
+    let mut path = String::new();
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            path.push_str(&format!("M{} {}h1v1h-1z", index % width + quiet, index / width + quiet));
+        }
+    }
+    Some(format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {size} {size}" shape-rendering="crispEdges"><rect width="{size}" height="{size}" fill="#fff"/><path d="{path}" fill="#000"/></svg>"##
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::circle::Visibility;
+
+    fn token() -> InviteToken {
+        InviteToken {
+            circle_id: "family".to_string(),
+            invite_id: "invite".to_string(),
+            key_id: "key".to_string(),
+            expires_at: 1_000,
+            details: CircleDetails {
+                name: "Family".to_string(),
+                description: String::new(),
+                icon: "bi-house-heart".to_string(),
+                visibility: Visibility::Private,
+                parent: None,
+            },
+            invited_by: "Ana".to_string(),
+        }
+    }
+
+    #[test]
+    fn tokens_carry_the_circle_and_verify_under_its_secret() {
+        let issued = issue("secret", &token());
+        assert_eq!(decode(&issued), Ok(token()));
+        assert_eq!(verify("secret", &issued), Ok(token()));
+        assert!(verify("other secret", &issued).is_err());
+        assert!(verify("", &issued).is_err());
+    }
+
+    #[test]
+    fn altered_tokens_are_rejected() {
+        let issued = issue("secret", &token());
+        let mut renamed = token();
+        renamed.details.name = "Not family".to_string();
+        let forged = issue("other", &renamed);
+        let (_, signature) = issued.split_once('.').unwrap();
+        let (payload, _) = forged.split_once('.').unwrap();
+        assert!(verify("secret", &format!("{}.{}", payload, signature)).is_err());
+        assert!(decode("no-dot").is_err());
+        assert!(decode("!!!.sig").is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::message::{Conversation, Message};
+use crate::services::crypto::keystore::{DirectEnvelope, KeyMaterial};
+use crate::services::crypto::sender_keys::{SenderKeyDistribution, SenderKeyMessage};
+use crate::services::crypto::x3dh::PreKeyBundle;
+use crate::services::crypto::CryptoError;
+use crate::services::repositories::{use_key_repo, use_message_repo, KeyRepository, KeyState, MessageRepository};
+use crate::utils::sleep;
+
+// End-to-end encrypted message delivery. Every change to a message (sending,
+// editing, reacting, deleting) goes out as the whole record, which the other
+// side merges like any synced copy. 1:1 conversations seal it with the
+// contact's Double Ratchet session; groups seal it once under our sender
+// key, after handing that key to each member over their 1:1 sessions.
+// Sessions start from the prekey bundles people publish to the API at
+// JEEBON_API_URL, and sealed records travel over the realtime socket.
+
+const API_URL: Option<&str> = option_env!("JEEBON_API_URL");
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+const CHANNEL: &str = "message";
+const PUMP_INTERVAL_MILLIS: u32 = 1000;
+
+// What travels inside a 1:1 session
+#[derive(Serialize, Deserialize)]
+enum Content {
+    Message(Box<Message>),
+    SenderKey(SenderKeyDistribution),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Envelope {
+    Direct(DirectEnvelope),
+    Group(SenderKeyMessage),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outgoing {
+    pub to: String,
+    pub envelope: Envelope,
+}
+
+fn encode(content: &Content) -> Vec<u8> {
+    serde_json::to_vec(content).expect("message content serialises")
+}
+
+fn decode(plaintext: &[u8]) -> Result<Content, CryptoError> {
+    serde_json::from_slice(plaintext).map_err(|e| CryptoError::Corrupted(e.to_string()))
+}
+
+// Recipients we have no session with yet
+pub fn missing_sessions(keys: &KeyMaterial, conversation: &Conversation) -> Vec<String> {
+    conversation
+        .recipients()
+        .into_iter()
+        .filter(|peer| !keys.has_session(peer))
+        .collect()
+}
+
+// Seal `message` for everyone else in `conversation`. Fails with NoSession
+// until there is a session with each recipient.
+pub fn seal(keys: &mut KeyMaterial, conversation: &Conversation, message: &Message) -> Result<Vec<Outgoing>, CryptoError> {
+    let content = encode(&Content::Message(Box::new(message.clone())));
+    if !conversation.is_group() {
+        let envelope = keys.encrypt_direct(&conversation.id, &content)?;
+        return Ok(vec![Outgoing { to: conversation.id.clone(), envelope: Envelope::Direct(envelope) }]);
+    }
+
+    let mut outgoing = Vec::new();
+    for member in keys.sender_key_recipients(&conversation.id, &conversation.members) {
+        let distribution = encode(&Content::SenderKey(keys.group_distribution(&conversation.id)?));
+        let envelope = keys.encrypt_direct(&member, &distribution)?;
+        keys.mark_distributed(&conversation.id, &member);
+        outgoing.push(Outgoing { to: member, envelope: Envelope::Direct(envelope) });
+    }
+    let sealed = keys.encrypt_group(&conversation.id, &content)?;
+    outgoing.extend(conversation.members.iter().map(|member| Outgoing {
+        to: member.clone(),
+        envelope: Envelope::Group(sealed.clone()),
+    }));
+    Ok(outgoing)
+}
+
+// Open an envelope from `from`. Sender keys are taken in and give nothing
+// to show; a 1:1 message belongs to the conversation with its sender.
+pub fn open(keys: &mut KeyMaterial, from: &str, envelope: &Envelope) -> Result<Option<Message>, CryptoError> {
+    match envelope {
+        Envelope::Direct(direct) => match decode(&keys.decrypt_direct(from, direct)?)? {
+            Content::SenderKey(distribution) => {
+                keys.accept_distribution(from, distribution);
+                Ok(None)
+            }
+            Content::Message(mut message) => {
+                message.conversation_id = from.to_string();
+                Ok(Some(*message))
+            }
+        },
+        Envelope::Group(sealed) => match decode(&keys.decrypt_group(from, sealed)?)? {
+            Content::Message(message) if message.conversation_id == sealed.group_id => Ok(Some(*message)),
+            _ => Err(CryptoError::Corrupted("group message for another conversation".to_string())),
+        },
+    }
+}
+
+async fn fetch_bundle(api: &str, peer_id: &str) -> Result<PreKeyBundle, String> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/keys/{}", api, peer_id))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Couldn't fetch the keys of {}: {}", peer_id, e))?;
+    response.json().await.map_err(|e| format!("Unexpected keys for {}: {}", peer_id, e))
+}
+
+async fn publish_bundle(api: &str, bundle: &PreKeyBundle) -> Result<(), String> {
+    reqwest::Client::new()
+        .put(format!("{}/keys/{}", api, crate::models::LOCAL_USER_ID))
+        .json(bundle)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("Couldn't publish our keys: {}", e))
+}
+
+// Whether sealed messages can go out right now. Sealing advances the
+// session, so nothing is sealed that can't be sent.
+fn connected() -> bool {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        crate::services::realtime::shared(crate::models::LOCAL_USER_ID).is_some_and(|socket| socket.is_open())
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    false
+}
+
+fn transmit(outgoing: &[Outgoing]) {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    if let Some(socket) = crate::services::realtime::shared(crate::models::LOCAL_USER_ID) {
+        for item in outgoing {
+            socket.send(CHANNEL, &item.to, &item.envelope);
+        }
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    let _ = outgoing;
+}
+
+// Envelopes with the id of the person who sent them, as stamped by the relay
+fn incoming() -> Vec<(String, Envelope)> {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        crate::services::realtime::shared(crate::models::LOCAL_USER_ID)
+            .map(|socket| socket.take(CHANNEL))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(from, payload)| Some((from, serde_json::from_value(payload).ok()?)))
+            .collect()
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    Vec::new()
+}
+
+fn receive(mut messages: Signal<MessageRepository>, mut keys: Signal<KeyRepository>) {
+    for (from, envelope) in incoming() {
+        match keys.write().try_update(|m| open(m, &from, &envelope)) {
+            Ok(Some(message)) => {
+                if let Err(e) = messages.write().receive(&from, message) {
+                    log::warn!("Ignoring message from {}: {}", from, e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Couldn't decrypt a message from {}: {}", from, e),
+        }
+    }
+}
+
+async fn deliver(api: &str, mut messages: Signal<MessageRepository>, mut keys: Signal<KeyRepository>) {
+    let pending = messages.read().pending();
+    for (conversation, message) in pending {
+        let missing = keys.read().material().map(|m| missing_sessions(m, &conversation)).unwrap_or_default();
+        for peer in missing {
+            let started = match fetch_bundle(api, &peer).await {
+                Ok(bundle) => keys.write().try_update(|m| m.start_session(&peer, &bundle)).map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = started {
+                log::debug!("No session with {} yet: {}", peer, e);
+            }
+        }
+        match keys.write().try_update(|m| seal(m, &conversation, &message)) {
+            Ok(outgoing) => {
+                transmit(&outgoing);
+                messages.write().delivered(&message.id);
+            }
+            // Tried again on the next pass
+            Err(CryptoError::NoSession) => {}
+            Err(e) => log::warn!("Couldn't encrypt message {}: {}", message.id, e),
+        }
+    }
+}
+
+// Keep sending and receiving messages for the life of the app while the
+// keys are unlocked; needs the repositories first
+pub fn use_message_delivery() {
+    let messages = use_message_repo();
+    let keys = use_key_repo();
+    use_future(move || async move {
+        let mut published = None;
+        loop {
+            sleep(PUMP_INTERVAL_MILLIS).await;
+            if keys.read().state() != KeyState::Unlocked {
+                continue;
+            }
+            receive(messages, keys);
+            let (Some(api), true) = (API_URL, connected()) else {
+                continue;
+            };
+
+            // Publish again whenever a one-time prekey has been used up
+            let bundle = keys.read().material().map(|m| m.bundle());
+            if bundle != published
+                && let Some(ours) = &bundle
+            {
+                match publish_bundle(api, ours).await {
+                    Ok(()) => published = bundle,
+                    Err(e) => log::warn!("{}", e),
+                }
+            }
+            deliver(api, messages, keys).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn conversation(id: &str, members: &[&str]) -> Conversation {
+        Conversation {
+            id: id.to_string(),
+            name: id.to_string(),
+            avatar: String::new(),
+            last_read_at: 0,
+            members: members.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    // Everyone in `people` with sessions to everyone else
+    fn connected_people(people: &[&str]) -> Vec<KeyMaterial> {
+        let mut keys: Vec<KeyMaterial> = people.iter().map(|_| KeyMaterial::generate().unwrap()).collect();
+        for a in 0..people.len() {
+            for b in a + 1..people.len() {
+                let bundle = keys[b].bundle();
+                keys[a].start_session(people[b], &bundle).unwrap();
+                let hello = keys[a].encrypt_direct(people[b], b"hello").unwrap();
+                keys[b].decrypt_direct(people[a], &hello).unwrap();
+            }
+        }
+        keys
+    }
+
+    fn deliver_to(keys: &mut KeyMaterial, from: &str, to: &str, outgoing: &[Outgoing]) -> Vec<Message> {
+        outgoing
+            .iter()
+            .filter(|o| o.to == to)
+            .filter_map(|o| open(keys, from, &o.envelope).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn direct_messages_are_sealed_for_the_contact() {
+        let mut keys = connected_people(&["ana", "ben"]);
+        let (ana, ben) = keys.split_at_mut(1);
+        let message = Message::new("ben", "ana", "Ana", "the secret plan");
+
+        let outgoing = seal(&mut ana[0], &conversation("ben", &[]), &message).unwrap();
+        assert_eq!(outgoing.len(), 1);
+        let wire = serde_json::to_string(&outgoing[0].envelope).unwrap();
+        assert!(!wire.contains("secret plan"));
+
+        let received = deliver_to(&mut ben[0], "ana", "ben", &outgoing);
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].text(), "the secret plan");
+        // On Ben's side the chat with Ana is the conversation "ana"
+        assert_eq!(received[0].conversation_id, "ana");
+    }
+
+    #[test]
+    fn sealing_needs_a_session() {
+        let mut ana = KeyMaterial::generate().unwrap();
+        let chat = conversation("ben", &[]);
+        assert_eq!(missing_sessions(&ana, &chat), vec!["ben".to_string()]);
+        let message = Message::new("ben", "ana", "Ana", "hi");
+        assert_eq!(seal(&mut ana, &chat, &message), Err(CryptoError::NoSession));
+    }
+
+    #[test]
+    fn group_messages_use_sender_keys() {
+        let mut keys = connected_people(&["ana", "ben", "cal"]);
+        let group = conversation("team", &["ben", "cal"]);
+
+        let first = Message::new("team", "ana", "Ana", "standup at 10");
+        let outgoing = seal(&mut keys[0], &group, &first).unwrap();
+        // A sender key for each member, then the message itself
+        assert_eq!(outgoing.iter().filter(|o| matches!(o.envelope, Envelope::Direct(_))).count(), 2);
+        let ciphertexts: BTreeSet<String> = outgoing
+            .iter()
+            .filter_map(|o| match &o.envelope {
+                Envelope::Group(sealed) => Some(format!("{:?}", sealed.ciphertext)),
+                Envelope::Direct(_) => None,
+            })
+            .collect();
+        assert_eq!(ciphertexts.len(), 1);
+        for (index, member) in [(1, "ben"), (2, "cal")] {
+            let received = deliver_to(&mut keys[index], "ana", member, &outgoing);
+            assert_eq!(received.iter().map(|m| m.text()).collect::<Vec<_>>(), ["standup at 10"]);
+            assert_eq!(received[0].conversation_id, "team");
+        }
+
+        // The key only goes out once
+        let second = Message::new("team", "ana", "Ana", "moved to 11");
+        let outgoing = seal(&mut keys[0], &group, &second).unwrap();
+        assert!(outgoing.iter().all(|o| matches!(o.envelope, Envelope::Group(_))));
+        assert_eq!(deliver_to(&mut keys[1], "ana", "ben", &outgoing)[0].text(), "moved to 11");
+    }
+
+    #[test]
+    fn removed_members_lose_the_group_key() {
+        let mut keys = connected_people(&["ana", "ben", "cal"]);
+        let before = Message::new("team", "ana", "Ana", "hello both");
+        let outgoing = seal(&mut keys[0], &conversation("team", &["ben", "cal"]), &before).unwrap();
+        deliver_to(&mut keys[1], "ana", "ben", &outgoing);
+        deliver_to(&mut keys[2], "ana", "cal", &outgoing);
+
+        let after = Message::new("team", "ana", "Ana", "just us now");
+        let outgoing = seal(&mut keys[0], &conversation("team", &["ben"]), &after).unwrap();
+        assert!(outgoing.iter().all(|o| o.to == "ben"));
+        assert_eq!(deliver_to(&mut keys[1], "ana", "ben", &outgoing)[0].text(), "just us now");
+        // Cal's copy of the old key can't open the new one
+        let sealed = outgoing
+            .iter()
+            .find(|o| matches!(o.envelope, Envelope::Group(_)))
+            .unwrap();
+        assert!(open(&mut keys[2], "ana", &sealed.envelope).is_err());
+    }
+
+    #[test]
+    fn group_messages_stay_in_their_group() {
+        let mut keys = connected_people(&["ana", "ben"]);
+        let message = Message::new("other", "ana", "Ana", "misfiled");
+        let mut outgoing = seal(&mut keys[0], &conversation("team", &["ben"]), &message).unwrap();
+        // Ben takes the team key, then gets a record of another conversation under it
+        for item in &mut outgoing {
+            if let Envelope::Direct(_) = item.envelope {
+                open(&mut keys[1], "ana", &item.envelope).unwrap();
+            }
+        }
+        let sealed = outgoing.iter().find(|o| matches!(o.envelope, Envelope::Group(_))).unwrap();
+        assert!(matches!(open(&mut keys[1], "ana", &sealed.envelope), Err(CryptoError::Corrupted(_))));
+    }
+}
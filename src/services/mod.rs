@@ -0,0 +1,20 @@
+// Services behind the repositories:
+// storage, sync, encryption and message delivery, calls, polls and circle discovery,
+// contact, calendar and GEDCOM files, and the family tree layout and export
+pub mod calls;
+pub mod chart_export;
+pub mod crypto;
+pub mod csv;
+pub mod discovery;
+pub mod gedcom;
+pub mod ical;
+pub mod invites;
+pub mod messaging;
+pub mod polls;
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+pub mod realtime;
+pub mod repositories;
+pub mod storage;
+pub mod sync;
+pub mod tree_layout;
+pub mod vcard;
@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::poll::Ballot;
+use crate::services::repositories::use_feed_repo;
+use crate::utils::sleep;
+
+// Live poll results. Each vote is sent over the realtime socket to the
+// circles the poll was shared with ("circle:<id>", fanned out by the relay),
+// and votes from others are merged into the feed as they arrive.
+
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+const CHANNEL: &str = "poll";
+const PUMP_INTERVAL_MILLIS: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BallotUpdate {
+    pub post_id: String,
+    pub key: String,
+    pub ballot: Ballot,
+}
+
+pub fn broadcast(audience: &BTreeSet<String>, update: &BallotUpdate) {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    if let Some(socket) = crate::services::realtime::shared(crate::models::LOCAL_USER_ID) {
+        for circle_id in audience {
+            socket.send(CHANNEL, &format!("circle:{}", circle_id), update);
+        }
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    let _ = (audience, update);
+}
+
+// Updates with the id of the person who sent them, as stamped by the relay
+fn incoming() -> Vec<(String, BallotUpdate)> {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        crate::services::realtime::shared(crate::models::LOCAL_USER_ID)
+            .map(|socket| socket.take(CHANNEL))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(from, payload)| Some((from, serde_json::from_value(payload).ok()?)))
+            .collect()
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    Vec::new()
+}
+
+// Keep merging votes from others for the life of the app; needs the
+// repositories first
+pub fn use_live_polls() {
+    let mut feed = use_feed_repo();
+    use_future(move || async move {
+        loop {
+            sleep(PUMP_INTERVAL_MILLIS).await;
+            let updates = incoming();
+            if updates.is_empty() {
+                continue;
+            }
+            let mut feed = feed.write();
+            for (sender, update) in updates {
+                if let Err(e) = feed.receive_ballot(&update.post_id, &sender, update.key, update.ballot) {
+                    log::warn!("Ignoring ballot from {}: {}", sender, e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+// Realtime socket to the backend relay. Every frame names a channel (such as
+// "call") so each feature only sees its own traffic. The relay address is set
+// at build time with JEEBON_WS_URL; without it the app stays offline.
+
+const REALTIME_URL: Option<&str> = option_env!("JEEBON_WS_URL");
+
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    channel: String,
+    from: String,
+    to: String,
+    payload: serde_json::Value,
+}
+
+type Inbox = Rc<RefCell<HashMap<String, VecDeque<(String, serde_json::Value)>>>>;
+
+pub struct RealtimeSocket {
+    local_id: String,
+    socket: WebSocket,
+    inbox: Inbox,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+thread_local! {
+    static SHARED: RefCell<Option<Rc<RealtimeSocket>>> = const { RefCell::new(None) };
+}
+
+// The app-wide socket, connected on first use
+pub fn shared(local_id: &str) -> Option<Rc<RealtimeSocket>> {
+    SHARED.with(|shared| {
+        let mut shared = shared.borrow_mut();
+        if shared.is_none() {
+            *shared = RealtimeSocket::connect(local_id).map(Rc::new);
+        }
+        shared.clone()
+    })
+}
+
+impl RealtimeSocket {
+    fn connect(local_id: &str) -> Option<Self> {
+        let url = REALTIME_URL?;
+        let socket = match WebSocket::new(url) {
+            Ok(socket) => socket,
+            Err(e) => {
+                log::error!("Failed to open realtime socket to {}: {:?}", url, e);
+                return None;
+            }
+        };
+
+        let inbox = Inbox::default();
+        let sink = inbox.clone();
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Some(text) = event.data().as_string() else {
+                return;
+            };
+            match serde_json::from_str::<Frame>(&text) {
+                Ok(frame) => sink
+                    .borrow_mut()
+                    .entry(frame.channel)
+                    .or_default()
+                    .push_back((frame.from, frame.payload)),
+                Err(e) => log::warn!("Ignoring malformed realtime frame: {}", e),
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        log::info!("Realtime socket connecting to {}", url);
+
+        Some(Self {
+            local_id: local_id.to_string(),
+            socket,
+            inbox,
+            _on_message: on_message,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+
+    pub fn send<T: Serialize>(&self, channel: &str, to: &str, payload: &T) {
+        if self.socket.ready_state() != WebSocket::OPEN {
+            log::warn!("Realtime socket is not open, dropping {} message", channel);
+            return;
+        }
+        let frame = serde_json::to_value(payload).map(|payload| Frame {
+            channel: channel.to_string(),
+            from: self.local_id.clone(),
+            to: to.to_string(),
+            payload,
+        });
+        match frame.and_then(|f| serde_json::to_string(&f)) {
+            Ok(text) => {
+                if let Err(e) = self.socket.send_with_str(&text) {
+                    log::error!("Failed to send realtime frame: {:?}", e);
+                }
+            }
+            Err(e) => log::error!("Failed to encode realtime frame: {}", e),
+        }
+    }
+
+    // Frames received on `channel` since the last call, with the sender's id
+    pub fn take(&self, channel: &str) -> Vec<(String, serde_json::Value)> {
+        self.inbox
+            .borrow_mut()
+            .get_mut(channel)
+            .map(|queue| queue.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
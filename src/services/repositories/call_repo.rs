@@ -0,0 +1,120 @@
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::call::{Call, CallDirection, CallGroup, CallKind, CallOutcome, CallParticipant};
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge};
+use crate::utils::format::local_offset_millis;
+use crate::utils::now_millis;
+
+const STORAGE_KEY: &str = "calls";
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct CallRepository {
+    pub calls: Vec<Call>,
+}
+
+impl Merge for CallRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.calls, other.calls);
+    }
+}
+
+impl CallRepository {
+    pub fn load() -> Self {
+        storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        })
+    }
+
+    fn persist(&mut self) {
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::save(STORAGE_KEY, self);
+    }
+
+    // Add a finished call to the history
+    pub fn record(&mut self, call: Call) {
+        merge_by_id(&mut self.calls, vec![call]);
+        self.persist();
+    }
+
+    // Call log for one kind, newest first, with related calls grouped
+    pub fn log(&self, kind: CallKind) -> Vec<CallGroup> {
+        let mut calls: Vec<&Call> = self.calls.iter().filter(|c| c.kind == kind).collect();
+        calls.sort_by_key(|c| std::cmp::Reverse(c.started_at));
+
+        let offset = local_offset_millis();
+        let mut groups: Vec<CallGroup> = Vec::new();
+        for call in calls {
+            match groups.last_mut() {
+                Some(group) if group.latest.groups_with(call, offset) => group.count += 1,
+                _ => groups.push(CallGroup { latest: call.clone(), count: 1 }),
+            }
+        }
+        groups
+    }
+
+    pub fn unseen_missed(&self, kind: CallKind) -> usize {
+        self.calls
+            .iter()
+            .filter(|c| c.kind == kind && c.is_unseen_missed())
+            .count()
+    }
+
+    pub fn mark_missed_seen(&mut self, kind: CallKind) {
+        if self.unseen_missed(kind) == 0 {
+            return;
+        }
+        for call in self.calls.iter_mut().filter(|c| c.kind == kind) {
+            call.seen = true;
+        }
+        self.persist();
+    }
+
+    // Demo history shown until the backend is connected
+    fn seed() -> Self {
+        let now = now_millis();
+        let hour = 60 * 60 * 1000;
+        let person = |id: &str, name: &str, img: u32| CallParticipant {
+            id: id.to_string(),
+            name: name.to_string(),
+            avatar: format!("https://i.pravatar.cc/150?img={}", img),
+        };
+        let sarah = person("sarah", "Sarah Johnson", 1);
+        let david = person("david", "David Lee", 2);
+        let alex = person("alex", "Alex Wong", 4);
+
+        let entries = [
+            ("seed-call-1", CallKind::Audio, CallDirection::Incoming, vec![sarah.clone()], hour, 0, CallOutcome::Missed),
+            ("seed-call-2", CallKind::Audio, CallDirection::Incoming, vec![sarah.clone()], 2 * hour, 0, CallOutcome::Missed),
+            ("seed-call-3", CallKind::Audio, CallDirection::Outgoing, vec![david.clone()], 26 * hour, 312, CallOutcome::Completed),
+            ("seed-call-4", CallKind::Audio, CallDirection::Incoming, vec![alex.clone()], 74 * hour, 0, CallOutcome::Declined),
+            ("seed-call-5", CallKind::Video, CallDirection::Incoming, vec![david.clone(), alex.clone()], 5 * hour, 1845, CallOutcome::Completed),
+            ("seed-call-6", CallKind::Video, CallDirection::Incoming, vec![alex], 28 * hour, 0, CallOutcome::Missed),
+            ("seed-call-7", CallKind::Video, CallDirection::Outgoing, vec![sarah], 50 * hour, 0, CallOutcome::Missed),
+        ];
+        Self {
+            calls: entries
+                .into_iter()
+                .map(|(id, kind, direction, participants, age, duration_secs, outcome)| Call {
+                    id: id.to_string(),
+                    kind,
+                    direction,
+                    participants,
+                    started_at: now - age,
+                    duration_secs,
+                    outcome,
+                    seen: false,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn use_call_repo() -> Signal<CallRepository> {
+    use_context()
+}
@@ -0,0 +1,954 @@
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::{Circle, CircleDetails, CircleDraft, CircleMember, Invite, InviteStatus, Permission, Role, Visibility};
+use crate::models::contact::{ContactCard, MatchReason};
+use crate::models::LOCAL_USER_ID;
+use crate::services::invites::{self, InviteToken};
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge, Stamp};
+use crate::utils::{new_id, now_millis};
+
+const STORAGE_KEY: &str = "circles";
+
+// What someone opening an invite link sees before joining
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvitePreview {
+    pub circle: Circle,
+    pub invite: Invite,
+    pub inviter: String,
+    pub already_member: bool,
+    // Checked with an invite key on this device; otherwise the server has
+    // to redeem the token before we join
+    pub verified: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportAction {
+    Add,
+    // Fold into the contact it duplicates
+    Merge,
+    Skip,
+}
+
+// One card from an imported file, with the contact it may duplicate
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportCandidate {
+    pub card: ContactCard,
+    pub duplicate: Option<(CircleMember, MatchReason)>,
+    pub action: ImportAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub merged: usize,
+    pub skipped: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct CircleRepository {
+    pub circles: Vec<Circle>,
+    // Address book details of the people in our circles
+    #[serde(default)]
+    pub cards: Vec<ContactCard>,
+}
+
+impl Merge for CircleRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.circles, other.circles);
+        merge_by_id(&mut self.cards, other.cards);
+    }
+}
+
+impl ImportAction {
+    pub const ALL: [ImportAction; 3] = [ImportAction::Merge, ImportAction::Add, ImportAction::Skip];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImportAction::Add => "Add as new",
+            ImportAction::Merge => "Merge",
+            ImportAction::Skip => "Skip",
+        }
+    }
+}
+
+impl CircleRepository {
+    pub fn load() -> Self {
+        storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        })
+    }
+
+    fn persist(&mut self) {
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::save(STORAGE_KEY, self);
+    }
+
+    pub fn circle(&self, id: &str) -> Option<&Circle> {
+        self.circles.iter().find(|c| c.id == id && !c.is_deleted())
+    }
+
+    // Circles we belong to on one tab, alphabetically
+    pub fn with_visibility(&self, visibility: Visibility) -> Vec<Circle> {
+        let mut circles: Vec<Circle> = self
+            .circles
+            .iter()
+            .filter(|c| !c.is_deleted() && c.details.visibility == visibility && c.role_of(LOCAL_USER_ID).is_some())
+            .cloned()
+            .collect();
+        circles.sort_by_key(|c| c.details.name.to_lowercase());
+        circles
+    }
+
+    // Sharing posts and events with a circle needs the Post permission there
+    pub fn check_post(&self, id: &str) -> Result<(), String> {
+        let circle = self.circle(id).ok_or_else(|| "This circle no longer exists".to_string())?;
+        if !circle.can(LOCAL_USER_ID, Permission::Post) {
+            return Err(format!("You can't post in {}", circle.details.name));
+        }
+        Ok(())
+    }
+
+    fn circle_mut(&mut self, id: &str) -> Result<&mut Circle, String> {
+        self.circles
+            .iter_mut()
+            .find(|c| c.id == id && !c.is_deleted())
+            .ok_or_else(|| "This circle no longer exists".to_string())
+    }
+
+    // The circle's parent, if it has one that still exists
+    pub fn parent_of(&self, id: &str) -> Option<&Circle> {
+        self.circle(id)?.details.parent.as_deref().and_then(|parent| self.circle(parent))
+    }
+
+    // Parent, grandparent and so on, nearest first
+    pub fn ancestors(&self, id: &str) -> Vec<String> {
+        let mut ancestors: Vec<String> = Vec::new();
+        let mut current = id.to_string();
+        while let Some(parent) = self.parent_of(&current) {
+            // Two devices re-parenting at once can briefly make a loop
+            if parent.id == id || ancestors.contains(&parent.id) {
+                break;
+            }
+            ancestors.push(parent.id.clone());
+            current = parent.id.clone();
+        }
+        ancestors
+    }
+
+    // Direct sub-circles, alphabetically
+    pub fn children(&self, id: &str) -> Vec<Circle> {
+        let mut children: Vec<Circle> = self
+            .circles
+            .iter()
+            .filter(|c| !c.is_deleted() && c.id != id && c.details.parent.as_deref() == Some(id))
+            .cloned()
+            .collect();
+        children.sort_by_key(|c| c.details.name.to_lowercase());
+        children
+    }
+
+    // Every circle below this one
+    pub fn descendants(&self, id: &str) -> Vec<String> {
+        let mut descendants: Vec<String> = Vec::new();
+        let mut queue = vec![id.to_string()];
+        while let Some(next) = queue.pop() {
+            for child in self.children(&next) {
+                if child.id != id && !descendants.contains(&child.id) {
+                    descendants.push(child.id.clone());
+                    queue.push(child.id);
+                }
+            }
+        }
+        descendants
+    }
+
+    // Distinct people in the circle and all its sub-circles
+    pub fn member_count(&self, id: &str) -> usize {
+        let mut members: BTreeSet<String> = BTreeSet::new();
+        for circle_id in std::iter::once(id.to_string()).chain(self.descendants(id)) {
+            if let Some(circle) = self.circle(&circle_id) {
+                members.extend(circle.active_members().into_iter().map(|m| m.id));
+            }
+        }
+        members.len()
+    }
+
+    // Circles that could hold `id` (or a new circle when None): ones we can
+    // edit, other than the circle itself and anything below it
+    pub fn parent_options(&self, id: Option<&str>) -> Vec<Circle> {
+        let below = id.map(|id| self.descendants(id)).unwrap_or_default();
+        let mut options: Vec<Circle> = self
+            .circles
+            .iter()
+            .filter(|c| !c.is_deleted() && c.can(LOCAL_USER_ID, Permission::Rename))
+            .filter(|c| Some(c.id.as_str()) != id && !below.contains(&c.id))
+            .cloned()
+            .collect();
+        options.sort_by_key(|c| c.details.name.to_lowercase());
+        options
+    }
+
+    fn check_parent(&self, id: Option<&str>, details: &CircleDetails) -> Result<(), String> {
+        let Some(parent_id) = details.parent.as_deref() else {
+            return Ok(());
+        };
+        // Keeping a parent we can no longer edit is fine; moving under one isn't
+        let unchanged = id.and_then(|id| self.circle(id)).is_some_and(|c| c.details.parent == details.parent);
+        let parent = self.circle(parent_id).ok_or_else(|| "The parent circle no longer exists".to_string())?;
+        if !unchanged && !self.parent_options(id).iter().any(|c| c.id == parent_id) {
+            return Err("You can't put this circle there".to_string());
+        }
+        if parent.details.visibility != details.visibility {
+            return Err(format!("Sub-circles of {} must be {} too", parent.details.name, parent.details.visibility.label().to_lowercase()));
+        }
+        Ok(())
+    }
+
+    // Members of a sub-circle under `parent` also join every circle above
+    // it, so adding anyone new needs the right to invite in each of those.
+    // Joining ourselves is covered by the directory or the invite.
+    fn check_share_upwards(&self, parent: Option<&str>, joining: &[String]) -> Result<(), String> {
+        let Some(parent) = parent else {
+            return Ok(());
+        };
+        for ancestor in std::iter::once(parent.to_string()).chain(self.ancestors(parent)) {
+            let Some(circle) = self.circle(&ancestor) else {
+                continue;
+            };
+            let newcomer = joining.iter().any(|id| id != LOCAL_USER_ID && circle.role_of(id).is_none());
+            if newcomer && !circle.can(LOCAL_USER_ID, Permission::Invite) {
+                return Err(format!("Members here also join {}, where you can't add people", circle.details.name));
+            }
+        }
+        Ok(())
+    }
+
+    // Members of a sub-circle belong to every circle above it
+    fn share_upwards(&mut self, id: &str) {
+        let Some(members) = self.circle(id).map(|c| c.active_members()) else {
+            return;
+        };
+        for ancestor in self.ancestors(id) {
+            if let Ok(circle) = self.circle_mut(&ancestor) {
+                for member in members.iter() {
+                    circle.add_member(member.clone(), Role::Member);
+                }
+            }
+        }
+    }
+
+    // Removing someone else takes them out of the circles below as well, so
+    // it needs the right to remove them from each of those
+    fn check_remove_downwards(&self, id: &str, member_id: &str) -> Result<(), String> {
+        if member_id == LOCAL_USER_ID {
+            return Ok(());
+        }
+        for descendant in self.descendants(id) {
+            if let Some(circle) = self.circle(&descendant)
+                && circle.role_of(member_id).is_some_and(|role| role != Role::Owner)
+                && !circle.can_manage(LOCAL_USER_ID, Permission::Remove, member_id)
+            {
+                return Err(format!("They are also in {}, where you can't remove them", circle.details.name));
+            }
+        }
+        Ok(())
+    }
+
+    // Whoever leaves a circle leaves the circles below it as well, unless
+    // they own one of those
+    fn remove_downwards(&mut self, id: &str, member_id: &str) {
+        for descendant in self.descendants(id) {
+            if let Ok(circle) = self.circle_mut(&descendant)
+                && circle.role_of(member_id) != Some(Role::Owner)
+            {
+                circle.remove_member(member_id);
+            }
+        }
+    }
+
+    // Everyone we share a circle with, to pick members from
+    pub fn contacts(&self) -> Vec<CircleMember> {
+        let mut contacts: Vec<CircleMember> = Vec::new();
+        for circle in self.circles.iter().filter(|c| !c.is_deleted()) {
+            for member in circle.active_members() {
+                if member.id != LOCAL_USER_ID && !contacts.iter().any(|c| c.id == member.id) {
+                    contacts.push(member);
+                }
+            }
+        }
+        contacts.sort_by(|a, b| a.name.cmp(&b.name));
+        contacts
+    }
+
+    pub fn create(&mut self, draft: CircleDraft) -> Result<String, String> {
+        draft.validate()?;
+        self.check_parent(None, &draft.details)?;
+        let joining: Vec<String> = draft.members.iter().map(|m| m.id.clone()).collect();
+        self.check_share_upwards(draft.details.parent.as_deref(), &joining)?;
+        let circle = Circle::create(draft, CircleMember::local());
+        let id = circle.id.clone();
+        self.circles.push(circle);
+        self.share_upwards(&id);
+        self.persist();
+        Ok(id)
+    }
+
+    pub fn update(&mut self, id: &str, draft: CircleDraft) -> Result<(), String> {
+        draft.validate()?;
+        self.check_parent(Some(id), &draft.details)?;
+        let visibility = draft.details.visibility;
+        let leaving: Vec<String> = self
+            .circle(id)
+            .map(|c| c.active_members())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| m.id != LOCAL_USER_ID && !draft.members.iter().any(|d| d.id == m.id))
+            .map(|m| m.id)
+            .collect();
+        for member_id in &leaving {
+            self.check_remove_downwards(id, member_id)?;
+        }
+        let joining: Vec<String> = draft.members.iter().map(|m| m.id.clone()).collect();
+        self.check_share_upwards(draft.details.parent.as_deref(), &joining)?;
+        // Sub-circles follow their parent's visibility, so we must be able
+        // to edit each of them to change it
+        if self.circle(id).is_some_and(|c| c.details.visibility != visibility) {
+            for descendant in self.descendants(id) {
+                if let Some(child) = self.circle(&descendant)
+                    && !child.can(LOCAL_USER_ID, Permission::Rename)
+                {
+                    return Err(format!("{} sits below this circle and you can't change its visibility", child.details.name));
+                }
+            }
+        }
+        let circle = self.circle_mut(id)?;
+        let before: Vec<String> = circle.active_members().into_iter().map(|m| m.id).collect();
+        circle.apply(draft, LOCAL_USER_ID)?;
+        let after: Vec<String> = circle.active_members().into_iter().map(|m| m.id).collect();
+
+        for descendant in self.descendants(id) {
+            if let Ok(child) = self.circle_mut(&descendant)
+                && child.details.visibility != visibility
+            {
+                child.details.visibility = visibility;
+                child.edited = Stamp::after(&child.edited);
+            }
+        }
+        for member_id in before.iter().filter(|m| !after.contains(m)) {
+            self.remove_downwards(id, member_id);
+        }
+        self.share_upwards(id);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<(), String> {
+        let circle = self.circle_mut(id)?;
+        if !circle.can(LOCAL_USER_ID, Permission::Delete) {
+            return Err("Only the owner can delete a circle".to_string());
+        }
+        circle.delete(LOCAL_USER_ID);
+        // Sub-circles we can edit move up to take its place; the rest show
+        // at the top level, since a deleted parent no longer counts
+        let grandparent = circle.details.parent.clone();
+        for child in self.children(id) {
+            if let Ok(child) = self.circle_mut(&child.id)
+                && child.can(LOCAL_USER_ID, Permission::Rename)
+            {
+                child.details.parent = grandparent.clone();
+                child.edited = Stamp::after(&child.edited);
+            }
+        }
+        self.persist();
+        Ok(())
+    }
+
+    pub fn invite(&mut self, id: &str, member: CircleMember) -> Result<(), String> {
+        let circle = self.circle(id).ok_or_else(|| "This circle no longer exists".to_string())?;
+        if !circle.can(LOCAL_USER_ID, Permission::Invite) {
+            return Err("You can't add members to this circle".to_string());
+        }
+        self.check_share_upwards(circle.details.parent.as_deref(), std::slice::from_ref(&member.id))?;
+        let circle = self.circle_mut(id)?;
+        circle.add_member(member, Role::Member);
+        self.share_upwards(id);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn remove_member(&mut self, id: &str, member_id: &str) -> Result<(), String> {
+        let circle = self.circle_mut(id)?;
+        if !circle.can_manage(LOCAL_USER_ID, Permission::Remove, member_id) {
+            return Err("You can't remove this member".to_string());
+        }
+        self.check_remove_downwards(id, member_id)?;
+        let circle = self.circle_mut(id)?;
+        circle.remove_member(member_id);
+        self.remove_downwards(id, member_id);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn set_role(&mut self, id: &str, member_id: &str, role: Role) -> Result<(), String> {
+        let circle = self.circle_mut(id)?;
+        if !circle.can_manage(LOCAL_USER_ID, Permission::ChangeRoles, member_id)
+            || !circle.assignable_roles(LOCAL_USER_ID).contains(&role)
+        {
+            return Err(format!("You can't make this member {}", role.label().to_lowercase()));
+        }
+        circle.set_role(member_id, role);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn check_leave(&self, id: &str) -> Result<(), String> {
+        let circle = self.circle(id).ok_or_else(|| "This circle no longer exists".to_string())?;
+        if circle.role_of(LOCAL_USER_ID) == Some(Role::Owner) {
+            return Err("The owner can't leave; delete the circle instead".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn leave(&mut self, id: &str) -> Result<(), String> {
+        self.check_leave(id)?;
+        let circle = self.circle_mut(id)?;
+        circle.remove_member(LOCAL_USER_ID);
+        self.remove_downwards(id, LOCAL_USER_ID);
+        self.persist();
+        Ok(())
+    }
+
+    // Join a public circle found in the directory
+    pub fn join_public(&mut self, id: &str, details: CircleDetails) -> Result<(), String> {
+        if details.visibility != Visibility::Public {
+            return Err("Only public circles can be joined without an invite".to_string());
+        }
+        if !self.circles.iter().any(|c| c.id == id) {
+            self.circles.push(Circle::listed(id, details));
+        }
+        let circle = self.circle_mut(id)?;
+        circle.add_member(CircleMember::local(), Role::Member);
+        self.share_upwards(id);
+        self.persist();
+        Ok(())
+    }
+
+    // Take the directory's word for whether we belong to a public circle,
+    // after a join or leave went through on only one side
+    pub fn set_public_membership(&mut self, id: &str, details: CircleDetails, member: bool) -> Result<(), String> {
+        let is_member = self.circle(id).is_some_and(|c| c.role_of(LOCAL_USER_ID).is_some());
+        match (member, is_member) {
+            (true, false) => self.join_public(id, details),
+            (false, true) => {
+                self.circle_mut(id)?.remove_member(LOCAL_USER_ID);
+                self.remove_downwards(id, LOCAL_USER_ID);
+                self.persist();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The stored card for a member, or a bare one from what the circle knows
+    pub fn card(&self, member: &CircleMember) -> ContactCard {
+        self.cards.iter().find(|c| c.member_id == member.id).cloned().unwrap_or_else(|| ContactCard {
+            member_id: member.id.clone(),
+            name: member.name.clone(),
+            photo: member.avatar.clone(),
+            ..ContactCard::default()
+        })
+    }
+
+    // Cards for everyone in a circle but ourselves, for exporting
+    pub fn cards_for(&self, id: &str) -> Vec<ContactCard> {
+        let Some(circle) = self.circle(id) else {
+            return Vec::new();
+        };
+        let mut cards: Vec<ContactCard> = circle
+            .active_members()
+            .iter()
+            .filter(|m| m.id != LOCAL_USER_ID)
+            .map(|m| self.card(m))
+            .collect();
+        cards.sort_by(|a, b| a.name.cmp(&b.name));
+        cards
+    }
+
+    // Pair each imported card with anyone we already know who looks like
+    // the same person. Repeats within the file are folded together first.
+    pub fn review_import(&self, imported: Vec<ContactCard>) -> Vec<ImportCandidate> {
+        let mut unique: Vec<ContactCard> = Vec::new();
+        for card in imported {
+            let repeat = unique
+                .iter_mut()
+                .find(|u| matches!(u.matches(&card), Some(MatchReason::Phone | MatchReason::Email)));
+            match repeat {
+                Some(first) => first.absorb(&card),
+                None => unique.push(card),
+            }
+        }
+        let known: Vec<(CircleMember, ContactCard)> = self
+            .contacts()
+            .into_iter()
+            .map(|member| {
+                let card = self.card(&member);
+                (member, card)
+            })
+            .collect();
+        unique
+            .into_iter()
+            .map(|card| {
+                // Prefer a phone or email match over a name that merely looks alike
+                let duplicate = known
+                    .iter()
+                    .filter_map(|(member, existing)| existing.matches(&card).map(|reason| (member.clone(), reason)))
+                    .min_by_key(|(_, reason)| *reason);
+                let action = if duplicate.is_some() { ImportAction::Merge } else { ImportAction::Add };
+                ImportCandidate { card, duplicate, action }
+            })
+            .collect()
+    }
+
+    // Add the reviewed cards to a circle
+    pub fn import(&mut self, id: &str, candidates: Vec<ImportCandidate>) -> Result<ImportSummary, String> {
+        let circle = self.circle(id).filter(|c| c.can(LOCAL_USER_ID, Permission::Invite));
+        let parent = circle.ok_or_else(|| "You can't add members to this circle".to_string())?.details.parent.clone();
+        let mut summary = ImportSummary::default();
+        let mut joining = Vec::new();
+        for candidate in candidates {
+            let (member, card) = match (candidate.action, candidate.duplicate) {
+                (ImportAction::Skip, _) => {
+                    summary.skipped += 1;
+                    continue;
+                }
+                (ImportAction::Merge, Some((member, _))) => {
+                    let mut card = self.card(&member);
+                    card.absorb(&candidate.card);
+                    summary.merged += 1;
+                    (member, card)
+                }
+                _ => {
+                    let member_id = new_id();
+                    let avatar = if candidate.card.photo.is_empty() {
+                        format!("https://i.pravatar.cc/150?u={}", member_id)
+                    } else {
+                        candidate.card.photo.clone()
+                    };
+                    let member = CircleMember { id: member_id.clone(), name: candidate.card.name.clone(), avatar };
+                    summary.added += 1;
+                    (member, ContactCard { member_id, stamp: Stamp::now(), ..candidate.card })
+                }
+            };
+            joining.push((member, card));
+        }
+        let ids: Vec<String> = joining.iter().map(|(member, _)| member.id.clone()).collect();
+        self.check_share_upwards(parent.as_deref(), &ids)?;
+        for (member, card) in joining {
+            self.circle_mut(id)?.add_member(member, Role::Member);
+            match self.cards.iter_mut().find(|c| c.member_id == card.member_id) {
+                Some(existing) => *existing = card,
+                None => self.cards.push(card),
+            }
+        }
+        self.share_upwards(id);
+        self.persist();
+        Ok(summary)
+    }
+
+    // Create an invite and return its signed token
+    pub fn create_invite(&mut self, id: &str, ttl_millis: u64, max_uses: Option<u32>) -> Result<String, String> {
+        let circle = self.circle_mut(id)?;
+        if !circle.can(LOCAL_USER_ID, Permission::Invite) {
+            return Err("You can't invite people to this circle".to_string());
+        }
+        let invite_id = circle.create_invite(LOCAL_USER_ID, ttl_millis, max_uses)?.id;
+        self.persist();
+        self.invite_token(id, &invite_id).ok_or_else(|| "This circle no longer exists".to_string())
+    }
+
+    // The token for an existing invite, to show its link again
+    pub fn invite_token(&self, id: &str, invite_id: &str) -> Option<String> {
+        let circle = self.circle(id)?;
+        let invite = circle.invites.get(invite_id)?;
+        let (key_id, key) = circle.signing_key()?;
+        let token = InviteToken {
+            circle_id: circle.id.clone(),
+            invite_id: invite.id.clone(),
+            key_id: key_id.clone(),
+            expires_at: invite.expires_at,
+            // The invitee sees the circle on its own, not where it sits here
+            details: CircleDetails { parent: None, ..circle.details.clone() },
+            invited_by: circle.members.get(&invite.created_by).map(|m| m.member.name.clone()).unwrap_or_default(),
+        };
+        Some(invites::issue(&key.secret, &token))
+    }
+
+    pub fn revoke_invite(&mut self, id: &str, invite_id: &str) -> Result<(), String> {
+        let circle = self.circle_mut(id)?;
+        if !circle.can(LOCAL_USER_ID, Permission::RevokeInvites) {
+            return Err("Only admins can revoke invites".to_string());
+        }
+        let invite = circle.invites.get_mut(invite_id).ok_or_else(|| "No such invite".to_string())?;
+        invite.revoked = true;
+        self.persist();
+        Ok(())
+    }
+
+    // Describe the circle a token leads to. A device holding the key it was
+    // signed with checks it; anyone else sees what the token says.
+    pub fn preview_invite(&self, token: &str) -> Result<InvitePreview, String> {
+        let decoded = invites::decode(token)?;
+        let held = self
+            .circle(&decoded.circle_id)
+            .and_then(|c| Some((c, c.invite_keys.get(&decoded.key_id)?)));
+        let Some((circle, key)) = held else {
+            let invite = Invite {
+                id: decoded.invite_id,
+                created_by: String::new(),
+                created_at: 0,
+                expires_at: decoded.expires_at,
+                max_uses: None,
+                used_by: BTreeSet::new(),
+                revoked: false,
+            };
+            return Ok(InvitePreview {
+                already_member: self.circle(&decoded.circle_id).is_some_and(|c| c.role_of(LOCAL_USER_ID).is_some()),
+                circle: Circle::listed(&decoded.circle_id, decoded.details),
+                invite,
+                inviter: decoded.invited_by,
+                verified: false,
+            });
+        };
+        let verified = invites::verify(&key.secret, token)?;
+        let invite = circle
+            .invites
+            .get(&verified.invite_id)
+            .filter(|i| i.expires_at == verified.expires_at)
+            .ok_or_else(|| "This invite link isn't genuine".to_string())?;
+        Ok(InvitePreview {
+            inviter: circle.members.get(&invite.created_by).map(|m| m.member.name.clone()).unwrap_or(verified.invited_by),
+            already_member: circle.role_of(LOCAL_USER_ID).is_some(),
+            invite: invite.clone(),
+            circle: circle.clone(),
+            verified: true,
+        })
+    }
+
+    // Join the circle an invite leads to; returns the circle id. `redeemed`
+    // says the server has accepted the token, which is required for circles
+    // this device can't check itself.
+    pub fn accept_invite(&mut self, token: &str, redeemed: bool) -> Result<String, String> {
+        let preview = self.preview_invite(token)?;
+        if preview.already_member {
+            return Err("You're already a member of this circle".to_string());
+        }
+        if !preview.verified && !redeemed {
+            return Err("This invite can only be checked online".to_string());
+        }
+        match preview.invite.status(now_millis()) {
+            InviteStatus::Active => {}
+            InviteStatus::Expired => return Err("This invite has expired".to_string()),
+            InviteStatus::UsedUp => return Err("This invite has been used up".to_string()),
+            InviteStatus::Revoked => return Err("This invite was revoked".to_string()),
+        }
+        let id = preview.circle.id.clone();
+        // The rest of the circle arrives when it syncs
+        if !self.circles.iter().any(|c| c.id == id) {
+            self.circles.push(preview.circle);
+        }
+        let circle = self.circle_mut(&id)?;
+        circle.add_member(CircleMember::local(), Role::Member);
+        if let Some(invite) = circle.invites.get_mut(&preview.invite.id) {
+            invite.used_by.insert(LOCAL_USER_ID.to_string());
+        }
+        self.share_upwards(&id);
+        self.persist();
+        Ok(id)
+    }
+
+    // Demo circles shown until the backend is connected
+    fn seed() -> Self {
+        let person = |id: &str, name: &str, img: u32| CircleMember {
+            id: id.to_string(),
+            name: name.to_string(),
+            avatar: format!("https://i.pravatar.cc/150?img={}", img),
+        };
+        let sarah = person("sarah", "Sarah Johnson", 1);
+        let david = person("david", "David Lee", 2);
+        let alex = person("alex", "Alex Wong", 4);
+        let maria = person("maria", "Maria Garcia", 6);
+        let rahim = person("rahim", "Rahim Uddin", 7);
+        let nadia = person("nadia", "Nadia Islam", 9);
+        let tom = person("tom", "Tom Baker", 11);
+        let priya = person("priya", "Priya Sen", 16);
+
+        // Our own role differs per circle so every permission level shows up
+        let entries = [
+            ("seed-circle-contacts", "Contacts", "Your personal contacts list", "bi-person-lines-fill", Visibility::Personal, Role::Owner, None,
+                vec![sarah.clone(), david.clone(), alex.clone(), maria.clone(), rahim.clone(), nadia.clone(), tom.clone(), priya.clone()]),
+            ("seed-circle-family", "Family", "Close family members", "bi-house-heart", Visibility::Personal, Role::Owner, None,
+                vec![rahim.clone(), nadia.clone()]),
+            ("seed-circle-work", "Work", "Professional contacts", "bi-briefcase", Visibility::Personal, Role::Owner, None,
+                vec![david.clone(), alex.clone(), tom.clone()]),
+            ("seed-circle-alpha", "Project Alpha", "Development team for Project Alpha", "bi-lock", Visibility::Private, Role::Member, None,
+                vec![david.clone(), alex.clone(), priya.clone()]),
+            ("seed-circle-books", "Book Club", "Monthly book discussions", "bi-book", Visibility::Private, Role::Admin, None,
+                vec![sarah.clone(), maria.clone(), nadia.clone()]),
+            ("seed-circle-tech", "Tech News", "Latest in technology and development", "bi-globe", Visibility::Public, Role::Member, None,
+                vec![alex.clone(), tom.clone(), priya.clone(), david.clone()]),
+            ("seed-circle-rust", "Rust Community", "Discussions about Rust programming", "bi-gear", Visibility::Public, Role::Moderator, None,
+                vec![alex.clone(), tom.clone(), sarah.clone(), maria]),
+            ("seed-circle-cousins", "Cousins", "Cousins on both sides", "bi-house-heart", Visibility::Personal, Role::Owner, Some("seed-circle-family"),
+                vec![nadia]),
+            ("seed-circle-rust-beginners", "Rust Beginners", "Questions from people new to Rust", "bi-gear", Visibility::Public, Role::Member, Some("seed-circle-rust"),
+                vec![tom, alex, sarah]),
+        ];
+        let circles: Vec<Circle> = entries
+            .into_iter()
+            .map(|(id, name, description, icon, visibility, role, parent, mut members)| {
+                // Circles we don't own belong to their first member
+                let owner = if role == Role::Owner { CircleMember::local() } else { members.remove(0) };
+                let details = CircleDetails {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    icon: icon.to_string(),
+                    visibility,
+                    parent: parent.map(str::to_string),
+                };
+                let mut circle = Circle::create(CircleDraft { details, members }, owner);
+                circle.add_member(CircleMember::local(), role);
+                circle.id = id.to_string();
+                circle
+            })
+            .collect();
+
+        // Address book details for some of them, so imports find duplicates
+        let cards = [
+            ("sarah", "Sarah Johnson", "+1 415 555 0134", "sarah.johnson@example.com"),
+            ("david", "David Lee", "+1 646 555 0178", "david.lee@example.com"),
+            ("rahim", "Rahim Uddin", "+880 1712 345678", "rahim.uddin@example.com"),
+            ("nadia", "Nadia Islam", "+880 1819 876543", "nadia.islam@example.com"),
+        ]
+        .into_iter()
+        .map(|(id, name, phone, email)| ContactCard {
+            member_id: id.to_string(),
+            name: name.to_string(),
+            phones: vec![phone.to_string()],
+            emails: vec![email.to_string()],
+            photo: circles
+                .iter()
+                .find_map(|c| c.members.get(id))
+                .map(|m| m.member.avatar.clone())
+                .unwrap_or_default(),
+            stamp: Stamp::now(),
+            ..ContactCard::default()
+        })
+        .collect();
+        Self { circles, cards }
+    }
+}
+
+pub fn use_circle_repo() -> Signal<CircleRepository> {
+    use_context()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person(id: &str) -> CircleMember {
+        CircleMember { id: id.to_string(), name: id.to_string(), avatar: String::new() }
+    }
+
+    // A circle owned by "owner" where we hold `role`
+    fn circle(id: &str, parent: Option<&str>, role: Role, members: &[&str]) -> Circle {
+        let details = CircleDetails {
+            name: id.to_string(),
+            description: String::new(),
+            icon: "bi-house-heart".to_string(),
+            visibility: Visibility::Private,
+            parent: parent.map(str::to_string),
+        };
+        let members = members.iter().map(|id| person(id)).collect();
+        let mut circle = Circle::create(CircleDraft { details, members }, person("owner"));
+        circle.add_member(CircleMember::local(), role);
+        circle.id = id.to_string();
+        circle
+    }
+
+    fn repo(circles: Vec<Circle>) -> CircleRepository {
+        CircleRepository { circles, cards: Vec::new() }
+    }
+
+    fn is_member(repo: &CircleRepository, id: &str, member: &str) -> bool {
+        repo.circle(id).is_some_and(|c| c.role_of(member).is_some())
+    }
+
+    #[test]
+    fn removal_needs_rights_in_every_sub_circle() {
+        let mut circles = repo(vec![
+            circle("family", None, Role::Admin, &["ana"]),
+            circle("cousins", Some("family"), Role::Member, &["ana"]),
+        ]);
+        let result = circles.remove_member("family", "ana");
+        assert_eq!(result, Err("They are also in cousins, where you can't remove them".to_string()));
+        assert!(is_member(&circles, "family", "ana"));
+        assert!(is_member(&circles, "cousins", "ana"));
+
+        circles.circle_mut("cousins").unwrap().set_role(LOCAL_USER_ID, Role::Moderator);
+        circles.remove_member("family", "ana").unwrap();
+        assert!(!is_member(&circles, "family", "ana"));
+        assert!(!is_member(&circles, "cousins", "ana"));
+    }
+
+    #[test]
+    fn leaving_takes_us_out_of_sub_circles() {
+        let mut circles = repo(vec![
+            circle("family", None, Role::Member, &[]),
+            circle("cousins", Some("family"), Role::Member, &[]),
+        ]);
+        circles.leave("family").unwrap();
+        assert!(!is_member(&circles, "family", LOCAL_USER_ID));
+        assert!(!is_member(&circles, "cousins", LOCAL_USER_ID));
+    }
+
+    #[test]
+    fn members_cannot_post_where_they_have_no_role() {
+        let circles = repo(vec![circle("family", None, Role::Member, &[])]);
+        assert_eq!(circles.check_post("family"), Ok(()));
+        let mut outside = circle("work", None, Role::Member, &[]);
+        outside.remove_member(LOCAL_USER_ID);
+        let circles = repo(vec![outside]);
+        assert_eq!(circles.check_post("work"), Err("You can't post in work".to_string()));
+        assert_eq!(circles.check_post("gone"), Err("This circle no longer exists".to_string()));
+    }
+
+    #[test]
+    fn deleting_only_moves_sub_circles_we_can_edit() {
+        let mut circles = repo(vec![
+            circle("top", None, Role::Owner, &[]),
+            circle("family", Some("top"), Role::Owner, &[]),
+            circle("ours", Some("family"), Role::Admin, &[]),
+            circle("theirs", Some("family"), Role::Member, &[]),
+        ]);
+        circles.delete("family").unwrap();
+        assert_eq!(circles.circle("ours").unwrap().details.parent.as_deref(), Some("top"));
+        assert_eq!(circles.circle("theirs").unwrap().details.parent.as_deref(), Some("family"));
+        assert!(circles.parent_of("theirs").is_none());
+    }
+
+    #[test]
+    fn adding_to_a_sub_circle_needs_invite_rights_above() {
+        let mut circles = repo(vec![
+            circle("family", None, Role::Member, &["ana"]),
+            circle("cousins", Some("family"), Role::Admin, &[]),
+        ]);
+        let result = circles.invite("cousins", person("ben"));
+        assert_eq!(result, Err("Members here also join family, where you can't add people".to_string()));
+        assert!(!is_member(&circles, "cousins", "ben"));
+
+        // Someone already in the circle above is fine
+        circles.invite("cousins", person("ana")).unwrap();
+        assert!(is_member(&circles, "cousins", "ana"));
+
+        circles.circle_mut("family").unwrap().set_role(LOCAL_USER_ID, Role::Moderator);
+        circles.invite("cousins", person("ben")).unwrap();
+        assert!(is_member(&circles, "family", "ben"));
+    }
+
+    #[test]
+    fn visibility_change_needs_rights_in_every_sub_circle() {
+        let mut circles = repo(vec![
+            circle("family", None, Role::Owner, &[]),
+            circle("cousins", Some("family"), Role::Member, &[]),
+        ]);
+        let mut draft = CircleDraft::of(circles.circle("family").unwrap(), LOCAL_USER_ID);
+        draft.details.visibility = Visibility::Public;
+        let result = circles.update("family", draft.clone());
+        assert_eq!(result, Err("cousins sits below this circle and you can't change its visibility".to_string()));
+        assert_eq!(circles.circle("family").unwrap().details.visibility, Visibility::Private);
+
+        circles.circle_mut("cousins").unwrap().set_role(LOCAL_USER_ID, Role::Admin);
+        circles.update("family", draft).unwrap();
+        assert_eq!(circles.circle("cousins").unwrap().details.visibility, Visibility::Public);
+    }
+
+    #[test]
+    fn invites_to_circles_on_this_device_are_checked_here() {
+        let mut circles = repo(vec![circle("family", None, Role::Admin, &[])]);
+        let token = circles.create_invite("family", 60_000, Some(1)).unwrap();
+        circles.circle_mut("family").unwrap().remove_member(LOCAL_USER_ID);
+
+        let preview = circles.preview_invite(&token).unwrap();
+        assert!(preview.verified);
+        assert_eq!(preview.inviter, crate::models::LOCAL_USER_NAME);
+        assert_eq!(circles.accept_invite(&token, false), Ok("family".to_string()));
+        assert!(is_member(&circles, "family", LOCAL_USER_ID));
+    }
+
+    #[test]
+    fn invites_to_other_circles_need_the_server() {
+        let mut theirs = repo(vec![circle("family", None, Role::Admin, &["ana"])]);
+        let token = theirs.create_invite("family", 60_000, None).unwrap();
+
+        let mut ours = repo(Vec::new());
+        let preview = ours.preview_invite(&token).unwrap();
+        assert!(!preview.verified);
+        assert_eq!(preview.circle.details.name, "family");
+        assert_eq!(ours.accept_invite(&token, false), Err("This invite can only be checked online".to_string()));
+        assert!(ours.circle("family").is_none());
+
+        assert_eq!(ours.accept_invite(&token, true), Ok("family".to_string()));
+        assert!(is_member(&ours, "family", LOCAL_USER_ID));
+        assert_eq!(ours.accept_invite(&token, true), Err("You're already a member of this circle".to_string()));
+    }
+
+    #[test]
+    fn members_do_not_keep_invite_keys() {
+        let mut moderators = repo(vec![circle("family", None, Role::Moderator, &[])]);
+        let token = moderators.create_invite("family", 60_000, None).unwrap();
+        let mut copy = moderators.circle("family").unwrap().clone();
+
+        // The same circle as a plain member's device sees it, where that role
+        // is the latest word on ours
+        let mut member = circle("family", None, Role::Member, &[]);
+        member.members.get_mut(LOCAL_USER_ID).unwrap().stamp = Stamp { millis: u64::MAX, device: String::new() };
+        member.merge(copy.clone());
+        assert!(member.invite_keys.is_empty());
+        let mut members = repo(vec![member]);
+        assert!(!members.preview_invite(&token).unwrap().verified);
+        assert!(members.create_invite("family", 60_000, None).is_err());
+
+        // Losing the role drops them too
+        copy.members.get_mut(LOCAL_USER_ID).unwrap().role = Role::Member;
+        copy.merge(moderators.circle("family").unwrap().clone());
+        assert!(copy.invite_keys.is_empty());
+    }
+
+    #[test]
+    fn invite_keys_from_two_devices_are_both_kept() {
+        let mut ours = repo(vec![circle("family", None, Role::Admin, &[])]);
+        let mut theirs = ours.clone();
+        let our_token = ours.create_invite("family", 60_000, None).unwrap();
+        let their_token = theirs.create_invite("family", 60_000, None).unwrap();
+
+        let their_copy = theirs.circle("family").unwrap().clone();
+        ours.circle_mut("family").unwrap().merge(their_copy);
+        assert_eq!(ours.circle("family").unwrap().invite_keys.len(), 2);
+        assert!(ours.preview_invite(&our_token).unwrap().verified);
+        assert!(ours.preview_invite(&their_token).unwrap().verified);
+
+        // New invites are signed with the newest key
+        let newest = ours.circle("family").unwrap().signing_key().map(|(id, _)| id.clone());
+        let token = ours.create_invite("family", 60_000, None).unwrap();
+        assert_eq!(invites::decode(&token).map(|t| Some(t.key_id)), Ok(newest));
+    }
+}
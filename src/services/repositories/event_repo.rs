@@ -0,0 +1,219 @@
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::CircleMember;
+use crate::models::event::{CircleEvent, EventDraft, Frequency, Occurrence, Recurrence, Rsvp, Weekday};
+use crate::models::LOCAL_USER_ID;
+use crate::services::repositories::CircleRepository;
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge, Stamp};
+use crate::utils::format::local_offset_millis;
+use crate::utils::now_millis;
+
+const STORAGE_KEY: &str = "events";
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct EventImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    // Matching events someone else organises, which we may not change
+    pub skipped: usize,
+}
+
+// Events of every circle we belong to
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct EventRepository {
+    pub events: Vec<CircleEvent>,
+}
+
+impl Merge for EventRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.events, other.events);
+    }
+}
+
+impl EventRepository {
+    pub fn load() -> Self {
+        storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        })
+    }
+
+    fn persist(&mut self) {
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::save(STORAGE_KEY, self);
+    }
+
+    pub fn event(&self, id: &str) -> Option<&CircleEvent> {
+        self.events.iter().find(|e| e.id == id && !e.is_deleted())
+    }
+
+    fn event_mut(&mut self, id: &str) -> Result<&mut CircleEvent, String> {
+        self.events
+            .iter_mut()
+            .find(|e| e.id == id && !e.is_deleted())
+            .ok_or_else(|| "This event no longer exists".to_string())
+    }
+
+    pub fn for_circle(&self, circle_id: &str) -> Vec<CircleEvent> {
+        let mut events: Vec<CircleEvent> =
+            self.events.iter().filter(|e| e.circle_id == circle_id && !e.is_deleted()).cloned().collect();
+        events.sort_by_key(|e| e.starts_at);
+        events
+    }
+
+    // Occurrences in the given circles overlapping [from, to), earliest first
+    pub fn agenda(&self, circle_ids: &[String], from: u64, to: u64) -> Vec<Occurrence> {
+        let offset = local_offset_millis();
+        let mut occurrences: Vec<Occurrence> = self
+            .events
+            .iter()
+            .filter(|e| !e.is_deleted() && circle_ids.contains(&e.circle_id))
+            .flat_map(|event| {
+                event.occurrences(from, to, offset).into_iter().map(|starts_at| Occurrence {
+                    event: event.clone(),
+                    starts_at,
+                    ends_at: starts_at + event.duration(),
+                })
+            })
+            .collect();
+        occurrences.sort_by(|a, b| (a.starts_at, &a.event.title).cmp(&(b.starts_at, &b.event.title)));
+        occurrences
+    }
+
+    pub fn create(&mut self, circle_id: &str, draft: &EventDraft, circles: &CircleRepository) -> Result<String, String> {
+        circles.check_post(circle_id)?;
+        let event = draft.to_event(circle_id, CircleMember::local(), local_offset_millis())?;
+        let id = event.id.clone();
+        self.events.push(event);
+        self.persist();
+        Ok(id)
+    }
+
+    // Only the organiser changes the details
+    pub fn update(&mut self, id: &str, draft: &EventDraft) -> Result<(), String> {
+        let event = self.event_mut(id)?;
+        if event.organizer.id != LOCAL_USER_ID {
+            return Err("Only the organiser can change this event".to_string());
+        }
+        draft.apply_to(event, local_offset_millis())?;
+        self.persist();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<(), String> {
+        let event = self.event_mut(id)?;
+        if event.organizer.id != LOCAL_USER_ID {
+            return Err("Only the organiser can cancel this event".to_string());
+        }
+        event.delete(LOCAL_USER_ID);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn rsvp(&mut self, id: &str, answer: Rsvp) -> Result<(), String> {
+        self.event_mut(id)?.set_rsvp(CircleMember::local(), answer);
+        self.persist();
+        Ok(())
+    }
+
+    // Add events read from an .ics file. An event already in the circle with
+    // the same UID is updated instead, keeping its answers, as long as we
+    // organise it.
+    pub fn import(
+        &mut self,
+        circle_id: &str,
+        events: Vec<CircleEvent>,
+        circles: &CircleRepository,
+    ) -> Result<EventImportSummary, String> {
+        circles.check_post(circle_id)?;
+        let mut summary = EventImportSummary::default();
+        for mut event in events {
+            event.circle_id = circle_id.to_string();
+            let existing = self
+                .events
+                .iter_mut()
+                .find(|e| e.circle_id == circle_id && e.uid == event.uid && !e.is_deleted());
+            match existing {
+                Some(existing) if existing.organizer.id != LOCAL_USER_ID => summary.skipped += 1,
+                Some(existing) => {
+                    event.id = existing.id.clone();
+                    event.organizer = existing.organizer.clone();
+                    event.rsvps = std::mem::take(&mut existing.rsvps);
+                    event.stamp = Stamp::after(&existing.stamp);
+                    *existing = event;
+                    summary.updated += 1;
+                }
+                None => {
+                    self.events.push(event);
+                    summary.added += 1;
+                }
+            }
+        }
+        self.persist();
+        Ok(summary)
+    }
+
+    // Demo events shown until the backend is connected
+    fn seed() -> Self {
+        let offset = local_offset_millis();
+        let day = 24 * 60 * 60 * 1000;
+        let hour = 60 * 60 * 1000;
+        let today = (now_millis() as i64 + offset).div_euclid(day) * day - offset;
+        let at = |days: i64, hours: i64| (today + days * day + hours * hour).max(0) as u64;
+        let person = |id: &str, name: &str, img: u32| CircleMember {
+            id: id.to_string(),
+            name: name.to_string(),
+            avatar: format!("https://i.pravatar.cc/150?img={}", img),
+        };
+        let rahim = person("rahim", "Rahim Uddin", 7);
+        let maria = person("maria", "Maria Garcia", 6);
+        let david = person("david", "David Lee", 2);
+
+        let weekly = |day: Weekday| Recurrence { frequency: Frequency::Weekly, interval: 1, by_day: vec![day], count: None, until: None };
+        let monthly = Recurrence { frequency: Frequency::Monthly, interval: 1, by_day: Vec::new(), count: None, until: None };
+        let book_club_day = at(-14, 19);
+        let book_club_weekday = Weekday::of_day((book_club_day as i64 + offset).div_euclid(day));
+
+        let entries = [
+            ("seed-circle-family", rahim.clone(), "Eid lunch", "Rahim's place, Dhanmondi", at(5, 13), at(5, 16), false, None),
+            ("seed-circle-family", CircleMember::local(), "Family video call", "", at(-10, 20), at(-10, 21), false, Some(monthly)),
+            ("seed-circle-family", rahim, "Picnic at Botanical Garden", "Mirpur", at(12, 0), at(13, 0), true, None),
+            ("seed-circle-books", maria, "Book club", "Library café", book_club_day, book_club_day + 2 * hour as u64, false, Some(weekly(book_club_weekday))),
+            ("seed-circle-work", david, "Sprint review", "Room 4B", at(1, 10), at(1, 11), false, None),
+        ];
+
+        let mut repo = Self::default();
+        for (circle_id, organizer, title, location, starts_at, ends_at, all_day, recurrence) in entries {
+            let id = format!("seed-event-{}", title.to_lowercase().replace(' ', "-"));
+            repo.events.push(CircleEvent {
+                uid: format!("{}@jeebon", id),
+                id,
+                circle_id: circle_id.to_string(),
+                title: title.to_string(),
+                description: String::new(),
+                location: location.to_string(),
+                starts_at,
+                ends_at,
+                all_day,
+                recurrence,
+                organizer,
+                rsvps: Default::default(),
+                stamp: Stamp::default(),
+                deleted: None,
+            });
+        }
+        repo.events[0].set_rsvp(CircleMember::local(), Rsvp::Going);
+        repo.events[0].set_rsvp(person("nadia", "Nadia Islam", 9), Rsvp::Going);
+        repo.events[3].set_rsvp(person("sarah", "Sarah Johnson", 1), Rsvp::Maybe);
+        repo
+    }
+}
+
+pub fn use_event_repo() -> Signal<EventRepository> {
+    use_context()
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::circle::CircleMember;
+use crate::models::poll::{Ballot, PollDraft};
+use crate::models::post::{FeedCursor, Post, PostDraft};
+use crate::models::LOCAL_USER_ID;
+use crate::services::repositories::CircleRepository;
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge};
+use crate::utils::now_millis;
+
+const STORAGE_KEY: &str = "feed";
+
+// Posts of every circle we belong to. The stored copy doubles as the offline
+// cache: the feed renders from it and new posts land in it first.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeedRepository {
+    pub posts: Vec<Post>,
+    pub cursors: Vec<FeedCursor>,
+}
+
+impl Merge for FeedRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.posts, other.posts);
+        merge_by_id(&mut self.cursors, other.cursors);
+    }
+}
+
+impl FeedRepository {
+    pub fn load() -> Self {
+        storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        })
+    }
+
+    fn persist(&mut self) {
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::save(STORAGE_KEY, self);
+    }
+
+    // Posts shared with a circle, newest first
+    pub fn feed(&self, circle_id: &str) -> Vec<Post> {
+        let mut posts: Vec<Post> = self
+            .posts
+            .iter()
+            .filter(|p| !p.is_deleted() && p.audience.contains(circle_id))
+            .cloned()
+            .collect();
+        posts.sort_by_key(|p| std::cmp::Reverse(p.posted_at));
+        posts
+    }
+
+    pub fn last_read_at(&self, circle_id: &str) -> u64 {
+        self.cursors.iter().find(|c| c.circle_id == circle_id).map_or(0, |c| c.last_read_at)
+    }
+
+    // Posts by others we haven't seen yet
+    pub fn unread_count(&self, circle_id: &str) -> usize {
+        let last_read_at = self.last_read_at(circle_id);
+        self.posts
+            .iter()
+            .filter(|p| {
+                !p.is_deleted() && p.audience.contains(circle_id) && p.author.id != LOCAL_USER_ID && p.posted_at > last_read_at
+            })
+            .count()
+    }
+
+    pub fn mark_read(&mut self, circle_id: &str) {
+        if self.unread_count(circle_id) == 0 {
+            return;
+        }
+        let now = now_millis();
+        match self.cursors.iter_mut().find(|c| c.circle_id == circle_id) {
+            Some(cursor) => cursor.last_read_at = now,
+            None => self.cursors.push(FeedCursor { circle_id: circle_id.to_string(), last_read_at: now }),
+        }
+        self.persist();
+    }
+
+    pub fn publish(&mut self, draft: PostDraft, circles: &CircleRepository) -> Result<String, String> {
+        draft.validate()?;
+        for circle_id in &draft.audience {
+            circles.check_post(circle_id)?;
+        }
+        let post = Post::create(draft, CircleMember::local());
+        let id = post.id.clone();
+        self.posts.push(post);
+        self.persist();
+        Ok(id)
+    }
+
+    fn post_mut(&mut self, id: &str) -> Result<&mut Post, String> {
+        self.posts
+            .iter_mut()
+            .find(|p| p.id == id && !p.is_deleted())
+            .ok_or_else(|| "This post no longer exists".to_string())
+    }
+
+    pub fn toggle_like(&mut self, post_id: &str) -> Result<(), String> {
+        self.post_mut(post_id)?.toggle_like(LOCAL_USER_ID);
+        self.persist();
+        Ok(())
+    }
+
+    // Commenting needs the Post permission in one of the post's circles
+    pub fn comment(&mut self, post_id: &str, body: &str, circles: &CircleRepository) -> Result<(), String> {
+        let post = self.post_mut(post_id)?;
+        if !post.audience.iter().any(|id| circles.check_post(id).is_ok()) {
+            return Err("You can't comment in the circles this was shared with".to_string());
+        }
+        post.add_comment(CircleMember::local(), body)?;
+        self.persist();
+        Ok(())
+    }
+
+    pub fn delete_comment(&mut self, post_id: &str, comment_id: &str) -> Result<(), String> {
+        self.post_mut(post_id)?.delete_comment(comment_id, LOCAL_USER_ID)?;
+        self.persist();
+        Ok(())
+    }
+
+    // Vote in a poll. Returns the ballot so it can be sent to the circles.
+    pub fn vote(&mut self, post_id: &str, option_id: &str) -> Result<(String, Ballot), String> {
+        let post = self.post_mut(post_id)?;
+        let poll = post.poll.as_mut().ok_or_else(|| "This post isn't a poll".to_string())?;
+        let vote = poll.toggle(post_id, CircleMember::local(), option_id, now_millis())?;
+        self.persist();
+        Ok(vote)
+    }
+
+    // A vote someone else cast, as it arrives over the realtime channel
+    pub fn receive_ballot(&mut self, post_id: &str, sender: &str, key: String, ballot: Ballot) -> Result<(), String> {
+        let Some(poll) = self.posts.iter_mut().find(|p| p.id == post_id).and_then(|p| p.poll.as_mut()) else {
+            return Err("No poll for this ballot".to_string());
+        };
+        poll.receive(post_id, sender, key, ballot, now_millis())?;
+        self.persist();
+        Ok(())
+    }
+
+    // The author can end a poll before its deadline
+    pub fn close_poll(&mut self, post_id: &str) -> Result<(), String> {
+        let post = self.post_mut(post_id)?;
+        if post.author.id != LOCAL_USER_ID {
+            return Err("Only the person who asked can close this poll".to_string());
+        }
+        let poll = post.poll.as_mut().ok_or_else(|| "This post isn't a poll".to_string())?;
+        poll.close(now_millis());
+        self.persist();
+        Ok(())
+    }
+
+    // Only the author can delete a post, and it goes from every circle
+    pub fn delete(&mut self, post_id: &str) -> Result<(), String> {
+        let post = self.post_mut(post_id)?;
+        if post.author.id != LOCAL_USER_ID {
+            return Err("You can only delete your own posts".to_string());
+        }
+        post.delete(LOCAL_USER_ID);
+        self.persist();
+        Ok(())
+    }
+
+    // Demo posts shown until the backend is connected
+    fn seed() -> Self {
+        let now = now_millis();
+        let hour = 60 * 60 * 1000;
+        let person = |id: &str, name: &str, img: u32| CircleMember {
+            id: id.to_string(),
+            name: name.to_string(),
+            avatar: format!("https://i.pravatar.cc/150?img={}", img),
+        };
+        let sarah = person("sarah", "Sarah Johnson", 1);
+        let david = person("david", "David Lee", 2);
+        let alex = person("alex", "Alex Wong", 4);
+        let maria = person("maria", "Maria Garcia", 6);
+        let rahim = person("rahim", "Rahim Uddin", 7);
+        let nadia = person("nadia", "Nadia Islam", 9);
+        let tom = person("tom", "Tom Baker", 11);
+
+        let entries = [
+            (rahim.clone(), "Eid lunch is at our place this year. Bring your appetite!", vec![], vec!["seed-circle-family"], 1),
+            (nadia.clone(), "Photos from the picnic", vec!["https://picsum.photos/seed/jeebon-picnic/600/400"], vec!["seed-circle-family"], 26),
+            (david.clone(), "Sprint review moved to Thursday. Notes: https://example.com/alpha/sprint-12", vec![], vec!["seed-circle-work", "seed-circle-alpha"], 3),
+            (maria.clone(), "Next month we're reading \"The Remains of the Day\". Who's in?", vec![], vec!["seed-circle-books"], 50),
+            (alex.clone(), "Rust 2024 edition is out. Worth reading the guide: https://doc.rust-lang.org/edition-guide/", vec![], vec!["seed-circle-rust", "seed-circle-tech"], 5),
+            (tom.clone(), "Anyone tried running Dioxus on Android yet?", vec![], vec!["seed-circle-rust"], 30),
+        ];
+
+        let mut repo = Self::default();
+        for (author, body, images, audience, hours_ago) in entries {
+            let draft = PostDraft {
+                body: body.to_string(),
+                images: images.into_iter().map(str::to_string).collect(),
+                audience: audience.into_iter().map(str::to_string).collect(),
+                poll: None,
+            };
+            let mut post = Post::create(draft, author);
+            post.posted_at = now - hours_ago * hour;
+            repo.posts.push(post);
+        }
+
+        // A little activity so likes and comments show up
+        let _ = repo.posts[0].add_comment(sarah.clone(), "Can't wait!");
+        repo.posts[0].toggle_like(LOCAL_USER_ID);
+        repo.posts[1].toggle_like("rahim");
+        let _ = repo.posts[4].add_comment(tom, "The new let chains are great");
+        let _ = repo.posts[5].add_comment(sarah, "Yes, it works with `dx serve --platform android`");
+        for post in repo.posts.iter_mut() {
+            post.toggle_like("alex");
+            let posted_at = post.posted_at;
+            for comment in post.comments.iter_mut() {
+                comment.posted_at = posted_at + hour / 2;
+            }
+        }
+
+        // A family poll with a few votes in, open for another week
+        let reunion = PostDraft {
+            body: "When should we hold the family reunion?".to_string(),
+            images: Vec::new(),
+            audience: BTreeSet::from(["seed-circle-family".to_string()]),
+            poll: Some(PollDraft {
+                options: vec!["First weekend of December".to_string(), "Over the winter holidays".to_string(), "After Eid".to_string()],
+                multiple: true,
+                anonymous: false,
+                closes_at: Some(now + 7 * 24 * hour),
+            }),
+        };
+        let mut poll_post = Post::create(reunion, nadia.clone());
+        poll_post.posted_at = now - 4 * hour;
+        if let Some(poll) = poll_post.poll.as_mut() {
+            let options: Vec<String> = poll.options.iter().map(|o| o.id.clone()).collect();
+            for (voter, choice) in [(&nadia, 0), (&rahim, 0), (&rahim, 1), (&maria, 2)] {
+                let _ = poll.toggle(&poll_post.id, voter.clone(), &options[choice], now);
+            }
+        }
+        repo.posts.push(poll_post);
+
+        // Everything older than a day has been read
+        repo.cursors = ["seed-circle-family", "seed-circle-work", "seed-circle-alpha", "seed-circle-books", "seed-circle-tech", "seed-circle-rust"]
+            .into_iter()
+            .map(|id| FeedCursor { circle_id: id.to_string(), last_read_at: now - 24 * hour })
+            .collect();
+        repo
+    }
+}
+
+pub fn use_feed_repo() -> Signal<FeedRepository> {
+    use_context()
+}
@@ -0,0 +1,98 @@
+use dioxus::prelude::*;
+
+use crate::services::crypto::keystore::{derive_wrapping_key, KeyMaterial, SealedKeys, PBKDF2_ROUNDS};
+use crate::services::crypto::{random, CryptoError};
+use crate::services::storage;
+
+const STORAGE_KEY: &str = "keystore";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyState {
+    NotSetUp,
+    Locked,
+    Unlocked,
+}
+
+struct Unlocked {
+    material: KeyMaterial,
+    wrapping_key: [u8; 32],
+}
+
+// Encryption keys, stored sealed under the user's passphrase and only held
+// in memory while unlocked
+pub struct KeyRepository {
+    sealed: Option<SealedKeys>,
+    unlocked: Option<Unlocked>,
+}
+
+impl KeyRepository {
+    pub fn load() -> Self {
+        Self {
+            sealed: storage::load(STORAGE_KEY),
+            unlocked: None,
+        }
+    }
+
+    pub fn state(&self) -> KeyState {
+        match (&self.sealed, &self.unlocked) {
+            (_, Some(_)) => KeyState::Unlocked,
+            (Some(_), None) => KeyState::Locked,
+            (None, None) => KeyState::NotSetUp,
+        }
+    }
+
+    // Generate a new identity and seal it under `passphrase`
+    pub fn set_up(&mut self, passphrase: &str) -> Result<(), CryptoError> {
+        let salt = random::<16>()?;
+        let wrapping_key = derive_wrapping_key(passphrase, &salt, PBKDF2_ROUNDS);
+        let material = KeyMaterial::generate()?;
+        let sealed = SealedKeys::seal(&material, &wrapping_key, salt, PBKDF2_ROUNDS)?;
+        storage::save(STORAGE_KEY, &sealed);
+        self.sealed = Some(sealed);
+        self.unlocked = Some(Unlocked { material, wrapping_key });
+        Ok(())
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), CryptoError> {
+        let sealed = self.sealed.as_ref().ok_or(CryptoError::NoSession)?;
+        let wrapping_key = derive_wrapping_key(passphrase, &sealed.salt, sealed.rounds);
+        let material = sealed.open(&wrapping_key)?;
+        self.unlocked = Some(Unlocked { material, wrapping_key });
+        Ok(())
+    }
+
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    pub fn material(&self) -> Option<&KeyMaterial> {
+        self.unlocked.as_ref().map(|u| &u.material)
+    }
+
+    // Change the key material and write it back sealed. Nothing changes if
+    // the new state cannot be sealed.
+    pub fn update<R>(&mut self, change: impl FnOnce(&mut KeyMaterial) -> R) -> Result<R, CryptoError> {
+        self.try_update(|material| Ok(change(material)))
+    }
+
+    // Like `update`, but a failed change is thrown away rather than saved
+    pub fn try_update<R>(&mut self, change: impl FnOnce(&mut KeyMaterial) -> Result<R, CryptoError>) -> Result<R, CryptoError> {
+        let unlocked = self.unlocked.as_mut().ok_or(CryptoError::NoSession)?;
+        let mut material = unlocked.material.clone();
+        let result = change(&mut material)?;
+        let salt = match &self.sealed {
+            Some(sealed) => sealed.salt,
+            None => random()?,
+        };
+        let rounds = self.sealed.as_ref().map_or(PBKDF2_ROUNDS, |s| s.rounds);
+        let sealed = SealedKeys::seal(&material, &unlocked.wrapping_key, salt, rounds)?;
+        storage::save(STORAGE_KEY, &sealed);
+        unlocked.material = material;
+        self.sealed = Some(sealed);
+        Ok(result)
+    }
+}
+
+pub fn use_key_repo() -> Signal<KeyRepository> {
+    use_context()
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::message::{Conversation, Message};
+use crate::models::{LOCAL_USER_ID, LOCAL_USER_NAME};
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge};
+use crate::utils::now_millis;
+
+const STORAGE_KEY: &str = "messages";
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct MessageRepository {
+    pub conversations: Vec<Conversation>,
+    pub messages: Vec<Message>,
+    // Messages changed here that still have to be sent. Not merged: each
+    // copy only sends its own changes.
+    #[serde(default)]
+    pub outbox: BTreeSet<String>,
+}
+
+impl Merge for MessageRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.conversations, other.conversations);
+        merge_by_id(&mut self.messages, other.messages);
+    }
+}
+
+impl MessageRepository {
+    pub fn load() -> Self {
+        storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        })
+    }
+
+    // Write back, first folding in whatever another tab or device stored
+    // since we loaded so that neither side's changes are lost
+    fn persist(&mut self) {
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::save(STORAGE_KEY, self);
+    }
+
+    pub fn conversation(&self, id: &str) -> Option<&Conversation> {
+        self.conversations.iter().find(|c| c.id == id)
+    }
+
+    pub fn message(&self, id: &str) -> Option<&Message> {
+        self.messages.iter().find(|m| m.id == id)
+    }
+
+    // Messages of a conversation, oldest first
+    pub fn thread(&self, conversation_id: &str) -> Vec<Message> {
+        let mut thread: Vec<Message> = self
+            .messages
+            .iter()
+            .filter(|m| m.conversation_id == conversation_id)
+            .cloned()
+            .collect();
+        thread.sort_by_key(|m| m.sent_at);
+        thread
+    }
+
+    pub fn last_message(&self, conversation_id: &str) -> Option<&Message> {
+        self.messages
+            .iter()
+            .filter(|m| m.conversation_id == conversation_id)
+            .max_by_key(|m| m.sent_at)
+    }
+
+    pub fn has_unread(&self, conversation_id: &str) -> bool {
+        let Some(conversation) = self.conversation(conversation_id) else {
+            return false;
+        };
+        self.messages.iter().any(|m| {
+            m.conversation_id == conversation_id
+                && m.sender_id != LOCAL_USER_ID
+                && m.sent_at > conversation.last_read_at
+        })
+    }
+
+    // Conversations with the most recent activity first
+    pub fn recent_conversations(&self) -> Vec<Conversation> {
+        let mut conversations = self.conversations.clone();
+        conversations.sort_by_key(|c| std::cmp::Reverse(self.last_message(&c.id).map_or(0, |m| m.sent_at)));
+        conversations
+    }
+
+    pub fn send(&mut self, conversation_id: &str, body: &str, reply_to: Option<&str>) {
+        let mut message = Message::new(conversation_id, LOCAL_USER_ID, LOCAL_USER_NAME, body);
+        if let Some(original) = reply_to.and_then(|id| self.message(id)) {
+            message = message.replying_to(original);
+        }
+        self.outbox.insert(message.id.clone());
+        self.messages.push(message);
+        self.persist();
+    }
+
+    pub fn edit(&mut self, message_id: &str, body: &str) {
+        if let Some(message) = self.find_own_mut(message_id) {
+            message.edit(body);
+            self.queue(message_id);
+        }
+    }
+
+    pub fn toggle_reaction(&mut self, message_id: &str, emoji: &str) {
+        if let Some(message) = self.messages.iter_mut().find(|m| m.id == message_id) {
+            message.toggle_reaction(emoji, LOCAL_USER_ID);
+            self.queue(message_id);
+        }
+    }
+
+    pub fn delete_for_everyone(&mut self, message_id: &str) {
+        if let Some(message) = self.find_own_mut(message_id) {
+            message.delete_for_everyone(LOCAL_USER_ID);
+            self.queue(message_id);
+        }
+    }
+
+    fn queue(&mut self, message_id: &str) {
+        self.outbox.insert(message_id.to_string());
+        self.persist();
+    }
+
+    // Queued messages with the conversation they go to
+    pub fn pending(&self) -> Vec<(Conversation, Message)> {
+        self.outbox
+            .iter()
+            .filter_map(|id| {
+                let message = self.message(id)?;
+                Some((self.conversation(&message.conversation_id)?.clone(), message.clone()))
+            })
+            .collect()
+    }
+
+    pub fn delivered(&mut self, message_id: &str) {
+        if self.outbox.remove(message_id) {
+            self.persist();
+        }
+    }
+
+    // Take in a copy of a message as sent by `from`. Only its sender can
+    // write or change its text; anyone else only brings their own reactions.
+    pub fn receive(&mut self, from: &str, mut message: Message) -> Result<(), String> {
+        let existing = self.message(&message.id).cloned();
+        if existing.as_ref().is_some_and(|m| m.conversation_id != message.conversation_id) {
+            return Err("the message belongs to another conversation".to_string());
+        }
+        if message.sender_id != from {
+            let existing = existing.ok_or_else(|| "new messages must come from their sender".to_string())?;
+            message = Message { reactions: message.reactions, ..existing };
+        }
+        for marks in message.reactions.values_mut() {
+            marks.retain(|user, _| user == from);
+        }
+
+        match self.conversation(&message.conversation_id) {
+            Some(conversation) if conversation.is_group() && !conversation.members.contains(from) => {
+                return Err("the sender isn't in this group".to_string());
+            }
+            Some(_) => {}
+            // Someone new writing to us directly starts a conversation
+            None if message.conversation_id == from => self.conversations.push(Conversation {
+                id: from.to_string(),
+                name: message.sender_name.clone(),
+                avatar: format!("https://i.pravatar.cc/150?u={}", from),
+                last_read_at: 0,
+                members: BTreeSet::new(),
+            }),
+            None => return Err("unknown conversation".to_string()),
+        }
+        merge_by_id(&mut self.messages, vec![message]);
+        self.persist();
+        Ok(())
+    }
+
+    pub fn mark_read(&mut self, conversation_id: &str) {
+        if let Some(conversation) = self.conversations.iter_mut().find(|c| c.id == conversation_id) {
+            conversation.last_read_at = now_millis();
+            self.persist();
+        }
+    }
+
+    // Only the sender may edit or delete a message for everyone
+    fn find_own_mut(&mut self, message_id: &str) -> Option<&mut Message> {
+        self.messages
+            .iter_mut()
+            .find(|m| m.id == message_id && m.sender_id == LOCAL_USER_ID)
+    }
+
+    // Demo conversations shown until the backend is connected
+    fn seed() -> Self {
+        let now = now_millis();
+        let minute = 60 * 1000;
+        let day = 24 * 60 * minute;
+        let contacts = [
+            ("sarah", "Sarah Johnson", 1, "Hey, how's the project coming along?", 30 * minute, false),
+            ("david", "David Lee", 2, "I've sent you the files you requested", day, true),
+            ("tech-team", "Tech Team", 3, "Meeting scheduled for tomorrow at 2 PM", day + 2 * 60 * minute, true),
+            ("alex", "Alex Wong", 4, "Thanks for your help!", 3 * day, true),
+        ];
+
+        let mut repo = Self::default();
+        for (id, name, img, text, age, read) in contacts {
+            let sent_at = now - age;
+            let members = match id {
+                "tech-team" => BTreeSet::from(["sarah", "david", "alex"].map(String::from)),
+                _ => BTreeSet::new(),
+            };
+            repo.conversations.push(Conversation {
+                id: id.to_string(),
+                name: name.to_string(),
+                avatar: format!("https://i.pravatar.cc/150?img={}", img),
+                last_read_at: if read { sent_at } else { 0 },
+                members,
+            });
+            // Group messages come from one of the members
+            let (sender, sender_name) = if id == "tech-team" { ("david", "David Lee") } else { (id, name) };
+            let mut message = Message::new(id, sender, sender_name, text);
+            message.sent_at = sent_at;
+            repo.messages.push(message);
+        }
+        repo
+    }
+}
+
+pub fn use_message_repo() -> Signal<MessageRepository> {
+    use_context()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo() -> MessageRepository {
+        let mut repo = MessageRepository::default();
+        repo.conversations.push(Conversation {
+            id: "ben".to_string(),
+            name: "Ben".to_string(),
+            avatar: String::new(),
+            last_read_at: 0,
+            members: BTreeSet::new(),
+        });
+        repo
+    }
+
+    #[test]
+    fn changes_are_queued_until_delivered() {
+        let mut repo = repo();
+        repo.send("ben", "hello", None);
+        let id = repo.messages[0].id.clone();
+        assert_eq!(repo.pending().len(), 1);
+        repo.delivered(&id);
+        assert!(repo.pending().is_empty());
+        repo.toggle_reaction(&id, "👍");
+        assert_eq!(repo.pending()[0].1.id, id);
+    }
+
+    #[test]
+    fn others_only_bring_their_own_reactions() {
+        let mut repo = repo();
+        repo.send("ben", "hello", None);
+        let ours = repo.messages[0].clone();
+
+        // Ben reacts, and also tries to rewrite our message and react for us
+        let mut theirs = ours.clone();
+        theirs.toggle_reaction("❤️", "ben");
+        theirs.toggle_reaction("😂", LOCAL_USER_ID);
+        theirs.edit("rewritten");
+        theirs.conversation_id = "ben".to_string();
+        repo.receive("ben", theirs).unwrap();
+
+        let merged = repo.message(&ours.id).unwrap();
+        assert_eq!(merged.text(), "hello");
+        assert_eq!(merged.reaction_counts("ben").iter().map(|r| r.emoji.as_str()).collect::<Vec<_>>(), ["❤️"]);
+
+        // Nobody can put words in someone else's mouth
+        let forged = Message::new("ben", LOCAL_USER_ID, LOCAL_USER_NAME, "I owe Ben money");
+        assert!(repo.receive("ben", forged).is_err());
+    }
+
+    #[test]
+    fn strangers_start_a_conversation_but_cannot_post_in_groups() {
+        let mut repo = repo();
+        repo.conversations.push(Conversation {
+            id: "team".to_string(),
+            name: "Team".to_string(),
+            avatar: String::new(),
+            last_read_at: 0,
+            members: BTreeSet::from(["ben".to_string()]),
+        });
+        repo.receive("cal", Message::new("cal", "cal", "Cal", "hi")).unwrap();
+        assert_eq!(repo.conversation("cal").map(|c| c.name.as_str()), Some("Cal"));
+        assert!(repo.receive("cal", Message::new("team", "cal", "Cal", "let me in")).is_err());
+        assert!(repo.receive("ben", Message::new("team", "ben", "Ben", "morning")).is_ok());
+    }
+}
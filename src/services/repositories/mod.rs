@@ -0,0 +1,30 @@
+// Repositories are the gatekeepers for all data access. Each one is loaded
+// once at startup and shared with components through a context signal.
+use dioxus::prelude::*;
+
+pub mod call_repo;
+pub mod circle_repo;
+pub mod event_repo;
+pub mod feed_repo;
+pub mod key_repo;
+pub mod message_repo;
+pub mod tree_repo;
+
+pub use call_repo::{use_call_repo, CallRepository};
+pub use circle_repo::{use_circle_repo, CircleRepository};
+pub use event_repo::{use_event_repo, EventRepository};
+pub use feed_repo::{use_feed_repo, FeedRepository};
+pub use key_repo::{use_key_repo, KeyRepository, KeyState};
+pub use message_repo::{use_message_repo, MessageRepository};
+pub use tree_repo::{use_tree_repo, TreeRepository};
+
+// Make every repository available to the component tree
+pub fn use_repositories_provider() {
+    use_context_provider(|| Signal::new(MessageRepository::load()));
+    use_context_provider(|| Signal::new(KeyRepository::load()));
+    use_context_provider(|| Signal::new(CallRepository::load()));
+    use_context_provider(|| Signal::new(CircleRepository::load()));
+    use_context_provider(|| Signal::new(FeedRepository::load()));
+    use_context_provider(|| Signal::new(EventRepository::load()));
+    use_context_provider(|| Signal::new(TreeRepository::load()));
+}
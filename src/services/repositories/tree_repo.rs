@@ -0,0 +1,499 @@
+use std::cell::OnceCell;
+use std::collections::BTreeSet;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::family::{FamilyTree, Gender, Person, PersonDraft, RelationKind, Relationship, Relative, TreePrivacy};
+use crate::models::kinship::{Kinship, Language};
+use crate::models::person_match::{self, Candidate};
+use crate::models::tree_health::{self, Issue};
+use crate::models::LOCAL_USER_ID;
+use crate::services::storage;
+use crate::services::sync::{merge_by_id, Merge, Stamp};
+
+const STORAGE_KEY: &str = "family_tree";
+
+// The family tree, with the person it is drawn around
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct TreeRepository {
+    pub tree: FamilyTree,
+    pub home_id: String,
+    #[serde(default)]
+    pub privacy: TreePrivacy,
+    // Language of relationship names
+    #[serde(default)]
+    pub language: Language,
+    // Pairs marked as different people, so they aren't offered as duplicates
+    #[serde(default)]
+    pub distinct: Vec<(String, String)>,
+    // What the validator found, kept up to date as the tree changes
+    #[serde(skip)]
+    pub health: Vec<Issue>,
+    // Merges that can still be undone, latest last
+    #[serde(skip)]
+    pub merges: Vec<MergeUndo>,
+    // Possible duplicates, worked out when first asked for after a change
+    #[serde(skip)]
+    duplicates: OnceCell<Vec<Candidate>>,
+}
+
+// Everything a merge changed, as it was before
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeUndo {
+    pub kept: String,
+    pub removed: String,
+    persons: Vec<Person>,
+    relationships: Vec<Relationship>,
+    added: Vec<String>,
+}
+
+impl Merge for TreeRepository {
+    fn merge(&mut self, other: Self) {
+        merge_by_id(&mut self.tree.persons, other.tree.persons);
+        merge_by_id(&mut self.tree.relationships, other.tree.relationships);
+        for pair in other.distinct {
+            if !self.distinct.contains(&pair) {
+                self.distinct.push(pair);
+            }
+        }
+        if self.tree.person(&self.home_id).is_none() {
+            self.home_id = other.home_id;
+        }
+    }
+}
+
+impl TreeRepository {
+    pub fn load() -> Self {
+        let mut repo = storage::load(STORAGE_KEY).unwrap_or_else(|| {
+            let seeded = Self::seed();
+            storage::save(STORAGE_KEY, &seeded);
+            seeded
+        });
+        repo.health = tree_health::check_all(&repo.tree);
+        repo
+    }
+
+    fn persist(&mut self) -> Result<(), String> {
+        self.duplicates.take();
+        if let Some(stored) = storage::load::<Self>(STORAGE_KEY) {
+            self.merge(stored);
+        }
+        storage::try_save(STORAGE_KEY, self).map_err(|e| format!("Couldn't save the tree: {}", e))
+    }
+
+    // Keep an edit to the tree only if it could be saved, going back to
+    // `before` otherwise
+    fn commit(&mut self, before: FamilyTree) -> Result<(), String> {
+        if let Err(e) = self.persist() {
+            self.tree = before;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // A merge can't be undone once either person has been changed since,
+    // as undoing it would throw the change away
+    fn forget_merges_of(&mut self, ids: &[&str]) {
+        self.merges.retain(|m| !ids.contains(&m.kept.as_str()) && !ids.contains(&m.removed.as_str()));
+    }
+
+    // For settings with nowhere to show an error
+    fn persist_or_log(&mut self) {
+        if let Err(e) = self.persist() {
+            log::error!("{}", e);
+        }
+    }
+
+    // Validate again around the people an edit touched, including anyone
+    // they were or are linked to
+    fn recheck<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) {
+        let ids: BTreeSet<String> = ids.into_iter().map(str::to_string).collect();
+        let touched: BTreeSet<String> = self
+            .tree
+            .relationships
+            .iter()
+            .filter(|r| ids.contains(&r.from) || ids.contains(&r.to))
+            .flat_map(|r| [r.from.clone(), r.to.clone()])
+            .chain(ids.iter().cloned())
+            .collect();
+        self.health.retain(|issue| !issue.involves(&touched));
+        self.health.extend(tree_health::check(&self.tree, &touched));
+        self.health.sort();
+        self.health.dedup();
+    }
+
+    // Add the people and links read from another program's file
+    pub fn import(&mut self, tree: FamilyTree) {
+        if self.tree.person(&self.home_id).is_none()
+            && let Some(first) = tree.persons.first()
+        {
+            self.home_id = first.id.clone();
+        }
+        let added: Vec<String> = tree.persons.iter().map(|p| p.id.clone()).collect();
+        self.tree.persons.extend(tree.persons);
+        self.tree.relationships.extend(tree.relationships);
+        self.persist_or_log();
+        self.recheck(added.iter().map(String::as_str));
+    }
+
+    // Create someone, or update them when `id` is set. Returns their id.
+    pub fn save_person(&mut self, id: Option<&str>, draft: &PersonDraft) -> Result<String, String> {
+        draft.validate()?;
+        let before = self.tree.clone();
+        let id = match id {
+            Some(id) => {
+                let person = self
+                    .tree
+                    .persons
+                    .iter_mut()
+                    .find(|p| p.id == id && !p.deleted)
+                    .ok_or_else(|| "That person is no longer in the tree".to_string())?;
+                draft.apply_to(person);
+                id.to_string()
+            }
+            None => {
+                let person = draft.to_person();
+                let id = person.id.clone();
+                self.tree.persons.push(person);
+                id
+            }
+        };
+        self.commit(before)?;
+        self.forget_merges_of(&[&id]);
+        self.recheck([id.as_str()]);
+        Ok(id)
+    }
+
+    // Create someone as a new relative of `of`. Returns their id.
+    pub fn add_relative(&mut self, of: &str, relative: Relative, draft: &PersonDraft) -> Result<String, String> {
+        draft.validate()?;
+        if self.tree.person(of).is_none() {
+            return Err("That person is no longer in the tree".to_string());
+        }
+        let before = self.tree.clone();
+        let person = draft.to_person();
+        let id = person.id.clone();
+        self.tree.persons.push(person);
+        let (kind, from, to) = relative.link(of, &id);
+        if let Err(e) = self.tree.relate(kind, &from, &to) {
+            self.tree = before;
+            return Err(e);
+        }
+        self.commit(before)?;
+        self.forget_merges_of(&[of]);
+        self.recheck([of, id.as_str()]);
+        Ok(id)
+    }
+
+    // Link someone already in the tree to `of`
+    pub fn link(&mut self, of: &str, relative: Relative, other: &str) -> Result<(), String> {
+        let before = self.tree.clone();
+        let (kind, from, to) = relative.link(of, other);
+        self.tree.relate(kind, &from, &to)?;
+        self.commit(before)?;
+        self.forget_merges_of(&[of, other]);
+        self.recheck([of, other]);
+        Ok(())
+    }
+
+    pub fn unlink(&mut self, relationship_id: &str) -> Result<(), String> {
+        let before = self.tree.clone();
+        let relationship = self
+            .tree
+            .relationships
+            .iter_mut()
+            .find(|r| r.id == relationship_id && !r.removed)
+            .ok_or_else(|| "That link is already gone".to_string())?;
+        relationship.removed = true;
+        relationship.stamp = Stamp::after(&relationship.stamp);
+        let ends = [relationship.from.clone(), relationship.to.clone()];
+        self.commit(before)?;
+        self.forget_merges_of(&[&ends[0], &ends[1]]);
+        self.recheck(ends.iter().map(String::as_str));
+        Ok(())
+    }
+
+    // Remove someone along with their links. We stay in our own tree.
+    pub fn delete_person(&mut self, id: &str) -> Result<(), String> {
+        if id == self.home_id {
+            return Err("You can't remove yourself from your own tree".to_string());
+        }
+        let before = self.tree.clone();
+        let person = self
+            .tree
+            .persons
+            .iter_mut()
+            .find(|p| p.id == id && !p.deleted)
+            .ok_or_else(|| "That person is no longer in the tree".to_string())?;
+        person.deleted = true;
+        person.stamp = Stamp::after(&person.stamp);
+        for relationship in self.tree.relationships.iter_mut().filter(|r| !r.removed && r.involves(id)) {
+            relationship.removed = true;
+            relationship.stamp = Stamp::after(&relationship.stamp);
+        }
+        self.commit(before)?;
+        self.forget_merges_of(&[id]);
+        self.recheck([id]);
+        Ok(())
+    }
+
+    // Fold `remove` into `keep`, with `draft` holding the properties picked
+    // from each. Their links move to the kept person and both names are
+    // kept as spellings. We always stay in the tree, whichever side we were.
+    pub fn merge_people(&mut self, keep: &str, remove: &str, draft: &PersonDraft) -> Result<(), String> {
+        draft.validate()?;
+        if keep == remove {
+            return Err("Choose two different people".to_string());
+        }
+        let (keep, remove) = if remove == self.home_id { (remove, keep) } else { (keep, remove) };
+        let (Some(kept), Some(removed)) = (self.tree.person(keep).cloned(), self.tree.person(remove).cloned()) else {
+            return Err("That person is no longer in the tree".to_string());
+        };
+        let before = self.tree.clone();
+        let moving: Vec<Relationship> = self.tree.links_of(remove).into_iter().cloned().collect();
+        let mut undo = MergeUndo {
+            kept: keep.to_string(),
+            removed: remove.to_string(),
+            persons: vec![kept.clone(), removed.clone()],
+            relationships: moving.clone(),
+            added: Vec::new(),
+        };
+
+        for person in self.tree.persons.iter_mut() {
+            if person.id == keep {
+                draft.apply_to(person);
+                let name = person.full_name();
+                let mut spellings = kept.alternate_names.clone();
+                spellings.extend(removed.alternate_names.iter().cloned());
+                spellings.extend([kept.full_name(), removed.full_name()]);
+                person.alternate_names.clear();
+                for spelling in spellings {
+                    if spelling != name && !person.alternate_names.contains(&spelling) {
+                        person.alternate_names.push(spelling);
+                    }
+                }
+                if person.member_id.is_empty() {
+                    person.member_id = removed.member_id.clone();
+                }
+            } else if person.id == remove {
+                person.deleted = true;
+                person.stamp = Stamp::after(&person.stamp);
+            }
+        }
+        for relationship in self.tree.relationships.iter_mut().filter(|r| moving.iter().any(|m| m.id == r.id)) {
+            relationship.removed = true;
+            relationship.stamp = Stamp::after(&relationship.stamp);
+        }
+        // Links the kept person already has, or that would loop, are dropped
+        for r in moving {
+            let swap = |id: &str| if id == remove { keep.to_string() } else { id.to_string() };
+            if self.tree.relate(r.kind, &swap(&r.from), &swap(&r.to)).is_ok()
+                && let Some(added) = self.tree.relationships.last()
+            {
+                undo.added.push(added.id.clone());
+            }
+        }
+        self.commit(before)?;
+        self.forget_merges_of(&[keep, remove]);
+        self.merges.push(undo);
+        self.recheck([keep, remove]);
+        Ok(())
+    }
+
+    // Put back the people and links the last merge changed
+    pub fn undo_merge(&mut self) -> Result<(), String> {
+        let undo = self.merges.last().cloned().ok_or_else(|| "Nothing to undo".to_string())?;
+        let saved = self.tree.clone();
+        for before in undo.persons {
+            if let Some(person) = self.tree.persons.iter_mut().find(|p| p.id == before.id) {
+                let stamp = Stamp::after(&person.stamp);
+                *person = Person { stamp, ..before };
+            }
+        }
+        for before in undo.relationships {
+            if let Some(relationship) = self.tree.relationships.iter_mut().find(|r| r.id == before.id) {
+                let stamp = Stamp::after(&relationship.stamp);
+                *relationship = Relationship { stamp, ..before };
+            }
+        }
+        for relationship in self.tree.relationships.iter_mut().filter(|r| undo.added.contains(&r.id)) {
+            relationship.removed = true;
+            relationship.stamp = Stamp::after(&relationship.stamp);
+        }
+        self.commit(saved)?;
+        self.merges.pop();
+        self.recheck([undo.kept.as_str(), undo.removed.as_str()]);
+        Ok(())
+    }
+
+    // Stop offering two people as the same person
+    pub fn mark_distinct(&mut self, first: &str, second: &str) {
+        self.distinct.push((first.to_string(), second.to_string()));
+        self.persist_or_log();
+    }
+
+    pub fn set_privacy(&mut self, privacy: TreePrivacy) {
+        self.privacy = privacy;
+        self.persist_or_log();
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+        self.persist_or_log();
+    }
+
+    pub fn duplicates(&self) -> &[Candidate] {
+        self.duplicates.get_or_init(|| person_match::duplicates(&self.tree, &self.distinct))
+    }
+
+    // What someone in the tree is to us
+    pub fn kinship_to_me(&self, id: &str) -> Kinship {
+        self.tree.kinship(&self.home_id, id)
+    }
+
+    fn seed() -> Self {
+        let people = [
+            ("seed-person-john", "John", "Doe", Gender::Male, "1970", "", "Engineer", ""),
+            ("seed-person-jane", "Jane", "Smith", Gender::Female, "1972", "", "Doctor", LOCAL_USER_ID),
+            ("seed-person-bob", "Bob", "Doe", Gender::Male, "1995", "", "Teacher", ""),
+            ("seed-person-alice", "Alice", "Doe", Gender::Female, "1997", "", "Artist", ""),
+            ("seed-person-carol", "Carol", "Johnson", Gender::Female, "1996", "", "Architect", ""),
+            ("seed-person-david", "David", "Doe", Gender::Male, "2020", "", "Student", ""),
+            // Jane's side, with cousins on the father's and the mother's side
+            ("seed-person-robert", "Robert", "Smith", Gender::Male, "1940", "2015", "Merchant", ""),
+            ("seed-person-rokeya", "Rokeya", "Begum", Gender::Female, "1945", "", "Teacher", ""),
+            ("seed-person-margaret", "Margaret", "Smith", Gender::Female, "1915", "1999", "", ""),
+            ("seed-person-salma", "Salma", "Islam", Gender::Female, "1943", "", "", ""),
+            ("seed-person-nadia", "Nadia", "Islam", Gender::Female, "1975", "", "Journalist", "nadia"),
+            ("seed-person-abdul", "Abdul", "Uddin", Gender::Male, "1918", "1990", "Farmer", ""),
+            ("seed-person-karim", "Karim", "Uddin", Gender::Male, "1948", "", "Doctor", ""),
+            ("seed-person-rahim", "Rahim", "Uddin", Gender::Male, "1976", "", "Engineer", "rahim"),
+        ];
+        let links = [
+            (RelationKind::Spouse, "john", "jane"),
+            (RelationKind::Parent, "john", "bob"),
+            (RelationKind::Parent, "jane", "bob"),
+            (RelationKind::Parent, "john", "alice"),
+            (RelationKind::Parent, "jane", "alice"),
+            (RelationKind::Spouse, "bob", "carol"),
+            (RelationKind::Parent, "bob", "david"),
+            (RelationKind::Parent, "carol", "david"),
+            (RelationKind::Spouse, "robert", "rokeya"),
+            (RelationKind::Parent, "robert", "jane"),
+            (RelationKind::Parent, "rokeya", "jane"),
+            (RelationKind::Parent, "margaret", "robert"),
+            (RelationKind::Parent, "margaret", "salma"),
+            (RelationKind::Parent, "salma", "nadia"),
+            (RelationKind::Parent, "abdul", "rokeya"),
+            (RelationKind::Parent, "abdul", "karim"),
+            (RelationKind::Parent, "karim", "rahim"),
+        ];
+
+        let mut repo = Self { home_id: "seed-person-jane".to_string(), ..Self::default() };
+        for (id, given_name, surname, gender, birth_date, death_date, occupation, member_id) in people {
+            repo.tree.persons.push(Person {
+                id: id.to_string(),
+                given_name: given_name.to_string(),
+                surname: surname.to_string(),
+                gender,
+                birth_date: birth_date.to_string(),
+                death_date: death_date.to_string(),
+                occupation: occupation.to_string(),
+                member_id: member_id.to_string(),
+                stamp: Stamp::now(),
+                ..Person::default()
+            });
+        }
+        for (kind, from, to) in links {
+            repo.tree.relationships.push(Relationship {
+                id: format!("seed-relation-{}-{}", from, to),
+                kind,
+                from: format!("seed-person-{}", from),
+                to: format!("seed-person-{}", to),
+                removed: false,
+                stamp: Stamp::now(),
+            });
+        }
+        repo
+    }
+}
+
+pub fn use_tree_repo() -> Signal<TreeRepository> {
+    use_context()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(given_name: &str, birth_date: &str) -> PersonDraft {
+        PersonDraft {
+            given_name: given_name.to_string(),
+            surname: "Uddin".to_string(),
+            birth_date: birth_date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Us, and the same grandfather entered twice
+    fn repo() -> (TreeRepository, String, String) {
+        let mut repo = TreeRepository::default();
+        repo.home_id = repo.save_person(None, &draft("Nadia", "1990")).unwrap();
+        let first = repo.save_person(None, &draft("Mohammad", "1930")).unwrap();
+        let second = repo.save_person(None, &draft("Muhammad", "1930")).unwrap();
+        (repo, first, second)
+    }
+
+    #[test]
+    fn duplicates_are_worked_out_again_after_a_change() {
+        let (mut repo, first, second) = repo();
+        assert_eq!(repo.duplicates().len(), 1);
+        repo.mark_distinct(&first, &second);
+        assert!(repo.duplicates().is_empty());
+        repo.save_person(None, &draft("Mohamad", "1930")).unwrap();
+        assert_eq!(repo.duplicates().len(), 2);
+    }
+
+    #[test]
+    fn merges_undo_until_either_person_changes() {
+        let (mut repo, first, second) = repo();
+        repo.merge_people(&first, &second, &draft("Mohammad", "1930")).unwrap();
+        assert!(repo.tree.person(&second).is_none());
+        assert!(repo.duplicates().is_empty());
+        repo.undo_merge().unwrap();
+        assert!(repo.tree.person(&second).is_some());
+        assert_eq!(repo.duplicates().len(), 1);
+
+        repo.merge_people(&first, &second, &draft("Mohammad", "1930")).unwrap();
+        let mut edited = draft("Mohammad", "1930");
+        edited.occupation = "Farmer".to_string();
+        repo.save_person(Some(&first), &edited).unwrap();
+        assert!(repo.undo_merge().is_err());
+        assert_eq!(repo.tree.person(&first).map(|p| p.occupation.as_str()), Some("Farmer"));
+    }
+
+    #[test]
+    fn issues_come_and_go_with_the_edits() {
+        let (mut repo, grandfather, _) = repo();
+        let home = repo.home_id.clone();
+        repo.link(&home, Relative::Parent(RelationKind::Parent), &grandfather).unwrap();
+        assert!(repo.health.is_empty());
+        repo.save_person(Some(&grandfather), &draft("Mohammad", "1995")).unwrap();
+        assert_eq!(repo.health.len(), 1);
+        assert_eq!(repo.health[0].problem, tree_health::Problem::BornBeforeParent);
+        repo.save_person(Some(&grandfather), &draft("Mohammad", "1930")).unwrap();
+        assert!(repo.health.is_empty());
+    }
+
+    #[test]
+    fn a_link_with_nobody_left_clears_when_removed() {
+        let (mut repo, _, _) = repo();
+        repo.tree.relationships.push(Relationship::new(RelationKind::Spouse, "ghost", "gone"));
+        repo.health = tree_health::check_all(&repo.tree);
+        assert_eq!(repo.health.len(), 1);
+        let id = repo.tree.relationships[0].id.clone();
+        repo.unlink(&id).unwrap();
+        assert!(repo.health.is_empty());
+    }
+}
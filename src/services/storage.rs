@@ -0,0 +1,103 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+// Key-value storage for repositories.
+// Web keeps JSON documents in local storage, mobile and desktop keep one JSON
+// file per key under the platform data directory. Other builds (such as the
+// web feature compiled natively for `cargo test`) keep values in memory.
+
+const KEY_PREFIX: &str = "jeebon.";
+
+// Load a JSON document stored under `key`
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let raw = read_raw(key)?;
+    match serde_json::from_str(&raw) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::error!("Failed to parse stored value for {}: {}", key, e);
+            None
+        }
+    }
+}
+
+// Store `value` as a JSON document under `key`, logging any failure
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    if let Err(e) = try_save(key, value) {
+        log::error!("Failed to save {}: {}", key, e);
+    }
+}
+
+// Store `value` as a JSON document under `key`, for callers that tell the
+// user when it doesn't fit
+pub fn try_save<T: Serialize>(key: &str, value: &T) -> Result<(), String> {
+    let raw = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    write_raw(key, &raw)
+}
+
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+fn read_raw(key: &str) -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok().flatten()?;
+    match storage.get_item(&format!("{}{}", KEY_PREFIX, key)) {
+        Ok(value) => value,
+        Err(e) => {
+            log::error!("Error reading {} from local storage: {:?}", key, e);
+            None
+        }
+    }
+}
+
+// Fails when the browser's storage quota is used up
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+fn write_raw(key: &str, raw: &str) -> Result<(), String> {
+    let storage = web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .ok_or_else(|| "local storage is not available".to_string())?;
+    storage
+        .set_item(&format!("{}{}", KEY_PREFIX, key), raw)
+        .map_err(|_| "this device's storage is full".to_string())
+}
+
+#[cfg(all(not(feature = "web"), any(feature = "mobile", feature = "desktop")))]
+fn file_path(key: &str) -> Option<std::path::PathBuf> {
+    let dir = dirs::data_dir()?.join("jeebon");
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::error!("Failed to create data directory {:?}: {}", dir, e);
+        return None;
+    }
+    Some(dir.join(format!("{}{}.json", KEY_PREFIX, key)))
+}
+
+#[cfg(all(not(feature = "web"), any(feature = "mobile", feature = "desktop")))]
+fn read_raw(key: &str) -> Option<String> {
+    std::fs::read_to_string(file_path(key)?).ok()
+}
+
+#[cfg(all(not(feature = "web"), any(feature = "mobile", feature = "desktop")))]
+fn write_raw(key: &str, raw: &str) -> Result<(), String> {
+    let path = file_path(key).ok_or_else(|| "there is no data directory".to_string())?;
+    std::fs::write(&path, raw).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(
+    all(feature = "web", target_arch = "wasm32"),
+    all(not(feature = "web"), any(feature = "mobile", feature = "desktop"))
+)))]
+thread_local! {
+    static MEMORY: std::cell::RefCell<std::collections::HashMap<String, String>> = Default::default();
+}
+
+#[cfg(not(any(
+    all(feature = "web", target_arch = "wasm32"),
+    all(not(feature = "web"), any(feature = "mobile", feature = "desktop"))
+)))]
+fn read_raw(key: &str) -> Option<String> {
+    MEMORY.with(|m| m.borrow().get(&format!("{}{}", KEY_PREFIX, key)).cloned())
+}
+
+#[cfg(not(any(
+    all(feature = "web", target_arch = "wasm32"),
+    all(not(feature = "web"), any(feature = "mobile", feature = "desktop"))
+)))]
+fn write_raw(key: &str, raw: &str) -> Result<(), String> {
+    MEMORY.with(|m| m.borrow_mut().insert(format!("{}{}", KEY_PREFIX, key), raw.to_string()));
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::storage;
+use crate::utils::{new_id, now_millis};
+
+// Synchronisation primitives shared by the repositories.
+// Every synced record implements `Merge` so that the copy held on this device
+// and a copy changed elsewhere converge to the same value whichever order
+// they are merged in.
+
+const DEVICE_ID_KEY: &str = "device_id";
+
+// Identifier of this installation, generated once and kept in storage
+pub fn device_id() -> &'static str {
+    static DEVICE_ID: OnceLock<String> = OnceLock::new();
+    DEVICE_ID.get_or_init(|| {
+        storage::load::<String>(DEVICE_ID_KEY).unwrap_or_else(|| {
+            let id = new_id();
+            storage::save(DEVICE_ID_KEY, &id);
+            id
+        })
+    })
+}
+
+// Last-writer-wins timestamp. Ties on the wall clock are broken by device id
+// so that two devices never disagree on which write came last.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Stamp {
+    pub millis: u64,
+    pub device: String,
+}
+
+impl Stamp {
+    pub fn now() -> Self {
+        Self {
+            millis: now_millis(),
+            device: device_id().to_string(),
+        }
+    }
+
+    // A stamp that orders after `previous`, even if the local clock lags
+    pub fn after(previous: &Stamp) -> Self {
+        let mut stamp = Self::now();
+        if stamp <= *previous {
+            stamp.millis = previous.millis + 1;
+        }
+        stamp
+    }
+}
+
+// A record that can absorb a concurrently modified copy of itself
+pub trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+// A record with a stable identity across devices
+pub trait Identified {
+    fn id(&self) -> &str;
+}
+
+// Merge `incoming` records into `local`, matching them by id.
+// Returns true when anything changed.
+pub fn merge_by_id<T>(local: &mut Vec<T>, incoming: Vec<T>) -> bool
+where
+    T: Merge + Identified + Clone + PartialEq,
+{
+    let mut changed = false;
+    for record in incoming {
+        match local.iter_mut().find(|r| r.id() == record.id()) {
+            Some(existing) => {
+                let before = existing.clone();
+                existing.merge(record);
+                changed |= *existing != before;
+            }
+            None => {
+                local.push(record);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,519 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::models::family::{FamilyTree, Gender, Person, RelationKind, TreeIndex};
+use crate::models::fuzzy_date;
+
+// Placing the family tree for drawing. Everyone connected to the root is
+// given a generation counted from it, spouses in the same generation are
+// kept side by side as one unit, units in each generation are reordered to
+// cut down on crossing lines, and then slid sideways so parents sit over
+// their children.
+
+pub const CARD_WIDTH: f64 = 220.0;
+pub const CARD_HEIGHT: f64 = 80.0;
+const SPOUSE_GAP: f64 = 30.0;
+const UNIT_GAP: f64 = 40.0;
+const ROW_GAP: f64 = 80.0;
+const MARGIN: f64 = 40.0;
+// Rounds of reordering, and of sliding
+const SWEEPS: usize = 4;
+const SETTLE_PASSES: usize = 4;
+
+// Where one person's card goes, by its top left corner
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardBox {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    pub generation: i32,
+}
+
+impl CardBox {
+    pub fn centre(&self) -> (f64, f64) {
+        (self.x + CARD_WIDTH / 2.0, self.y + CARD_HEIGHT / 2.0)
+    }
+}
+
+// A line between cards: Spouse for couples, the parental kinds down to a child
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub points: Vec<(f64, f64)>,
+    pub kind: RelationKind,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub cards: Vec<CardBox>,
+    pub lines: Vec<Line>,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Layout {
+    pub fn card(&self, id: &str) -> Option<&CardBox> {
+        self.cards.iter().find(|c| c.id == id)
+    }
+}
+
+// How cards and lines look, on screen and in exports
+pub fn card_fill(gender: Gender) -> &'static str {
+    match gender {
+        Gender::Male => "#d4e6f9",
+        Gender::Female => "#f9d4d4",
+        Gender::Unknown => "#e2e3e5",
+    }
+}
+
+pub fn line_stroke(kind: RelationKind) -> &'static str {
+    if kind == RelationKind::Spouse { "#495057" } else { "#6c757d" }
+}
+
+pub fn line_dash(kind: RelationKind) -> &'static str {
+    match kind {
+        RelationKind::Adoptive => "8 4",
+        RelationKind::Step => "2 4",
+        _ => "none",
+    }
+}
+
+fn clip(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    format!("{}…", text.chars().take(max - 1).collect::<String>())
+}
+
+// The name, dates and relationship written on a card, shortened to fit
+pub fn card_text(person: &Person, title: &str) -> [String; 3] {
+    let dates = match (person.birth_date.is_empty(), person.death_date.is_empty()) {
+        (true, true) => String::new(),
+        (false, true) => format!("b. {}", clip(&fuzzy_date::tidy(&person.birth_date), 18)),
+        (true, false) => format!("† {}", clip(&fuzzy_date::tidy(&person.death_date), 18)),
+        (false, false) => {
+            format!("{} – † {}", clip(&fuzzy_date::tidy_years(&person.birth_date), 10), clip(&fuzzy_date::tidy_years(&person.death_date), 10))
+        }
+    };
+    [clip(&person.full_name(), 20), dates, clip(title, 22)]
+}
+
+// Spouses in one generation, drawn next to each other
+struct Unit {
+    members: Vec<String>,
+    generation: i32,
+}
+
+impl Unit {
+    fn width(&self) -> f64 {
+        self.members.len() as f64 * (CARD_WIDTH + SPOUSE_GAP) - SPOUSE_GAP
+    }
+}
+
+pub fn layout(tree: &FamilyTree, root: &str) -> Layout {
+    let index = tree.index();
+    let found = generations(&index, root);
+    if found.is_empty() {
+        return Layout::default();
+    }
+    let units = units(&index, &found);
+    let unit_of: BTreeMap<&str, (usize, usize)> = units
+        .iter()
+        .enumerate()
+        .flat_map(|(u, unit)| unit.members.iter().enumerate().map(move |(slot, id)| (id.as_str(), (u, slot))))
+        .collect();
+
+    // Which units hold each unit's parents and children
+    let mut parents: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); units.len()];
+    let mut children: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); units.len()];
+    for (u, unit) in units.iter().enumerate() {
+        for id in unit.members.iter() {
+            for (parent, _) in index.parents_of(id) {
+                if let Some(&(p, _)) = unit_of.get(parent.id.as_str()) {
+                    parents[u].insert(p);
+                    children[p].insert(u);
+                }
+            }
+        }
+    }
+
+    let rows = order(&units, &parents, &children);
+    let left = settle(&units, &rows, &parents, &children);
+
+    let first_generation = units.iter().map(|u| u.generation).min().unwrap_or(0);
+    let shift = MARGIN - left.iter().cloned().fold(f64::INFINITY, f64::min);
+    let mut cards = Vec::new();
+    for (u, unit) in units.iter().enumerate() {
+        let y = MARGIN + (unit.generation - first_generation) as f64 * (CARD_HEIGHT + ROW_GAP);
+        for (slot, id) in unit.members.iter().enumerate() {
+            cards.push(CardBox {
+                id: id.clone(),
+                x: left[u] + shift + slot as f64 * (CARD_WIDTH + SPOUSE_GAP),
+                y,
+                generation: unit.generation,
+            });
+        }
+    }
+    let width = cards.iter().map(|c| c.x + CARD_WIDTH).fold(0.0, f64::max) + MARGIN;
+    let height = cards.iter().map(|c| c.y + CARD_HEIGHT).fold(0.0, f64::max) + MARGIN;
+    let mut layout = Layout { cards, lines: Vec::new(), width, height };
+    layout.lines = lines(tree, &index, &layout, &unit_of);
+    layout
+}
+
+// The tree drawn around us, or around `picked` when they aren't connected
+// to us
+pub fn around(tree: &FamilyTree, home: &str, picked: &str) -> Layout {
+    let ours = layout(tree, home);
+    if ours.card(picked).is_some() { ours } else { layout(tree, picked) }
+}
+
+// Everyone connected to `root` with their generation, parents one above
+// and children one below, in the order they were reached with brothers and
+// sisters oldest first
+fn generations(tree: &TreeIndex, root: &str) -> Vec<(String, i32)> {
+    let mut found: Vec<(String, i32)> = Vec::new();
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    let mut queue: VecDeque<(String, i32)> = VecDeque::new();
+    if tree.person(root).is_some() {
+        seen.insert(root.to_string());
+        queue.push_back((root.to_string(), 0));
+    }
+    while let Some((id, generation)) = queue.pop_front() {
+        let mut children = tree.children_of(&id);
+        // Unknown births last
+        children.sort_by_key(|(child, _)| (child.born().is_none(), child.born()));
+        let next: Vec<(String, i32)> = tree
+            .spouses_of(&id)
+            .into_iter()
+            .map(|p| (p.id.clone(), generation))
+            .chain(tree.parents_of(&id).into_iter().map(|(p, _)| (p.id.clone(), generation - 1)))
+            .chain(children.into_iter().map(|(p, _)| (p.id.clone(), generation + 1)))
+            .collect();
+        for (other, other_generation) in next {
+            if seen.insert(other.clone()) {
+                queue.push_back((other, other_generation));
+            }
+        }
+        found.push((id, generation));
+    }
+    found
+}
+
+// Group spouses of the same generation, each group walked along its
+// marriages so that every couple stands side by side where it can
+fn units(tree: &TreeIndex, found: &[(String, i32)]) -> Vec<Unit> {
+    let generation_of: BTreeMap<&str, i32> = found.iter().map(|(id, g)| (id.as_str(), *g)).collect();
+    let spouses = |id: &str, generation: i32| -> Vec<String> {
+        tree.spouses_of(id)
+            .into_iter()
+            .filter(|s| generation_of.get(s.id.as_str()) == Some(&generation))
+            .map(|s| s.id.clone())
+            .collect()
+    };
+    let mut placed: BTreeSet<String> = BTreeSet::new();
+    let mut units = Vec::new();
+    for (id, generation) in found {
+        if placed.contains(id) {
+            continue;
+        }
+        // Everyone married into this group
+        let mut group = vec![id.clone()];
+        let mut index = 0;
+        while index < group.len() {
+            for spouse in spouses(&group[index], *generation) {
+                if !group.contains(&spouse) {
+                    group.push(spouse);
+                }
+            }
+            index += 1;
+        }
+        // Start from an end of the chain, men first for a plain couple
+        let degree = |id: &String| spouses(id, *generation).len();
+        let is_man = |id: &String| tree.person(id).is_some_and(|p| p.gender == Gender::Male);
+        let start = group
+            .iter()
+            .filter(|m| degree(m) <= 1)
+            .max_by_key(|m| is_man(m))
+            .cloned()
+            .unwrap_or_else(|| group[0].clone());
+        let mut members = vec![start];
+        while members.len() < group.len() {
+            let last = members.last().cloned().unwrap_or_default();
+            let next = spouses(&last, *generation)
+                .into_iter()
+                .find(|s| !members.contains(s))
+                .or_else(|| group.iter().find(|m| !members.contains(m)).cloned());
+            match next {
+                Some(next) => members.push(next),
+                None => break,
+            }
+        }
+        placed.extend(members.iter().cloned());
+        units.push(Unit { members, generation: *generation });
+    }
+    units
+}
+
+// Crossing lines between each generation and the next
+fn crossings(rows: &BTreeMap<i32, Vec<usize>>, children: &[BTreeSet<usize>]) -> usize {
+    let position: BTreeMap<usize, usize> = rows.values().flat_map(|row| row.iter().enumerate().map(|(i, u)| (*u, i))).collect();
+    let mut total = 0;
+    for row in rows.values() {
+        let edges: Vec<(usize, usize)> = row
+            .iter()
+            .enumerate()
+            .flat_map(|(i, u)| children[*u].iter().map(move |c| (i, *c)))
+            .map(|(i, c)| (i, position[&c]))
+            .collect();
+        for (n, a) in edges.iter().enumerate() {
+            total += edges[n + 1..].iter().filter(|b| (a.0 < b.0 && a.1 > b.1) || (a.0 > b.0 && a.1 < b.1)).count();
+        }
+    }
+    total
+}
+
+// Units of each generation left to right. Each sweep sorts a generation by
+// the average place of its parents, going down, then of its children,
+// going up; the ordering with the fewest crossings wins.
+fn order(units: &[Unit], parents: &[BTreeSet<usize>], children: &[BTreeSet<usize>]) -> BTreeMap<i32, Vec<usize>> {
+    let mut rows: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    for (u, unit) in units.iter().enumerate() {
+        rows.entry(unit.generation).or_default().push(u);
+    }
+    let generations: Vec<i32> = rows.keys().cloned().collect();
+    let mut best = (crossings(&rows, children), rows.clone());
+    for _ in 0..SWEEPS {
+        let downwards = generations.iter().skip(1).map(|g| (*g, g - 1, parents));
+        let upwards = generations.iter().rev().skip(1).map(|g| (*g, g + 1, children));
+        for (generation, neighbour, links) in downwards.chain(upwards) {
+            let places: BTreeMap<usize, usize> =
+                rows.get(&neighbour).map(|row| row.iter().enumerate().map(|(i, u)| (*u, i)).collect()).unwrap_or_default();
+            let Some(row) = rows.get_mut(&generation) else {
+                continue;
+            };
+            let mut keyed: Vec<(f64, usize)> = row
+                .iter()
+                .enumerate()
+                .map(|(i, u)| {
+                    let near: Vec<f64> = links[*u].iter().filter_map(|n| places.get(n)).map(|p| *p as f64).collect();
+                    let key = if near.is_empty() { i as f64 } else { near.iter().sum::<f64>() / near.len() as f64 };
+                    (key, *u)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            *row = keyed.into_iter().map(|(_, u)| u).collect();
+        }
+        let count = crossings(&rows, children);
+        if count < best.0 {
+            best = (count, rows.clone());
+        }
+    }
+    best.1
+}
+
+// Left edge of each unit. Every pass pulls units under their parents, then
+// over their children, as far as their neighbours in the row allow.
+fn settle(units: &[Unit], rows: &BTreeMap<i32, Vec<usize>>, parents: &[BTreeSet<usize>], children: &[BTreeSet<usize>]) -> Vec<f64> {
+    let mut left = vec![0.0; units.len()];
+    for row in rows.values() {
+        place(units, row, &mut left, &[]);
+    }
+    for _ in 0..SETTLE_PASSES {
+        for (links, reverse) in [(parents, false), (children, true)] {
+            let generations: Vec<&Vec<usize>> = if reverse { rows.values().rev().collect() } else { rows.values().collect() };
+            for row in generations {
+                let wanted: Vec<Option<f64>> = row
+                    .iter()
+                    .map(|u| {
+                        let centres: Vec<f64> = links[*u].iter().map(|n| left[*n] + units[*n].width() / 2.0).collect();
+                        (!centres.is_empty()).then(|| centres.iter().sum::<f64>() / centres.len() as f64 - units[*u].width() / 2.0)
+                    })
+                    .collect();
+                place(units, row, &mut left, &wanted);
+            }
+        }
+    }
+    left
+}
+
+// Put a row's units as close to where they want to be as they fit: packed
+// from the left and from the right, then halfway between
+fn place(units: &[Unit], row: &[usize], left: &mut [f64], wanted: &[Option<f64>]) {
+    let want: Vec<f64> = row.iter().enumerate().map(|(i, u)| wanted.get(i).copied().flatten().unwrap_or(left[*u])).collect();
+    let mut from_left = want.clone();
+    for i in 1..row.len() {
+        from_left[i] = from_left[i].max(from_left[i - 1] + units[row[i - 1]].width() + UNIT_GAP);
+    }
+    let mut from_right = want;
+    for i in (0..row.len().saturating_sub(1)).rev() {
+        from_right[i] = from_right[i].min(from_right[i + 1] - units[row[i]].width() - UNIT_GAP);
+    }
+    for (i, u) in row.iter().enumerate() {
+        left[*u] = (from_left[i] + from_right[i]) / 2.0;
+    }
+}
+
+fn lines(tree: &FamilyTree, index: &TreeIndex, layout: &Layout, unit_of: &BTreeMap<&str, (usize, usize)>) -> Vec<Line> {
+    let cards: BTreeMap<&str, &CardBox> = layout.cards.iter().map(|c| (c.id.as_str(), c)).collect();
+    let card = |id: &str| cards.get(id).copied();
+    let side_by_side = |a: &str, b: &str| match (unit_of.get(a), unit_of.get(b)) {
+        (Some((ua, sa)), Some((ub, sb))) => ua == ub && sa.abs_diff(*sb) == 1,
+        _ => false,
+    };
+    let mut lines = Vec::new();
+    for r in tree.links().filter(|r| r.kind == RelationKind::Spouse) {
+        let (Some(a), Some(b)) = (card(&r.from), card(&r.to)) else {
+            continue;
+        };
+        let (a, b) = if a.x <= b.x { (a, b) } else { (b, a) };
+        let points = if side_by_side(&a.id, &b.id) {
+            let y = a.y + CARD_HEIGHT / 2.0;
+            vec![(a.x + CARD_WIDTH, y), (b.x, y)]
+        } else {
+            vec![a.centre(), b.centre()]
+        };
+        lines.push(Line { points, kind: RelationKind::Spouse });
+    }
+    for child in layout.cards.iter() {
+        let mut parents: Vec<(String, RelationKind)> = index
+            .parents_of(&child.id)
+            .into_iter()
+            .filter(|(p, _)| card(&p.id).is_some())
+            .map(|(p, kind)| (p.id.clone(), kind))
+            .collect();
+        let bus = child.y - ROW_GAP / 2.0;
+        let (child_x, _) = child.centre();
+        while !parents.is_empty() {
+            let (first, kind) = parents.remove(0);
+            // A couple's children hang from the middle of their marriage line
+            let partner = parents.iter().position(|(p, _)| side_by_side(&first, p)).map(|i| parents.remove(i));
+            let Some(first) = card(&first) else {
+                continue;
+            };
+            let (origin, kind) = match partner.as_ref().and_then(|(p, k)| card(p).map(|c| (c, *k))) {
+                Some((other, other_kind)) => {
+                    let x = (first.centre().0 + other.centre().0) / 2.0;
+                    let kind = if kind == RelationKind::Parent { other_kind } else { kind };
+                    ((x, first.y + CARD_HEIGHT / 2.0), kind)
+                }
+                None => ((first.centre().0, first.y + CARD_HEIGHT), kind),
+            };
+            lines.push(Line { points: vec![origin, (origin.0, bus), (child_x, bus), (child_x, child.y)], kind });
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::family::Relationship;
+
+    fn person(id: &str, gender: Gender, born: &str) -> Person {
+        Person { id: id.to_string(), given_name: id.to_string(), gender, birth_date: born.to_string(), ..Person::default() }
+    }
+
+    // Grandad and grandma, their son married to Mum, and two children,
+    // the younger listed first
+    fn family() -> FamilyTree {
+        let persons = vec![
+            person("grandad", Gender::Male, "1930"),
+            person("grandma", Gender::Female, "1932"),
+            person("mum", Gender::Female, "1962"),
+            person("dad", Gender::Male, "1960"),
+            person("younger", Gender::Female, "1992"),
+            person("elder", Gender::Male, "1990"),
+        ];
+        let links = [
+            (RelationKind::Spouse, "grandma", "grandad"),
+            (RelationKind::Parent, "grandad", "dad"),
+            (RelationKind::Parent, "grandma", "dad"),
+            (RelationKind::Spouse, "mum", "dad"),
+            (RelationKind::Parent, "dad", "younger"),
+            (RelationKind::Parent, "mum", "younger"),
+            (RelationKind::Parent, "dad", "elder"),
+            (RelationKind::Adoptive, "mum", "elder"),
+        ];
+        let relationships = links.iter().map(|(kind, from, to)| Relationship::new(*kind, from, to)).collect();
+        FamilyTree { persons, relationships }
+    }
+
+    fn generation_of(found: &[(String, i32)], id: &str) -> Option<i32> {
+        found.iter().find(|(other, _)| other == id).map(|(_, g)| *g)
+    }
+
+    #[test]
+    fn generations_count_from_the_root() {
+        let tree = family();
+        let found = generations(&tree.index(), "elder");
+        assert_eq!(found.len(), 6);
+        assert_eq!(found[0], ("elder".to_string(), 0));
+        for (id, generation) in [("younger", 0), ("dad", -1), ("mum", -1), ("grandad", -2), ("grandma", -2)] {
+            assert_eq!(generation_of(&found, id), Some(generation), "{}", id);
+        }
+        // Brothers and sisters are reached oldest first
+        let found = generations(&tree.index(), "dad");
+        let order: Vec<&str> = found.iter().filter(|(_, g)| *g == 1).map(|(id, _)| id.as_str()).collect();
+        assert_eq!(order, vec!["elder", "younger"]);
+        assert!(generations(&tree.index(), "nobody").is_empty());
+    }
+
+    #[test]
+    fn spouses_share_a_unit_with_the_husband_first() {
+        let tree = family();
+        let index = tree.index();
+        let grouped = units(&index, &generations(&index, "elder"));
+        let members: Vec<Vec<&str>> = grouped.iter().map(|u| u.members.iter().map(String::as_str).collect()).collect();
+        assert!(members.contains(&vec!["dad", "mum"]));
+        assert!(members.contains(&vec!["grandad", "grandma"]));
+        assert!(members.contains(&vec!["elder"]));
+        assert_eq!(grouped.len(), 4);
+
+        // Someone married twice stands between both spouses
+        let mut remarried = family();
+        remarried.persons.push(person("stepmum", Gender::Female, "1965"));
+        remarried.relationships.push(Relationship::new(RelationKind::Spouse, "dad", "stepmum"));
+        let index = remarried.index();
+        let grouped = units(&index, &generations(&index, "elder"));
+        let parents = grouped.iter().find(|u| u.members.contains(&"dad".to_string())).unwrap();
+        assert_eq!(parents.members.len(), 3);
+        assert_eq!(parents.members[1], "dad");
+    }
+
+    #[test]
+    fn crossings_are_counted_and_ordered_away() {
+        let unit = |generation| Unit { members: vec![String::new()], generation };
+        let units = [unit(0), unit(0), unit(1), unit(1)];
+        // The first parent's child is on the right and the second's on the left
+        let children = vec![BTreeSet::from([3]), BTreeSet::from([2]), BTreeSet::new(), BTreeSet::new()];
+        let parents = vec![BTreeSet::new(), BTreeSet::new(), BTreeSet::from([1]), BTreeSet::from([0])];
+        let crossed = BTreeMap::from([(0, vec![0, 1]), (1, vec![2, 3])]);
+        assert_eq!(crossings(&crossed, &children), 1);
+        let straight = BTreeMap::from([(0, vec![0, 1]), (1, vec![3, 2])]);
+        assert_eq!(crossings(&straight, &children), 0);
+        assert_eq!(crossings(&order(&units, &parents, &children), &children), 0);
+    }
+
+    #[test]
+    fn cards_sit_in_rows_with_parents_over_their_children() {
+        let layout = layout(&family(), "elder");
+        let card = |id: &str| layout.card(id).unwrap().clone();
+        let row = |generation: i32| MARGIN + f64::from(generation) * (CARD_HEIGHT + ROW_GAP);
+        for (id, generation) in [("grandad", 0), ("grandma", 0), ("dad", 1), ("mum", 1), ("elder", 2), ("younger", 2)] {
+            assert_eq!(card(id).y, row(generation), "{}", id);
+        }
+        // Couples side by side, children apart and in birth order
+        assert_eq!(card("mum").x - card("dad").x, CARD_WIDTH + SPOUSE_GAP);
+        assert_eq!(card("grandma").x - card("grandad").x, CARD_WIDTH + SPOUSE_GAP);
+        assert!(card("younger").x - card("elder").x >= CARD_WIDTH + UNIT_GAP);
+        // Each couple centred over their children
+        let middle = |a: &str, b: &str| (card(a).centre().0 + card(b).centre().0) / 2.0;
+        assert!((middle("dad", "mum") - middle("elder", "younger")).abs() < 1.0);
+        assert!((middle("grandad", "grandma") - card("dad").centre().0).abs() < CARD_WIDTH);
+        assert!(layout.cards.iter().all(|c| c.x >= MARGIN - 1e-9));
+        // One marriage line per couple and one line per child's parents
+        let spouse_lines = layout.lines.iter().filter(|l| l.kind == RelationKind::Spouse).count();
+        assert_eq!(spouse_lines, 2);
+        assert_eq!(layout.lines.len(), 2 + 3);
+        assert!(layout.lines.iter().any(|l| l.kind == RelationKind::Adoptive));
+    }
+}
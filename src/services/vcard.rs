@@ -0,0 +1,444 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::models::contact::ContactCard;
+use crate::services::sync::Stamp;
+
+// vCard 3.0 (RFC 2426) and 4.0 (RFC 6350) reading and writing. Only the
+// properties a contact card holds are kept; anything else is skipped. The
+// content-line helpers are shared with iCalendar, which uses the same syntax.
+
+// Lines longer than this many bytes are folded when writing
+const FOLD_AT: usize = 75;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VCardVersion {
+    V3,
+    V4,
+}
+
+impl VCardVersion {
+    pub const ALL: [VCardVersion; 2] = [VCardVersion::V3, VCardVersion::V4];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VCardVersion::V3 => "3.0",
+            VCardVersion::V4 => "4.0",
+        }
+    }
+}
+
+// One content line: `group.NAME;PARAM=value:value`
+pub struct Property {
+    pub name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    pub fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside a quoted parameter
+        let mut quoted = false;
+        let split = line.char_indices().find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ':' && !quoted
+        })?;
+        let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?;
+        let name = name.rsplit('.').next().unwrap_or(name).to_ascii_uppercase();
+        let params = parts
+            .map(|p| match p.split_once('=') {
+                Some((key, value)) => (key.to_ascii_uppercase(), value.trim_matches('"').to_string()),
+                // vCard 2.1 style bare types such as "TEL;CELL:"
+                None => ("TYPE".to_string(), p.to_string()),
+            })
+            .collect();
+        Some(Self { name, params, value: value.to_string() })
+    }
+
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    // The value with any transfer encoding removed
+    fn raw(&self) -> String {
+        match self.param("ENCODING").map(|e| e.to_ascii_uppercase()) {
+            Some(e) if e == "QUOTED-PRINTABLE" => decode_quoted_printable(&self.value),
+            _ => self.value.clone(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        unescape(&self.raw())
+    }
+
+    // Structured values such as N are separated by unescaped semicolons
+    fn components(&self) -> Vec<String> {
+        split_unescaped(&self.raw(), ';').iter().map(|c| unescape(c)).collect()
+    }
+}
+
+// Every card in the text. Cards without any name, phone or email are dropped.
+pub fn parse(text: &str) -> Result<Vec<ContactCard>, String> {
+    let mut cards = Vec::new();
+    let mut current: Option<ContactCard> = None;
+    for line in unfold(text) {
+        let Some(property) = Property::parse(&line) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "BEGIN" if property.value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(ContactCard { stamp: Stamp::now(), ..ContactCard::default() });
+            }
+            "END" if property.value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(mut card) = current.take() {
+                    if card.name.is_empty() {
+                        card.name = [card.given_name.as_str(), card.family_name.as_str()]
+                            .into_iter()
+                            .filter(|n| !n.is_empty())
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                    }
+                    if card.name.is_empty() {
+                        card.name = card.emails.first().or(card.phones.first()).cloned().unwrap_or_default();
+                    }
+                    if !card.name.is_empty() {
+                        cards.push(card);
+                    }
+                }
+            }
+            _ => {
+                if let Some(card) = current.as_mut() {
+                    apply(card, &property);
+                }
+            }
+        }
+    }
+    if cards.is_empty() {
+        return Err("No contacts found in this file".to_string());
+    }
+    Ok(cards)
+}
+
+fn apply(card: &mut ContactCard, property: &Property) {
+    match property.name.as_str() {
+        "FN" => card.name = property.text().trim().to_string(),
+        "N" => {
+            let parts = property.components();
+            card.family_name = parts.first().cloned().unwrap_or_default().trim().to_string();
+            card.given_name = parts.get(1).cloned().unwrap_or_default().trim().to_string();
+        }
+        "TEL" => {
+            let phone = property.text();
+            let phone = phone.strip_prefix("tel:").unwrap_or(&phone).trim().to_string();
+            if !phone.is_empty() {
+                card.phones.push(phone);
+            }
+        }
+        "EMAIL" => {
+            let email = property.text().trim().to_string();
+            if !email.is_empty() {
+                card.emails.push(email);
+            }
+        }
+        "ORG" => card.organization = property.components().into_iter().filter(|c| !c.is_empty()).collect::<Vec<_>>().join(", "),
+        "TITLE" => card.title = property.text(),
+        "NOTE" => card.note = property.text(),
+        "BDAY" => card.birthday = property.text(),
+        "UID" => card.uid = property.text(),
+        "PHOTO" => card.photo = photo(property),
+        _ => {}
+    }
+}
+
+// PHOTO as a web address or data: URI, whichever form the card used
+fn photo(property: &Property) -> String {
+    let value = property.value.trim();
+    let inline = property.param("ENCODING").is_some_and(|e| e.eq_ignore_ascii_case("b") || e.eq_ignore_ascii_case("BASE64"));
+    if inline {
+        let kind = property.param("TYPE").unwrap_or("jpeg").to_ascii_lowercase();
+        let kind = kind.strip_prefix("image/").unwrap_or(&kind);
+        format!("data:image/{};base64,{}", kind, value)
+    } else {
+        value.to_string()
+    }
+}
+
+// Join folded lines back together; a line starting with a space or tab
+// continues the one before
+pub fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => {
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(rest);
+                }
+            }
+            _ => {
+                // Quoted-printable soft line breaks end in "="
+                if let Some(last) = lines.last_mut().filter(|l| l.ends_with('=') && l.to_ascii_uppercase().contains("QUOTED-PRINTABLE")) {
+                    last.pop();
+                    last.push_str(line);
+                } else {
+                    lines.push(line.to_string());
+                }
+            }
+        }
+    }
+    lines
+}
+
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == separator {
+            parts.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(c);
+        // Keep escapes for `unescape`, and don't split on an escaped separator
+        if c == '\\' {
+            current.extend(chars.next());
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+pub fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'=')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Write one content line, folding it at FOLD_AT bytes without splitting a
+// character
+pub fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > FOLD_AT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+pub fn serialize(cards: &[ContactCard], version: VCardVersion) -> String {
+    let mut out = String::new();
+    for card in cards {
+        push_line(&mut out, "BEGIN:VCARD");
+        push_line(&mut out, &format!("VERSION:{}", version.label()));
+        push_line(&mut out, &format!("FN:{}", escape(&card.name)));
+        push_line(&mut out, &format!("N:{};{};;;", escape(&card.family_name), escape(&card.given_name)));
+        for phone in card.phones.iter() {
+            match version {
+                VCardVersion::V3 => push_line(&mut out, &format!("TEL;TYPE=CELL:{}", escape(phone))),
+                VCardVersion::V4 => push_line(&mut out, &format!("TEL;VALUE=uri;TYPE=cell:tel:{}", tel_uri(phone))),
+            }
+        }
+        for email in card.emails.iter() {
+            push_line(&mut out, &format!("EMAIL;TYPE=INTERNET:{}", escape(email)));
+        }
+        for (name, value) in [("ORG", &card.organization), ("TITLE", &card.title), ("BDAY", &card.birthday), ("NOTE", &card.note)] {
+            if !value.is_empty() {
+                push_line(&mut out, &format!("{}:{}", name, escape(value)));
+            }
+        }
+        if !card.photo.is_empty() {
+            push_line(&mut out, &photo_line(&card.photo, version));
+        }
+        let uid = if card.uid.is_empty() { &card.member_id } else { &card.uid };
+        if !uid.is_empty() {
+            push_line(&mut out, &format!("UID:{}", escape(uid)));
+        }
+        push_line(&mut out, "END:VCARD");
+    }
+    out
+}
+
+// Phone number as the body of a tel: URI, with spaces as visual separators
+fn tel_uri(phone: &str) -> String {
+    phone
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            '0'..='9' | '+' | '-' => Some(c),
+            ' ' | '.' => Some('-'),
+            _ => None,
+        })
+        .collect()
+}
+
+fn photo_line(photo: &str, version: VCardVersion) -> String {
+    match version {
+        VCardVersion::V4 => format!("PHOTO:{}", photo),
+        VCardVersion::V3 => {
+            // 3.0 carries inline images as plain base64 with the type beside it
+            let inline = photo
+                .strip_prefix("data:image/")
+                .and_then(|rest| rest.split_once(";base64,"))
+                .filter(|(_, data)| STANDARD.decode(data).is_ok());
+            match inline {
+                Some((kind, data)) => format!("PHOTO;ENCODING=b;TYPE={}:{}", kind.to_ascii_uppercase(), data),
+                None => format!("PHOTO;VALUE=uri:{}", photo),
+            }
+        }
+    }
+}
+
+// A data: URI for downloading the cards as a .vcf file
+pub fn data_url(cards: &[ContactCard], version: VCardVersion) -> String {
+    format!("data:text/vcard;charset=utf-8;base64,{}", STANDARD.encode(serialize(cards, version)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card() -> ContactCard {
+        ContactCard {
+            name: "রহিম উদ্দিন".to_string(),
+            given_name: "রহিম".to_string(),
+            family_name: "উদ্দিন".to_string(),
+            phones: vec!["+880-1711-000000".to_string(), "01712-345678".to_string()],
+            emails: vec!["rahim@example.com".to_string()],
+            organization: "Acme, Inc".to_string(),
+            title: "Engineer; Dhaka".to_string(),
+            birthday: "1990-04-21".to_string(),
+            note: "Met at the wedding,\nsecond cousin \\ friend".to_string(),
+            photo: format!("data:image/png;base64,{}", STANDARD.encode([7u8; 120])),
+            uid: "urn:uuid:1234".to_string(),
+            ..ContactCard::default()
+        }
+    }
+
+    // Everything but the stamp, which reading sets afresh
+    fn same(a: &ContactCard, b: &ContactCard) {
+        assert_eq!(ContactCard { stamp: Stamp::default(), ..a.clone() }, ContactCard { stamp: Stamp::default(), ..b.clone() });
+    }
+
+    #[test]
+    fn cards_come_back_as_written_in_both_versions() {
+        for version in VCardVersion::ALL {
+            let text = serialize(&[card()], version);
+            assert!(text.contains(&format!("VERSION:{}\r\n", version.label())));
+            let read = parse(&text).unwrap();
+            assert_eq!(read.len(), 1);
+            same(&read[0], &card());
+            // and again, for anything lost on the second pass
+            same(&parse(&serialize(&read, version)).unwrap()[0], &card());
+        }
+    }
+
+    #[test]
+    fn long_lines_fold_between_characters() {
+        let text = serialize(&[card()], VCardVersion::V4);
+        assert!(text.split("\r\n").all(|line| line.len() <= FOLD_AT));
+        assert!(text.contains("\r\n "));
+        let mut out = String::new();
+        push_line(&mut out, &format!("NOTE:{}", "আ".repeat(40)));
+        assert!(out.split("\r\n").all(|line| line.len() <= FOLD_AT));
+        assert_eq!(unfold(&out), [format!("NOTE:{}", "আ".repeat(40))]);
+    }
+
+    #[test]
+    fn photos_are_written_for_each_version() {
+        let photo = card().photo;
+        let data = photo.strip_prefix("data:image/png;base64,").unwrap();
+        assert_eq!(photo_line(&photo, VCardVersion::V3), format!("PHOTO;ENCODING=b;TYPE=PNG:{}", data));
+        assert_eq!(photo_line(&photo, VCardVersion::V4), format!("PHOTO:{}", photo));
+        assert_eq!(photo_line("https://example.com/a.jpg", VCardVersion::V3), "PHOTO;VALUE=uri:https://example.com/a.jpg");
+        let read = parse("BEGIN:VCARD\nFN:A\nPHOTO;ENCODING=BASE64;TYPE=image/jpeg:AAAA\nEND:VCARD\n").unwrap();
+        assert_eq!(read[0].photo, "data:image/jpeg;base64,AAAA");
+        let read = parse("BEGIN:VCARD\nFN:A\nPHOTO;VALUE=uri:https://example.com/a.jpg\nEND:VCARD\n").unwrap();
+        assert_eq!(read[0].photo, "https://example.com/a.jpg");
+    }
+
+    #[test]
+    fn version_2_1_cards_are_read() {
+        let text = "BEGIN:VCARD\r\n\
+                    VERSION:2.1\r\n\
+                    N;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:=E0=A6=89=E0=A6=A6=E0=A7=8D=E0=A6=A6=E0=A6=BF=E0=A6=A8;=E0=A6=B0=E0=A6=B9=\r\n\
+                    =E0=A6=BF=E0=A6=AE;;;\r\n\
+                    TEL;CELL;PREF:+8801711000000\r\n\
+                    item1.EMAIL;INTERNET:rahim@example.com\r\n\
+                    END:VCARD\r\n";
+        let read = parse(text).unwrap();
+        assert_eq!(read[0].name, "রহিম উদ্দিন");
+        assert_eq!(read[0].phones, ["+8801711000000"]);
+        assert_eq!(read[0].emails, ["rahim@example.com"]);
+        let tel = Property::parse("TEL;CELL;PREF:+8801711000000").unwrap();
+        assert_eq!(tel.param("TYPE"), Some("CELL"));
+    }
+
+    #[test]
+    fn tel_uris_lose_their_scheme() {
+        assert_eq!(tel_uri(" +880 1711.000 (0) "), "+880-1711-000-0");
+        let read = parse("BEGIN:VCARD\nVERSION:4.0\nFN:A\nTEL;VALUE=uri;TYPE=\"cell,voice\":tel:+880-1711-000000\nEND:VCARD\n").unwrap();
+        assert_eq!(read[0].phones, ["+880-1711-000000"]);
+    }
+
+    #[test]
+    fn cards_without_a_name_are_named_or_dropped() {
+        let text = "BEGIN:VCARD\nN:Uddin;Rahim;;;\nEND:VCARD\n\
+                    BEGIN:VCARD\nEMAIL:karim@example.com\nEND:VCARD\n\
+                    BEGIN:VCARD\nNOTE:nobody\nEND:VCARD\n";
+        let names: Vec<String> = parse(text).unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["Rahim Uddin", "karim@example.com"]);
+        assert!(parse("BEGIN:VCARD\nNOTE:nobody\nEND:VCARD\n").is_err());
+        assert!(parse("not a vcard").is_err());
+    }
+}
@@ -1,17 +1,12 @@
 use dioxus::prelude::*;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Default)]
 pub enum Theme {
+    #[default]
     Light,
     Dark,
 }
 
-impl Default for Theme {
-    fn default() -> Self {
-        Theme::Light
-    }
-}
-
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct AppState {
     pub counter: i32,
@@ -64,11 +59,10 @@ fn get_initial_theme() -> Theme {
                 .match_media("(prefers-color-scheme: dark)")
                 .ok()
                 .flatten()
+                && media_query.matches()
             {
-                if media_query.matches() {
-                    log::info!("System preference is dark theme.");
-                    return Theme::Dark;
-                }
+                log::info!("System preference is dark theme.");
+                return Theme::Dark;
             }
             log::info!("No theme in local storage, and system preference is not dark (or not detectable).");
         } else {
@@ -132,16 +126,16 @@ pub fn toggle_theme(mut state: Signal<AppState>) {
     // This was likely how it worked before for the web.
     #[cfg(feature = "web")]
     {
-        if let Some(document) = web_sys::window().and_then(|w| w.document()) {
-            if let Some(html) = document.document_element() {
-                let theme_value = match app_state.theme {
-                    Theme::Dark => "dark",
-                    Theme::Light => "light",
-                };
-                match html.set_attribute("data-bs-theme", theme_value) {
-                    Ok(_) => log::info!("toggle_theme: Applied data-bs-theme: {}", theme_value),
-                    Err(e) => log::error!("toggle_theme: Failed to set data-bs-theme: {:?}", e),
-                }
+        if let Some(document) = web_sys::window().and_then(|w| w.document())
+            && let Some(html) = document.document_element()
+        {
+            let theme_value = match app_state.theme {
+                Theme::Dark => "dark",
+                Theme::Light => "light",
+            };
+            match html.set_attribute("data-bs-theme", theme_value) {
+                Ok(_) => log::info!("toggle_theme: Applied data-bs-theme: {}", theme_value),
+                Err(e) => log::error!("toggle_theme: Failed to set data-bs-theme: {:?}", e),
             }
         }
     }
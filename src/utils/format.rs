@@ -0,0 +1,90 @@
+use super::now_millis;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const WEEKDAYS: [&str; 7] = ["Thursday", "Friday", "Saturday", "Sunday", "Monday", "Tuesday", "Wednesday"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Offset of local time from UTC in milliseconds
+pub fn local_offset_millis() -> i64 {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        // getTimezoneOffset is UTC minus local time, in minutes
+        -(js_sys::Date::new_0().get_timezone_offset() as i64) * 60 * 1000
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    {
+        0
+    }
+}
+
+// Convert days since the Unix epoch into a (year, month, day) civil date
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Days since the Unix epoch of a civil date; the inverse of civil_from_days
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    let next = if month == 12 { days_from_civil(year + 1, 1, 1) } else { days_from_civil(year, month + 1, 1) };
+    (next - days_from_civil(year, month, 1)) as u32
+}
+
+// Clock time such as "10:30 AM"
+pub fn clock_time(millis: u64) -> String {
+    let local = millis as i64 + local_offset_millis();
+    let minutes = local.rem_euclid(DAY_MILLIS) / 60_000;
+    let (hour, minute) = (minutes / 60, minutes % 60);
+    let suffix = if hour < 12 { "AM" } else { "PM" };
+    let hour12 = if hour % 12 == 0 { 12 } else { hour % 12 };
+    format!("{}:{:02} {}", hour12, minute, suffix)
+}
+
+// Short date such as "12 Mar 2025"
+pub fn short_date(millis: u64) -> String {
+    let local = millis as i64 + local_offset_millis();
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY_MILLIS));
+    format!("{} {} {}", day, MONTHS[month as usize - 1], year)
+}
+
+// Timestamp label for lists: clock time today, then "Yesterday", the weekday
+// within the last week and a short date after that
+pub fn relative_time(millis: u64) -> String {
+    let offset = local_offset_millis();
+    let day = (millis as i64 + offset).div_euclid(DAY_MILLIS);
+    let today = (now_millis() as i64 + offset).div_euclid(DAY_MILLIS);
+    match today - day {
+        0 => clock_time(millis),
+        1 => "Yesterday".to_string(),
+        2..=6 => WEEKDAYS[day.rem_euclid(7) as usize].to_string(),
+        _ => short_date(millis),
+    }
+}
+
+// Duration such as "1:05:09" or "4:02"
+pub fn duration(seconds: u64) -> String {
+    let (hours, minutes, secs) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
@@ -0,0 +1,58 @@
+// Shared helpers
+pub mod format;
+
+// Milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        js_sys::Date::now() as u64
+    }
+
+    #[cfg(not(all(feature = "web", target_arch = "wasm32")))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+// Wait for `millis` milliseconds without blocking the UI
+pub async fn sleep(millis: u32) {
+    #[cfg(all(feature = "web", target_arch = "wasm32"))]
+    {
+        let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis as i32);
+            }
+        });
+        let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    }
+
+    #[cfg(all(not(feature = "web"), any(feature = "mobile", feature = "desktop")))]
+    tokio::time::sleep(std::time::Duration::from_millis(millis as u64)).await;
+
+    #[cfg(not(any(
+        all(feature = "web", target_arch = "wasm32"),
+        all(not(feature = "web"), any(feature = "mobile", feature = "desktop"))
+    )))]
+    let _ = millis;
+}
+
+// Random bytes from the platform generator. These become key material, so
+// a failure is returned rather than papered over with a fixed value.
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to read random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+// Random 128-bit identifier, hex encoded. Ids made without a random source
+// would collide, so this stops instead.
+pub fn new_id() -> String {
+    to_hex(&random_bytes::<16>().expect("the platform random generator is unavailable"))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}